@@ -0,0 +1,55 @@
+//! COM の呼び出しのオーバーヘッドのベンチマーク
+//!
+//! CeVIO がインストールされた環境で実行します（操作する製品は `HostKind::default()`）。
+//!
+//! ```sh
+//! cargo bench --bench invoke
+//! ```
+//!
+//! `cold` は新しいインスタンスでの初回の呼び出し（`GetIDsOfNames` を含む）、`warm` は DISPID のキャッシュを使う 2 回目以降の呼び出しです。
+//! 差が 1 回の呼び出しごとに減ったオーバーヘッドです。
+
+use cevio::{CeVIO, HostKind};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+fn cevio() -> CeVIO {
+    let cevio = CeVIO::with_host(HostKind::default()).unwrap();
+    cevio.start_host(false).unwrap();
+    cevio
+}
+
+fn property(c: &mut Criterion) {
+    let warm = cevio();
+    let mut group = c.benchmark_group("get_volume");
+    group.bench_function("cold", |b| {
+        b.iter_batched(
+            || CeVIO::with_host(HostKind::default()).unwrap(),
+            // 破棄にかかる時間を含めないよう返す
+            |cevio| {
+                cevio.get_volume().unwrap();
+                cevio
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.bench_function("warm", |b| b.iter(|| warm.get_volume().unwrap()));
+    group.finish();
+
+    c.bench_function("set_volume", |b| {
+        let volume = warm.get_volume().unwrap();
+        b.iter(|| warm.set_volume(volume).unwrap())
+    });
+}
+
+fn phonemes(c: &mut Criterion) {
+    let cevio = cevio();
+    let cast = cevio.get_available_casts().unwrap().remove(0);
+    cevio.set_cast(&cast).unwrap();
+    // 音素ごとに `At`、`Phoneme`、`StartTime`、`EndTime` を呼ぶ
+    c.bench_function("get_phonemes", |b| {
+        b.iter(|| cevio.get_phonemes("こんにちは。").unwrap())
+    });
+}
+
+criterion_group!(benches, property, phonemes);
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/cevio.proto");
+        let fds =
+            protox::compile(["proto/cevio.proto"], ["proto"]).expect("Failed to compile proto");
+        tonic_build::configure()
+            .build_client(false)
+            .compile_fds(fds)
+            .expect("Failed to generate gRPC code");
+    }
+}
@@ -0,0 +1,60 @@
+/*
+ * cevio - CeVIO/CeVIO AI の COM コンポーネント API を C 言語から使うためのヘッダー
+ *
+ * cevio クレートを `capi` フィーチャーを有効にしてビルドした cdylib（cevio.dll）と一緒に使います。
+ * 依存するクレートで余分な cdylib をビルドしないように、Cargo.toml では cdylib を指定していません。
+ * cevio.dll は次のコマンドでビルドします（target/release/cevio.dll に出力されます）。
+ *
+ *   cargo rustc --release --lib --features capi --crate-type cdylib
+ *
+ * 戻り値が int の関数は、成功した場合は 0、失敗した場合は -1 を返します。
+ * 失敗した理由は cevio_last_error で取得できます。
+ * 文字列はすべて NUL 終端の UTF-8 です。
+ */
+#ifndef CEVIO_H
+#define CEVIO_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct CevioHandle CevioHandle;
+
+#define CEVIO_HOST_AI 0
+#define CEVIO_HOST_CS 1
+
+/* 直前に失敗した関数のエラーメッセージ。同じスレッドで次に失敗するまで有効。エラーがない場合は NULL */
+const char *cevio_last_error(void);
+
+/* ハンドルを作成する。失敗した場合は NULL */
+CevioHandle *cevio_create(int host);
+/* ハンドルを破棄する。NULL の場合は何もしない */
+void cevio_destroy(CevioHandle *handle);
+
+/* 【CeVIO Creative Studio】を起動する。StartHost の戻り値（0 以下）、呼び出しに失敗した場合は -100 */
+int cevio_start_host(const CevioHandle *handle, bool no_wait);
+
+int cevio_set_cast(const CevioHandle *handle, const char *cast);
+int cevio_set_volume(const CevioHandle *handle, int value);
+int cevio_set_speed(const CevioHandle *handle, int value);
+int cevio_set_tone(const CevioHandle *handle, int value);
+int cevio_set_tone_scale(const CevioHandle *handle, int value);
+int cevio_set_alpha(const CevioHandle *handle, int value);
+int cevio_set_component(const CevioHandle *handle, const char *name, int value);
+
+/* セリフを再生する。wait が true の場合は再生終了まで戻らない */
+int cevio_speak(const CevioHandle *handle, const char *text, bool wait);
+
+/* セリフを WAV 形式のバイト列に変換する。バッファは cevio_free_buffer で解放する */
+int cevio_synthesize(const CevioHandle *handle, const char *text, uint8_t **out, size_t *out_len);
+void cevio_free_buffer(uint8_t *buffer, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CEVIO_H */
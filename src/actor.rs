@@ -0,0 +1,323 @@
+use std::{
+    fmt,
+    sync::{mpsc, Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    CeVIO, CeVIOBuilder, HostHealth, HostKind, SpeakingState,
+};
+
+type Job = Box<dyn FnOnce(&CeVIO) + Send>;
+
+/// CeVIO を専用スレッドで動かし、他のスレッドから操作するためのハンドルです。
+///
+/// COM オブジェクトは作成したスレッドでしか使えないため、すべての操作を専用スレッドに送って順番に実行します。
+/// 操作は送った順に 1 つずつ実行されるため、再生して終了を待つ操作を送るとそのまま読み上げキューになります。
+///
+/// ハンドルは `Send + Sync` で、`Clone` は参照カウントを増やすだけです。アプリの状態に保持したり、クロージャに移動したり、
+/// 複数の機能で共有したりする場合は、`Rc<RefCell<CeVIO>>` などで包まずにハンドルをそのまま複製してください。
+/// すべてのハンドルが破棄されるとスレッドも終了します。終了の仕方は `set_drop_policy` で指定します。
+///
+/// ```no_run
+/// use cevio::{actor::Handle, HostKind};
+/// let handle = Handle::spawn(HostKind::Ai).unwrap();
+///
+/// let casts = handle.call(|cevio| cevio.get_available_casts()).unwrap();
+/// handle
+///     .send(|cevio| {
+///         let _ = cevio.speak("こんにちは。").and_then(|state| state.wait());
+///     })
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Handle {
+    inner: Arc<Inner>,
+}
+
+/// すべてのハンドルが破棄されたときの動作です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// 送った操作をすべて実行してから専用スレッドを終了します。破棄は待たずに戻ります。再生中のセリフはそのまま再生されます。（既定）
+    #[default]
+    Detach,
+    /// 送った操作と再生中のセリフが終わるまで、最大 `timeout` 待ちます。
+    ///
+    /// 過ぎた場合は残りの操作を捨て、再生を停止します。
+    ///
+    /// 最後のハンドルを専用スレッドで実行中の操作の中で破棄した場合は、自分自身の終了を待てないため `Detach` と同じく待たずに戻ります。
+    Wait {
+        /// 最大待機時間
+        timeout: Duration,
+    },
+    /// 残りの操作を捨て、再生を停止します。実行中の操作は終わるまで実行されます。
+    Stop,
+}
+
+/// すべてのハンドルで共有する状態
+struct Inner {
+    sender: mpsc::Sender<Job>,
+    host: HostKind,
+    shutdown: Arc<Shutdown>,
+    /// 専用スレッドの ID
+    thread: thread::ThreadId,
+    /// 専用スレッドが終了すると切断される
+    done: Mutex<mpsc::Receiver<()>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let _ = self.shutdown.dropped_at.set(Instant::now());
+        // 送信側を閉じて、専用スレッドに送る操作がもうないことを知らせる
+        drop(std::mem::replace(&mut self.sender, mpsc::channel().0));
+        // 専用スレッドで破棄した場合に待つと、タイムアウトまで自分自身を待ち続ける
+        let on_thread = thread::current().id() == self.thread;
+        if let (DropPolicy::Wait { timeout }, false) = (self.shutdown.policy(), on_thread) {
+            let done = self.done.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = done.recv_timeout(timeout);
+        }
+    }
+}
+
+/// ハンドルと専用スレッドで共有する、終了の設定
+#[derive(Default)]
+struct Shutdown {
+    policy: Mutex<DropPolicy>,
+    /// すべてのハンドルが破棄された時刻
+    dropped_at: OnceLock<Instant>,
+}
+
+impl Shutdown {
+    fn policy(&self) -> DropPolicy {
+        *self.policy.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// すべてのハンドルが破棄されてからの時間
+    fn elapsed(&self) -> Option<Duration> {
+        self.dropped_at.get().map(Instant::elapsed)
+    }
+
+    /// 残りの操作を捨てるかどうか
+    fn is_aborted(&self) -> bool {
+        let Some(elapsed) = self.elapsed() else {
+            return false;
+        };
+        match self.policy() {
+            DropPolicy::Detach => false,
+            DropPolicy::Wait { timeout } => elapsed >= timeout,
+            DropPolicy::Stop => true,
+        }
+    }
+
+    /// すべての操作を終えた後、再生中のセリフを待つか停止する
+    fn finish(&self, cevio: &CeVIO) {
+        match self.policy() {
+            DropPolicy::Detach => {}
+            DropPolicy::Wait { timeout } => {
+                let remaining = timeout.saturating_sub(self.elapsed().unwrap_or_default());
+                if !matches!(wait_last_speech(cevio, remaining), Ok(true)) {
+                    let _ = cevio.stop();
+                }
+            }
+            DropPolicy::Stop => {
+                let _ = cevio.stop();
+            }
+        }
+    }
+}
+
+/// 最後に `speak` したセリフの再生終了を最大 `timeout` 待ち、終わったかどうかを返す
+fn wait_last_speech(cevio: &CeVIO, timeout: Duration) -> error::Result<bool> {
+    let Some(state) = cevio.last_speech.borrow().clone() else {
+        return Ok(true);
+    };
+    let state = SpeakingState::new(state, false);
+    state.wait_timeout(timeout.as_secs_f64())?;
+    state.is_completed()
+}
+
+impl fmt::Debug for Handle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("host", &self.inner.host)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Handle {
+    /// 専用スレッドを起動し、指定した製品用のインスタンスを作成します。
+    pub fn spawn(host: HostKind) -> error::Result<Self> {
+        Self::spawn_with(CeVIO::builder().host(host))
+    }
+
+    /// 専用スレッドを起動し、ビルダーの設定でインスタンスを作成します。
+    ///
+    /// 起動やキャストの設定に失敗した場合はエラーを返し、スレッドは終了します。
+    pub fn spawn_with(builder: CeVIOBuilder) -> error::Result<Self> {
+        let host = builder.get_host();
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let (init_sender, init_receiver) = mpsc::channel();
+        let (done_sender, done) = mpsc::channel::<()>();
+        let shutdown = Arc::new(Shutdown::default());
+        let thread_shutdown = shutdown.clone();
+        let thread = thread::Builder::new()
+            .name("cevio".to_string())
+            .spawn(move || {
+                // スレッドの終了まで保持し、終了を知らせる
+                let _done = done_sender;
+                let cevio = match builder.build() {
+                    Ok(cevio) => {
+                        let _ = init_sender.send(Ok(()));
+                        cevio
+                    }
+                    Err(e) => {
+                        let _ = init_sender.send(Err(e));
+                        return;
+                    }
+                };
+                for job in receiver {
+                    if !thread_shutdown.is_aborted() {
+                        job(&cevio);
+                    }
+                }
+                thread_shutdown.finish(&cevio);
+            })
+            .context("Failed to spawn CeVIO thread")
+            .map_err(error::CeVIOError::from)?
+            .thread()
+            .id();
+        init_receiver
+            .recv()
+            .context("CeVIO thread has stopped")
+            .map_err(error::CeVIOError::from)??;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                sender,
+                host,
+                shutdown,
+                thread,
+                done: Mutex::new(done),
+            }),
+        })
+    }
+
+    /// 操作対象の製品を取得します。
+    pub fn host(&self) -> HostKind {
+        self.inner.host
+    }
+
+    /// すべてのハンドルが破棄されたときの動作を指定します。既定は `DropPolicy::Detach` です。
+    ///
+    /// 複製したすべてのハンドルで共有します。
+    pub fn set_drop_policy(&self, policy: DropPolicy) {
+        *self
+            .inner
+            .shutdown
+            .policy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    /// すべてのハンドルが破棄されたときの動作を取得します。
+    pub fn drop_policy(&self) -> DropPolicy {
+        self.inner.shutdown.policy()
+    }
+
+    /// 2 つのハンドルが同じスレッド（同じインスタンス）を操作するかどうかを取得します。
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// 操作を送り、完了を待たずに戻ります。
+    pub fn send(&self, f: impl FnOnce(&CeVIO) + Send + 'static) -> error::Result<()> {
+        self.inner
+            .sender
+            .send(Box::new(f))
+            .map_err(|_| error::CeVIOError::Other(report!("CeVIO thread has stopped")))
+    }
+
+    /// 操作を送り、完了を待って結果を返します。
+    pub fn call<R, F>(&self, f: F) -> error::Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&CeVIO) -> error::Result<R> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.send(move |cevio| {
+            let _ = sender.send(f(cevio));
+        })?;
+        receiver
+            .recv()
+            .context("CeVIO thread has stopped")
+            .map_err(error::CeVIOError::from)?
+    }
+
+    /// 製品が応答するかどうかを、最大 `timeout` 待って確認します。
+    ///
+    /// 専用スレッドで `HostVersion` を読み込みます。送った操作の後に実行されるため、再生などの処理中は `HostHealth::Slow` になります。
+    /// 期限を過ぎた場合も確認の操作は後で実行されますが、結果は捨てます。
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use cevio::{actor::Handle, HostHealth, HostKind};
+    /// let handle = Handle::spawn(HostKind::Ai).unwrap();
+    ///
+    /// match handle.ping(Duration::from_secs(2)) {
+    ///     HostHealth::Healthy { latency } => println!("{latency:?}"),
+    ///     HostHealth::Slow => println!("処理中"),
+    ///     HostHealth::Dead { reason } => eprintln!("{reason}"),
+    /// }
+    /// ```
+    pub fn ping(&self, timeout: Duration) -> HostHealth {
+        let start = Instant::now();
+        let (sender, receiver) = mpsc::channel();
+        if let Err(e) = self.send(move |cevio| {
+            let _ = sender.send(cevio.ping(Duration::MAX));
+        }) {
+            return HostHealth::Dead {
+                reason: e.to_string(),
+            };
+        }
+        match receiver.recv_timeout(timeout) {
+            // 待ち時間を含めた時間にする
+            Ok(HostHealth::Healthy { .. }) => HostHealth::Healthy {
+                latency: start.elapsed(),
+            },
+            Ok(health) => health,
+            Err(mpsc::RecvTimeoutError::Timeout) => HostHealth::Slow,
+            Err(mpsc::RecvTimeoutError::Disconnected) => HostHealth::Dead {
+                reason: "CeVIO thread has stopped".to_string(),
+            },
+        }
+    }
+
+    /// 再生を開始し、完了を待たずに戻ります。再生が終わると `on_done` を専用スレッドから呼び出します。
+    ///
+    /// 待てない GUI アプリなどで、再生の終了を知るために使います。
+    /// 再生は専用スレッドで終了まで待つため、続けて送った操作はこの再生が終わってから実行されます。
+    /// `on_done` には再生の結果（厳格モードで再生に失敗した場合は `CeVIOError::OperationFailed`）を渡します。
+    ///
+    /// 専用スレッドが終了していて操作を送れなかった場合は、`on_done` を呼ばずにエラーを返します。
+    ///
+    /// ```no_run
+    /// use cevio::{actor::Handle, HostKind};
+    /// let handle = Handle::spawn(HostKind::Ai).unwrap();
+    ///
+    /// handle
+    ///     .speak_with_callback("こんにちは。", |result| match result {
+    ///         Ok(()) => println!("再生が終わりました"),
+    ///         Err(e) => eprintln!("{e}"),
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn speak_with_callback(
+        &self,
+        text: impl Into<String>,
+        on_done: impl FnOnce(error::Result<()>) + Send + 'static,
+    ) -> error::Result<()> {
+        let text = text.into();
+        self.send(move |cevio| on_done(cevio.speak(&text).and_then(|state| state.wait())))
+    }
+}
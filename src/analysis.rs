@@ -0,0 +1,112 @@
+//! 音素データの統計
+//!
+//! セリフごとの音素数、モーラ数、話す速さ（モーラ/秒）、最も長い間（`pau`）を求めます。
+//! 長いプロジェクト全体で話す速さや間をそろえるために使います。
+//!
+//! ```no_run
+//! use cevio::CeVIO;
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let stats = cevio.analyze("こんにちは。よろしくお願いします。").unwrap();
+//! println!("{:.1} モーラ/秒", stats.morae_per_sec().unwrap_or_default());
+//! ```
+//!
+//! ```
+//! use cevio::{analysis::PhonemeStats, PhonemeData};
+//!
+//! let phoneme = |phoneme: &str, start_time: f64, end_time: f64| PhonemeData {
+//!     phoneme: phoneme.to_string(),
+//!     start_time,
+//!     end_time,
+//! };
+//! let stats = PhonemeStats::from_phonemes(&[
+//!     phoneme("sil", 0.0, 0.1),
+//!     phoneme("k", 0.1, 0.2),
+//!     phoneme("a", 0.2, 0.4),
+//!     phoneme("pau", 0.4, 0.7),
+//!     phoneme("N", 0.7, 0.9),
+//!     phoneme("sil", 0.9, 1.0),
+//! ]);
+//! assert_eq!(stats.phonemes, 3);
+//! assert_eq!(stats.morae, 2);
+//! assert_eq!(stats.pauses, 1);
+//! assert!((stats.longest_pause - 0.3).abs() < 1e-9);
+//! assert!((stats.speech_duration - 0.8).abs() < 1e-9);
+//! ```
+
+use crate::{error, CeVIO, PhonemeData};
+
+/// 無音を表す音素
+const SILENCE: &str = "sil";
+/// 間を表す音素
+const PAUSE: &str = "pau";
+
+/// セリフの音素データの統計です。
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PhonemeStats {
+    /// 音素の数（`sil`、`pau` を除く）
+    pub phonemes: usize,
+    /// モーラの数（母音、`N`、`cl` の数）
+    pub morae: usize,
+    /// 間（`pau`）の数
+    pub pauses: usize,
+    /// 最も長い間。単位は秒
+    pub longest_pause: f64,
+    /// 間の合計。単位は秒
+    pub total_pause: f64,
+    /// 最初の音素の開始から最後の音素の終了まで（前後の `sil` を除く）。単位は秒
+    pub speech_duration: f64,
+}
+
+impl PhonemeStats {
+    /// 音素データから統計を求めます。
+    pub fn from_phonemes(phonemes: &[PhonemeData]) -> Self {
+        let mut stats = Self::default();
+        for phoneme in phonemes {
+            let length = phoneme.end_time - phoneme.start_time;
+            match phoneme.phoneme.as_str() {
+                SILENCE => {}
+                PAUSE => {
+                    stats.pauses += 1;
+                    stats.total_pause += length;
+                    stats.longest_pause = stats.longest_pause.max(length);
+                }
+                p => {
+                    stats.phonemes += 1;
+                    if is_mora(p) {
+                        stats.morae += 1;
+                    }
+                }
+            }
+        }
+        let mut voiced = phonemes.iter().filter(|p| p.phoneme != SILENCE);
+        if let Some(first) = voiced.next() {
+            let last = voiced.next_back().unwrap_or(first);
+            stats.speech_duration = last.end_time - first.start_time;
+        }
+        stats
+    }
+
+    /// 話す速さ（モーラ/秒）を取得します。間は含めません。話す時間がない場合は `None` です。
+    pub fn morae_per_sec(&self) -> Option<f64> {
+        let duration = self.speech_duration - self.total_pause;
+        (duration > 0.0).then(|| self.morae as f64 / duration)
+    }
+}
+
+/// 1 モーラになる音素かどうか（無声化した母音は大文字）
+fn is_mora(phoneme: &str) -> bool {
+    matches!(
+        phoneme,
+        "a" | "i" | "u" | "e" | "o" | "A" | "I" | "U" | "E" | "O" | "N" | "cl"
+    )
+}
+
+impl CeVIO {
+    /// 現在のキャストとパラメータで、セリフの音素データの統計を求めます。音声は合成しません。
+    pub fn analyze(&self, text: &str) -> error::Result<PhonemeStats> {
+        Ok(PhonemeStats::from_phonemes(&self.get_phonemes(text)?))
+    }
+}
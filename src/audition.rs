@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::{self, report},
+    fs_util::create_dir_all,
+    params::Params,
+    CeVIO,
+};
+
+/// スイープで変化させるパラメータです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Axis {
+    /// 音の大きさ
+    Volume,
+    /// 話す速さ
+    Speed,
+    /// 音の高さ
+    Tone,
+    /// 抑揚
+    ToneScale,
+    /// 声質
+    Alpha,
+    /// 感情パラメータ（名前）
+    Component(String),
+}
+
+impl Axis {
+    /// ファイル名に使う名前を取得します。
+    pub fn name(&self) -> &str {
+        match self {
+            Axis::Volume => "Volume",
+            Axis::Speed => "Speed",
+            Axis::Tone => "Tone",
+            Axis::ToneScale => "ToneScale",
+            Axis::Alpha => "Alpha",
+            Axis::Component(name) => name,
+        }
+    }
+
+    fn apply(&self, params: &mut Params, value: i32) {
+        match self {
+            Axis::Volume => params.volume = Some(value),
+            Axis::Speed => params.speed = Some(value),
+            Axis::Tone => params.tone = Some(value),
+            Axis::ToneScale => params.tone_scale = Some(value),
+            Axis::Alpha => params.alpha = Some(value),
+            Axis::Component(name) => {
+                *params = params.merge(&Params {
+                    components: vec![(name.clone(), value)],
+                    ..Default::default()
+                })
+            }
+        }
+    }
+}
+
+/// 同じセリフをパラメータの組み合わせごとに出力し、聴き比べるためのものです。
+///
+/// ```no_run
+/// use cevio::{audition::{Axis, Sweep}, CeVIO, Params};
+/// let cevio = CeVIO::new().unwrap();
+///
+/// Sweep::new(Params {
+///     cast: Some("花隈千冬".to_string()),
+///     ..Default::default()
+/// })
+/// .axis(Axis::Component("嬉しい".to_string()), [0, 25, 50, 75, 100])
+/// .axis(Axis::Speed, [45, 50, 55])
+/// .render(&cevio, "こんにちは。", r"E:\sweep")
+/// .unwrap(); // E:\sweep\嬉しい=0_Speed=45.wav など 15 ファイルを出力
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Sweep {
+    base: Params,
+    axes: Vec<(Axis, Vec<i32>)>,
+}
+
+impl Sweep {
+    /// すべての組み合わせに共通するパラメータを指定して作成します。
+    pub fn new(base: Params) -> Self {
+        Self {
+            base,
+            axes: Vec::new(),
+        }
+    }
+
+    /// 変化させるパラメータとその値を追加します。
+    pub fn axis(mut self, axis: Axis, values: impl IntoIterator<Item = i32>) -> Self {
+        self.axes.push((axis, values.into_iter().collect()));
+        self
+    }
+
+    /// すべての組み合わせを `(名前, パラメータ)` で取得します。
+    ///
+    /// 名前は `軸名=値` を `_` でつないだものです。軸がない場合は `base` になります。
+    pub fn combinations(&self) -> Vec<(String, Params)> {
+        self.axes
+            .iter()
+            .fold(
+                vec![(Vec::new(), self.base.clone())],
+                |acc, (axis, values)| {
+                    acc.iter()
+                        .flat_map(|(labels, params)| {
+                            values.iter().map(move |&value| {
+                                let mut labels = labels.clone();
+                                labels.push(format!("{}={value}", axis.name()));
+                                let mut params = params.clone();
+                                axis.apply(&mut params, value);
+                                (labels, params)
+                            })
+                        })
+                        .collect()
+                },
+            )
+            .into_iter()
+            .map(|(labels, params)| match labels.is_empty() {
+                true => ("base".to_string(), params),
+                false => (labels.join("_"), params),
+            })
+            .collect()
+    }
+
+    /// すべての組み合わせを `out_dir` に `<名前>.wav` として出力し、出力したファイルのパスとパラメータを返します。
+    ///
+    /// ファイルが既にある場合は `CeVIO::set_overwrite_policy` の設定に従います。スキップした場合は既存のファイルのパスを返します。
+    pub fn render(
+        &self,
+        cevio: &CeVIO,
+        text: &str,
+        out_dir: impl AsRef<Path>,
+    ) -> error::Result<Vec<(PathBuf, Params)>> {
+        let out_dir = cevio.resolve_output_path(out_dir)?;
+        create_dir_all(&out_dir)?;
+        self.combinations()
+            .into_iter()
+            .map(|(name, params)| {
+                let path = out_dir.join(format!("{name}.wav"));
+                cevio.apply_params(&params)?;
+                let path = cevio.output_wave(text, &path)?.unwrap_or(path);
+                Ok((path, params))
+            })
+            .collect()
+    }
+}
+
+/// 同じセリフを複数のキャストで `out_dir` に出力し、出力したファイルのパスを返します。
+///
+/// ファイル名は `<番号>_<キャスト名>.wav` です。ファイルが既にある場合は `CeVIO::set_overwrite_policy` の設定に従います。
+/// キャストごとにパラメータを変えたい場合は、キャスト名の代わりに `Params` を渡します。
+///
+/// ```no_run
+/// use cevio::{audition::compare_casts, CeVIO, Params};
+/// let cevio = CeVIO::new().unwrap();
+///
+/// compare_casts(
+///     &cevio,
+///     "初めまして。",
+///     [
+///         Params::from("花隈千冬"),
+///         Params::from("さとうささら"),
+///         Params {
+///             speed: Some(55),
+///             ..Params::from("小春六花")
+///         },
+///     ],
+///     r"E:\casts",
+/// )
+/// .unwrap(); // E:\casts\01_花隈千冬.wav など 3 ファイルを出力
+/// ```
+pub fn compare_casts(
+    cevio: &CeVIO,
+    text: &str,
+    casts: impl IntoIterator<Item = impl Into<Params>>,
+    out_dir: impl AsRef<Path>,
+) -> error::Result<Vec<PathBuf>> {
+    let out_dir = cevio.resolve_output_path(out_dir)?;
+    create_dir_all(&out_dir)?;
+    casts
+        .into_iter()
+        .enumerate()
+        .map(|(i, params)| {
+            let params = params.into();
+            let cast = params
+                .cast
+                .as_deref()
+                .ok_or_else(|| report!("Cast is not specified at index {i}"))
+                .map_err(error::CeVIOError::InvalidInput)?;
+            let path = out_dir.join(format!("{:02}_{cast}.wav", i + 1));
+            cevio.apply_params(&params)?;
+            Ok(cevio.output_wave(text, &path)?.unwrap_or(path))
+        })
+        .collect()
+}
@@ -0,0 +1,403 @@
+//! HTTP/WebSocket サーバーのアクセス制限（`server` フィーチャー）
+//!
+//! - トークンを設定すると、`Authorization: Bearer <トークン>` ヘッダーか `?token=<トークン>` クエリが必要になります。
+//!   ブラウザーの WebSocket はヘッダーを設定できないため、`/ws` ではクエリを使います。
+//! - 許可するアドレスを設定すると、それ以外のアドレスからの接続を拒否します。
+//!
+//! 拒否したリクエストにはトークンの誤りなら `401`（`ErrorKind::Unauthorized`）、アドレスの誤りなら `403`（`ErrorKind::Forbidden`）を、
+//! ほかのエンドポイントと同じ `error::ErrorBody` の JSON で返します。
+//!
+//! ```no_run
+//! # async fn run() {
+//! use cevio::{actor::Handle, auth::Auth, server, HostKind};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//!
+//! let auth = Auth::new()
+//!     .token("secret")
+//!     .allow("127.0.0.1")
+//!     .unwrap()
+//!     .allow("192.168.0.0/16")
+//!     .unwrap();
+//! let router = auth.apply(server::router(handle).unwrap());
+//! server::serve_router("0.0.0.0:8080", router, std::future::pending())
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+
+use crate::{
+    error::{self, report, Context as _, ErrorKind},
+    server::ServerError,
+};
+
+/// 許可するアドレスの範囲です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AddrRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl AddrRange {
+    fn parse(s: &str) -> error::Result<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr
+            .trim()
+            .parse::<IpAddr>()
+            .with_context(|| format!("Invalid address `{s}`"))
+            .map_err(error::CeVIOError::from)?;
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|&p| p <= max)
+                .ok_or_else(|| report!("Invalid prefix length in `{s}`"))
+                .map_err(error::CeVIOError::InvalidInput)?,
+            None => max,
+        };
+        // IPv4 射影アドレスの範囲は、接続元のアドレスと同じく IPv4 の範囲として扱う
+        if let IpAddr::V6(v6) = addr {
+            if let (Some(v4), true) = (v6.to_ipv4_mapped(), prefix >= 96) {
+                return Ok(Self {
+                    addr: IpAddr::V4(v4),
+                    prefix: prefix - 96,
+                });
+            }
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    fn contains(&self, addr: IpAddr) -> bool {
+        // IPv4 射影アドレス（::ffff:a.b.c.d）で接続された場合も IPv4 として比べる
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+            addr => addr,
+        };
+        match (self.addr, addr) {
+            (IpAddr::V4(range), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(range) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(range) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// HTTP/WebSocket サーバーのアクセス制限です。
+///
+/// 何も設定しない場合はすべてのリクエストを許可します。
+#[derive(Debug, Clone, Default)]
+pub struct Auth {
+    token: Option<String>,
+    allowed: Vec<AddrRange>,
+}
+
+impl Auth {
+    /// すべてのリクエストを許可するアクセス制限を作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 必要なトークンを設定します。
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// 許可するアドレスを追加します。`192.168.0.10` のような単一のアドレスか、`192.168.0.0/16` のような範囲で指定します。
+    pub fn allow(mut self, addr: &str) -> error::Result<Self> {
+        self.allowed.push(AddrRange::parse(addr)?);
+        Ok(self)
+    }
+
+    /// アドレスが許可されているかどうかを取得します。
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        self.allowed.is_empty() || self.allowed.iter().any(|range| range.contains(addr))
+    }
+
+    /// ルーターにアクセス制限を追加します。
+    ///
+    /// 接続元のアドレスを取得するため、`server::serve_router` などで起動してください。
+    pub fn apply(self, router: Router) -> Router {
+        router.layer(middleware::from_fn_with_state(Arc::new(self), check))
+    }
+
+    /// 接続元のアドレスとトークンを確かめ、拒否する場合はそのエラーを返す
+    fn authorize(
+        &self,
+        addr: Option<IpAddr>,
+        request: &Request,
+        query: Option<&HashMap<String, String>>,
+    ) -> Result<(), ServerError> {
+        if !self.allowed.is_empty() {
+            match addr {
+                Some(addr) if self.is_allowed(addr) => {}
+                Some(addr) => {
+                    return Err(ServerError::new(
+                        StatusCode::FORBIDDEN,
+                        ErrorKind::Forbidden,
+                        format!("Address `{addr}` is not allowed"),
+                    ))
+                }
+                None => {
+                    return Err(ServerError::new(
+                        StatusCode::FORBIDDEN,
+                        ErrorKind::Forbidden,
+                        "Client address is unknown",
+                    ))
+                }
+            }
+        }
+        if !self.check_token(request, query) {
+            return Err(ServerError::new(
+                StatusCode::UNAUTHORIZED,
+                ErrorKind::Unauthorized,
+                "Invalid token",
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_token(&self, request: &Request, query: Option<&HashMap<String, String>>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let bearer = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let query = query
+            .and_then(|query| query.get("token"))
+            .map(String::as_str);
+        bearer
+            .into_iter()
+            .chain(query)
+            .any(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    }
+}
+
+/// トークンの一致までの時間から内容を推測されないよう、長さが同じなら常に全体を比べる
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn check(
+    State(auth): State<Arc<Auth>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    query: Option<Query<HashMap<String, String>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let addr = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    match auth.authorize(addr, &request, query.as_ref().map(|Query(query)| query)) {
+        Ok(()) => next.run(request).await,
+        Err(e) if e.0 == StatusCode::UNAUTHORIZED => {
+            ([(header::WWW_AUTHENTICATE, "Bearer")], e).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> Request {
+        let mut request = Request::builder().uri("/speak");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    /// 拒否した場合のステータスコードと JSON
+    fn rejection(result: Result<(), ServerError>) -> (StatusCode, serde_json::Value) {
+        let e = result.unwrap_err();
+        (e.0, serde_json::to_value(&e.1).unwrap())
+    }
+
+    fn contains(range: &str, addr: &str) -> bool {
+        AddrRange::parse(range)
+            .unwrap()
+            .contains(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn parse_without_prefix_is_single_address() {
+        let range = AddrRange::parse("192.168.0.10").unwrap();
+        assert_eq!(range.prefix, 32);
+        assert_eq!(AddrRange::parse("::1").unwrap().prefix, 128);
+        assert!(contains("192.168.0.10", "192.168.0.10"));
+        assert!(!contains("192.168.0.10", "192.168.0.11"));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_prefix() {
+        for s in [
+            "10.0.0.0/33",
+            "::/129",
+            "10.0.0.0/-1",
+            "10.0.0.0/x",
+            "10.0.0.0/",
+        ] {
+            assert!(
+                matches!(AddrRange::parse(s), Err(error::CeVIOError::InvalidInput(_))),
+                "{s}"
+            );
+        }
+        assert!(AddrRange::parse("10.0.0/8").is_err());
+        assert!(AddrRange::parse("localhost").is_err());
+    }
+
+    #[test]
+    fn zero_prefix_matches_every_address_of_the_family() {
+        assert!(contains("0.0.0.0/0", "255.255.255.255"));
+        assert!(contains("10.1.2.3/0", "1.1.1.1"));
+        assert!(contains("::/0", "2001:db8::1"));
+        assert!(!contains("0.0.0.0/0", "2001:db8::1"));
+    }
+
+    #[test]
+    fn full_prefix_matches_only_that_address() {
+        assert!(contains("10.0.0.1/32", "10.0.0.1"));
+        assert!(!contains("10.0.0.1/32", "10.0.0.2"));
+        assert!(contains("2001:db8::1/128", "2001:db8::1"));
+        assert!(!contains("2001:db8::1/128", "2001:db8::2"));
+    }
+
+    #[test]
+    fn prefix_masks_host_bits() {
+        assert!(contains("192.168.0.0/16", "192.168.255.1"));
+        assert!(!contains("192.168.0.0/16", "192.169.0.1"));
+        // 範囲のアドレスのホスト部は無視する
+        assert!(contains("192.168.12.34/24", "192.168.12.1"));
+        assert!(contains("10.0.0.0/9", "10.127.0.1"));
+        assert!(!contains("10.0.0.0/9", "10.128.0.1"));
+        assert!(contains("2001:db8::/32", "2001:db8:ffff::1"));
+        assert!(!contains("2001:db8::/32", "2001:db9::1"));
+    }
+
+    #[test]
+    fn ipv4_mapped_ipv6_is_compared_as_ipv4() {
+        assert!(contains("127.0.0.1", "::ffff:127.0.0.1"));
+        assert!(contains("192.168.0.0/16", "::ffff:192.168.1.2"));
+        assert!(!contains("192.168.0.0/16", "::ffff:10.0.0.1"));
+        assert!(contains("::ffff:192.168.0.0/112", "192.168.3.4"));
+        assert!(contains("::ffff:192.168.0.0/112", "::ffff:192.168.3.4"));
+        assert!(!contains("::ffff:192.168.0.0/112", "192.169.0.1"));
+    }
+
+    #[test]
+    fn families_do_not_match_each_other() {
+        assert!(!contains("127.0.0.1", "::1"));
+        assert!(!contains("::1", "127.0.0.1"));
+    }
+
+    #[test]
+    fn missing_token_is_unauthorized_json() {
+        let auth = Auth::new().token("secret");
+        let (status, body) = rejection(auth.authorize(None, &request(None), None));
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+        assert_eq!(body["kind"], "unauthorized");
+        assert_eq!(body["message"], "Invalid token");
+        assert_eq!(body["retryable"], false);
+    }
+
+    #[test]
+    fn wrong_token_is_unauthorized_json() {
+        let auth = Auth::new().token("secret");
+        let query = HashMap::from([("token".to_string(), "wrong".to_string())]);
+        for (authorization, query) in [
+            (Some("Bearer wrong"), None),
+            (Some("secret"), None),
+            (None, Some(&query)),
+        ] {
+            let (status, body) = rejection(auth.authorize(None, &request(authorization), query));
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["kind"], "unauthorized");
+        }
+    }
+
+    #[test]
+    fn token_is_accepted_from_header_or_query() {
+        let auth = Auth::new().token("secret");
+        assert!(auth
+            .authorize(None, &request(Some("Bearer secret")), None)
+            .is_ok());
+        let query = HashMap::from([("token".to_string(), "secret".to_string())]);
+        assert!(auth.authorize(None, &request(None), Some(&query)).is_ok());
+    }
+
+    #[test]
+    fn disallowed_address_is_forbidden_json() {
+        let auth = Auth::new().allow("127.0.0.1").unwrap();
+        let addr = "203.0.113.1".parse().ok();
+        let (status, body) = rejection(auth.authorize(addr, &request(None), None));
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "forbidden");
+        assert_eq!(body["kind"], "forbidden");
+        assert_eq!(body["message"], "Address `203.0.113.1` is not allowed");
+
+        let (status, body) = rejection(auth.authorize(None, &request(None), None));
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["message"], "Client address is unknown");
+    }
+
+    #[test]
+    fn address_is_checked_before_token() {
+        let auth = Auth::new().token("secret").allow("127.0.0.1").unwrap();
+        let (status, _) = rejection(auth.authorize(
+            "203.0.113.1".parse().ok(),
+            &request(Some("Bearer secret")),
+            None,
+        ));
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = rejection(auth.authorize("127.0.0.1".parse().ok(), &request(None), None));
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(auth
+            .authorize(
+                "127.0.0.1".parse().ok(),
+                &request(Some("Bearer secret")),
+                None
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn empty_allow_list_allows_everything() {
+        let auth = Auth::new();
+        assert!(auth.is_allowed("203.0.113.1".parse().unwrap()));
+        let auth = Auth::new().allow("127.0.0.1").unwrap();
+        assert!(auth.is_allowed("127.0.0.1".parse().unwrap()));
+        assert!(!auth.is_allowed("203.0.113.1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,536 @@
+//! トークの操作を抽象化したトレイトと、テスト用のモック
+//!
+//! 読み上げの処理を `TalkerBackend` に対して書いておくと、CeVIO がインストールされていない環境でも
+//! `MockBackend` に差し替えてテストできます。
+//!
+//! ```
+//! use cevio::backend::{MockBackend, MockCall, TalkerBackend};
+//!
+//! fn greet(backend: &impl TalkerBackend, name: &str) -> cevio::error::Result<()> {
+//!     backend.set_cast("花隈千冬")?;
+//!     backend.speak(&format!("こんにちは、{name}さん。"))?.wait()
+//! }
+//!
+//! let mock = MockBackend::new();
+//! greet(&mock, "太郎").unwrap();
+//! assert_eq!(
+//!     mock.calls(),
+//!     vec![
+//!         MockCall::SetCast("花隈千冬".to_string()),
+//!         MockCall::Speak("こんにちは、太郎さん。".to_string()),
+//!     ]
+//! );
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    overwrite::OverwritePolicy,
+    processor::OutputProcessor,
+    CeVIO, Params, PhonemeData, SpeakingState,
+};
+
+/// `MockBackend` のパラメータの初期値。CeVIO でキャストを切り替えた直後の値
+const MOCK_DEFAULT_PARAM: i32 = 50;
+
+/// 再生状態です。
+pub trait Playback {
+    /// 再生が完了したかどうかを取得します。（失敗した場合も `true`。）
+    fn is_completed(&self) -> error::Result<bool>;
+    /// 再生が成功したかどうかを取得します。
+    fn is_succeeded(&self) -> error::Result<bool>;
+    /// 再生終了を待ちます。
+    fn wait(&self) -> error::Result<()>;
+    /// 再生終了を最大 `timeout` 秒待ちます。
+    fn wait_timeout(&self, timeout: f64) -> error::Result<()>;
+}
+
+impl Playback for SpeakingState {
+    fn is_completed(&self) -> error::Result<bool> {
+        SpeakingState::is_completed(self)
+    }
+
+    fn is_succeeded(&self) -> error::Result<bool> {
+        SpeakingState::is_succeeded(self)
+    }
+
+    fn wait(&self) -> error::Result<()> {
+        SpeakingState::wait(self)
+    }
+
+    fn wait_timeout(&self, timeout: f64) -> error::Result<()> {
+        SpeakingState::wait_timeout(self, timeout)
+    }
+}
+
+/// トークの操作です。
+///
+/// `CeVIO` と `MockBackend` が実装しています。
+/// `Project::render`、`queue::SpeechQueue::with_backend`、`playlist::Playlist::with_backend`、`text::speak_lines` は
+/// このトレイトに対して書かれているため、`MockBackend`、`fixture::ReplayBackend`、`fault::FaultyBackend` でも動かせます。
+pub trait TalkerBackend {
+    /// キャストを取得します。
+    fn get_cast(&self) -> error::Result<String>;
+    /// キャストを設定します。
+    fn set_cast(&self, cast: &str) -> error::Result<()>;
+    /// 利用可能なキャスト名を取得します。
+    fn get_available_casts(&self) -> error::Result<Vec<String>>;
+    /// 現在のキャストとパラメータをすべて取得します。
+    ///
+    /// キャストが設定されていない場合、`cast` は `None`、`components` は空です。
+    fn current_params(&self) -> error::Result<Params>;
+    /// パラメータをまとめて設定します。
+    fn apply_params(&self, params: &Params) -> error::Result<()>;
+    /// セリフの再生を開始します。
+    fn speak(&self, text: &str) -> error::Result<Box<dyn Playback>>;
+    /// 再生を停止します。
+    fn stop(&self) -> error::Result<bool>;
+    /// セリフの音素単位のデータを取得します。
+    fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>>;
+    /// セリフを WAV ファイルに出力します。
+    fn output_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()>;
+
+    /// 後処理（`output_processors`）を適用せずに、セリフを WAV ファイルに出力します。
+    ///
+    /// 後処理を別のスレッドで行う場合に使います。既定では `output_wave_to_file` と同じです。
+    fn output_raw_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        self.output_wave_to_file(text, path)
+    }
+
+    /// 出力先のファイルが既にある場合の動作を取得します。既定では上書きします。
+    fn overwrite_policy(&self) -> OverwritePolicy {
+        OverwritePolicy::default()
+    }
+
+    /// 出力した WAV に適用する後処理を取得します。既定ではありません。
+    fn output_processors(&self) -> Vec<Arc<dyn OutputProcessor>> {
+        Vec::new()
+    }
+
+    /// キャストに切り替えたときに適用する既定のパラメータを取得します。既定ではありません。
+    fn cast_profile(&self, _cast: &str) -> Option<Params> {
+        None
+    }
+
+    /// キャストがインストールされていない場合に選ぶキャストを、優先する順に取得します。既定ではありません。
+    fn fallback_casts(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// `params` を設定して `f` を実行し、元の値に戻します。
+    ///
+    /// `f` が失敗した場合も元の値に戻し、`f` のエラーを返します。
+    /// キャストを切り替える場合はパラメータが初期化されるため、すべての値を戻します。それ以外は `params` で変える項目だけを戻します。
+    fn with_params<T>(
+        &self,
+        params: &Params,
+        f: impl FnOnce() -> error::Result<T>,
+    ) -> error::Result<T>
+    where
+        Self: Sized,
+    {
+        if *params == Params::default() {
+            return f();
+        }
+        let original = self.current_params()?.restore_for(params);
+        let result = self.apply_params(params).and_then(|()| f());
+        // 失敗した場合も戻し、`f` のエラーを優先する
+        let restored = self.apply_params(&original);
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+}
+
+impl<B: TalkerBackend> TalkerBackend for Arc<B> {
+    fn get_cast(&self) -> error::Result<String> {
+        (**self).get_cast()
+    }
+
+    fn set_cast(&self, cast: &str) -> error::Result<()> {
+        (**self).set_cast(cast)
+    }
+
+    fn get_available_casts(&self) -> error::Result<Vec<String>> {
+        (**self).get_available_casts()
+    }
+
+    fn current_params(&self) -> error::Result<Params> {
+        (**self).current_params()
+    }
+
+    fn apply_params(&self, params: &Params) -> error::Result<()> {
+        (**self).apply_params(params)
+    }
+
+    fn speak(&self, text: &str) -> error::Result<Box<dyn Playback>> {
+        (**self).speak(text)
+    }
+
+    fn stop(&self) -> error::Result<bool> {
+        (**self).stop()
+    }
+
+    fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>> {
+        (**self).get_phonemes(text)
+    }
+
+    fn output_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        (**self).output_wave_to_file(text, path)
+    }
+
+    fn output_raw_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        (**self).output_raw_wave_to_file(text, path)
+    }
+
+    fn overwrite_policy(&self) -> OverwritePolicy {
+        (**self).overwrite_policy()
+    }
+
+    fn output_processors(&self) -> Vec<Arc<dyn OutputProcessor>> {
+        (**self).output_processors()
+    }
+
+    fn cast_profile(&self, cast: &str) -> Option<Params> {
+        (**self).cast_profile(cast)
+    }
+
+    fn fallback_casts(&self) -> Vec<String> {
+        (**self).fallback_casts()
+    }
+
+    fn with_params<T>(
+        &self,
+        params: &Params,
+        f: impl FnOnce() -> error::Result<T>,
+    ) -> error::Result<T> {
+        (**self).with_params(params, f)
+    }
+}
+
+impl TalkerBackend for CeVIO {
+    fn get_cast(&self) -> error::Result<String> {
+        CeVIO::get_cast(self)
+    }
+
+    fn set_cast(&self, cast: &str) -> error::Result<()> {
+        CeVIO::set_cast(self, cast)
+    }
+
+    fn get_available_casts(&self) -> error::Result<Vec<String>> {
+        CeVIO::get_available_casts(self)
+    }
+
+    fn current_params(&self) -> error::Result<Params> {
+        CeVIO::current_params(self)
+    }
+
+    fn apply_params(&self, params: &Params) -> error::Result<()> {
+        CeVIO::apply_params(self, params)
+    }
+
+    fn speak(&self, text: &str) -> error::Result<Box<dyn Playback>> {
+        Ok(Box::new(CeVIO::speak(self, text)?))
+    }
+
+    fn stop(&self) -> error::Result<bool> {
+        CeVIO::stop(self)
+    }
+
+    fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>> {
+        CeVIO::get_phonemes(self, text)
+    }
+
+    fn output_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        CeVIO::output_wave_to_file(self, text, path)
+    }
+
+    fn output_raw_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        CeVIO::output_raw_wave(self, text, path).map(drop)
+    }
+
+    fn overwrite_policy(&self) -> OverwritePolicy {
+        CeVIO::overwrite_policy(self)
+    }
+
+    fn output_processors(&self) -> Vec<Arc<dyn OutputProcessor>> {
+        CeVIO::output_processors(self)
+    }
+
+    fn cast_profile(&self, cast: &str) -> Option<Params> {
+        CeVIO::cast_profile(self, cast)
+    }
+
+    fn fallback_casts(&self) -> Vec<String> {
+        CeVIO::fallback_casts(self)
+    }
+
+    fn with_params<T>(
+        &self,
+        params: &Params,
+        f: impl FnOnce() -> error::Result<T>,
+    ) -> error::Result<T> {
+        // 変更する項目だけを読み込む `Say` の処理を使う
+        CeVIO::with_params(self, params, |_| f())
+    }
+}
+
+/// `MockBackend` が受けた呼び出しです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    /// `set_cast`
+    SetCast(String),
+    /// `apply_params`
+    ApplyParams(Params),
+    /// `speak`
+    Speak(String),
+    /// `stop`
+    Stop,
+    /// `get_phonemes`
+    GetPhonemes(String),
+    /// `output_wave_to_file`
+    OutputWaveToFile {
+        /// セリフ
+        text: String,
+        /// 出力先
+        path: PathBuf,
+    },
+}
+
+struct MockState {
+    calls: Vec<MockCall>,
+    params: Params,
+    casts: Vec<String>,
+    phonemes: Vec<PhonemeData>,
+    wave: Vec<u8>,
+    fail_next: Option<error::ErrorKind>,
+}
+
+impl MockState {
+    fn is_available(&self, cast: &str) -> bool {
+        self.casts.is_empty() || self.casts.iter().any(|c| c == cast)
+    }
+
+    /// キャストを切り替え、パラメータを初期値に戻す。同じキャストの場合は何もしない
+    fn switch_cast(&mut self, cast: &str) -> error::Result<()> {
+        if !self.is_available(cast) {
+            return Err(error::CeVIOError::InvalidCast(report!(
+                "Cast `{cast}` is not available"
+            )));
+        }
+        if self.params.cast.as_deref() != Some(cast) {
+            self.params = Params {
+                cast: Some(cast.to_string()),
+                ..mock_default_params()
+            };
+        }
+        Ok(())
+    }
+}
+
+/// キャストを設定していない `MockBackend` のパラメータ
+fn mock_default_params() -> Params {
+    Params {
+        cast: None,
+        volume: Some(MOCK_DEFAULT_PARAM),
+        speed: Some(MOCK_DEFAULT_PARAM),
+        tone: Some(MOCK_DEFAULT_PARAM),
+        tone_scale: Some(MOCK_DEFAULT_PARAM),
+        alpha: Some(MOCK_DEFAULT_PARAM),
+        components: Vec::new(),
+    }
+}
+
+/// 呼び出しを記録し、あらかじめ設定したデータを返すモックです。
+///
+/// - `with_casts` でキャストを設定した場合、`set_cast` はそれ以外を指定すると `ErrorKind::InvalidCast` で失敗します。
+/// - パラメータの初期値は 50 で、CeVIO と同じくキャストを切り替えると初期値に戻ります。
+/// - `speak` はすぐに再生が完了した状態を返します。
+/// - `output_wave_to_file` は `with_wave` で設定したバイト列（既定では長さ 0 の WAV）を書き込みます。
+pub struct MockBackend {
+    state: Mutex<MockState>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    /// どのキャストでも設定できるモックを作成します。
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MockState {
+                calls: Vec::new(),
+                params: mock_default_params(),
+                casts: Vec::new(),
+                phonemes: Vec::new(),
+                wave: EMPTY_WAV.to_vec(),
+                fail_next: None,
+            }),
+        }
+    }
+
+    /// 利用可能なキャストを設定します。
+    pub fn with_casts<S: Into<String>>(self, casts: impl IntoIterator<Item = S>) -> Self {
+        self.lock().casts = casts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// `get_phonemes` が返すデータを設定します。
+    pub fn with_phonemes(self, phonemes: Vec<PhonemeData>) -> Self {
+        self.lock().phonemes = phonemes;
+        self
+    }
+
+    /// `output_wave_to_file` が書き込むバイト列を設定します。
+    pub fn with_wave(self, wave: Vec<u8>) -> Self {
+        self.lock().wave = wave;
+        self
+    }
+
+    /// 次の呼び出しを `kind` の種類のエラーで失敗させます。
+    ///
+    /// 失敗した呼び出しも記録されます。
+    pub fn fail_next(&self, kind: error::ErrorKind) {
+        self.lock().fail_next = Some(kind);
+    }
+
+    /// 受けた呼び出しを古い順に取得します。
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.lock().calls.clone()
+    }
+
+    /// `speak` に渡されたセリフを古い順に取得します。
+    pub fn spoken(&self) -> Vec<String> {
+        self.lock()
+            .calls
+            .iter()
+            .filter_map(|call| match call {
+                MockCall::Speak(text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 記録した呼び出しを消去します。
+    pub fn clear_calls(&self) {
+        self.lock().calls.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 呼び出しを記録し、`fail_next` が設定されていれば失敗する
+    fn record(&self, call: Option<MockCall>) -> error::Result<MutexGuard<'_, MockState>> {
+        let mut state = self.lock();
+        let name = match &call {
+            Some(call) => format!("{call:?}"),
+            None => "query".to_string(),
+        };
+        state.calls.extend(call);
+        match state.fail_next.take() {
+            Some(kind) => Err(error::CeVIOError::new(
+                kind,
+                report!("Injected failure in mock call `{name}`"),
+            )),
+            None => Ok(state),
+        }
+    }
+}
+
+impl TalkerBackend for MockBackend {
+    fn get_cast(&self) -> error::Result<String> {
+        Ok(self.record(None)?.params.cast.clone().unwrap_or_default())
+    }
+
+    fn set_cast(&self, cast: &str) -> error::Result<()> {
+        self.record(Some(MockCall::SetCast(cast.to_string())))?
+            .switch_cast(cast)
+    }
+
+    fn get_available_casts(&self) -> error::Result<Vec<String>> {
+        Ok(self.record(None)?.casts.clone())
+    }
+
+    fn current_params(&self) -> error::Result<Params> {
+        Ok(self.record(None)?.params.clone())
+    }
+
+    fn apply_params(&self, params: &Params) -> error::Result<()> {
+        let mut state = self.record(Some(MockCall::ApplyParams(params.clone())))?;
+        if let Some(cast) = &params.cast {
+            state.switch_cast(cast)?;
+        }
+        state.params = state.params.merge(&Params {
+            cast: None,
+            ..params.clone()
+        });
+        Ok(())
+    }
+
+    fn speak(&self, text: &str) -> error::Result<Box<dyn Playback>> {
+        drop(self.record(Some(MockCall::Speak(text.to_string())))?);
+        Ok(Box::new(Completed))
+    }
+
+    fn stop(&self) -> error::Result<bool> {
+        drop(self.record(Some(MockCall::Stop))?);
+        Ok(true)
+    }
+
+    fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>> {
+        Ok(self
+            .record(Some(MockCall::GetPhonemes(text.to_string())))?
+            .phonemes
+            .clone())
+    }
+
+    fn output_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        let wave = self
+            .record(Some(MockCall::OutputWaveToFile {
+                text: text.to_string(),
+                path: path.to_path_buf(),
+            }))?
+            .wave
+            .clone();
+        std::fs::write(path, wave)
+            .with_context(|| format!("Failed to write `{}`", path.display()))
+            .map_err(error::CeVIOError::from)
+    }
+}
+
+/// すぐに再生が完了した状態
+pub(crate) struct Completed;
+
+impl Playback for Completed {
+    fn is_completed(&self) -> error::Result<bool> {
+        Ok(true)
+    }
+
+    fn is_succeeded(&self) -> error::Result<bool> {
+        Ok(true)
+    }
+
+    fn wait(&self) -> error::Result<()> {
+        Ok(())
+    }
+
+    fn wait_timeout(&self, _timeout: f64) -> error::Result<()> {
+        Ok(())
+    }
+}
+
+/// 長さ 0 の WAV（48kHz, 16bit, モノラル）
+const EMPTY_WAV: [u8; 44] = [
+    b'R', b'I', b'F', b'F', 36, 0, 0, 0, b'W', b'A', b'V', b'E', // RIFF ヘッダー
+    b'f', b'm', b't', b' ', 16, 0, 0, 0, 1, 0, 1, 0, // PCM, 1 チャンネル
+    0x80, 0xBB, 0, 0, 0x00, 0x77, 0x01, 0, 2, 0, 16, 0, // 48000Hz, 96000 B/s, 16bit
+    b'd', b'a', b't', b'a', 0, 0, 0, 0, // data チャンク
+];
@@ -0,0 +1,231 @@
+//! Bevy 用のプラグイン（`bevy` フィーチャー）
+//!
+//! `CeVIOPlugin` を追加すると、CeVIO を専用スレッドで動かすハンドルを `CeVIOHandle` リソースとして登録します。
+//! 専用スレッドを起動できなかった場合はパニックせず、代わりに `CeVIOUnavailable` リソースを登録します。
+//! `SpeakRequest` イベントを送るとセリフを順番に再生し、再生の開始時に `SpeakStarted`、終了時に `SpeakFinished` イベントを送ります。
+//!
+//! ```no_run
+//! use bevy_app::{App, Startup, Update};
+//! use bevy_ecs::prelude::*;
+//! use cevio::{bevy::{CeVIOPlugin, SpeakFinished, SpeakRequest}, Params};
+//!
+//! fn greet(mut requests: EventWriter<SpeakRequest>) {
+//!     requests.send(SpeakRequest::new("こんにちは。").with_params(Params::from("花隈千冬")));
+//! }
+//!
+//! fn on_finished(mut finished: EventReader<SpeakFinished>) {
+//!     for event in finished.read() {
+//!         println!("{}: {:?}", event.id, event.error);
+//!     }
+//! }
+//!
+//! App::new()
+//!     .add_plugins(CeVIOPlugin::default())
+//!     .add_systems(Startup, greet)
+//!     .add_systems(Update, on_finished)
+//!     .run();
+//! ```
+
+use std::{
+    ops::Deref,
+    sync::{mpsc, Mutex},
+};
+
+use bevy_app::{App, Plugin, Update};
+use bevy_ecs::prelude::*;
+
+use crate::{actor::Handle, HostKind, Params};
+
+/// CeVIO を Bevy で使うためのプラグインです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CeVIOPlugin {
+    /// 使用する製品
+    pub host: HostKind,
+    /// `true` の場合はプラグインの追加時に CeVIO を起動します
+    pub start_host: bool,
+}
+
+impl Default for CeVIOPlugin {
+    fn default() -> Self {
+        Self {
+            host: HostKind::default(),
+            start_host: true,
+        }
+    }
+}
+
+/// CeVIO を操作するハンドルのリソースです。
+///
+/// イベントを使わずに直接操作する場合に使います。
+#[derive(Resource, Clone)]
+pub struct CeVIOHandle(pub Handle);
+
+impl Deref for CeVIOHandle {
+    type Target = Handle;
+
+    fn deref(&self) -> &Handle {
+        &self.0
+    }
+}
+
+/// CeVIO を使えない場合に、`CeVIOHandle` の代わりに登録するリソースです。
+///
+/// CeVIO がインストールされていない環境などで専用スレッドを起動できなかった場合に登録します。
+/// このとき `SpeakRequest` はすぐに、`error` を付けた `SpeakFinished` になります。
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct CeVIOUnavailable {
+    /// 起動できなかった理由
+    pub error: String,
+}
+
+/// セリフの再生を依頼するイベントです。
+#[derive(Event, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpeakRequest {
+    /// 識別子。`SpeakStarted` と `SpeakFinished` にそのまま付けて返します
+    pub id: u64,
+    /// セリフ
+    pub text: String,
+    /// このセリフの間だけ適用するパラメータ。再生が終わると元の値に戻します
+    pub params: Params,
+}
+
+impl SpeakRequest {
+    /// セリフを再生するイベントを作成します。
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    /// 識別子を設定します。
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// このセリフの間だけ適用するパラメータを設定します。
+    pub fn with_params(mut self, params: Params) -> Self {
+        self.params = params;
+        self
+    }
+}
+
+/// セリフの再生を開始したときのイベントです。
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct SpeakStarted {
+    /// `SpeakRequest` の識別子
+    pub id: u64,
+}
+
+/// セリフの再生が終了したときのイベントです。
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct SpeakFinished {
+    /// `SpeakRequest` の識別子
+    pub id: u64,
+    /// 失敗した場合はその内容
+    pub error: Option<String>,
+}
+
+enum Progress {
+    Started(u64),
+    Finished(u64, Option<String>),
+}
+
+/// CeVIO のスレッドから再生の進み具合を受け取る
+#[derive(Resource)]
+struct ProgressChannel {
+    sender: mpsc::Sender<Progress>,
+    receiver: Mutex<mpsc::Receiver<Progress>>,
+}
+
+impl Plugin for CeVIOPlugin {
+    fn build(&self, app: &mut App) {
+        match Handle::spawn(self.host) {
+            Ok(handle) => {
+                if self.start_host {
+                    // 起動を待つとアプリの起動が止まるため、CeVIO のスレッドで起動する
+                    let _ = handle.send(|cevio| {
+                        let _ = cevio.start_host(false);
+                    });
+                }
+                app.insert_resource(CeVIOHandle(handle));
+            }
+            // CeVIO のない環境でもアプリは起動できるようにする
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::error!(error = %e, "Failed to spawn CeVIO thread");
+                app.insert_resource(CeVIOUnavailable {
+                    error: format!("{e:#}"),
+                });
+            }
+        }
+        let (sender, receiver) = mpsc::channel();
+        app.insert_resource(ProgressChannel {
+            sender,
+            receiver: Mutex::new(receiver),
+        })
+        .add_event::<SpeakRequest>()
+        .add_event::<SpeakStarted>()
+        .add_event::<SpeakFinished>()
+        .add_systems(Update, (send_requests, emit_progress).chain());
+    }
+}
+
+fn send_requests(
+    handle: Option<Res<CeVIOHandle>>,
+    unavailable: Option<Res<CeVIOUnavailable>>,
+    channel: Res<ProgressChannel>,
+    mut requests: EventReader<SpeakRequest>,
+) {
+    let Some(handle) = handle else {
+        let error = unavailable.map_or_else(
+            || "CeVIO is not available".to_string(),
+            |unavailable| unavailable.error.clone(),
+        );
+        for request in requests.read() {
+            let _ = channel
+                .sender
+                .send(Progress::Finished(request.id, Some(error.clone())));
+        }
+        return;
+    };
+    for request in requests.read() {
+        let id = request.id;
+        let request = request.clone();
+        let sender = channel.sender.clone();
+        let result = handle.send(move |cevio| {
+            // 再生中にパラメータを戻さないよう、終わるまで待つ
+            let result = cevio.with_params(&request.params, |cevio| {
+                let state = cevio.speak(&request.text)?;
+                let _ = sender.send(Progress::Started(request.id));
+                state.wait()
+            });
+            let error = result.err().map(|e| format!("{e:#}"));
+            let _ = sender.send(Progress::Finished(request.id, error));
+        });
+        if let Err(e) = result {
+            let _ = channel
+                .sender
+                .send(Progress::Finished(id, Some(format!("{e:#}"))));
+        }
+    }
+}
+
+fn emit_progress(
+    channel: Res<ProgressChannel>,
+    mut started: EventWriter<SpeakStarted>,
+    mut finished: EventWriter<SpeakFinished>,
+) {
+    let receiver = channel.receiver.lock().unwrap_or_else(|e| e.into_inner());
+    for progress in receiver.try_iter() {
+        match progress {
+            Progress::Started(id) => {
+                started.send(SpeakStarted { id });
+            }
+            Progress::Finished(id, error) => {
+                finished.send(SpeakFinished { id, error });
+            }
+        }
+    }
+}
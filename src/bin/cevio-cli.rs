@@ -0,0 +1,583 @@
+//! CeVIO/CeVIO AI をコマンドラインから操作します。
+//!
+//! `cargo install cevio --features cli` でインストールできます。
+//!
+//! メッセージの言語は `--lang <ja|en>` か環境変数 `CEVIO_LANG` で指定できます（省略時は日本語）。
+//!
+//! 最後に使ったキャストとパラメータは `%APPDATA%\cevio-rs\settings.txt`（`settings::Settings`）に保存し、次の実行でも使います。
+
+use std::{io::BufRead, path::Path, process::ExitCode, sync::atomic::AtomicBool};
+
+use anyhow::{anyhow, bail, Context as _};
+use cevio::{
+    clipboard, diagnose, error::CeVIOError, hotkey, i18n, i18n::Lang, jsonl,
+    overwrite::OverwritePolicy, settings::Settings, subtitle::Subtitle, tail, text, tr, CeVIO,
+    HostKind, Params,
+};
+
+const USAGE_JA: &str = "\
+使い方: cevio-cli [オプション] <サブコマンド> [引数]
+        cevio-cli [オプション] --jsonl
+
+サブコマンド:
+  speak <セリフ>                   セリフを再生します
+  save <セリフ> <出力パス>         セリフを WAV ファイルとして出力します
+  list-casts                       利用可能なキャストを表示します
+  phonemes <セリフ>                セリフの音素データを表示します
+  components                       キャストの感情パラメータを表示します
+  batch <台本> <出力ディレクトリ>  台本の各行を 0001.wav, 0002.wav, ... として出力します
+  reading <台本>                   台本の各行の実際の読みをカタカナで表示します（音声は出力しません）
+  analyze <台本>                   台本の各行のモーラ数、話す速さ（モーラ/秒）、最も長い間（秒）を表示します
+  stdin [出力ディレクトリ]         標準入力から 1 行ずつ読み込んで再生します
+                                   出力ディレクトリを指定した場合は再生せずに出力します
+                                   `:cast <名前>` や `:speed <値>` で途中でパラメータを変更できます
+  clipboard [最大文字数]           クリップボードを監視し、コピーしたテキストを読み上げます
+                                   最大文字数を超えた部分は読み上げません（省略時は 200）
+  hotkey [読み上げキー] [停止キー] ホットキーで選択中のテキスト（なければクリップボード）を読み上げます
+                                   省略時は Ctrl+Alt+S で読み上げ、Ctrl+Alt+X で停止します
+  tail <ファイル> [文字列]         ファイルに追記された行を読み上げます
+                                   文字列を指定した場合は、それを含む行だけを読み上げます
+  diagnose                         実行環境を診断し、不具合の報告に使える形式で表示します
+
+オプション:
+  --cs                             CeVIO Creative Studio を使用します（省略時は CeVIO AI、cevio-cs フィーチャーが必要です）
+  --cast <名前>                    キャスト
+  --fallback-cast <名前>           キャストがない場合に代わりに使うキャスト（複数指定可、先に指定したものを優先）
+  --volume <0-100>                 音の大きさ
+  --speed <0-100>                  話す速さ
+  --tone <0-100>                   音の高さ
+  --tone-scale <0-100>             抑揚
+  --alpha <0-100>                  声質
+  --component <名前>=<0-100>       感情パラメータ（複数指定可）
+  --preset <名前>                  保存したプリセット（%APPDATA%\\cevio-rs\\settings.txt の [preset.<名前>]）
+  --no-settings                    保存した設定を読み込まず、最後に使ったパラメータも保存しません
+  --overwrite <動作>               出力先のファイルが既にある場合の動作（overwrite、skip、error、rename。省略時は overwrite）
+  --subtitle <ファイル>            speak と stdin で、再生中のセリフをファイルに書き込みます（OBS の字幕用）
+  --jsonl                          標準入力から 1 行に 1 つ JSON のリクエストを読み込み、
+                                   標準出力に 1 行に 1 つ JSON のレスポンスを書き込みます
+  --lang <ja|en>                   メッセージの言語（省略時は環境変数 CEVIO_LANG か日本語）
+  -h, --help                       この説明を表示します
+";
+
+const USAGE_EN: &str = "\
+Usage: cevio-cli [options] <subcommand> [arguments]
+       cevio-cli [options] --jsonl
+
+Subcommands:
+  speak <text>                     Speak the text
+  save <text> <output path>        Write the text to a WAV file
+  list-casts                       List the available casts
+  phonemes <text>                  Print the phonemes of the text
+  components                       Print the emotion parameters of the cast
+  batch <script> <output dir>      Write each line of the script as 0001.wav, 0002.wav, ...
+  reading <script>                 Print the actual reading of each line in katakana (without audio)
+  analyze <script>                 Print the morae, speaking rate (morae/sec) and longest pause (sec) of each line
+  stdin [output dir]               Read lines from standard input and speak them
+                                   With an output directory, write them to files instead
+                                   `:cast <name>`, `:speed <value>` etc. change parameters on the way
+  clipboard [max chars]            Watch the clipboard and speak copied text
+                                   Text beyond max chars is not spoken (default 200)
+  hotkey [speak key] [stop key]    Speak the selected text (or the clipboard) on a hotkey
+                                   Defaults to Ctrl+Alt+S to speak and Ctrl+Alt+X to stop
+  tail <file> [pattern]            Speak lines appended to the file
+                                   With a pattern, speak only lines containing it
+  diagnose                         Diagnose the environment in a form suitable for bug reports
+
+Options:
+  --cs                             Use CeVIO Creative Studio (default CeVIO AI, requires the cevio-cs feature)
+  --cast <name>                    Cast
+  --fallback-cast <name>           Cast to use when the cast is not installed (repeatable, first one wins)
+  --volume <0-100>                 Volume
+  --speed <0-100>                  Speed
+  --tone <0-100>                   Tone
+  --tone-scale <0-100>             Intonation
+  --alpha <0-100>                  Alpha
+  --component <name>=<0-100>       Emotion parameter (repeatable)
+  --preset <name>                  Saved preset ([preset.<name>] in %APPDATA%\\cevio-rs\\settings.txt)
+  --no-settings                    Neither load saved settings nor save the last used parameters
+  --overwrite <policy>             What to do when an output file exists (overwrite, skip, error, rename; default overwrite)
+  --subtitle <file>                With speak and stdin, write the current text to the file (for OBS subtitles)
+  --jsonl                          Read one JSON request per line from standard input and
+                                   write one JSON response per line to standard output
+  --lang <ja|en>                   Message language (default CEVIO_LANG or Japanese)
+  -h, --help                       Show this help
+";
+
+/// 現在の言語の使い方
+fn usage() -> &'static str {
+    Lang::current().pick(USAGE_JA, USAGE_EN)
+}
+
+/// エラーを現在の言語で表示する形式に変換します
+fn describe(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<CeVIOError>() {
+        // `context` を付けていない場合は原因をそのまま表示する
+        Some(cevio) if e.chain().count() == cevio.inner().chain().count() => cevio.localized(),
+        Some(cevio) => format!("{e}: {}", cevio.localized()),
+        None => format!("{e:#}"),
+    }
+}
+
+struct Args {
+    host: HostKind,
+    params: Params,
+    overwrite: OverwritePolicy,
+    fallback_casts: Vec<String>,
+    preset: Option<String>,
+    settings: bool,
+    subtitle: Option<Subtitle>,
+    jsonl: bool,
+    command: Vec<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Option<Args>> {
+    #[cfg_attr(not(feature = "cevio-cs"), allow(unused_mut))]
+    let mut host = HostKind::default();
+    let mut params = Params::default();
+    let mut overwrite = OverwritePolicy::default();
+    let mut fallback_casts = Vec::new();
+    let mut preset = None;
+    let mut settings = true;
+    let mut subtitle = None;
+    let mut jsonl = false;
+    let mut command = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| anyhow!(tr!("`{}` には値が必要です", "`{}` requires a value", name)))
+        };
+        let parse_i32 = |name: &str, value: String| {
+            value.parse::<i32>().with_context(|| {
+                tr!(
+                    "`{}` の値 `{}` が不正です",
+                    "Invalid value `{1}` for `{0}`",
+                    name,
+                    value
+                )
+            })
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--lang" => {
+                let lang = value("--lang")?;
+                let lang = Lang::parse(&lang).ok_or_else(|| {
+                    anyhow!(tr!(
+                        "`--lang` の値 `{}` が不正です",
+                        "Invalid value `{}` for `--lang`",
+                        lang
+                    ))
+                })?;
+                i18n::set_lang(lang);
+            }
+            #[cfg(feature = "cevio-cs")]
+            "--cs" => host = HostKind::Cs,
+            "--jsonl" => jsonl = true,
+            "--overwrite" => {
+                let policy = value("--overwrite")?;
+                overwrite = policy.parse().map_err(|_| {
+                    anyhow!(tr!(
+                        "`--overwrite` の値 `{}` が不正です",
+                        "Invalid value `{}` for `--overwrite`",
+                        policy
+                    ))
+                })?;
+            }
+            "--subtitle" => subtitle = Some(Subtitle::new(value("--subtitle")?)),
+            "--cast" => params.cast = Some(value("--cast")?),
+            "--fallback-cast" => fallback_casts.push(value("--fallback-cast")?),
+            "--preset" => preset = Some(value("--preset")?),
+            "--no-settings" => settings = false,
+            "--volume" => params.volume = Some(parse_i32("--volume", value("--volume")?)?),
+            "--speed" => params.speed = Some(parse_i32("--speed", value("--speed")?)?),
+            "--tone" => params.tone = Some(parse_i32("--tone", value("--tone")?)?),
+            "--tone-scale" => {
+                params.tone_scale = Some(parse_i32("--tone-scale", value("--tone-scale")?)?)
+            }
+            "--alpha" => params.alpha = Some(parse_i32("--alpha", value("--alpha")?)?),
+            "--component" => {
+                let component = value("--component")?;
+                let (name, v) = component.split_once('=').ok_or_else(|| {
+                    anyhow!(tr!(
+                        "`--component` は `<名前>=<値>` で指定してください",
+                        "Specify `--component` as `<name>=<value>`"
+                    ))
+                })?;
+                params
+                    .components
+                    .push((name.to_string(), parse_i32("--component", v.to_string())?));
+            }
+            _ if arg.starts_with("--") => bail!(tr!(
+                "不明なオプション `{}` です",
+                "Unknown option `{}`",
+                arg
+            )),
+            _ => command.push(arg),
+        }
+    }
+
+    if command.is_empty() && !jsonl {
+        return Ok(None);
+    }
+    Ok(Some(Args {
+        host,
+        params,
+        overwrite,
+        fallback_casts,
+        preset,
+        settings,
+        subtitle,
+        jsonl,
+        command,
+    }))
+}
+
+fn start(args: &Args) -> anyhow::Result<CeVIO> {
+    let cevio = CeVIO::with_host(args.host)?;
+    cevio.set_overwrite_policy(args.overwrite);
+    cevio.set_fallback_casts(args.fallback_casts.iter().cloned());
+    cevio.on_cast_fallback(|fallback| {
+        eprintln!(
+            "{}",
+            tr!(
+                "キャスト `{}` がないため `{}` を使います",
+                "Cast `{}` is not available, using `{}`",
+                fallback.requested,
+                fallback.used
+            )
+        )
+    });
+    cevio
+        .start_host(false)
+        .with_context(|| tr!("起動に失敗しました", "Failed to start"))?;
+    cevio.apply_params(&args.params)?;
+    Ok(cevio)
+}
+
+/// 台本を読み込み、空行と `#` から始まる行を除いた各行を返す
+///
+/// 文字コードは `text::decode` で判定する（Shift_JIS の台本も読める）
+fn read_script(path: &str) -> anyhow::Result<Vec<String>> {
+    let read_error = || {
+        tr!(
+            "台本 `{}` を読み込めません",
+            "Failed to read script `{}`",
+            path
+        )
+    };
+    let bytes = std::fs::read(path).with_context(read_error)?;
+    let script = text::decode(&bytes)
+        .map_err(anyhow::Error::from)
+        .with_context(read_error)?;
+    Ok(script
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn run(args: Args) -> anyhow::Result<()> {
+    if args.jsonl {
+        if !args.command.is_empty() {
+            bail!(tr!(
+                "`--jsonl` とサブコマンドは同時に指定できません",
+                "`--jsonl` cannot be combined with a subcommand"
+            ));
+        }
+        let cevio = start(&args)?;
+        jsonl::serve(&cevio, std::io::stdin().lock(), std::io::stdout().lock())?;
+        return Ok(());
+    }
+    let command: Vec<&str> = args.command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["speak", text] => speak(&start(&args)?, text, args.subtitle.as_ref())?,
+        ["save", text, path] => {
+            start(&args)?.output_wave_to_file(text, path)?;
+        }
+        ["list-casts"] => {
+            for cast in start(&args)?.get_available_casts()? {
+                println!("{cast}");
+            }
+        }
+        ["phonemes", text] => {
+            for phoneme in start(&args)?.get_phonemes(text)? {
+                println!(
+                    "{:.3}\t{:.3}\t{}",
+                    phoneme.start_time, phoneme.end_time, phoneme.phoneme
+                );
+            }
+        }
+        ["components"] => {
+            for component in start(&args)?.get_components()? {
+                println!("{}\t{}", component.name, component.value);
+            }
+        }
+        ["batch", script, out_dir] => {
+            let lines = read_script(script)?;
+            std::fs::create_dir_all(out_dir)
+                .with_context(|| tr!("`{}` を作成できません", "Failed to create `{}`", out_dir))?;
+            let cevio = start(&args)?;
+            for (i, line) in lines.iter().enumerate() {
+                let path = Path::new(out_dir).join(format!("{:04}.wav", i + 1));
+                let path = cevio.output_wave(line, &path)?.unwrap_or(path);
+                println!("{}\t{line}", path.display());
+            }
+        }
+        ["reading", script] => {
+            let lines = read_script(script)?;
+            let cevio = start(&args)?;
+            for line in &lines {
+                println!("{line}\t{}", cevio.check_reading(line)?.kana);
+            }
+        }
+        ["analyze", script] => {
+            let lines = read_script(script)?;
+            let cevio = start(&args)?;
+            for line in &lines {
+                let stats = cevio.analyze(line)?;
+                println!(
+                    "{}\t{:.2}\t{:.2}\t{line}",
+                    stats.morae,
+                    stats.morae_per_sec().unwrap_or_default(),
+                    stats.longest_pause
+                );
+            }
+        }
+        ["stdin"] => read_stdin(&start(&args)?, None, args.subtitle.as_ref())?,
+        ["stdin", out_dir] => {
+            std::fs::create_dir_all(out_dir)
+                .with_context(|| tr!("`{}` を作成できません", "Failed to create `{}`", out_dir))?;
+            read_stdin(&start(&args)?, Some(Path::new(out_dir)), None)?;
+        }
+        ["clipboard"] => watch_clipboard(&start(&args)?, None)?,
+        ["clipboard", max_chars] => {
+            let max_chars = max_chars.parse::<usize>().with_context(|| {
+                tr!(
+                    "最大文字数 `{}` が不正です",
+                    "Invalid max chars `{}`",
+                    max_chars
+                )
+            })?;
+            watch_clipboard(&start(&args)?, Some(max_chars))?;
+        }
+        ["hotkey", keys @ ..] if keys.len() <= 2 => {
+            let mut options = hotkey::HotkeyOptions::default();
+            if let Some(key) = keys.first() {
+                options.speak = key.parse()?;
+            }
+            if let Some(key) = keys.get(1) {
+                options.stop = key.parse()?;
+            }
+            eprintln!(
+                "{}",
+                tr!(
+                    "ホットキーを待っています（Ctrl+C で終了）",
+                    "Waiting for hotkeys (Ctrl+C to exit)"
+                )
+            );
+            hotkey::run(&start(&args)?, &options, &AtomicBool::new(false))?;
+        }
+        ["tail", path] => tail_file(&start(&args)?, path, "")?,
+        ["tail", path, pattern] => tail_file(&start(&args)?, path, pattern)?,
+        ["diagnose"] => print!("{}", diagnose::diagnose()),
+        _ => bail!(tr!(
+            "引数が不正です\n\n{}",
+            "Invalid arguments\n\n{}",
+            usage()
+        )),
+    }
+    Ok(())
+}
+
+/// セリフを再生し、再生終了まで待ちます
+fn speak(cevio: &CeVIO, text: &str, subtitle: Option<&Subtitle>) -> anyhow::Result<()> {
+    match subtitle {
+        Some(subtitle) => cevio.speak_with_subtitle(text, subtitle)?,
+        None => cevio.speak(text)?.wait()?,
+    }
+    Ok(())
+}
+
+/// 標準入力の各行を再生（`out_dir` がある場合は出力）します。`:` から始まる行はコマンドとして扱います
+fn read_stdin(
+    cevio: &CeVIO,
+    out_dir: Option<&Path>,
+    subtitle: Option<&Subtitle>,
+) -> anyhow::Result<()> {
+    let mut count = 0;
+    for line in std::io::stdin().lock().lines() {
+        let line =
+            line.with_context(|| tr!("標準入力を読み込めません", "Failed to read standard input"))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(command) = line.strip_prefix(':') {
+            match command.trim() {
+                "q" | "quit" => break,
+                command => {
+                    // コマンドの誤りで読み上げを止めないよう、エラーは表示だけして続ける
+                    if let Err(e) = run_inline_command(cevio, command) {
+                        eprintln!("{}", tr!("エラー: {}", "Error: {}", describe(&e)));
+                    }
+                }
+            }
+            continue;
+        }
+        match out_dir {
+            Some(out_dir) => {
+                count += 1;
+                let path = out_dir.join(format!("{count:04}.wav"));
+                let path = cevio.output_wave(line, &path)?.unwrap_or(path);
+                println!("{}\t{line}", path.display());
+            }
+            None => speak(cevio, line, subtitle)?,
+        }
+    }
+    Ok(())
+}
+
+/// Ctrl+C で終了するまでクリップボードを監視します
+fn watch_clipboard(cevio: &CeVIO, max_chars: Option<usize>) -> anyhow::Result<()> {
+    let mut options = clipboard::WatchOptions::default();
+    if max_chars.is_some() {
+        options.max_chars = max_chars;
+    }
+    eprintln!(
+        "{}",
+        tr!(
+            "クリップボードを監視しています（Ctrl+C で終了）",
+            "Watching the clipboard (Ctrl+C to exit)"
+        )
+    );
+    clipboard::watch(cevio, &options, &AtomicBool::new(false))?;
+    Ok(())
+}
+
+/// Ctrl+C で終了するまで `path` に追記された `pattern` を含む行を読み上げます
+fn tail_file(cevio: &CeVIO, path: &str, pattern: &str) -> anyhow::Result<()> {
+    eprintln!(
+        "{}",
+        tr!(
+            "`{}` を監視しています（Ctrl+C で終了）",
+            "Watching `{}` (Ctrl+C to exit)",
+            path
+        )
+    );
+    tail::tail_and_speak(
+        cevio,
+        path,
+        |line| line.contains(pattern),
+        &tail::TailOptions::default(),
+        &AtomicBool::new(false),
+    )?;
+    Ok(())
+}
+
+fn run_inline_command(cevio: &CeVIO, command: &str) -> anyhow::Result<()> {
+    let (name, value) = command
+        .split_once(char::is_whitespace)
+        .unwrap_or((command, ""));
+    let value = value.trim();
+    let parse_i32 = || {
+        value.parse::<i32>().with_context(|| {
+            tr!(
+                "`:{}` の値 `{}` が不正です",
+                "Invalid value `{1}` for `:{0}`",
+                name,
+                value
+            )
+        })
+    };
+    match name {
+        "cast" => cevio.set_cast(value)?,
+        "volume" => cevio.set_volume(parse_i32()?)?,
+        "speed" => cevio.set_speed(parse_i32()?)?,
+        "tone" => cevio.set_tone(parse_i32()?)?,
+        "tone-scale" => cevio.set_tone_scale(parse_i32()?)?,
+        "alpha" => cevio.set_alpha(parse_i32()?)?,
+        "component" => {
+            let (name, v) = value.split_once('=').ok_or_else(|| {
+                anyhow!(tr!(
+                    "`:component` は `<名前>=<値>` で指定してください",
+                    "Specify `:component` as `<name>=<value>`"
+                ))
+            })?;
+            let v = v.trim().parse::<i32>().with_context(|| {
+                tr!(
+                    "`:component` の値 `{}` が不正です",
+                    "Invalid value `{}` for `:component`",
+                    v
+                )
+            })?;
+            cevio.set_component(name.trim(), v)?;
+        }
+        _ => bail!(tr!(
+            "不明なコマンド `:{}` です",
+            "Unknown command `:{}`",
+            name
+        )),
+    }
+    Ok(())
+}
+
+/// 保存した設定を読み込み、最後に使ったパラメータ、プリセット、引数のパラメータの順に上書きしたものを `args.params` にする
+///
+/// 戻り値は、実行に成功した後に保存する設定
+fn load_settings(args: &mut Args) -> anyhow::Result<Option<Settings>> {
+    if !args.settings {
+        if let Some(preset) = &args.preset {
+            bail!(tr!(
+                "`--preset {}` は `--no-settings` と同時に指定できません",
+                "`--preset {}` cannot be combined with `--no-settings`",
+                preset
+            ));
+        }
+        return Ok(None);
+    }
+    let mut settings =
+        Settings::load().with_context(|| tr!("設定を読み込めません", "Failed to load settings"))?;
+    let preset = match &args.preset {
+        Some(name) => settings.presets.get(name).cloned().ok_or_else(|| {
+            anyhow!(tr!(
+                "プリセット `{}` がありません",
+                "Unknown preset `{}`",
+                name
+            ))
+        })?,
+        None => Params::default(),
+    };
+    args.params = settings.params.merge(&preset).merge(&args.params);
+    settings.remember(&args.params);
+    Ok(Some(settings))
+}
+
+fn main() -> ExitCode {
+    let mut args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", usage());
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}", tr!("エラー: {}", "Error: {}", describe(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = load_settings(&mut args).and_then(|settings| {
+        run(args)?;
+        if let Some(settings) = settings {
+            settings.save()?;
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", tr!("エラー: {}", "Error: {}", describe(&e)));
+            ExitCode::FAILURE
+        }
+    }
+}
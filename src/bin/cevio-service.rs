@@ -0,0 +1,306 @@
+//! HTTP サーバーを Windows サービスとしてインストール・実行します。
+//!
+//! `cargo install cevio --features service` でインストールできます。
+//!
+//! メッセージの言語は `--lang <ja|en>` か環境変数 `CEVIO_LANG` で指定できます（省略時は日本語）。
+
+use std::process::ExitCode;
+
+use anyhow::{anyhow, bail, Context as _};
+use cevio::{
+    error::CeVIOError,
+    i18n::{self, Lang},
+    service::{self, ServiceOptions},
+};
+
+/// 現在の言語で書式を選んで `String` を作る
+macro_rules! tr {
+    ($ja:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        match Lang::current() {
+            Lang::Ja => format!($ja $(, $arg)*),
+            Lang::En => format!($en $(, $arg)*),
+        }
+    };
+}
+
+const USAGE_JA: &str = "\
+使い方: cevio-service [オプション] <サブコマンド>
+
+サブコマンド:
+  install                          サービスをインストールします（管理者権限が必要です）
+  uninstall                        サービスを停止してアンインストールします（管理者権限が必要です）
+  run                              サービスとして実行します（サービスコントロールマネージャーが使用します）
+  console                          サービスと同じサーバーをこのコンソールで実行します
+
+オプション:
+  --name <名前>                    サービス名（省略時は cevio-rs）
+  --cs                             CeVIO Creative Studio を使用します（省略時は CeVIO AI、cevio-cs フィーチャーが必要です）
+  --addr <アドレス>                HTTP サーバーのアドレス（省略時は 127.0.0.1:8080）
+  --pipe <パイプ名>                名前付きパイプサーバーも起動します（pipe フィーチャーが必要です）
+  --no-start-host                  開始時に CeVIO を起動しません
+  --config <パス>                  設定ファイル（POST /config/reload で読み込み直します）
+  --token <トークン>                HTTP サーバーに必要なトークン（Authorization: Bearer <トークン>）
+  --allow <アドレス>               接続を許可するアドレス（192.168.0.0/16 のような範囲も可、複数指定可）
+  --user <アカウント>              サービスを実行するアカウント（install のみ）
+  --password <パスワード>          アカウントのパスワード（install のみ）
+  --lang <ja|en>                   メッセージの言語（省略時は環境変数 CEVIO_LANG か日本語）
+  -h, --help                       この説明を表示します
+";
+
+const USAGE_EN: &str = "\
+Usage: cevio-service [options] <subcommand>
+
+Subcommands:
+  install                          Install the service (requires administrator rights)
+  uninstall                        Stop and uninstall the service (requires administrator rights)
+  run                              Run as a service (used by the service control manager)
+  console                          Run the same server as the service in this console
+
+Options:
+  --name <name>                    Service name (default cevio-rs)
+  --cs                             Use CeVIO Creative Studio (default CeVIO AI, requires the cevio-cs feature)
+  --addr <address>                 Address of the HTTP server (default 127.0.0.1:8080)
+  --pipe <pipe name>               Also start the named pipe server (requires the pipe feature)
+  --no-start-host                  Do not start CeVIO when the service starts
+  --config <path>                  Config file (reloaded with POST /config/reload)
+  --token <token>                  Token required by the HTTP server (Authorization: Bearer <token>)
+  --allow <address>                Allowed client address (ranges like 192.168.0.0/16, repeatable)
+  --user <account>                 Account to run the service as (install only)
+  --password <password>            Password of the account (install only)
+  --lang <ja|en>                   Message language (default CEVIO_LANG or Japanese)
+  -h, --help                       Show this help
+";
+
+/// 現在の言語の使い方
+fn usage() -> &'static str {
+    Lang::current().pick(USAGE_JA, USAGE_EN)
+}
+
+/// エラーを現在の言語で表示する形式に変換します
+fn describe(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<CeVIOError>() {
+        // `context` を付けていない場合は原因をそのまま表示する
+        Some(cevio) if e.chain().count() == cevio.inner().chain().count() => cevio.localized(),
+        Some(cevio) => format!("{e}: {}", cevio.localized()),
+        None => format!("{e:#}"),
+    }
+}
+
+struct Args {
+    name: String,
+    options: ServiceOptions,
+    /// サービスとして起動するときに渡すオプション
+    forwarded: Vec<String>,
+    account: Option<(String, String)>,
+    command: String,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> anyhow::Result<Option<Args>> {
+    let mut name = service::DEFAULT_SERVICE_NAME.to_string();
+    let mut options = ServiceOptions::default();
+    let mut forwarded = Vec::new();
+    let mut user = None;
+    let mut password = None;
+    let mut command = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| anyhow!(tr!("`{}` には値が必要です", "`{}` requires a value", name)))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--lang" => {
+                let value = value("--lang")?;
+                let lang = Lang::parse(&value).ok_or_else(|| {
+                    anyhow!(tr!(
+                        "`--lang` の値 `{}` が不正です",
+                        "Invalid value `{}` for `--lang`",
+                        value
+                    ))
+                })?;
+                i18n::set_lang(lang);
+                forwarded.extend([arg, value]);
+            }
+            "--name" => name = value("--name")?,
+            #[cfg(feature = "cevio-cs")]
+            "--cs" => {
+                options.host = cevio::HostKind::Cs;
+                forwarded.push(arg);
+            }
+            "--addr" => {
+                options.addr = value("--addr")?;
+                forwarded.extend([arg, options.addr.clone()]);
+            }
+            #[cfg(feature = "pipe")]
+            "--pipe" => {
+                let pipe = value("--pipe")?;
+                forwarded.extend([arg, pipe.clone()]);
+                options.pipe_name = Some(pipe);
+            }
+            #[cfg(not(feature = "pipe"))]
+            "--pipe" => bail!(tr!(
+                "`--pipe` には pipe フィーチャーが必要です",
+                "`--pipe` requires the pipe feature"
+            )),
+            "--no-start-host" => {
+                options.start_host = false;
+                forwarded.push(arg);
+            }
+            "--config" => {
+                let path = std::path::absolute(value("--config")?).with_context(|| {
+                    tr!(
+                        "設定ファイルのパスを取得できません",
+                        "Failed to get the path of the config file"
+                    )
+                })?;
+                let path_str = path.to_str().ok_or_else(|| {
+                    anyhow!(tr!(
+                        "パス `{}` が UTF-8 ではありません",
+                        "Path `{}` is not UTF-8",
+                        path.display()
+                    ))
+                })?;
+                forwarded.extend([arg, path_str.to_string()]);
+                options.config = Some(path);
+            }
+            "--token" => {
+                let token = value("--token")?;
+                forwarded.extend([arg, token.clone()]);
+                options.token = Some(token);
+            }
+            "--allow" => {
+                let addr = value("--allow")?;
+                forwarded.extend([arg, addr.clone()]);
+                options.allow.push(addr);
+            }
+            "--user" => user = Some(value("--user")?),
+            "--password" => password = Some(value("--password")?),
+            _ if arg.starts_with("--") => bail!(tr!(
+                "不明なオプション `{}` です",
+                "Unknown option `{}`",
+                arg
+            )),
+            _ if command.is_none() => command = Some(arg),
+            _ => bail!(tr!(
+                "引数が不正です\n\n{}",
+                "Invalid arguments\n\n{}",
+                usage()
+            )),
+        }
+    }
+
+    let account = match (user, password) {
+        (Some(user), password) => Some((user, password.unwrap_or_default())),
+        (None, Some(_)) => bail!(tr!(
+            "`--password` には `--user` が必要です",
+            "`--password` requires `--user`"
+        )),
+        (None, None) => None,
+    };
+    let Some(command) = command else {
+        return Ok(None);
+    };
+    Ok(Some(Args {
+        name,
+        options,
+        forwarded,
+        account,
+        command,
+    }))
+}
+
+/// 空白を含む引数を `"` で囲む
+fn quote(arg: &str) -> String {
+    match arg.contains(char::is_whitespace) {
+        true => format!("\"{arg}\""),
+        false => arg.to_string(),
+    }
+}
+
+fn run(args: Args) -> anyhow::Result<()> {
+    match args.command.as_str() {
+        "install" => {
+            let exe = std::env::current_exe().with_context(|| {
+                tr!(
+                    "実行ファイルのパスを取得できません",
+                    "Failed to get the path of the executable"
+                )
+            })?;
+            let exe = exe.to_str().ok_or_else(|| {
+                anyhow!(tr!(
+                    "パス `{}` が UTF-8 ではありません",
+                    "Path `{}` is not UTF-8",
+                    exe.display()
+                ))
+            })?;
+            let command_line = [quote(exe), "run".to_string(), "--name".to_string()]
+                .into_iter()
+                .chain([quote(&args.name)])
+                .chain(args.forwarded.iter().map(|arg| quote(arg)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            service::install(
+                &args.name,
+                &tr!(
+                    "CeVIO 読み上げサーバー ({})",
+                    "CeVIO speech server ({})",
+                    args.name
+                ),
+                &command_line,
+                args.account
+                    .as_ref()
+                    .map(|(user, password)| (user.as_str(), password.as_str())),
+            )?;
+            println!(
+                "{}",
+                tr!(
+                    "サービス `{}` をインストールしました",
+                    "Installed service `{}`",
+                    args.name
+                )
+            );
+        }
+        "uninstall" => {
+            service::uninstall(&args.name)?;
+            println!(
+                "{}",
+                tr!(
+                    "サービス `{}` をアンインストールしました",
+                    "Uninstalled service `{}`",
+                    args.name
+                )
+            );
+        }
+        "run" => service::run(&args.name, args.options)?,
+        "console" => service::run_foreground(&args.options)?,
+        _ => bail!(tr!(
+            "引数が不正です\n\n{}",
+            "Invalid arguments\n\n{}",
+            usage()
+        )),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", usage());
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}", tr!("エラー: {}", "Error: {}", describe(&e)));
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", tr!("エラー: {}", "Error: {}", describe(&e)));
+            ExitCode::FAILURE
+        }
+    }
+}
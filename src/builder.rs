@@ -0,0 +1,265 @@
+//! 設定をまとめてインスタンスを作成するビルダー
+//!
+//! `CeVIO::builder` で作成し、`build` で製品の起動、キャストの確認、パラメータの設定をまとめて行います。
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use cevio::{CeVIO, HostKind};
+//!
+//! let cevio = CeVIO::builder()
+//!     .host(HostKind::default())
+//!     .auto_start(true)
+//!     .timeout(Duration::from_secs(60))
+//!     .cast("花隈千冬")
+//!     .volume(90)
+//!     .build()
+//!     .unwrap();
+//! cevio.speak("こんにちは").unwrap().wait().unwrap();
+//! ```
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{self, report},
+    overwrite::OverwritePolicy,
+    CeVIO, HostKind, Params,
+};
+
+pub use crate::com::{LOCALE_SYSTEM_DEFAULT, LOCALE_USER_DEFAULT};
+
+/// 起動を待つ間の確認の間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `CeVIO` を作成するビルダーです。
+#[derive(Debug, Clone)]
+pub struct CeVIOBuilder {
+    host: HostKind,
+    auto_start: bool,
+    timeout: Option<Duration>,
+    strict: bool,
+    locale: u32,
+    overwrite: OverwritePolicy,
+    base_dir: Option<PathBuf>,
+    #[cfg(feature = "history")]
+    history: Option<crate::history::History>,
+    cast_profiles: Vec<(String, Params)>,
+    fallback_casts: Vec<String>,
+    warm_up: bool,
+    params: Params,
+}
+
+impl Default for CeVIOBuilder {
+    fn default() -> Self {
+        Self {
+            host: HostKind::default(),
+            auto_start: false,
+            timeout: None,
+            strict: true,
+            locale: LOCALE_USER_DEFAULT,
+            overwrite: OverwritePolicy::default(),
+            base_dir: None,
+            #[cfg(feature = "history")]
+            history: None,
+            cast_profiles: Vec::new(),
+            fallback_casts: Vec::new(),
+            warm_up: false,
+            params: Params::default(),
+        }
+    }
+}
+
+impl CeVIO {
+    /// 設定をまとめてインスタンスを作成するビルダーを取得します。
+    pub fn builder() -> CeVIOBuilder {
+        CeVIOBuilder::default()
+    }
+}
+
+impl CeVIOBuilder {
+    /// 操作する製品を指定します。省略時は `HostKind::default()` です。
+    pub fn host(mut self, host: HostKind) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// `build` で製品を起動するかどうかを指定します。省略時は起動しません。
+    pub fn auto_start(mut self, auto_start: bool) -> Self {
+        self.auto_start = auto_start;
+        self
+    }
+
+    /// 起動を待つ最大時間を指定します。省略時はアクセス可能になるまで待ち続けます。
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 厳格モード（`CeVIO::set_strict`）を指定します。省略時は有効です。
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// 出力先のファイルが既にある場合の動作（`CeVIO::set_overwrite_policy`）を指定します。省略時は上書きします。
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// 出力先の相対パスを解決するディレクトリ（`CeVIO::set_base_dir`）を指定します。省略時はカレントディレクトリから解決します。
+    pub fn base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
+    /// 合成の履歴を追記するファイル（`CeVIO::set_history`）を指定します。省略時は記録しません。
+    #[cfg(feature = "history")]
+    pub fn history(mut self, history: crate::history::History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// COM の呼び出し（`GetIDsOfNames` と `Invoke`）に使うロケール ID を指定します。省略時は `LOCALE_USER_DEFAULT` です。
+    ///
+    /// 日本語以外のロケールの Windows でメソッド名やプロパティ名を解決できない場合に、`0x0411`（日本語）などを指定します。
+    pub fn locale(mut self, locale: u32) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// キャストを指定します。`build` で利用可能なキャストか確認します。
+    pub fn cast(mut self, cast: impl Into<String>) -> Self {
+        self.params.cast = Some(cast.into());
+        self
+    }
+
+    /// 音の大きさ（0～100）を指定します。
+    pub fn volume(mut self, volume: i32) -> Self {
+        self.params.volume = Some(volume);
+        self
+    }
+
+    /// 話す速さ（0～100）を指定します。
+    pub fn speed(mut self, speed: i32) -> Self {
+        self.params.speed = Some(speed);
+        self
+    }
+
+    /// 音の高さ（0～100）を指定します。
+    pub fn tone(mut self, tone: i32) -> Self {
+        self.params.tone = Some(tone);
+        self
+    }
+
+    /// 抑揚（0～100）を指定します。
+    pub fn tone_scale(mut self, tone_scale: i32) -> Self {
+        self.params.tone_scale = Some(tone_scale);
+        self
+    }
+
+    /// 声質（0～100）を指定します。
+    pub fn alpha(mut self, alpha: i32) -> Self {
+        self.params.alpha = Some(alpha);
+        self
+    }
+
+    /// 感情パラメータ（0～100）を指定します。複数回呼ぶとすべて設定します。
+    pub fn component(mut self, name: impl Into<String>, value: i32) -> Self {
+        self.params.components.push((name.into(), value));
+        self
+    }
+
+    /// パラメータをまとめて指定します。個別に指定した項目は `params` で上書きされます。
+    pub fn params(mut self, params: &Params) -> Self {
+        self.params = self.params.merge(params);
+        self
+    }
+
+    /// キャストごとの既定のパラメータ（`CeVIO::set_cast_profile`）を登録します。複数回呼ぶとすべて登録します。
+    pub fn cast_profile(mut self, cast: impl Into<String>, params: Params) -> Self {
+        self.cast_profiles.push((cast.into(), params));
+        self
+    }
+
+    /// キャストがインストールされていない場合に選ぶキャスト（`CeVIO::set_fallback_casts`）を、優先する順に指定します。
+    pub fn fallback_casts(mut self, casts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.fallback_casts = casts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// `build` でキャストを設定した後に `CeVIO::warm_up` を呼ぶかどうかを指定します。省略時は呼びません。
+    ///
+    /// キャストを指定しなかった場合は呼びません。
+    pub fn warm_up(mut self, warm_up: bool) -> Self {
+        self.warm_up = warm_up;
+        self
+    }
+
+    /// 指定した製品を取得します。
+    pub fn get_host(&self) -> HostKind {
+        self.host
+    }
+
+    /// インスタンスを作成し、指定した設定を適用します。
+    ///
+    /// 次の順に行い、最初に失敗したところでエラーを返します。
+    ///
+    /// 1. インスタンスの作成
+    /// 2. 製品の起動（`auto_start(true)` の場合。`timeout` を過ぎると `CeVIOError::Timeout`）
+    /// 3. キャストの確認（利用可能でない場合は `CeVIOError::InvalidCast`）
+    /// 4. キャスト、パラメータ、感情パラメータの設定
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(host = ?self.host), err)
+    )]
+    pub fn build(self) -> error::Result<CeVIO> {
+        let cevio = CeVIO::with_host_and_locale(self.host, self.locale)?;
+        cevio.set_strict(self.strict);
+        cevio.set_overwrite_policy(self.overwrite);
+        cevio.set_base_dir(self.base_dir.as_deref())?;
+        #[cfg(feature = "history")]
+        cevio.set_history(self.history);
+        cevio.set_cast_profiles(self.cast_profiles);
+        cevio.set_fallback_casts(self.fallback_casts.iter().cloned());
+        if self.auto_start {
+            match self.timeout {
+                Some(timeout) => start_with_timeout(&cevio, timeout)?,
+                None => cevio.start_host(false)?,
+            }
+        }
+        if let Some(cast) = &self.params.cast {
+            let casts = cevio.get_available_casts()?;
+            let has_fallback = self.fallback_casts.iter().any(|c| casts.contains(c));
+            if !casts.contains(cast) && !has_fallback {
+                return Err(error::CeVIOError::InvalidCast(report!(
+                    "Cast `{cast}` is not available (available: {})",
+                    casts.join(", ")
+                )));
+            }
+        }
+        cevio.apply_params(&self.params)?;
+        if self.warm_up && self.params.cast.is_some() {
+            cevio.warm_up()?;
+        }
+        Ok(cevio)
+    }
+}
+
+/// 製品を起動し、`timeout` までにアクセス可能になるのを待つ
+fn start_with_timeout(cevio: &CeVIO, timeout: Duration) -> error::Result<()> {
+    let deadline = Instant::now() + timeout;
+    cevio.start_host(true)?;
+    while !cevio.get_is_host_started()? {
+        if Instant::now() >= deadline {
+            return Err(error::CeVIOError::Timeout(report!(
+                "{} did not become accessible within {timeout:?}",
+                cevio.host().product_name()
+            )));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
@@ -0,0 +1,266 @@
+//! C 言語から使うための API（`capi` フィーチャー）
+//!
+//! ヘッダーはクレートに同梱している `include/cevio.h` です。
+//! DLL は `cargo rustc --release --lib --features capi --crate-type cdylib` でビルドします。
+//!
+//! 戻り値が `int` の関数は、成功した場合は `0`、失敗した場合は `-1` を返します。
+//! 失敗した理由は `cevio_last_error` で取得できます。
+//!
+//! ハンドルは内部で専用スレッドを持つため、どのスレッドから呼び出しても構いません。
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    ptr,
+};
+
+use crate::{
+    actor::Handle,
+    error::{self, report},
+    HostKind,
+};
+
+/// C 言語に渡すハンドル
+pub struct CevioHandle(Handle);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(e: &error::CeVIOError) {
+    // エラーメッセージに NUL が含まれることはまずないが、含まれていたら取り除く
+    let message = format!("{e:#}").replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// 結果を C の戻り値に変換する
+fn to_status(result: error::Result<()>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(&e);
+            -1
+        }
+    }
+}
+
+unsafe fn handle_ref<'a>(handle: *const CevioHandle) -> error::Result<&'a Handle> {
+    handle
+        .as_ref()
+        .map(|h| &h.0)
+        .ok_or_else(|| error::CeVIOError::InvalidInput(report!("Handle is null")))
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> error::Result<&'a str> {
+    if s.is_null() {
+        return Err(error::CeVIOError::InvalidInput(report!("`{name}` is null")));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| error::CeVIOError::InvalidInput(report!("`{name}` is not valid UTF-8")))
+}
+
+/// 直前に失敗した関数のエラーメッセージ（UTF-8）を取得します。
+///
+/// 同じスレッドで次に失敗するまで有効です。エラーがない場合は NULL を返します。
+#[no_mangle]
+pub extern "C" fn cevio_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// ハンドルを作成します。失敗した場合は NULL を返します。
+///
+/// `host` は `0` で CeVIO AI、`1` で CeVIO Creative Studio です。無効にした製品を指定した場合も NULL を返します。
+#[no_mangle]
+pub extern "C" fn cevio_create(host: c_int) -> *mut CevioHandle {
+    let host = match host {
+        #[cfg(feature = "cevio-ai")]
+        0 => HostKind::Ai,
+        #[cfg(feature = "cevio-cs")]
+        1 => HostKind::Cs,
+        _ => {
+            set_last_error(&error::CeVIOError::InvalidInput(report!(
+                "Unknown host `{host}`"
+            )));
+            return ptr::null_mut();
+        }
+    };
+    match Handle::spawn(host) {
+        Ok(handle) => Box::into_raw(Box::new(CevioHandle(handle))),
+        Err(e) => {
+            set_last_error(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// ハンドルを破棄します。NULL の場合は何もしません。
+///
+/// # Safety
+///
+/// `handle` は `cevio_create` が返したもので、まだ破棄していないものである必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn cevio_destroy(handle: *mut CevioHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// 【CeVIO Creative Studio】を起動します。
+///
+/// 成功した場合は `0` を、起動に失敗した場合は `StartHost` の戻り値（`-1`～`-4`）を、呼び出しに失敗した場合は `-100` を返します。
+///
+/// # Safety
+///
+/// `handle` は `cevio_create` が返した有効なハンドルである必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn cevio_start_host(handle: *const CevioHandle, no_wait: bool) -> c_int {
+    let result = handle_ref(handle).and_then(|h| h.call(move |cevio| cevio.start_host(no_wait)));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(&e);
+            e.host_start_error().map_or(-100, |e| e.code())
+        }
+    }
+}
+
+/// キャストを設定します。
+///
+/// # Safety
+///
+/// `handle` は有効なハンドル、`cast` は NUL 終端の UTF-8 文字列である必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn cevio_set_cast(handle: *const CevioHandle, cast: *const c_char) -> c_int {
+    to_status((|| {
+        let cast = str_arg(cast, "cast")?.to_string();
+        handle_ref(handle)?.call(move |cevio| cevio.set_cast(&cast))
+    })())
+}
+
+macro_rules! param_setter {
+    ($(#[$doc:meta])* $name:ident, $method:ident) => {
+        $(#[$doc])*
+        ///
+        /// # Safety
+        ///
+        /// `handle` は `cevio_create` が返した有効なハンドルである必要があります。
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(handle: *const CevioHandle, value: c_int) -> c_int {
+            to_status(handle_ref(handle).and_then(|h| h.call(move |cevio| cevio.$method(value))))
+        }
+    };
+}
+
+param_setter!(
+    /// 音の大きさ（0～100）を設定します。
+    cevio_set_volume,
+    set_volume
+);
+param_setter!(
+    /// 話す速さ（0～100）を設定します。
+    cevio_set_speed,
+    set_speed
+);
+param_setter!(
+    /// 音の高さ（0～100）を設定します。
+    cevio_set_tone,
+    set_tone
+);
+param_setter!(
+    /// 抑揚（0～100）を設定します。
+    cevio_set_tone_scale,
+    set_tone_scale
+);
+param_setter!(
+    /// 声質（0～100）を設定します。
+    cevio_set_alpha,
+    set_alpha
+);
+
+/// 感情パラメータ（0～100）を名前で指定して設定します。
+///
+/// # Safety
+///
+/// `handle` は有効なハンドル、`name` は NUL 終端の UTF-8 文字列である必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn cevio_set_component(
+    handle: *const CevioHandle,
+    name: *const c_char,
+    value: c_int,
+) -> c_int {
+    to_status((|| {
+        let name = str_arg(name, "name")?.to_string();
+        handle_ref(handle)?.call(move |cevio| cevio.set_component(&name, value))
+    })())
+}
+
+/// セリフを再生します。`wait` が true の場合は再生終了まで戻りません。
+///
+/// # Safety
+///
+/// `handle` は有効なハンドル、`text` は NUL 終端の UTF-8 文字列である必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn cevio_speak(
+    handle: *const CevioHandle,
+    text: *const c_char,
+    wait: bool,
+) -> c_int {
+    to_status((|| {
+        let text = str_arg(text, "text")?.to_string();
+        handle_ref(handle)?.call(move |cevio| {
+            let state = cevio.speak(&text)?;
+            if wait {
+                state.wait()?;
+            }
+            Ok(())
+        })
+    })())
+}
+
+/// セリフを WAV 形式のバイト列に変換します。
+///
+/// 成功した場合は `*out` と `*out_len` にバッファとその長さを書き込みます。
+/// バッファは `cevio_free_buffer` で解放してください。
+///
+/// # Safety
+///
+/// `handle` は有効なハンドル、`text` は NUL 終端の UTF-8 文字列、
+/// `out` と `out_len` は書き込み可能なポインタである必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn cevio_synthesize(
+    handle: *const CevioHandle,
+    text: *const c_char,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    to_status((|| {
+        if out.is_null() || out_len.is_null() {
+            return Err(error::CeVIOError::InvalidInput(report!(
+                "`out` or `out_len` is null"
+            )));
+        }
+        let text = str_arg(text, "text")?.to_string();
+        let wav = handle_ref(handle)?.call(move |cevio| cevio.output_wave_to_vec(&text))?;
+        let wav = Box::into_raw(wav.into_boxed_slice());
+        *out_len = wav.len();
+        *out = wav.cast();
+        Ok(())
+    })())
+}
+
+/// `cevio_synthesize` で取得したバッファを解放します。NULL の場合は何もしません。
+///
+/// # Safety
+///
+/// `buffer` と `len` は `cevio_synthesize` が書き込んだものである必要があります。
+#[no_mangle]
+pub unsafe extern "C" fn cevio_free_buffer(buffer: *mut u8, len: usize) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, len)));
+    }
+}
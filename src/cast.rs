@@ -0,0 +1,249 @@
+use crate::{error, observer::CastFallback, CeVIO, HostKind, Params};
+
+/// キャストの言語です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    /// 日本語
+    Japanese,
+    /// 英語
+    English,
+}
+
+impl Language {
+    /// キャスト名から言語を推定します。
+    ///
+    /// API から言語を取得する方法がないため、英語キャストの名前が ASCII 文字のみで構成されていることを利用しています。
+    pub fn guess(cast: &str) -> Self {
+        if cast.is_ascii() {
+            Language::English
+        } else {
+            Language::Japanese
+        }
+    }
+}
+
+/// キャストの情報です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CastInfo {
+    /// キャスト名
+    pub name: String,
+    /// キャストを扱う製品
+    pub host: HostKind,
+    /// 言語
+    pub language: Language,
+    /// 感情パラメータの名前
+    pub components: Vec<String>,
+}
+
+/// インストールされているすべての製品のキャスト一覧です。
+#[derive(Debug, Clone, Default)]
+pub struct Casts {
+    casts: Vec<CastInfo>,
+    failures: Vec<(HostKind, String)>,
+}
+
+impl Casts {
+    /// インストールされている製品をすべて起動し、利用可能なキャストを取得します。
+    ///
+    /// インストールされていない製品は無視します。起動やキャストの取得に失敗した製品は飛ばして残りの製品を調べ、
+    /// そのエラーは `failures` で取得できます。
+    ///
+    /// 備考：
+    ///
+    /// 　起動していない製品は起動します（`CeVIO::start_host`）。取得した後も起動したままです。
+    /// 　起動したくない場合は、起動している製品の `CeVIO` を `discover_in` に渡してください。
+    ///
+    /// 　感情パラメータを取得するため、一時的にキャストを切り替えます。取得後は元のキャストに戻します。
+    pub fn discover() -> error::Result<Self> {
+        let mut casts = Self::default();
+        for &host in HostKind::ALL {
+            // COM オブジェクトを作れない製品はインストールされていない
+            let Ok(cevio) = CeVIO::with_host(host) else {
+                continue;
+            };
+            match cevio
+                .start_host(false)
+                .and_then(|()| Self::discover_in(&cevio))
+            {
+                Ok(found) => casts.casts.extend(found),
+                Err(e) => casts.failures.push((host, format!("{e:#}"))),
+            }
+        }
+        Ok(casts)
+    }
+
+    /// 指定したインスタンスの製品で利用可能なキャストを取得します。
+    pub fn discover_in(cevio: &CeVIO) -> error::Result<Vec<CastInfo>> {
+        cevio.casts()?.collect()
+    }
+
+    /// キャストの一覧を取得します。
+    pub fn iter(&self) -> impl Iterator<Item = &CastInfo> {
+        self.casts.iter()
+    }
+
+    /// キャスト名から情報を取得します。
+    ///
+    /// 同名のキャストが複数の製品にある場合は CeVIO AI のものを優先します。
+    pub fn find(&self, name: &str) -> Option<&CastInfo> {
+        self.casts.iter().find(|c| c.name == name)
+    }
+
+    /// キャストを扱う製品を取得します。
+    pub fn host_for(&self, name: &str) -> Option<HostKind> {
+        self.find(name).map(|c| c.host)
+    }
+
+    /// `discover` で起動やキャストの取得に失敗した製品と、そのエラーを取得します。
+    pub fn failures(&self) -> &[(HostKind, String)] {
+        &self.failures
+    }
+}
+
+impl CeVIO {
+    /// キャストがインストールされていない場合に選ぶキャストを、優先する順に指定します。空にすると代替キャストを使いません。
+    ///
+    /// 台本の一部のキャストがない環境でも、一括出力全体を失敗させずに続けるために使います。
+    ///
+    /// ```no_run
+    /// let cevio = cevio::CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    /// cevio.set_fallback_casts(["小春六花", "花隈千冬"]);
+    /// cevio.on_cast_fallback(|f| eprintln!("{} の代わりに {} を使います", f.requested, f.used));
+    ///
+    /// let used = cevio.select_cast("夏色花梨").unwrap();
+    /// ```
+    pub fn set_fallback_casts(&self, casts: impl IntoIterator<Item = impl Into<String>>) {
+        *self.fallback_casts.borrow_mut() = casts.into_iter().map(Into::into).collect();
+    }
+
+    /// 代替キャストを取得します。
+    pub fn fallback_casts(&self) -> Vec<String> {
+        self.fallback_casts.borrow().clone()
+    }
+
+    /// キャストを設定し、実際に設定したキャスト名を返します。
+    ///
+    /// 備考：
+    ///
+    /// 　キャストがインストールされていない場合は、代替キャスト（`set_fallback_casts`）のうち利用可能な最初のキャストを設定し、
+    /// 　`on_cast_fallback` で登録した関数を呼び出します。
+    /// 　利用可能な代替キャストがない場合は `CeVIOError::InvalidCast` を返します。
+    pub fn select_cast(&self, cast: &str) -> error::Result<String> {
+        let e = match self.set_cast_exact(cast) {
+            Ok(()) => return Ok(cast.to_string()),
+            Err(error::CeVIOError::InvalidCast(e)) if !self.fallback_casts.borrow().is_empty() => e,
+            Err(e) => return Err(e),
+        };
+        let available = self.get_available_casts()?;
+        let fallback = self
+            .fallback_casts()
+            .into_iter()
+            .find(|fallback| available.contains(fallback));
+        let Some(used) = fallback else {
+            return Err(error::CeVIOError::InvalidCast(e.context(format!(
+                "Cast `{cast}` and its fallbacks are not available"
+            ))));
+        };
+        self.set_cast_exact(&used)?;
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            requested = cast,
+            used,
+            "Cast is not available, using fallback"
+        );
+        self.observers.cast_fallback(&CastFallback {
+            requested: cast.to_string(),
+            used: used.clone(),
+        });
+        Ok(used)
+    }
+}
+
+/// `CeVIO::casts` が返すキャストの情報のイテレーターです。
+///
+/// 感情パラメータは次の要素を取り出すときに、そのキャストに切り替えて取得します。
+/// 破棄すると `CeVIO::casts` を呼んだときのキャスト、パラメータ、感情パラメータに戻します。
+pub struct CastIter<'a> {
+    cevio: &'a CeVIO,
+    names: std::vec::IntoIter<String>,
+    /// 元のキャスト、パラメータ、感情パラメータ
+    original: Params,
+    /// キャストを切り替えたかどうか
+    switched: bool,
+}
+
+impl CeVIO {
+    /// 利用可能なキャストの名前、製品、言語、感情パラメータの名前を取得します。
+    ///
+    /// キャスト選択の画面で、感情パラメータのスライダーまで 1 回の呼び出しで作れます。
+    ///
+    /// 備考：
+    ///
+    /// 　感情パラメータを取得するため、要素を取り出すたびにキャストを切り替えます。イテレーターを破棄すると元のキャストとパラメータ（感情パラメータを含む）に戻します。
+    ///
+    /// ```no_run
+    /// let cevio = cevio::CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    /// for cast in cevio.casts().unwrap() {
+    ///     let cast = cast.unwrap();
+    ///     println!("{} ({:?}): {}", cast.name, cast.language, cast.components.join(", "));
+    /// }
+    /// ```
+    pub fn casts(&self) -> error::Result<CastIter<'_>> {
+        // キャストを切り替えるとパラメータが初期化されるため、すべての値を記録しておく
+        let original = self.current_params()?;
+        Ok(CastIter {
+            cevio: self,
+            names: self.get_available_casts()?.into_iter(),
+            original,
+            switched: false,
+        })
+    }
+}
+
+impl Iterator for CastIter<'_> {
+    type Item = error::Result<CastInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.names.next()?;
+        let cevio = self.cevio;
+        self.switched = true;
+        let info = (|| {
+            cevio.set_cast(&name)?;
+            let components = cevio.get_component_names()?;
+            Ok(CastInfo {
+                host: cevio.host(),
+                language: Language::guess(&name),
+                components,
+                name,
+            })
+        })();
+        Some(info)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
+
+impl ExactSizeIterator for CastIter<'_> {}
+
+impl Drop for CastIter<'_> {
+    fn drop(&mut self) {
+        // キャストが設定されていなかった場合は戻せない
+        if self.switched && self.original.cast.is_some() {
+            // 破棄の途中では失敗を返せないため無視する
+            let _ = self.cevio.apply_params(&self.original);
+        }
+    }
+}
+
+impl IntoIterator for Casts {
+    type Item = CastInfo;
+    type IntoIter = std::vec::IntoIter<CastInfo>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.casts.into_iter()
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+/// 取得したキャストの一覧と、キャストごとの感情パラメータの名前
+///
+/// 画面のドロップダウンを作り直すたびに CeVIO に問い合わせないようにする。`CeVIO::refresh_casts` で消去する
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CastCache {
+    casts: Option<Vec<String>>,
+    /// キャストごとの感情パラメータの名前
+    components: HashMap<String, Vec<String>>,
+}
+
+impl CastCache {
+    pub(crate) fn casts(&self) -> Option<&[String]> {
+        self.casts.as_deref()
+    }
+
+    pub(crate) fn set_casts(&mut self, casts: Vec<String>) {
+        self.casts = Some(casts);
+    }
+
+    pub(crate) fn components(&self, cast: &str) -> Option<&[String]> {
+        self.components.get(cast).map(Vec::as_slice)
+    }
+
+    pub(crate) fn set_components(&mut self, cast: &str, names: Vec<String>) {
+        self.components.insert(cast.to_string(), names);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,126 @@
+//! よく使われるキャスト名
+//!
+//! キャスト名の打ち間違いを防ぐため、公式のキャストを列挙型で指定できます。ここにないキャストは `Cast::Custom` で指定します。
+//! `Cast` は `set_cast` などキャスト名を受け取るメソッドにそのまま渡せます。
+//!
+//! ```no_run
+//! use cevio::{casts::Cast, CeVIO};
+//!
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast(Cast::HanakumaChifuyu).unwrap();
+//! cevio.set_cast(Cast::Custom("弦巻マキ (日本語)".to_string())).unwrap();
+//! ```
+//!
+//! 名前からも変換できます。一覧にない名前は `Cast::Custom` になります。
+//!
+//! ```
+//! use cevio::casts::Cast;
+//!
+//! assert_eq!("小春六花".parse::<Cast>().unwrap(), Cast::KoharuRikka);
+//! assert_eq!(Cast::from("未知のキャスト"), Cast::Custom("未知のキャスト".to_string()));
+//! assert_eq!(Cast::SatoSasara.as_str(), "さとうささら");
+//! ```
+
+use std::{convert::Infallible, fmt, str::FromStr};
+
+/// キャストです。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Cast {
+    /// さとうささら
+    SatoSasara,
+    /// すずきつづみ
+    SuzukiTsudumi,
+    /// タカハシ
+    Takahashi,
+    /// 小春六花
+    KoharuRikka,
+    /// 夏色花梨
+    NatsukiKarin,
+    /// 花隈千冬
+    HanakumaChifuyu,
+    /// 双葉湊音
+    FutabaMinato,
+    /// 一覧にないキャスト
+    Custom(String),
+}
+
+impl Cast {
+    /// 一覧にあるキャスト
+    pub const KNOWN: &'static [Cast] = &[
+        Cast::SatoSasara,
+        Cast::SuzukiTsudumi,
+        Cast::Takahashi,
+        Cast::KoharuRikka,
+        Cast::NatsukiKarin,
+        Cast::HanakumaChifuyu,
+        Cast::FutabaMinato,
+    ];
+
+    /// キャスト名を取得します。
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::SatoSasara => "さとうささら",
+            Self::SuzukiTsudumi => "すずきつづみ",
+            Self::Takahashi => "タカハシ",
+            Self::KoharuRikka => "小春六花",
+            Self::NatsukiKarin => "夏色花梨",
+            Self::HanakumaChifuyu => "花隈千冬",
+            Self::FutabaMinato => "双葉湊音",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// 一覧にあるキャストかどうかを取得します。
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Self::Custom(_))
+    }
+}
+
+impl AsRef<str> for Cast {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for Cast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Cast {
+    fn from(name: &str) -> Self {
+        Self::KNOWN
+            .iter()
+            .find(|cast| cast.as_str() == name)
+            .cloned()
+            .unwrap_or_else(|| Self::Custom(name.to_string()))
+    }
+}
+
+impl From<String> for Cast {
+    fn from(name: String) -> Self {
+        match Self::from(name.as_str()) {
+            Self::Custom(_) => Self::Custom(name),
+            cast => cast,
+        }
+    }
+}
+
+impl From<Cast> for String {
+    fn from(cast: Cast) -> Self {
+        match cast {
+            Cast::Custom(name) => name,
+            cast => cast.as_str().to_string(),
+        }
+    }
+}
+
+impl FromStr for Cast {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
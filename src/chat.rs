@@ -0,0 +1,449 @@
+//! Twitch・YouTube Live のチャットを読み上げます（`chat` フィーチャー）
+//!
+//! `ChatSource` から受け取ったコメントに設定（`config::ServerConfig`）の NG ワードと読み替え辞書を適用し、
+//! `queue::SpeechQueue` に追加します。同じユーザーのコメントは `user_interval` に 1 回だけ読み上げます。
+//!
+//! - Twitch：IRC に匿名でログインしてチャンネルのコメントを受け取ります（`TwitchChat`）。
+//! - YouTube Live：YouTube Data API でコメントを取得します。API キーが必要です（`YouTubeChat`）。
+//!
+//! キューのクライアント名は `twitch`・`youtube` です。`SpeechQueue::set_priority` で優先度を設定できます。
+//!
+//! ```no_run
+//! use std::sync::atomic::AtomicBool;
+//! use cevio::{
+//!     actor::Handle,
+//!     chat::{ChatOptions, ChatReader, TwitchChat},
+//!     config::SharedConfig,
+//!     queue::SpeechQueue,
+//!     HostKind,
+//! };
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! handle.call(|cevio| cevio.start_host(false)).unwrap();
+//! let queue = SpeechQueue::new(handle).unwrap();
+//! let config = SharedConfig::load("cevio.conf").unwrap();
+//!
+//! let mut reader = ChatReader::new(queue, config, ChatOptions::default());
+//! let mut twitch = TwitchChat::connect("channel_name").unwrap();
+//! reader.run(&mut twitch, &AtomicBool::new(false)).unwrap();
+//! ```
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::TcpStream,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    config::SharedConfig,
+    error::{self, report, Context as _},
+    queue::SpeechQueue,
+    Params,
+};
+
+/// `ChatSource::poll` が待つ最大の時間
+const POLL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// チャットの配信サービスです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    /// Twitch
+    Twitch,
+    /// YouTube Live
+    YouTube,
+}
+
+impl Platform {
+    /// キューのクライアント名
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Platform::Twitch => "twitch",
+            Platform::YouTube => "youtube",
+        }
+    }
+}
+
+/// チャットのコメントです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    /// 配信サービス
+    pub platform: Platform,
+    /// ユーザー名（表示名）
+    pub user: String,
+    /// コメント
+    pub text: String,
+}
+
+/// コメントの取得元です。
+pub trait ChatSource {
+    /// 新しいコメントを取得します。
+    ///
+    /// コメントがない場合は少し待ってから空の `Vec` を返します。
+    fn poll(&mut self) -> error::Result<Vec<ChatMessage>>;
+}
+
+/// 読み上げの設定です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatOptions {
+    /// 同じユーザーのコメントを読み上げる最短の間隔。間隔内のコメントは読み上げません
+    pub user_interval: Duration,
+    /// コメントを読み上げる最大文字数。超えた部分は読み上げません。`None` は無制限
+    pub max_chars: Option<usize>,
+    /// `true` の場合はコメントの前にユーザー名を読み上げます
+    pub read_name: bool,
+    /// 読み上げのパラメータ。設定の既定のパラメータを上書きします
+    pub params: Params,
+}
+
+impl Default for ChatOptions {
+    fn default() -> Self {
+        Self {
+            user_interval: Duration::from_secs(5),
+            max_chars: Some(100),
+            read_name: false,
+            params: Params::default(),
+        }
+    }
+}
+
+/// コメントをフィルターしてキューに追加します。
+pub struct ChatReader {
+    queue: SpeechQueue,
+    config: SharedConfig,
+    options: ChatOptions,
+    last_spoken: HashMap<(Platform, String), Instant>,
+}
+
+impl ChatReader {
+    /// `queue` で読み上げ、`config` の NG ワードと読み替え辞書を適用するリーダーを作成します。
+    pub fn new(queue: SpeechQueue, config: SharedConfig, options: ChatOptions) -> Self {
+        Self {
+            queue,
+            config,
+            options,
+            last_spoken: HashMap::new(),
+        }
+    }
+
+    /// コメントをキューに追加し、キューの識別子を返します。
+    ///
+    /// 空のコメント、NG ワードを含むコメント、`user_interval` 内の同じユーザーのコメントは追加せずに `None` を返します。
+    pub fn feed(&mut self, message: &ChatMessage) -> Option<u64> {
+        let text = message.text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        let interval = self.options.user_interval;
+        self.last_spoken.retain(|_, at| at.elapsed() < interval);
+        let key = (message.platform, message.user.clone());
+        if self.last_spoken.contains_key(&key) {
+            return None;
+        }
+
+        let text: String = match self.options.max_chars {
+            Some(max) => text.chars().take(max).collect(),
+            None => text.to_string(),
+        };
+        let text = match self.options.read_name {
+            true => format!("{}、{text}", message.user),
+            false => text,
+        };
+        let config = self.config.get();
+        let text = config.prepare_text(&text).ok()?;
+        let params = config.params_for(None, &self.options.params).ok()?;
+
+        self.last_spoken.insert(key, Instant::now());
+        Some(self.queue.push(message.platform.as_str(), text, params))
+    }
+
+    /// `source` のコメントを読み上げ続けます。
+    ///
+    /// `stop` が `true` になるか、`source` がエラーを返すまで戻りません。
+    pub fn run(&mut self, source: &mut impl ChatSource, stop: &AtomicBool) -> error::Result<()> {
+        while !stop.load(Ordering::SeqCst) {
+            for message in source.poll()? {
+                self.feed(&message);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Twitch のチャットです。
+///
+/// IRC に匿名（`justinfan`）でログインするため、コメントの読み取りだけができます。
+pub struct TwitchChat {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    line: String,
+}
+
+impl TwitchChat {
+    /// Twitch の IRC に接続し、チャンネルに参加します。
+    ///
+    /// `channel` はチャンネル名（ログイン名）です。先頭の `#` は省略できます。
+    pub fn connect(channel: &str) -> error::Result<Self> {
+        let stream = TcpStream::connect("irc.chat.twitch.tv:6667")
+            .context("Failed to connect to Twitch IRC")
+            .map_err(error::CeVIOError::from)?;
+        stream
+            .set_read_timeout(Some(POLL_TIMEOUT))
+            .context("Failed to set read timeout")
+            .map_err(error::CeVIOError::from)?;
+        let writer = stream
+            .try_clone()
+            .context("Failed to clone Twitch IRC stream")
+            .map_err(error::CeVIOError::from)?;
+        let mut chat = Self {
+            reader: BufReader::new(stream),
+            writer,
+            line: String::new(),
+        };
+        let nick = format!("justinfan{}", std::process::id() % 100000);
+        let channel = channel.trim_start_matches('#').to_ascii_lowercase();
+        chat.send(&format!(
+            "CAP REQ :twitch.tv/tags\r\nNICK {nick}\r\nJOIN #{channel}\r\n"
+        ))?;
+        Ok(chat)
+    }
+
+    fn send(&mut self, s: &str) -> error::Result<()> {
+        self.writer
+            .write_all(s.as_bytes())
+            .context("Failed to write to Twitch IRC")
+            .map_err(error::CeVIOError::from)
+    }
+}
+
+/// IRC の `PRIVMSG` をコメントに変換する
+fn parse_privmsg(line: &str) -> Option<ChatMessage> {
+    let (tags, rest) = match line.strip_prefix('@') {
+        Some(rest) => rest.split_once(' ')?,
+        None => ("", line),
+    };
+    let (prefix, rest) = rest.strip_prefix(':')?.split_once(' ')?;
+    let (_channel, text) = rest.strip_prefix("PRIVMSG ")?.split_once(" :")?;
+    let display_name = tags
+        .split(';')
+        .find_map(|tag| tag.strip_prefix("display-name="))
+        .filter(|name| !name.is_empty());
+    let nick = prefix.split('!').next().unwrap_or(prefix);
+    Some(ChatMessage {
+        platform: Platform::Twitch,
+        user: display_name.unwrap_or(nick).to_string(),
+        text: text.to_string(),
+    })
+}
+
+impl ChatSource for TwitchChat {
+    fn poll(&mut self) -> error::Result<Vec<ChatMessage>> {
+        let mut messages = Vec::new();
+        loop {
+            // タイムアウトした場合は途中まで読んだ行が残るため、次の呼び出しで続きを読む
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => {
+                    return Err(error::CeVIOError::Io(report!(
+                        "Twitch IRC connection was closed"
+                    )))
+                }
+                Ok(_) => {}
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(messages)
+                }
+                Err(e) => {
+                    return Err(error::CeVIOError::Io(
+                        error::Report::new(e).context("Failed to read from Twitch IRC"),
+                    ))
+                }
+            }
+            let line = std::mem::take(&mut self.line);
+            let line = line.trim_end();
+            if let Some(server) = line.strip_prefix("PING ") {
+                self.send(&format!("PONG {server}\r\n"))?;
+            } else if let Some(message) = parse_privmsg(line) {
+                messages.push(message);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveChatMessages {
+    next_page_token: Option<String>,
+    polling_interval_millis: u64,
+    items: Vec<LiveChatMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveChatMessage {
+    snippet: LiveChatSnippet,
+    author_details: AuthorDetails,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveChatSnippet {
+    #[serde(default)]
+    display_message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthorDetails {
+    display_name: String,
+}
+
+const YOUTUBE_API: &str = "https://www.googleapis.com/youtube/v3";
+
+fn get_json<T: DeserializeOwned>(request: ureq::Request) -> std::result::Result<T, error::Report> {
+    Ok(request.call()?.into_json()?)
+}
+
+/// YouTube Live のチャットです。
+///
+/// YouTube Data API の `liveChatMessages` を、API が指定する間隔で取得します。
+/// 接続前のコメントは読み上げません。
+pub struct YouTubeChat {
+    api_key: String,
+    live_chat_id: String,
+    page_token: Option<String>,
+    next_poll: Instant,
+    first: bool,
+}
+
+impl YouTubeChat {
+    /// チャット ID を指定して作成します。
+    pub fn new(api_key: impl Into<String>, live_chat_id: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            live_chat_id: live_chat_id.into(),
+            page_token: None,
+            next_poll: Instant::now(),
+            first: true,
+        }
+    }
+
+    /// 配信中の動画 ID からチャット ID を取得して作成します。
+    pub fn from_video(api_key: impl Into<String>, video_id: &str) -> error::Result<Self> {
+        let api_key = api_key.into();
+        let request = ureq::get(&format!("{YOUTUBE_API}/videos"))
+            .query("part", "liveStreamingDetails")
+            .query("id", video_id)
+            .query("key", &api_key);
+        let response: serde_json::Value = get_json(request)
+            .context("Failed to get live streaming details")
+            .map_err(error::CeVIOError::from)?;
+        let live_chat_id = response["items"][0]["liveStreamingDetails"]["activeLiveChatId"]
+            .as_str()
+            .ok_or_else(|| report!("Video `{video_id}` has no active live chat"))
+            .map_err(error::CeVIOError::InvalidInput)?;
+        Ok(Self::new(api_key, live_chat_id))
+    }
+}
+
+impl ChatSource for YouTubeChat {
+    fn poll(&mut self) -> error::Result<Vec<ChatMessage>> {
+        let wait = self.next_poll.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait.min(POLL_TIMEOUT));
+            return Ok(Vec::new());
+        }
+        let mut request = ureq::get(&format!("{YOUTUBE_API}/liveChat/messages"))
+            .query("liveChatId", &self.live_chat_id)
+            .query("part", "snippet,authorDetails")
+            .query("key", &self.api_key);
+        if let Some(token) = &self.page_token {
+            request = request.query("pageToken", token);
+        }
+        let response: LiveChatMessages = get_json(request)
+            .context("Failed to get live chat messages")
+            .map_err(error::CeVIOError::from)?;
+        self.page_token = response.next_page_token;
+        self.next_poll = Instant::now() + Duration::from_millis(response.polling_interval_millis);
+
+        // 最初の取得では接続前のコメントが返るため読み上げない
+        if std::mem::take(&mut self.first) {
+            return Ok(Vec::new());
+        }
+        Ok(response
+            .items
+            .into_iter()
+            .map(|item| ChatMessage {
+                platform: Platform::YouTube,
+                user: item.author_details.display_name,
+                text: item.snippet.display_message,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(user: &str, text: &str) -> Option<ChatMessage> {
+        Some(ChatMessage {
+            platform: Platform::Twitch,
+            user: user.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    #[test]
+    fn parse_privmsg_uses_display_name_tag() {
+        let line = "@badge-info=;color=#FF0000;display-name=千冬;mod=0 :chifuyu!chifuyu@chifuyu.tmi.twitch.tv PRIVMSG #channel :こんにちは";
+        assert_eq!(parse_privmsg(line), message("千冬", "こんにちは"));
+    }
+
+    #[test]
+    fn parse_privmsg_falls_back_to_nick() {
+        // 表示名が空の場合
+        let line =
+            "@display-name=;mod=0 :chifuyu!chifuyu@chifuyu.tmi.twitch.tv PRIVMSG #channel :hi";
+        assert_eq!(parse_privmsg(line), message("chifuyu", "hi"));
+        // タグがない場合（`twitch.tv/tags` を要求していない場合）
+        let line = ":chifuyu!chifuyu@chifuyu.tmi.twitch.tv PRIVMSG #channel :hi";
+        assert_eq!(parse_privmsg(line), message("chifuyu", "hi"));
+        // 接頭辞が名前だけの場合
+        assert_eq!(
+            parse_privmsg(":chifuyu PRIVMSG #channel :hi"),
+            message("chifuyu", "hi")
+        );
+    }
+
+    #[test]
+    fn parse_privmsg_keeps_whole_trailing_text() {
+        let line = ":a!a@a.tmi.twitch.tv PRIVMSG #channel :時刻は 12:00 :) です";
+        assert_eq!(parse_privmsg(line), message("a", "時刻は 12:00 :) です"));
+        let line = ":a!a@a.tmi.twitch.tv PRIVMSG #channel ::colon";
+        assert_eq!(parse_privmsg(line), message("a", ":colon"));
+        let line = ":a!a@a.tmi.twitch.tv PRIVMSG #channel :";
+        assert_eq!(parse_privmsg(line), message("a", ""));
+    }
+
+    #[test]
+    fn parse_privmsg_ignores_other_lines() {
+        for line in [
+            "",
+            "PING :tmi.twitch.tv",
+            ":tmi.twitch.tv 001 bot :Welcome, GLHF!",
+            ":bot!bot@bot.tmi.twitch.tv JOIN #channel",
+            "@display-name=千冬 :chifuyu!chifuyu@chifuyu.tmi.twitch.tv USERNOTICE #channel :sub",
+            // 接頭辞がない
+            "PRIVMSG #channel :hi",
+            "@display-name=千冬 PRIVMSG #channel :hi",
+            // タグだけ
+            "@display-name=千冬",
+            // テキストがない
+            ":a!a@a.tmi.twitch.tv PRIVMSG #channel",
+        ] {
+            assert_eq!(parse_privmsg(line), None, "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! クリップボードを監視し、コピーしたテキストを読み上げます（`clipboard` フィーチャー）
+//!
+//! - 続けてコピーした場合は、最後のコピーから `debounce` の間コピーがなくなってから読み上げます。
+//! - 直前に読み上げたテキストと同じ場合は読み上げません。
+//! - 読み上げ中に新しいテキストをコピーした場合は、再生を停止して新しいテキストを読み上げます。
+//!
+//! ```no_run
+//! use std::sync::atomic::AtomicBool;
+//! use cevio::{clipboard, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let stop = AtomicBool::new(false);
+//! clipboard::watch(&cevio, &clipboard::WatchOptions::default(), &stop).unwrap();
+//! ```
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows::Win32::{
+    Foundation::{HGLOBAL, HWND},
+    System::{
+        DataExchange::{
+            CloseClipboard, GetClipboardData, GetClipboardSequenceNumber,
+            IsClipboardFormatAvailable, OpenClipboard,
+        },
+        Memory::{GlobalLock, GlobalUnlock},
+        Ole::CF_UNICODETEXT,
+    },
+};
+
+use crate::{error, CeVIO, SpeakingState};
+
+/// クリップボードの監視の設定です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchOptions {
+    /// クリップボードを確認する間隔
+    pub interval: Duration,
+    /// 最後のコピーから読み上げるまでの時間
+    pub debounce: Duration,
+    /// 読み上げる最大文字数。超えた部分は読み上げません。`None` は無制限
+    pub max_chars: Option<usize>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(100),
+            debounce: Duration::from_millis(300),
+            max_chars: Some(200),
+        }
+    }
+}
+
+/// 他のアプリがクリップボードを開いている
+pub(crate) struct Busy;
+
+/// クリップボードが変更されるたびに増える番号
+pub(crate) fn sequence_number() -> u32 {
+    unsafe { GetClipboardSequenceNumber() }
+}
+
+/// クリップボードのテキストを取得する。テキストでない場合は `None`
+pub(crate) fn read_text() -> Result<Option<String>, Busy> {
+    let format = CF_UNICODETEXT.0 as u32;
+    unsafe {
+        if !IsClipboardFormatAvailable(format).as_bool() {
+            return Ok(None);
+        }
+        if !OpenClipboard(HWND(0)).as_bool() {
+            return Err(Busy);
+        }
+        let text = GetClipboardData(format).ok().and_then(|handle| {
+            let hglobal = HGLOBAL(handle.0 as _);
+            let ptr = GlobalLock(hglobal) as *const u16;
+            if ptr.is_null() {
+                return None;
+            }
+            let len = (0..).take_while(|&i| *ptr.add(i) != 0).count();
+            let text = String::from_utf16_lossy(std::slice::from_raw_parts(ptr, len));
+            GlobalUnlock(hglobal);
+            Some(text)
+        });
+        CloseClipboard();
+        Ok(text)
+    }
+}
+
+/// 読み上げるテキストに整える。改行は空白にまとめる
+pub(crate) fn normalize(text: &str, max_chars: Option<usize>) -> String {
+    let text = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    match max_chars {
+        Some(max) => text.chars().take(max).collect(),
+        None => text,
+    }
+}
+
+/// クリップボードを監視し、コピーしたテキストを読み上げます。
+///
+/// `stop` が `true` になるまで戻りません。キャストなどのパラメータは `cevio` に設定されているものを使います。
+pub fn watch(cevio: &CeVIO, options: &WatchOptions, stop: &AtomicBool) -> error::Result<()> {
+    let mut sequence = sequence_number();
+    let mut changed_at: Option<Instant> = None;
+    let mut last_text = String::new();
+    let mut speaking: Option<SpeakingState> = None;
+
+    while !stop.load(Ordering::SeqCst) {
+        let current = sequence_number();
+        if current != sequence {
+            sequence = current;
+            changed_at = Some(Instant::now());
+        }
+
+        if changed_at.is_some_and(|at| at.elapsed() >= options.debounce) {
+            // 他のアプリがクリップボードを開いている場合は、次の確認で読み直す
+            if let Ok(text) = read_text() {
+                changed_at = None;
+                let text = normalize(text.as_deref().unwrap_or_default(), options.max_chars);
+                if !text.is_empty() && text != last_text {
+                    if let Some(state) = speaking.take() {
+                        if !state.is_completed()? {
+                            cevio.stop()?;
+                        }
+                    }
+                    speaking = Some(cevio.speak(&text)?);
+                    last_text = text;
+                }
+            }
+        }
+
+        thread::sleep(options.interval);
+    }
+    Ok(())
+}
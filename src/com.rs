@@ -1,20 +1,278 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::HashMap,
+    fmt::Write as _,
+    rc::Rc,
+    sync::Arc,
+    time::Instant,
+};
+
 use windows::{
     core::{self, ComInterface, GUID, HSTRING, PCWSTR},
+    Win32::Foundation::DISP_E_UNKNOWNNAME,
     Win32::System::{
         Com::{
             CLSIDFromString, CoCreateInstance, IDispatch, CLSCTX_ALL, CLSCTX_LOCAL_SERVER,
             DISPATCH_FLAGS, DISPATCH_METHOD, DISPATCH_PROPERTYGET, DISPATCH_PROPERTYPUT,
-            DISPPARAMS, VARIANT,
+            DISPPARAMS, VARIANT, VT_BOOL, VT_BSTR, VT_BYREF, VT_DISPATCH, VT_EMPTY, VT_I4, VT_NULL,
+            VT_R8,
         },
-        Ole::{GetActiveObject, DISPID_PROPERTYPUT},
+        Ole::{GetActiveObject, VariantClear, DISPID_PROPERTYPUT},
     },
 };
 
-const LOCALE_USER_DEFAULT: u32 = 0x400;
-const LOCALE_SYSTEM_DEFAULT: u32 = 0x0800;
+use crate::{
+    error,
+    metrics::Latencies,
+    variant_ext::{OwnedVariant, VariantExt},
+    HostKind,
+};
+
+/// ユーザーの既定のロケール（既定値）
+pub const LOCALE_USER_DEFAULT: u32 = 0x400;
+/// システムの既定のロケール
+pub const LOCALE_SYSTEM_DEFAULT: u32 = 0x0800;
+
+/// COM の呼び出しを `tracing` のスパンで囲み、かかった時間と失敗した場合の `HRESULT` を記録する
+#[cfg(feature = "tracing")]
+fn traced<T>(
+    kind: &'static str,
+    name: &str,
+    f: impl FnOnce() -> core::Result<T>,
+) -> core::Result<T> {
+    let _span = tracing::trace_span!("com", kind, name).entered();
+    let start = std::time::Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+    match &result {
+        Ok(_) => tracing::trace!(?duration, "COM call succeeded"),
+        Err(e) => tracing::debug!(?duration, hresult = %e.code(), error = %e, "COM call failed"),
+    }
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+fn traced<T>(
+    _kind: &'static str,
+    _name: &str,
+    f: impl FnOnce() -> core::Result<T>,
+) -> core::Result<T> {
+    f()
+}
+
+/// ProgID か CLSID 文字列から CLSID を得ます。登録されていない ProgID の場合は失敗します
+pub fn clsid_of(id: &str) -> core::Result<GUID> {
+    unsafe { CLSIDFromString(&HSTRING::from(id)) }
+}
+
+/// 呼び出しの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    GetProperty,
+    SetProperty,
+    Method,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::GetProperty => "get_property",
+            Kind::SetProperty => "set_property",
+            Kind::Method => "invoke_method",
+        }
+    }
+
+    fn flags(self) -> DISPATCH_FLAGS {
+        match self {
+            Kind::GetProperty => DISPATCH_PROPERTYGET,
+            Kind::SetProperty => DISPATCH_PROPERTYPUT,
+            Kind::Method => DISPATCH_METHOD,
+        }
+    }
+}
+
+/// エラーに付ける、操作対象の製品の情報
+pub struct HostContext {
+    host: HostKind,
+    controller: IDispatch,
+    /// `controller` の呼び出しに使うロケール
+    locale: u32,
+    /// 一度取得できたバージョン
+    version: OnceCell<String>,
+}
+
+impl HostContext {
+    /// `controller` は製品のバージョンの取得に使う ServiceControl
+    pub fn new(host: HostKind, controller: &ComObject) -> Self {
+        Self {
+            host,
+            controller: controller.disp.clone(),
+            locale: controller.locale,
+            version: OnceCell::new(),
+        }
+    }
+
+    /// 製品名とバージョン。`fetch` が `false` か取得できない場合はバージョンを省く
+    fn describe(&self, fetch: bool) -> String {
+        let version = match self.version.get() {
+            Some(version) => Some(version.as_str()),
+            None if fetch => {
+                let controller = ComObject::from(self.controller.clone()).with_locale(self.locale);
+                controller
+                    .get_id_from_name("HostVersion")
+                    .and_then(|id| {
+                        controller.invoke(id, &DISPPARAMS::default(), DISPATCH_PROPERTYGET)
+                    })
+                    .and_then(|version| version.to_string())
+                    .ok()
+                    .map(|version| self.version.get_or_init(|| version).as_str())
+            }
+            None => None,
+        };
+        match version {
+            Some(version) => format!("{} {version}", self.host.product_name()),
+            None => self.host.product_name().to_string(),
+        }
+    }
+}
+
+/// `ComObject::property` と `ComObject::call` で取得できる型
+pub trait FromVariant: Sized {
+    /// エラーメッセージに使う型の名前
+    const NAME: &'static str;
+    /// `parent` は値を取得したオブジェクト、`name` は取得に使ったプロパティかメソッドの名前
+    fn from_variant(variant: OwnedVariant, parent: &ComObject, name: &str) -> core::Result<Self>;
+}
+
+impl FromVariant for i32 {
+    const NAME: &'static str = "i32";
+    fn from_variant(variant: OwnedVariant, _: &ComObject, _: &str) -> core::Result<Self> {
+        variant.to_i32()
+    }
+}
+
+impl FromVariant for f64 {
+    const NAME: &'static str = "f64";
+    fn from_variant(variant: OwnedVariant, _: &ComObject, _: &str) -> core::Result<Self> {
+        variant.to_f64()
+    }
+}
+
+impl FromVariant for bool {
+    const NAME: &'static str = "bool";
+    fn from_variant(variant: OwnedVariant, _: &ComObject, _: &str) -> core::Result<Self> {
+        variant.to_bool()
+    }
+}
+
+impl FromVariant for String {
+    const NAME: &'static str = "String";
+    fn from_variant(variant: OwnedVariant, _: &ComObject, _: &str) -> core::Result<Self> {
+        variant.to_string()
+    }
+}
+
+impl FromVariant for ComObject {
+    const NAME: &'static str = "IDispatch";
+    /// 取得したオブジェクトのエラーにも製品の情報を付け、同じ名前で取得したオブジェクトと DISPID のキャッシュを共有する
+    fn from_variant(variant: OwnedVariant, parent: &ComObject, name: &str) -> core::Result<Self> {
+        Ok(Self {
+            disp: variant.into_dispatch()?,
+            latencies: None,
+            host: parent.host.clone(),
+            names: parent.names.child(name),
+            locale: parent.locale,
+        })
+    }
+}
+
+/// 名前から得た DISPID のキャッシュ
+///
+/// 同じオブジェクトの同じプロパティ・メソッドから得たオブジェクト（`At` で得た各要素など）は同じ型とみなし、
+/// 子のキャッシュを共有する。CeVIO の型は実行中に変わらないため、キャッシュを消すことはない
+#[derive(Default)]
+struct NameCache {
+    dispids: RefCell<HashMap<String, i32>>,
+    children: RefCell<HashMap<String, Rc<NameCache>>>,
+}
+
+impl NameCache {
+    fn get(&self, name: &str) -> Option<i32> {
+        self.dispids.borrow().get(name).copied()
+    }
+
+    fn insert(&self, name: &str, dispid: i32) {
+        self.dispids.borrow_mut().insert(name.to_string(), dispid);
+    }
+
+    /// `name` で取得したオブジェクトのキャッシュ
+    fn child(&self, name: &str) -> Rc<NameCache> {
+        if let Some(child) = self.children.borrow().get(name) {
+            return child.clone();
+        }
+        self.children
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+}
 
+/// エラーメッセージに載せる文字列の引数の最大の文字数
+const MAX_STR_CHARS: usize = 32;
+
+/// VARIANT の型と値を短い文字列にする
+fn summarize(variant: &VARIANT) -> String {
+    unsafe {
+        let v00 = &variant.Anonymous.Anonymous;
+        let vt = v00.vt;
+        match vt {
+            VT_EMPTY => String::from("EMPTY"),
+            VT_NULL => String::from("NULL"),
+            VT_I4 => format!("I4({})", v00.Anonymous.lVal),
+            VT_R8 => format!("R8({})", v00.Anonymous.dblVal),
+            VT_BOOL => format!("BOOL({})", v00.Anonymous.boolVal.as_bool()),
+            VT_BSTR => {
+                let s = v00.Anonymous.bstrVal.to_string();
+                let len = s.chars().count();
+                match len > MAX_STR_CHARS {
+                    true => format!(
+                        "BSTR({:?}… {len} chars)",
+                        s.chars().take(MAX_STR_CHARS).collect::<String>()
+                    ),
+                    false => format!("BSTR({s:?})"),
+                }
+            }
+            VT_DISPATCH => String::from("DISPATCH"),
+            vt if vt.0 & VT_BYREF.0 != 0 => format!("BYREF({:#06x})", vt.0),
+            vt => format!("VT({:#06x})", vt.0),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ComObject {
     disp: IDispatch,
+    /// 呼び出しにかかった時間の記録先
+    latencies: Option<Arc<Latencies>>,
+    /// エラーに付ける製品の情報
+    host: Option<Rc<HostContext>>,
+    /// 名前から得た DISPID
+    names: Rc<NameCache>,
+    /// `GetIDsOfNames` と `Invoke` に渡すロケール
+    locale: u32,
+}
+
+impl From<IDispatch> for ComObject {
+    fn from(disp: IDispatch) -> Self {
+        Self {
+            disp,
+            latencies: None,
+            host: None,
+            names: Rc::default(),
+            locale: LOCALE_USER_DEFAULT,
+        }
+    }
 }
 
 #[allow(unused)]
@@ -26,11 +284,11 @@ impl ComObject {
         unsafe {
             let lpsz = HSTRING::from(id);
             let rclsid = CLSIDFromString(&lpsz)?;
-            let disp = match CoCreateInstance(&rclsid, None, CLSCTX_ALL) {
+            let disp: IDispatch = match CoCreateInstance(&rclsid, None, CLSCTX_ALL) {
                 Ok(disp) => disp,
                 Err(_) => CoCreateInstance(&rclsid, None, CLSCTX_LOCAL_SERVER)?,
             };
-            Ok(Self { disp })
+            Ok(Self::from(disp))
         }
     }
     /// 起動中のExcelを捕まえるなどで使う
@@ -51,21 +309,84 @@ impl ComObject {
                 }
                 None => None,
             };
-            Ok(disp.map(|disp| Self { disp }))
+            Ok(disp.map(Self::from))
+        }
+    }
+    /// 呼び出しにかかった時間を `latencies` に記録するようにします
+    pub fn with_latencies(mut self, latencies: Arc<Latencies>) -> Self {
+        self.latencies = Some(latencies);
+        self
+    }
+    /// エラーに製品の情報を付けるようにします
+    pub fn with_host(mut self, host: Rc<HostContext>) -> Self {
+        self.host = Some(host);
+        self
+    }
+    /// `GetIDsOfNames` と `Invoke` に渡すロケールを指定します
+    pub fn with_locale(mut self, locale: u32) -> Self {
+        self.locale = locale;
+        self
+    }
+    /// 同じクラスの COM オブジェクトを、同じ設定（時間の記録先、製品の情報、ロケール）で新規に作成します
+    ///
+    /// `id` には `self` と同じクラスの ProgID を渡す（DISPID のキャッシュを共有するため）
+    pub fn new_sibling(&self, id: &str) -> core::Result<Self> {
+        Ok(Self {
+            latencies: self.latencies.clone(),
+            host: self.host.clone(),
+            names: self.names.clone(),
+            locale: self.locale,
+            ..Self::new(id)?
+        })
+    }
+    /// 呼び出しを `tracing` で記録し、かかった時間を `latencies` に記録する
+    fn measure<T>(
+        &self,
+        kind: Kind,
+        name: &str,
+        f: impl FnOnce() -> core::Result<T>,
+    ) -> core::Result<T> {
+        let Some(latencies) = &self.latencies else {
+            return traced(kind.as_str(), name, f);
+        };
+        let start = Instant::now();
+        let result = traced(kind.as_str(), name, f);
+        let prefix = match kind {
+            Kind::GetProperty => "get_",
+            Kind::SetProperty => "put_",
+            Kind::Method => "",
+        };
+        latencies.record(prefix, name, start.elapsed());
+        result
+    }
+    /// 名前のメンバー（プロパティかメソッド）があるかどうかを `GetIDsOfNames` で調べます
+    pub fn has_member(&self, name: &str) -> core::Result<bool> {
+        match self.get_id_from_name(name) {
+            Ok(_) => Ok(true),
+            Err(e) if e.code() == DISP_E_UNKNOWNNAME => Ok(false),
+            Err(e) => Err(e),
         }
     }
+    /// 名前から DISPID を得る。2 回目以降はキャッシュを使い、`GetIDsOfNames` を呼ばない
     fn get_id_from_name(&self, name: &str) -> core::Result<i32> {
+        if let Some(dispid) = self.names.get(name) {
+            return Ok(dispid);
+        }
+        let dispid = self.get_id_from_name_uncached(name)?;
+        self.names.insert(name, dispid);
+        Ok(dispid)
+    }
+    fn get_id_from_name_uncached(&self, name: &str) -> core::Result<i32> {
         unsafe {
             let hstring = HSTRING::from(name);
             let rgsznames = PCWSTR::from_raw(hstring.as_ptr());
             let mut rgdispid = 0;
-            self.disp.GetIDsOfNames(
-                &GUID::zeroed(),
-                &rgsznames,
-                1,
-                LOCALE_USER_DEFAULT,
-                &mut rgdispid,
-            )?;
+            let hr =
+                self.disp
+                    .GetIDsOfNames(&GUID::zeroed(), &rgsznames, 1, self.locale, &mut rgdispid);
+            #[cfg(feature = "com-trace")]
+            com_trace::log_get_id(name, &hr, rgdispid);
+            hr?;
             Ok(rgdispid)
         }
     }
@@ -74,37 +395,113 @@ impl ComObject {
         dispidmember: i32,
         pdispparams: &DISPPARAMS,
         wflags: DISPATCH_FLAGS,
-    ) -> core::Result<VARIANT> {
+    ) -> core::Result<OwnedVariant> {
         unsafe {
-            let mut result = VARIANT::default();
-            self.disp.Invoke(
+            let mut result = OwnedVariant::new(VARIANT::default());
+            let hr = self.disp.Invoke(
                 dispidmember,
                 &GUID::zeroed(),
-                LOCALE_SYSTEM_DEFAULT,
+                self.locale,
                 wflags,
                 pdispparams,
-                Some(&mut result),
+                Some(result.as_out()),
                 None,
                 None,
-            )?;
+            );
+            #[cfg(feature = "com-trace")]
+            com_trace::log_invoke(dispidmember, pdispparams, wflags, &hr, &result);
+            hr?;
             Ok(result)
         }
     }
+    /// 名前で呼び出し、失敗した場合はメソッド名、DISPID、引数、製品の情報をエラーに付ける
+    ///
+    /// `args` は `rgvarg` に渡す順（メソッドの場合は逆順）で渡す。呼び出し元のバッファをそのまま渡し、コピーしない
+    ///
+    /// `args` の所有権は受け取り、呼び出した後に `VariantClear` で BSTR などを解放する
+    fn dispatch(
+        &self,
+        kind: Kind,
+        name: &str,
+        args: &mut [VARIANT],
+    ) -> error::Result<OwnedVariant> {
+        let mut dispid = None;
+        let result = self.measure(kind, name, || {
+            let dispidmember = self.get_id_from_name(name)?;
+            dispid = Some(dispidmember);
+            let mut pdispparams = DISPPARAMS {
+                cArgs: args.len() as u32,
+                rgvarg: args.as_mut_ptr(),
+                ..Default::default()
+            };
+            let mut named_args = [DISPID_PROPERTYPUT];
+            if kind == Kind::SetProperty {
+                pdispparams.cNamedArgs = 1;
+                pdispparams.rgdispidNamedArgs = named_args.as_mut_ptr();
+            }
+            self.invoke(dispidmember, &pdispparams, kind.flags())
+        });
+        let result = result.map_err(|e| {
+            if kind == Kind::Method {
+                args.reverse();
+            }
+            self.error(kind, name, dispid, args, e)
+        });
+        for arg in args.iter_mut() {
+            let _ = unsafe { VariantClear(arg) };
+        }
+        result
+    }
+    fn error(
+        &self,
+        kind: Kind,
+        name: &str,
+        dispid: Option<i32>,
+        args: &[VARIANT],
+        e: core::Error,
+    ) -> error::CeVIOError {
+        let verb = match kind {
+            Kind::GetProperty => "get property",
+            Kind::SetProperty => "set property",
+            Kind::Method => "call method",
+        };
+        let mut message = format!("Failed to {verb} `{name}`");
+        if let Some(dispid) = dispid {
+            let _ = write!(message, " (DISPID {dispid})");
+        }
+        if !args.is_empty() {
+            let args: Vec<_> = args.iter().map(summarize).collect();
+            let _ = write!(message, " with [{}]", args.join(", "));
+        }
+        if let Some(host) = &self.host {
+            // 接続が切れている場合はバージョンを取得できないため問い合わせない
+            let fetch = error::kind_of_hresult(e.code()) != error::ErrorKind::HostNotRunning;
+            let _ = write!(message, " on {}", host.describe(fetch));
+        }
+        error::CeVIOError::from(error::Report::new(e).context(message))
+    }
+    /// `variant` を `T` に変換する
+    fn convert<T: FromVariant>(&self, variant: OwnedVariant, name: &str) -> error::Result<T> {
+        T::from_variant(variant, self, name).map_err(|e| {
+            error::CeVIOError::Conversion(
+                error::Report::new(e).context(format!("Failed to convert `{name}` to {}", T::NAME)),
+            )
+        })
+    }
     /// プロパティの値を得ます
     ///
     /// 値を得たいプロパティの名前を渡してください
     /// パラメータ付きプロパティの場合はパラメータを示すVARIANTを渡します
-    pub fn get_property(&self, prop: &str, param: Option<VARIANT>) -> core::Result<VARIANT> {
-        let dispidmember = self.get_id_from_name(prop)?;
-        let mut pdispparams = DISPPARAMS::default();
-        let mut args = if let Some(param) = param {
-            vec![param]
-        } else {
-            vec![]
-        };
-        pdispparams.cArgs = args.len() as u32;
-        pdispparams.rgvarg = args.as_mut_ptr();
-        self.invoke(dispidmember, &pdispparams, DISPATCH_PROPERTYGET)
+    pub fn get_property(&self, prop: &str, param: Option<VARIANT>) -> error::Result<OwnedVariant> {
+        match param {
+            Some(param) => self.dispatch(Kind::GetProperty, prop, &mut [param]),
+            None => self.dispatch(Kind::GetProperty, prop, &mut []),
+        }
+    }
+    /// プロパティの値を `T` として得ます
+    pub fn property<T: FromVariant>(&self, prop: &str) -> error::Result<T> {
+        let value = self.get_property(prop, None)?;
+        self.convert(value, prop)
     }
     /// プロパティに値をセットします
     ///
@@ -114,31 +511,94 @@ impl ComObject {
         prop: &str,
         param: Option<VARIANT>,
         value: VARIANT,
-    ) -> core::Result<()> {
-        let dispidmember = self.get_id_from_name(prop)?;
-        let mut pdispparams = DISPPARAMS::default();
-        let mut args = if let Some(param) = param {
-            vec![param, value]
-        } else {
-            vec![value]
+    ) -> error::Result<()> {
+        match param {
+            Some(param) => self.dispatch(Kind::SetProperty, prop, &mut [param, value])?,
+            None => self.dispatch(Kind::SetProperty, prop, &mut [value])?,
         };
-        let mut named_args = vec![DISPID_PROPERTYPUT];
-        pdispparams.cArgs = args.len() as u32;
-        pdispparams.rgvarg = args.as_mut_ptr();
-        pdispparams.cNamedArgs = 1;
-        pdispparams.rgdispidNamedArgs = named_args.as_mut_ptr();
-        self.invoke(dispidmember, &pdispparams, DISPATCH_PROPERTYPUT)?;
         Ok(())
     }
     /// メソッドを実行します
     ///
-    /// メソッド名とメソッドに渡す引数を渡します
-    pub fn invoke_method(&self, method: &str, mut args: Vec<VARIANT>) -> core::Result<VARIANT> {
-        let dispidmember = self.get_id_from_name(method)?;
-        let mut pdispparams = DISPPARAMS::default();
+    /// メソッド名とメソッドに渡す引数を渡します。引数は配列で渡すため、呼び出しごとにヒープを確保しません
+    pub fn invoke_method<const N: usize>(
+        &self,
+        method: &str,
+        mut args: [VARIANT; N],
+    ) -> error::Result<OwnedVariant> {
         args.reverse();
-        pdispparams.cArgs = args.len() as u32;
-        pdispparams.rgvarg = args.as_mut_ptr();
-        self.invoke(dispidmember, &pdispparams, DISPATCH_METHOD)
+        self.dispatch(Kind::Method, method, &mut args)
+    }
+    /// メソッドを実行し、戻り値を `T` として得ます
+    pub fn call<T: FromVariant, const N: usize>(
+        &self,
+        method: &str,
+        args: [VARIANT; N],
+    ) -> error::Result<T> {
+        let value = self.invoke_method(method, args)?;
+        self.convert(value, method)
+    }
+}
+
+/// `Invoke` の引数と戻り値をそのまま記録する（`com-trace` フィーチャー）
+#[cfg(feature = "com-trace")]
+mod com_trace {
+    use windows::{
+        core,
+        Win32::System::Com::{DISPATCH_FLAGS, DISPPARAMS, VARIANT},
+    };
+
+    use super::summarize;
+
+    pub(super) fn log_invoke(
+        dispid: i32,
+        params: &DISPPARAMS,
+        flags: DISPATCH_FLAGS,
+        hr: &core::Result<()>,
+        result: &VARIANT,
+    ) {
+        let args = unsafe { slice(params.rgvarg, params.cArgs) }
+            .iter()
+            .map(summarize)
+            .collect::<Vec<_>>();
+        let named_args = unsafe { slice(params.rgdispidNamedArgs, params.cNamedArgs) };
+        tracing::trace!(
+            target: "cevio::com",
+            dispid,
+            flags = format_args!("{:#06x}", flags.0),
+            // rgvarg は逆順（最後の引数が先頭）に並ぶ
+            args = ?args,
+            named_args = ?named_args,
+            hresult = format_args!("{:#010x}", hresult(hr)),
+            result = %match hr {
+                Ok(()) => summarize(result),
+                Err(_) => String::from("-"),
+            },
+            "Invoke"
+        );
+    }
+
+    pub(super) fn log_get_id(name: &str, hr: &core::Result<()>, dispid: i32) {
+        tracing::trace!(
+            target: "cevio::com",
+            name,
+            dispid,
+            hresult = format_args!("{:#010x}", hresult(hr)),
+            "GetIDsOfNames"
+        );
+    }
+
+    fn hresult(hr: &core::Result<()>) -> i32 {
+        match hr {
+            Ok(()) => 0,
+            Err(e) => e.code().0,
+        }
+    }
+
+    unsafe fn slice<'a, T>(ptr: *const T, len: u32) -> &'a [T] {
+        match ptr.is_null() {
+            true => &[],
+            false => std::slice::from_raw_parts(ptr, len as usize),
+        }
     }
 }
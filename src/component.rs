@@ -0,0 +1,100 @@
+use windows::Win32::System::Com::VARIANT;
+
+use crate::{error, variant_ext::VariantExt, CeVIO, ComObject};
+
+/// 感情パラメータです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// 識別子
+    pub id: String,
+    /// 感情の名前
+    pub name: String,
+    /// 感情の値（0～100）
+    pub value: i32,
+}
+
+impl CeVIO {
+    fn components_object(&self) -> error::Result<ComObject> {
+        self.talker.property("Components")
+    }
+
+    /// 現在のキャストの感情パラメータマップを取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　内容はキャストによって異なります。
+    pub fn get_components(&self) -> error::Result<Vec<Component>> {
+        let components = self.components_object()?;
+        let count = components.property("Count")?;
+
+        let components = (0..count)
+            .map(|i| {
+                let component: ComObject = components.call("At", [VARIANT::from_i32(i)])?;
+                Ok(Component {
+                    id: component.property("Id")?,
+                    name: component.property("Name")?,
+                    value: component.property("Value")?,
+                })
+            })
+            .collect::<error::Result<Vec<_>>>()?;
+        let mut written = self.written.borrow_mut();
+        for component in &components {
+            written.set_component(&component.name, component.value);
+        }
+        if let Some(cast) = written.cast() {
+            let names = components.iter().map(|c| c.name.clone()).collect();
+            self.cast_cache.borrow_mut().set_components(cast, names);
+        }
+        Ok(components)
+    }
+
+    /// 現在のキャストの感情パラメータの名前を取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　キャストごとに最初に取得した名前を記録し、以降は CeVIO を呼び出しません。（`CeVIO::refresh_casts` で消去します。）
+    /// 　値も必要な場合は `get_components` を使ってください。
+    pub fn get_component_names(&self) -> error::Result<Vec<String>> {
+        let cast = match self.written.borrow().cast() {
+            Some(cast) => cast.to_string(),
+            None => self.get_cast()?,
+        };
+        if let Some(names) = self.cast_cache.borrow().components(&cast) {
+            return Ok(names.to_vec());
+        }
+        let names = self
+            .get_components()?
+            .into_iter()
+            .map(|c| c.name)
+            .collect::<Vec<_>>();
+        self.cast_cache
+            .borrow_mut()
+            .set_components(&cast, names.clone());
+        Ok(names)
+    }
+
+    /// 現在のキャストの感情パラメータ（0～100）を名前で指定して設定します。
+    ///
+    /// 備考：
+    ///
+    /// 　前回書き込んだ値と同じ場合は CeVIO を呼び出しません。（`CeVIO::forget_written_values` を参照。）
+    pub fn set_component(&self, name: &str, value: i32) -> error::Result<()> {
+        #[cfg(feature = "history")]
+        self.requested.borrow_mut().set_component(name, value);
+        if self.written.borrow().component(name) == Some(value) {
+            return Ok(());
+        }
+        let result = self
+            .components_object()
+            .and_then(|components| components.call("ByName", [VARIANT::from_str(name)]))
+            .and_then(|component: ComObject| {
+                component.set_property("Value", None, VARIANT::from_i32(value))
+            });
+        let mut written = self.written.borrow_mut();
+        match result {
+            Ok(()) => written.set_component(name, value),
+            Err(_) => written.forget_component(name),
+        }
+        result
+    }
+}
@@ -0,0 +1,234 @@
+//! サーバーの設定ファイル
+//!
+//! 既定のパラメータ・プリセット・読み替え辞書・NG ワードを 1 つのテキストファイルに書きます。
+//! `[...]` の行から次の `[...]` の行までが 1 つのセクションです。`#` から始まる行と空行は無視されます。
+//!
+//! ```text
+//! # 最初のセクションより前は既定のパラメータ（`Params` のテキスト形式）
+//! cast = 花隈千冬
+//! speed = 50
+//!
+//! # プリセット（`Params` のテキスト形式）
+//! [preset.元気]
+//! component.嬉しい = 80
+//!
+//! # キャストごとの既定のパラメータ（`Params` のテキスト形式）。そのキャストを使うときに適用します
+//! [cast.小春六花]
+//! speed = 55
+//! component.元気 = 30
+//!
+//! # 読み替え辞書（`単語 = 読み`）。辞書ファイルとの変換は `lexicon::Lexicon` を参照
+//! [lexicon]
+//! CeVIO = チェビオ
+//!
+//! # NG ワード（1 行に 1 つ）。含むセリフは読み上げません
+//! [ng]
+//! 禁止語
+//! ```
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    error::{self, report},
+    fs_util::read_to_string,
+    lexicon::Lexicon,
+    params::Params,
+};
+
+/// サーバーの設定です。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerConfig {
+    /// すべてのリクエストに適用するパラメータ
+    pub defaults: Params,
+    /// リクエストで名前を指定して適用するパラメータ
+    pub presets: HashMap<String, Params>,
+    /// キャストごとの既定のパラメータ（キャスト名, パラメータ）
+    pub cast_profiles: HashMap<String, Params>,
+    /// 読み替え辞書
+    pub lexicon: Lexicon,
+    /// NG ワード
+    pub ng_words: Vec<String>,
+}
+
+enum Section {
+    Defaults,
+    Preset(String),
+    Cast(String),
+    Lexicon,
+    Ng,
+}
+
+impl ServerConfig {
+    /// テキスト形式の設定を読み込みます。
+    pub fn parse(s: &str) -> error::Result<Self> {
+        let mut config = ServerConfig::default();
+        let mut section = Section::Defaults;
+        let mut params_text = String::new();
+
+        // `Params` のセクションは行をまとめてから読み込む
+        let flush = |section: &Section, text: &mut String, config: &mut ServerConfig| {
+            let params = Params::parse(text).map_err(|e| {
+                error::CeVIOError::InvalidInput(match section {
+                    Section::Preset(name) => {
+                        e.into_inner().context(format!("Invalid preset `{name}`"))
+                    }
+                    Section::Cast(name) => e
+                        .into_inner()
+                        .context(format!("Invalid profile of cast `{name}`")),
+                    _ => e.into_inner().context("Invalid defaults"),
+                })
+            })?;
+            match section {
+                Section::Defaults => config.defaults = params,
+                Section::Preset(name) => {
+                    config.presets.insert(name.clone(), params);
+                }
+                Section::Cast(name) => {
+                    config.cast_profiles.insert(name.clone(), params);
+                }
+                Section::Lexicon | Section::Ng => {}
+            }
+            text.clear();
+            Ok::<_, error::CeVIOError>(())
+        };
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                flush(&section, &mut params_text, &mut config)?;
+                section = match header.trim() {
+                    "lexicon" => Section::Lexicon,
+                    "ng" => Section::Ng,
+                    header => {
+                        match (header.strip_prefix("preset."), header.strip_prefix("cast.")) {
+                            (Some(name), _) => Section::Preset(name.trim().to_string()),
+                            (_, Some(name)) => Section::Cast(name.trim().to_string()),
+                            (None, None) => {
+                                return Err(error::CeVIOError::InvalidInput(report!(
+                                    "Unknown section `[{header}]` at line {}",
+                                    i + 1
+                                )))
+                            }
+                        }
+                    }
+                };
+                continue;
+            }
+            match &section {
+                Section::Defaults | Section::Preset(_) | Section::Cast(_) => {
+                    params_text.push_str(line);
+                    params_text.push('\n');
+                }
+                Section::Lexicon => {
+                    let (word, reading) = line
+                        .split_once('=')
+                        .ok_or_else(|| report!("Missing `=` at line {}", i + 1))
+                        .map_err(error::CeVIOError::InvalidInput)?;
+                    config.lexicon.insert(word.trim(), reading.trim());
+                }
+                Section::Ng => config.ng_words.push(line.to_string()),
+            }
+        }
+        flush(&section, &mut params_text, &mut config)?;
+        Ok(config)
+    }
+
+    /// 設定ファイルを読み込みます。
+    pub fn load(path: impl AsRef<Path>) -> error::Result<Self> {
+        Self::parse(&read_to_string(path.as_ref())?)
+    }
+
+    /// セリフに NG ワードがないか確認し、読み替え辞書を適用したセリフを返します。
+    pub fn prepare_text(&self, text: &str) -> error::Result<String> {
+        if let Some(word) = self
+            .ng_words
+            .iter()
+            .find(|word| text.contains(word.as_str()))
+        {
+            return Err(error::CeVIOError::InvalidInput(report!(
+                "Text contains NG word `{word}`"
+            )));
+        }
+        Ok(self.lexicon.apply(text))
+    }
+
+    /// 既定のパラメータ、キャストごとの既定のパラメータ、プリセット、リクエストのパラメータの順に上書きしたパラメータを返します。
+    pub fn params_for(&self, preset: Option<&str>, params: &Params) -> error::Result<Params> {
+        let preset = match preset {
+            Some(name) => self
+                .presets
+                .get(name)
+                .ok_or_else(|| report!("Unknown preset `{name}`"))
+                .map_err(error::CeVIOError::InvalidInput)?
+                .clone(),
+            None => Params::default(),
+        };
+        let merged = self.defaults.merge(&preset).merge(params);
+        let profile = merged
+            .cast
+            .as_ref()
+            .and_then(|cast| self.cast_profiles.get(cast))
+            .cloned()
+            .unwrap_or_default();
+        Ok(self.defaults.merge(&profile).merge(&preset).merge(params))
+    }
+}
+
+/// 再読み込みできる設定です。
+///
+/// `Clone` したものは同じ設定を共有します。
+/// 再読み込みしても、すでに `get` で取得した設定は変わらないため、処理中のリクエストには影響しません。
+#[derive(Debug, Clone, Default)]
+pub struct SharedConfig {
+    path: Option<PathBuf>,
+    current: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl SharedConfig {
+    /// ファイルから読み込まない設定を作成します。`reload` はできません。
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            path: None,
+            current: Arc::new(RwLock::new(Arc::new(config))),
+        }
+    }
+
+    /// 設定ファイルを読み込みます。`reload` で同じファイルを読み込み直せます。
+    pub fn load(path: impl AsRef<Path>) -> error::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let config = ServerConfig::load(&path)?;
+        Ok(Self {
+            path: Some(path),
+            current: Arc::new(RwLock::new(Arc::new(config))),
+        })
+    }
+
+    /// 現在の設定を取得します。
+    pub fn get(&self) -> Arc<ServerConfig> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 設定ファイルを読み込み直します。
+    ///
+    /// 読み込みに失敗した場合は今までの設定のままです。
+    pub fn reload(&self) -> error::Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| report!("Config was not loaded from a file"))
+            .map_err(error::CeVIOError::from)?;
+        let config = ServerConfig::load(path)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+        Ok(())
+    }
+}
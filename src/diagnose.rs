@@ -0,0 +1,371 @@
+//! 実行環境の診断
+//!
+//! `diagnose` で、インストールされている製品、ProgID の登録、バージョン、COM のアパートメント、利用可能なキャストと、
+//! よくある設定の誤りをまとめて調べます。結果はそのまま不具合の報告に貼り付けられる形式で表示できます。
+//!
+//! CeVIO は起動しません。起動していない製品はバージョンとキャストを調べません。
+//!
+//! 表示と問題の説明は `i18n::Lang::current` の言語になります。
+//!
+//! ```no_run
+//! let report = cevio::diagnose::diagnose();
+//! println!("{report}");
+//! ```
+
+use std::fmt;
+
+use windows::Win32::{
+    Foundation::{CO_E_NOTINITIALIZED, RPC_E_CHANGED_MODE},
+    System::Com::{
+        CoGetApartmentType, APTTYPE, APTTYPEQUALIFIER, APTTYPE_MAINSTA, APTTYPE_MTA, APTTYPE_NA,
+        APTTYPE_STA,
+    },
+};
+
+use crate::{com, error, i18n::tr, CeVIO, HostKind};
+
+/// 現在のスレッドの COM のアパートメントです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apartment {
+    /// COM が初期化されていません（このライブラリが STA で初期化します）
+    NotInitialized,
+    /// シングルスレッドアパートメント
+    Sta,
+    /// メインのシングルスレッドアパートメント
+    MainSta,
+    /// マルチスレッドアパートメント（CeVIO を使えません）
+    Mta,
+    /// ニュートラルアパートメント
+    Neutral,
+    /// 不明（`CoGetApartmentType` が失敗した場合の `HRESULT` など）
+    Unknown(i32),
+}
+
+impl Apartment {
+    /// 現在のスレッドのアパートメントを取得します。
+    pub fn current() -> Self {
+        let mut apt_type = APTTYPE::default();
+        let mut qualifier = APTTYPEQUALIFIER::default();
+        match unsafe { CoGetApartmentType(&mut apt_type, &mut qualifier) } {
+            Err(e) if e.code() == CO_E_NOTINITIALIZED => Self::NotInitialized,
+            Err(e) => Self::Unknown(e.code().0),
+            Ok(()) => match apt_type {
+                APTTYPE_STA => Self::Sta,
+                APTTYPE_MAINSTA => Self::MainSta,
+                APTTYPE_MTA => Self::Mta,
+                APTTYPE_NA => Self::Neutral,
+                APTTYPE(other) => Self::Unknown(other),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Apartment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotInitialized => f.write_str(&tr!("未初期化", "not initialized")),
+            Self::Sta => f.write_str("STA"),
+            Self::MainSta => f.write_str(&tr!("STA（メイン）", "STA (main)")),
+            Self::Mta => f.write_str("MTA"),
+            Self::Neutral => f.write_str("NA"),
+            Self::Unknown(code) => {
+                f.write_str(&tr!("不明（{:#010x}）", "unknown ({:#010x})", code))
+            }
+        }
+    }
+}
+
+/// ProgID の登録状態です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgIdReport {
+    /// ProgID
+    pub prog_id: &'static str,
+    /// 登録されている場合はその CLSID
+    pub clsid: Option<String>,
+}
+
+/// 製品ごとの診断結果です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostReport {
+    /// 製品
+    pub host: HostKind,
+    /// Talker の ProgID
+    pub talker: ProgIdReport,
+    /// ServiceControl の ProgID
+    pub service_control: ProgIdReport,
+    /// COM オブジェクトを作成できなかった場合はそのエラー
+    pub instance_error: Option<String>,
+    /// 起動中のプロセスの ID
+    pub pid: Option<u32>,
+    /// 外部からアクセス可能かどうか
+    pub host_started: Option<bool>,
+    /// 製品のバージョン
+    pub host_version: Option<String>,
+    /// インターフェースのバージョン
+    pub interface_version: Option<String>,
+    /// 利用可能なキャスト
+    pub casts: Vec<String>,
+    /// 調べている途中で失敗した操作とそのエラー
+    pub errors: Vec<String>,
+}
+
+impl HostReport {
+    /// 製品がインストールされている（ProgID が登録されている）かどうかを取得します。
+    pub fn is_installed(&self) -> bool {
+        self.talker.clsid.is_some() && self.service_control.clsid.is_some()
+    }
+}
+
+/// 診断結果です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// このライブラリのバージョン
+    pub library_version: &'static str,
+    /// プロセスのビット数
+    pub pointer_width: u32,
+    /// 診断を始めた時点の現在のスレッドのアパートメント
+    pub apartment: Apartment,
+    /// 製品ごとの診断結果
+    pub hosts: Vec<HostReport>,
+    /// 見つかった問題とその対処
+    pub problems: Vec<String>,
+}
+
+impl Report {
+    /// 問題が見つからなかったかどうかを取得します。
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// 実行環境を診断します。
+pub fn diagnose() -> Report {
+    let apartment = Apartment::current();
+    let hosts: Vec<_> = HostKind::ALL.iter().copied().map(diagnose_host).collect();
+
+    let mut problems = Vec::new();
+    if apartment == Apartment::Mta {
+        problems.push(tr!(
+            "このスレッドは MTA で初期化されています。CeVIO は STA のスレッドから操作してください（`actor::Handle` を使うと専用のスレッドで動かせます）",
+            "This thread is initialized as MTA. Use CeVIO from an STA thread (`actor::Handle` runs it on a dedicated thread)",
+        ));
+    }
+    if !hosts.iter().any(HostReport::is_installed) {
+        let names: Vec<_> = HostKind::ALL
+            .iter()
+            .map(|host| host.product_name())
+            .collect();
+        problems.push(tr!(
+            "{} の ProgID が登録されていません。インストールされているか確認してください",
+            "ProgIDs of {} are not registered. Check that it is installed",
+            names.join(tr!("、", " / ").as_str())
+        ));
+        if cfg!(target_pointer_width = "32") {
+            problems.push(tr!(
+                "32 ビットのプロセスです。64 ビット版の CeVIO の COM コンポーネントは 64 ビットのプロセスから使用してください",
+                "This is a 32-bit process. Use the COM components of 64-bit CeVIO from a 64-bit process",
+            ));
+        }
+    }
+    for host in hosts.iter().filter(|h| h.is_installed()) {
+        let name = host.host.product_name();
+        if let Some(e) = &host.instance_error {
+            problems.push(tr!(
+                "{} の COM オブジェクトを作成できません: {}",
+                "Failed to create the COM object of {}: {}",
+                name,
+                e
+            ));
+        }
+        match (host.pid, host.host_started) {
+            (None, _) => {}
+            (Some(_), Some(false)) => problems.push(tr!(
+                "{} は起動していますが、外部からアクセスできません。起動が終わるまで待つか、ダイアログが開いていないか確認してください",
+                "{} is running but cannot be accessed. Wait for it to finish starting, or check that no dialog is open",
+                name
+            )),
+            (Some(_), Some(true)) if host.casts.is_empty() => problems.push(tr!(
+                "{} に利用可能なキャストがありません。ボイスがインストール・アクティベーションされているか確認してください",
+                "{} has no available casts. Check that voices are installed and activated",
+                name
+            )),
+            _ => {}
+        }
+    }
+
+    Report {
+        library_version: env!("CARGO_PKG_VERSION"),
+        pointer_width: usize::BITS,
+        apartment,
+        hosts,
+        problems,
+    }
+}
+
+fn prog_id_report(prog_id: &'static str) -> ProgIdReport {
+    ProgIdReport {
+        prog_id,
+        clsid: com::clsid_of(prog_id)
+            .ok()
+            .map(|clsid| format!("{{{clsid:?}}}")),
+    }
+}
+
+/// 失敗した場合は `errors` に追加して `None` を返す
+fn check<T>(errors: &mut Vec<String>, name: &str, result: error::Result<T>) -> Option<T> {
+    result
+        .map_err(|e| errors.push(format!("{name}: {e:#}")))
+        .ok()
+}
+
+fn diagnose_host(host: HostKind) -> HostReport {
+    let mut report = HostReport {
+        host,
+        talker: prog_id_report(host.talker_prog_id()),
+        service_control: prog_id_report(host.service_control_prog_id()),
+        instance_error: None,
+        pid: None,
+        host_started: None,
+        host_version: None,
+        interface_version: None,
+        casts: Vec::new(),
+        errors: Vec::new(),
+    };
+    if !report.is_installed() {
+        return report;
+    }
+    let cevio = match CeVIO::with_host(host) {
+        Ok(cevio) => cevio,
+        Err(e) => {
+            report.instance_error = Some(match e.hresult() {
+                Some(hresult) if hresult == RPC_E_CHANGED_MODE => {
+                    tr!(
+                        "このスレッドは STA 以外で初期化されています",
+                        "This thread is initialized as a non-STA apartment"
+                    )
+                }
+                _ => format!("{e:#}"),
+            });
+            return report;
+        }
+    };
+    let mut errors = Vec::new();
+    report.pid = check(&mut errors, "get_host_process", cevio.get_host_process())
+        .flatten()
+        .map(|process| process.pid);
+    if report.pid.is_some() {
+        report.host_started = check(
+            &mut errors,
+            "get_is_host_started",
+            cevio.get_is_host_started(),
+        );
+    }
+    if report.host_started == Some(true) {
+        report.host_version = check(&mut errors, "get_host_version", cevio.get_host_version());
+        report.interface_version = check(
+            &mut errors,
+            "get_interface_version",
+            cevio.get_interface_version(),
+        );
+        report.casts = check(
+            &mut errors,
+            "get_available_casts",
+            cevio.get_available_casts(),
+        )
+        .unwrap_or_default();
+    }
+    report.errors = errors;
+    report
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}",
+            tr!(
+                "cevio {} ({} ビット)",
+                "cevio {} ({}-bit)",
+                self.library_version,
+                self.pointer_width
+            )
+        )?;
+        writeln!(
+            f,
+            "{}",
+            tr!("アパートメント: {}", "Apartment: {}", self.apartment)
+        )?;
+        for host in &self.hosts {
+            let unknown = || "-".to_string();
+            writeln!(f, "[{}]", host.host.product_name())?;
+            for prog_id in [&host.talker, &host.service_control] {
+                writeln!(
+                    f,
+                    "  {}: {}",
+                    prog_id.prog_id,
+                    prog_id
+                        .clsid
+                        .clone()
+                        .unwrap_or_else(|| tr!("未登録", "not registered"))
+                )?;
+            }
+            if !host.is_installed() {
+                continue;
+            }
+            if let Some(e) = &host.instance_error {
+                writeln!(
+                    f,
+                    "{}",
+                    tr!(
+                        "  オブジェクトの作成: 失敗（{}）",
+                        "  Object creation: failed ({})",
+                        e
+                    )
+                )?;
+                continue;
+            }
+            let pid = host.pid.map_or_else(
+                || tr!("起動していません", "not running"),
+                |pid| format!("PID {pid}"),
+            );
+            writeln!(f, "{}", tr!("  プロセス: {}", "  Process: {}", pid))?;
+            let started = host.host_started.map_or_else(unknown, |s| s.to_string());
+            writeln!(
+                f,
+                "{}",
+                tr!("  アクセス可能: {}", "  Accessible: {}", started)
+            )?;
+            let version = host.host_version.clone().unwrap_or_else(unknown);
+            writeln!(f, "{}", tr!("  バージョン: {}", "  Version: {}", version))?;
+            let interface_version = host.interface_version.clone().unwrap_or_else(unknown);
+            writeln!(
+                f,
+                "{}",
+                tr!(
+                    "  インターフェースのバージョン: {}",
+                    "  Interface version: {}",
+                    interface_version
+                )
+            )?;
+            let casts = host.casts.join(", ");
+            writeln!(f, "{}", tr!("  キャスト: {}", "  Casts: {}", casts))?;
+            for e in &host.errors {
+                writeln!(f, "{}", tr!("  エラー: {}", "  Error: {}", e))?;
+            }
+        }
+        match self.problems.is_empty() {
+            true => writeln!(
+                f,
+                "{}",
+                tr!("問題は見つかりませんでした", "No problems found")
+            ),
+            false => {
+                writeln!(f, "{}", tr!("問題:", "Problems:"))?;
+                for problem in &self.problems {
+                    writeln!(f, "  - {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
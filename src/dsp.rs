@@ -0,0 +1,192 @@
+//! 音の高さを変えない伸縮（`dsp` フィーチャー）
+//!
+//! 合成した音声を、決まった長さ（映像の尺や字幕の表示時間など）にぴったり合わせるためのものです。
+//! 話速（`Speed`）を変えると声質も変わりますが、WSOLA（波形の重ね合わせ）で伸縮するため、音の高さと声質はほぼそのままです。
+//!
+//! `TimeStretch` は `OutputProcessor` なので、`CeVIO::add_output_processor` で出力に適用できます。
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use cevio::{dsp::TimeStretch, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//!
+//! cevio.add_output_processor(TimeStretch::Duration(Duration::from_secs(3)));
+//! cevio.output_wave_to_file("3 秒ちょうどで話します。", r"E:\file.wav").unwrap();
+//! ```
+//!
+//! ```
+//! use std::time::Duration;
+//! use cevio::{dsp, processor::AudioBuffer};
+//!
+//! // 1 秒の 200Hz の正弦波
+//! let buffer = AudioBuffer {
+//!     sample_rate: 8000,
+//!     channels: 1,
+//!     samples: (0..8000)
+//!         .map(|i| (i as f32 * 200.0 * std::f32::consts::TAU / 8000.0).sin() * 0.5)
+//!         .collect(),
+//! };
+//! let stretched = dsp::stretch_to(&buffer, Duration::from_millis(1500)).unwrap();
+//! assert_eq!(stretched.frames(), 12000);
+//!
+//! // 音の高さ（0 をまたぐ回数 / 秒）は変わらない
+//! let crossings = |samples: &[f32]| samples.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+//! let rate = crossings(&stretched.samples) as f64 / 1.5;
+//! assert!((rate - 400.0).abs() < 20.0, "{rate}");
+//! ```
+
+use std::{f32::consts::TAU, ops::RangeInclusive, time::Duration};
+
+use crate::{
+    error::{self, report},
+    processor::{AudioBuffer, OutputMeta, OutputProcessor},
+};
+
+/// 重ね合わせる 1 フレームの長さ（秒）
+const FRAME_SECS: f64 = 0.03;
+
+/// 伸縮の後処理です。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeStretch {
+    /// 長さを何倍にするか。2.0 で 2 倍の長さ（半分の速さ）になります
+    Factor(f64),
+    /// 伸縮した後の長さ
+    Duration(Duration),
+}
+
+impl OutputProcessor for TimeStretch {
+    fn process(&self, buffer: AudioBuffer, _meta: &OutputMeta) -> error::Result<AudioBuffer> {
+        match *self {
+            TimeStretch::Factor(factor) => stretch(&buffer, factor),
+            TimeStretch::Duration(duration) => stretch_to(&buffer, duration),
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// 音の高さを変えずに、長さを `factor` 倍にします。
+///
+/// 戻り値：
+///
+/// 　`factor` が正の有限の値でない場合は `CeVIOError::InvalidInput`。
+pub fn stretch(buffer: &AudioBuffer, factor: f64) -> error::Result<AudioBuffer> {
+    if !(factor.is_finite() && factor > 0.0) {
+        return Err(error::CeVIOError::InvalidInput(report!(
+            "Stretch factor must be positive, but got `{factor}`"
+        )));
+    }
+    let target = (buffer.frames() as f64 * factor).round() as usize;
+    Ok(stretch_frames(buffer, target))
+}
+
+/// 音の高さを変えずに、長さを `duration` にします。長さはサンプル単位で `duration` に一致します。
+///
+/// 戻り値：
+///
+/// 　`duration` が 0 の場合は `CeVIOError::InvalidInput`。
+pub fn stretch_to(buffer: &AudioBuffer, duration: Duration) -> error::Result<AudioBuffer> {
+    let target = (duration.as_secs_f64() * f64::from(buffer.sample_rate)).round() as usize;
+    if target == 0 {
+        return Err(error::CeVIOError::InvalidInput(report!(
+            "Stretch duration must be longer than one sample, but got `{duration:?}`"
+        )));
+    }
+    Ok(stretch_frames(buffer, target))
+}
+
+/// WSOLA で `target` フレームに伸縮する
+fn stretch_frames(buffer: &AudioBuffer, target: usize) -> AudioBuffer {
+    let channels = usize::from(buffer.channels.max(1));
+    let input = buffer.frames();
+    // 50% 重ねるため偶数にする
+    let frame = ((f64::from(buffer.sample_rate) * FRAME_SECS) as usize).max(4) & !1;
+    let hop = frame / 2;
+
+    // 1 フレームに満たない場合は伸縮できないので、無音で埋めるか切り詰める
+    if input < frame || target == input {
+        let mut samples = buffer.samples[..input * channels].to_vec();
+        samples.resize(target * channels, 0.0);
+        return AudioBuffer { samples, ..*buffer };
+    }
+
+    let analysis_hop = hop as f64 * input as f64 / target as f64;
+    let tolerance = hop / 2;
+    let last_start = input - frame;
+    // 位置合わせはチャンネルを混ぜた波形で行い、すべてのチャンネルに同じ位置を使う
+    let mono = buffer
+        .samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect::<Vec<_>>();
+    // 50% 重ねると合計が 1 になる窓
+    let window = (0..frame)
+        .map(|i| 0.5 - 0.5 * (TAU * i as f32 / frame as f32).cos())
+        .collect::<Vec<_>>();
+
+    let mut samples = vec![0.0f32; (target + frame) * channels];
+    let mut weights = vec![0.0f32; target + frame];
+    let mut previous = 0;
+    for k in 0.. {
+        let out_start = k * hop;
+        if out_start >= target {
+            break;
+        }
+        let nominal = ((k as f64 * analysis_hop).round() as usize).min(last_start);
+        let start = match k {
+            0 => 0,
+            // 前のフレームの続き（重なる部分）に最も似ている位置を選ぶ
+            _ => best_match(
+                &mono,
+                previous + hop,
+                hop,
+                nominal.saturating_sub(tolerance)..=(nominal + tolerance).min(last_start),
+            ),
+        };
+        for (i, w) in window.iter().enumerate() {
+            weights[out_start + i] += w;
+            let from = (start + i) * channels;
+            let to = (out_start + i) * channels;
+            for c in 0..channels {
+                samples[to + c] += w * buffer.samples[from + c];
+            }
+        }
+        previous = start;
+    }
+
+    samples.truncate(target * channels);
+    for (frame, weight) in samples.chunks_mut(channels).zip(&weights) {
+        if *weight > f32::EPSILON {
+            frame.iter_mut().for_each(|s| *s /= weight);
+        }
+    }
+    AudioBuffer { samples, ..*buffer }
+}
+
+/// `mono[natural..natural + len]` と最も相関の高い、`candidates` の中の開始位置を返す
+fn best_match(
+    mono: &[f32],
+    natural: usize,
+    len: usize,
+    candidates: RangeInclusive<usize>,
+) -> usize {
+    let reference = &mono[natural..natural + len];
+    let mut best = (*candidates.start(), f32::MIN);
+    for start in candidates {
+        let candidate = &mono[start..start + len];
+        let (dot, energy) = reference
+            .iter()
+            .zip(candidate)
+            .fold((0.0, 0.0), |(dot, energy), (r, c)| {
+                (dot + r * c, energy + c * c)
+            });
+        let score = dot / (energy + f32::EPSILON).sqrt();
+        if score > best.1 {
+            best = (start, score);
+        }
+    }
+    best.0
+}
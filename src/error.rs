@@ -1,5 +1,774 @@
+//! エラーの型
+//!
+//! `CeVIOError` は種類ごとのバリアントに、原因の連なり（`Report`）を持ちます。
+//! `Report` はこのクレートの型のため、利用側の依存関係に `anyhow` を加えません。
+//!
+//! `anyhow` フィーチャー（既定で有効）を有効にすると、`anyhow::Error` との相互変換を使えます。
+//! 依存関係を減らす場合は `default-features = false` で無効にしてください。
+
+use std::{error::Error as StdError, fmt, ops::Deref};
+
+use windows::{
+    core::HRESULT,
+    Win32::Foundation::{
+        CO_E_CLASSSTRING, CO_E_NOTINITIALIZED, CO_E_SERVER_EXEC_FAILURE, DISP_E_OVERFLOW,
+        DISP_E_TYPEMISMATCH, ERROR_TIMEOUT, REGDB_E_CLASSNOTREG, RPC_E_CALL_REJECTED,
+        RPC_E_CHANGED_MODE, RPC_E_DISCONNECTED, RPC_E_SERVERCALL_REJECTED,
+        RPC_E_SERVERCALL_RETRYLATER,
+    },
+};
+
+use crate::i18n::Lang;
+
+/// エラーの種類です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    any(feature = "server", feature = "pipe", feature = "jsonl"),
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ErrorKind {
+    /// COM の初期化に失敗しました
+    ComInit,
+    /// COM オブジェクトの作成に失敗しました（CeVIO がインストールされていないなど）
+    ObjectCreation,
+    /// CeVIO が起動していないか、接続が切れました
+    HostNotRunning,
+    /// CeVIO の起動に失敗しました
+    HostStart,
+    /// キャストが存在しません
+    InvalidCast,
+    /// COM の戻り値を変換できません
+    Conversion,
+    /// 時間内に終わりませんでした
+    Timeout,
+    /// ファイルなどの入出力に失敗しました
+    Io,
+    /// 引数や設定が不正です
+    InvalidInput,
+    /// CeVIO が処理に失敗したことを返しました（`OutputWaveToFile` が `false` を返した場合など）
+    OperationFailed,
+    /// その他の COM の呼び出しに失敗しました
+    Com,
+    /// 認証が必要です（サーバーのトークンが誤っている場合など）
+    Unauthorized,
+    /// 許可されていません（サーバーに接続できないアドレスの場合など）
+    Forbidden,
+    /// その他のエラー
+    Other,
+}
+
+impl ErrorKind {
+    /// すべての種類
+    const ALL: [Self; 14] = [
+        Self::ComInit,
+        Self::ObjectCreation,
+        Self::HostNotRunning,
+        Self::HostStart,
+        Self::InvalidCast,
+        Self::Conversion,
+        Self::Timeout,
+        Self::Io,
+        Self::InvalidInput,
+        Self::OperationFailed,
+        Self::Com,
+        Self::Unauthorized,
+        Self::Forbidden,
+        Self::Other,
+    ];
+
+    /// 種類の識別子（`host_not_running` など）を取得します。シリアライズした場合もこの値になります。
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ComInit => "com_init",
+            Self::ObjectCreation => "object_creation",
+            Self::HostNotRunning => "host_not_running",
+            Self::HostStart => "host_start",
+            Self::InvalidCast => "invalid_cast",
+            Self::Conversion => "conversion",
+            Self::Timeout => "timeout",
+            Self::Io => "io",
+            Self::InvalidInput => "invalid_input",
+            Self::OperationFailed => "operation_failed",
+            Self::Com => "com",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden => "forbidden",
+            Self::Other => "other",
+        }
+    }
+
+    /// 識別子から種類を取得します。
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// 同じ操作を再試行すると成功する可能性があるかどうかを取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　時間切れと、CeVIO が起動していない（起動し直せば成功する）場合は `true` です。
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Timeout | Self::HostNotRunning)
+    }
+
+    /// ユーザー向けの短い説明を取得します。
+    pub fn description(self, lang: Lang) -> &'static str {
+        match self {
+            Self::ComInit => lang.pick("COM の初期化に失敗しました", "Failed to initialize COM"),
+            Self::ObjectCreation => lang.pick(
+                "CeVIO に接続できませんでした（インストールされていない可能性があります）",
+                "Failed to connect to CeVIO (it may not be installed)",
+            ),
+            Self::HostNotRunning => lang.pick(
+                "CeVIO が起動していないか、接続が切れました",
+                "CeVIO is not running or the connection was lost",
+            ),
+            Self::HostStart => lang.pick("CeVIO の起動に失敗しました", "Failed to start CeVIO"),
+            Self::InvalidCast => lang.pick("キャストが存在しません", "The cast does not exist"),
+            Self::Conversion => lang.pick(
+                "CeVIO の戻り値を変換できませんでした",
+                "Failed to convert a value returned by CeVIO",
+            ),
+            Self::Timeout => lang.pick("時間内に終わりませんでした", "The operation timed out"),
+            Self::Io => lang.pick("ファイルの入出力に失敗しました", "An I/O error occurred"),
+            Self::InvalidInput => lang.pick("引数や設定が不正です", "Invalid argument or setting"),
+            Self::OperationFailed => lang.pick(
+                "CeVIO が処理に失敗しました",
+                "CeVIO reported that the operation failed",
+            ),
+            Self::Com => lang.pick("CeVIO の呼び出しに失敗しました", "A call to CeVIO failed"),
+            Self::Unauthorized => lang.pick("認証が必要です", "Authentication is required"),
+            Self::Forbidden => lang.pick("許可されていません", "Access is forbidden"),
+            Self::Other => lang.pick("エラーが発生しました", "An error occurred"),
+        }
+    }
+}
+
+/// このライブラリのエラーです。
+///
+/// 種類ごとのバリアントが元のエラー（`Report`）を持ちます。
+/// `kind` で種類を、`hresult` で COM の `HRESULT` を取得できます。
+///
+/// `Report`（`anyhow` フィーチャーでは `anyhow::Error` も）から変換した場合は、
+/// 原因の `windows::core::Error` の `HRESULT` や `std::io::Error` から種類を判定します。
 #[derive(Debug, thiserror::Error)]
-#[error(transparent)]
-pub struct CeVIOError(pub anyhow::Error);
+pub enum CeVIOError {
+    /// COM の初期化に失敗しました
+    #[error(transparent)]
+    ComInit(Report),
+    /// COM オブジェクトの作成に失敗しました
+    #[error(transparent)]
+    ObjectCreation(Report),
+    /// CeVIO が起動していないか、接続が切れました
+    #[error(transparent)]
+    HostNotRunning(Report),
+    /// CeVIO の起動に失敗しました。理由は `host_start_error` で取得できます
+    #[error(transparent)]
+    HostStart(Report),
+    /// キャストが存在しません
+    #[error(transparent)]
+    InvalidCast(Report),
+    /// COM の戻り値を変換できません
+    #[error(transparent)]
+    Conversion(Report),
+    /// 時間内に終わりませんでした
+    #[error(transparent)]
+    Timeout(Report),
+    /// ファイルなどの入出力に失敗しました
+    #[error(transparent)]
+    Io(Report),
+    /// 引数や設定が不正です
+    #[error(transparent)]
+    InvalidInput(Report),
+    /// CeVIO が処理に失敗したことを返しました
+    #[error(transparent)]
+    OperationFailed(Report),
+    /// その他の COM の呼び出しに失敗しました
+    #[error(transparent)]
+    Com(Report),
+    /// 認証が必要です
+    #[error(transparent)]
+    Unauthorized(Report),
+    /// 許可されていません
+    #[error(transparent)]
+    Forbidden(Report),
+    /// その他のエラー
+    #[error(transparent)]
+    Other(Report),
+}
 
 pub type Result<T> = std::result::Result<T, CeVIOError>;
+
+impl CeVIOError {
+    /// 種類を指定してエラーを作成します。
+    pub fn new(kind: ErrorKind, error: impl Into<Report>) -> Self {
+        let error = error.into();
+        match kind {
+            ErrorKind::ComInit => Self::ComInit(error),
+            ErrorKind::ObjectCreation => Self::ObjectCreation(error),
+            ErrorKind::HostNotRunning => Self::HostNotRunning(error),
+            ErrorKind::HostStart => Self::HostStart(error),
+            ErrorKind::InvalidCast => Self::InvalidCast(error),
+            ErrorKind::Conversion => Self::Conversion(error),
+            ErrorKind::Timeout => Self::Timeout(error),
+            ErrorKind::Io => Self::Io(error),
+            ErrorKind::InvalidInput => Self::InvalidInput(error),
+            ErrorKind::OperationFailed => Self::OperationFailed(error),
+            ErrorKind::Com => Self::Com(error),
+            ErrorKind::Unauthorized => Self::Unauthorized(error),
+            ErrorKind::Forbidden => Self::Forbidden(error),
+            ErrorKind::Other => Self::Other(error),
+        }
+    }
+
+    /// エラーの種類を取得します。
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ComInit(_) => ErrorKind::ComInit,
+            Self::ObjectCreation(_) => ErrorKind::ObjectCreation,
+            Self::HostNotRunning(_) => ErrorKind::HostNotRunning,
+            Self::HostStart(_) => ErrorKind::HostStart,
+            Self::InvalidCast(_) => ErrorKind::InvalidCast,
+            Self::Conversion(_) => ErrorKind::Conversion,
+            Self::Timeout(_) => ErrorKind::Timeout,
+            Self::Io(_) => ErrorKind::Io,
+            Self::InvalidInput(_) => ErrorKind::InvalidInput,
+            Self::OperationFailed(_) => ErrorKind::OperationFailed,
+            Self::Com(_) => ErrorKind::Com,
+            Self::Unauthorized(_) => ErrorKind::Unauthorized,
+            Self::Forbidden(_) => ErrorKind::Forbidden,
+            Self::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    /// 元のエラーを取得します。
+    pub fn inner(&self) -> &Report {
+        match self {
+            Self::ComInit(e)
+            | Self::ObjectCreation(e)
+            | Self::HostNotRunning(e)
+            | Self::HostStart(e)
+            | Self::InvalidCast(e)
+            | Self::Conversion(e)
+            | Self::Timeout(e)
+            | Self::Io(e)
+            | Self::InvalidInput(e)
+            | Self::OperationFailed(e)
+            | Self::Com(e)
+            | Self::Unauthorized(e)
+            | Self::Forbidden(e)
+            | Self::Other(e) => e,
+        }
+    }
+
+    /// 元のエラーに変換します。
+    pub fn into_inner(self) -> Report {
+        match self {
+            Self::ComInit(e)
+            | Self::ObjectCreation(e)
+            | Self::HostNotRunning(e)
+            | Self::HostStart(e)
+            | Self::InvalidCast(e)
+            | Self::Conversion(e)
+            | Self::Timeout(e)
+            | Self::Io(e)
+            | Self::InvalidInput(e)
+            | Self::OperationFailed(e)
+            | Self::Com(e)
+            | Self::Unauthorized(e)
+            | Self::Forbidden(e)
+            | Self::Other(e) => e,
+        }
+    }
+
+    /// 原因が COM の呼び出しの場合は、その `HRESULT` を取得します。
+    pub fn hresult(&self) -> Option<HRESULT> {
+        self.inner().chain().find_map(|cause| {
+            cause
+                .downcast_ref::<windows::core::Error>()
+                .map(|e| e.code())
+                .or_else(|| cause.downcast_ref::<HResultError>().map(|e| e.0))
+        })
+    }
+
+    /// CeVIO が起動していないか、接続が切れたかどうかを取得します。
+    pub fn is_host_not_running(&self) -> bool {
+        self.kind() == ErrorKind::HostNotRunning
+    }
+
+    /// CeVIO の起動に失敗したかどうかを取得します。
+    pub fn is_host_start_failed(&self) -> bool {
+        self.kind() == ErrorKind::HostStart
+    }
+
+    /// CeVIO が他の処理中で呼び出しを受け付けなかったかどうかを取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　少し待ってから再試行すると成功することがあります。
+    pub fn is_busy(&self) -> bool {
+        self.hresult().is_some_and(|hresult| {
+            [
+                RPC_E_CALL_REJECTED,
+                RPC_E_SERVERCALL_REJECTED,
+                RPC_E_SERVERCALL_RETRYLATER,
+            ]
+            .contains(&hresult)
+        })
+    }
+
+    /// キャストが存在しないかどうかを取得します。
+    pub fn is_cast_invalid(&self) -> bool {
+        self.kind() == ErrorKind::InvalidCast
+    }
+
+    /// 時間内に終わらなかったかどうかを取得します。
+    pub fn is_timeout(&self) -> bool {
+        self.kind() == ErrorKind::Timeout
+    }
+
+    /// 引数や設定が不正かどうかを取得します。
+    pub fn is_invalid_input(&self) -> bool {
+        self.kind() == ErrorKind::InvalidInput
+    }
+
+    /// CeVIO が処理に失敗したことを返したかどうかを取得します。
+    pub fn is_operation_failed(&self) -> bool {
+        self.kind() == ErrorKind::OperationFailed
+    }
+
+    /// 同じ操作を再試行すると成功する可能性があるかどうかを取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　`ErrorKind::is_retryable` が `true` の種類か、CeVIO が他の処理中で呼び出しを受け付けなかった（`is_busy`）場合は `true` です。
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable() || self.is_busy()
+    }
+
+    /// クライアントに返すためのエラーの内容に変換します。
+    pub fn to_body(&self) -> ErrorBody {
+        ErrorBody {
+            code: match self.is_busy() {
+                true => "busy".to_string(),
+                false => self.kind().name().to_string(),
+            },
+            kind: self.kind(),
+            message: format!("{self:#}"),
+            retryable: self.is_retryable(),
+        }
+    }
+
+    /// ユーザー向けのメッセージを `i18n::Lang::current` の言語で取得します。
+    pub fn localized(&self) -> String {
+        self.message(Lang::current())
+    }
+
+    /// ユーザー向けのメッセージを指定した言語で取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　種類の説明（起動に失敗した場合はその理由も）の後に、原因を含む元のエラー（英語）が続きます。
+    pub fn message(&self, lang: Lang) -> String {
+        let summary = self.kind().description(lang);
+        match self.host_start_error() {
+            Some(reason) => format!("{summary}（{}）: {self:#}", reason.description(lang)),
+            None => format!("{summary}: {self:#}"),
+        }
+    }
+
+    /// CeVIO の起動に失敗した場合は、その理由を取得します。
+    pub fn host_start_error(&self) -> Option<HostStartError> {
+        self.inner()
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<HostStartError>())
+            .copied()
+    }
+}
+
+/// クライアントに返すためのエラーの内容です。
+///
+/// HTTP サーバーと名前付きパイプサーバーは、失敗した場合にこの形式の JSON を返します。
+///
+/// ```json
+/// { "code": "busy", "kind": "com", "message": "...", "retryable": true }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    any(feature = "server", feature = "pipe", feature = "jsonl"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ErrorBody {
+    /// エラーコード。`kind` の識別子か、CeVIO が他の処理中で呼び出しを受け付けなかった場合は `busy`
+    pub code: String,
+    /// エラーの種類
+    pub kind: ErrorKind,
+    /// エラーの内容（原因を含む）
+    pub message: String,
+    /// 同じ操作を再試行すると成功する可能性があるかどうか
+    pub retryable: bool,
+}
+
+impl ErrorBody {
+    /// 種類と内容を指定して作成します。
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            code: kind.name().to_string(),
+            kind,
+            message: message.into(),
+            retryable: kind.is_retryable(),
+        }
+    }
+}
+
+impl From<&CeVIOError> for ErrorBody {
+    fn from(error: &CeVIOError) -> Self {
+        error.to_body()
+    }
+}
+
+impl From<ErrorBody> for CeVIOError {
+    /// クライアント側でエラーに戻す
+    fn from(body: ErrorBody) -> Self {
+        Self::new(body.kind, Report::msg(body.message))
+    }
+}
+
+/// `CeVIO::start_host` が失敗した理由です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+pub enum HostStartError {
+    /// インストール状態が不明（`-1`）
+    #[error("Installation state is unknown")]
+    InstallUnknown,
+    /// 実行ファイルが見つからない（`-2`）
+    #[error("Executable is not found")]
+    ExecutableNotFound,
+    /// プロセスの起動に失敗（`-3`）
+    #[error("Failed to start process")]
+    ProcessStartFailed,
+    /// アプリケーション起動後、エラーにより終了（`-4`）
+    #[error("Host terminated with an error after starting")]
+    TerminatedWithError,
+    /// 未知の戻り値
+    #[error("Unknown result {0}")]
+    Unknown(i32),
+}
+
+impl HostStartError {
+    /// `StartHost` の戻り値から変換します。成功（`0`）の場合は `None` です。
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => None,
+            -1 => Some(Self::InstallUnknown),
+            -2 => Some(Self::ExecutableNotFound),
+            -3 => Some(Self::ProcessStartFailed),
+            -4 => Some(Self::TerminatedWithError),
+            code => Some(Self::Unknown(code)),
+        }
+    }
+
+    /// `StartHost` の戻り値を取得します。
+    pub fn code(self) -> i32 {
+        match self {
+            Self::InstallUnknown => -1,
+            Self::ExecutableNotFound => -2,
+            Self::ProcessStartFailed => -3,
+            Self::TerminatedWithError => -4,
+            Self::Unknown(code) => code,
+        }
+    }
+
+    /// ユーザー向けの説明を取得します。
+    pub fn description(self, lang: Lang) -> String {
+        match self {
+            Self::InstallUnknown => lang
+                .pick(
+                    "インストール状態が不明です",
+                    "Installation state is unknown",
+                )
+                .to_string(),
+            Self::ExecutableNotFound => lang
+                .pick("実行ファイルが見つかりません", "Executable is not found")
+                .to_string(),
+            Self::ProcessStartFailed => lang
+                .pick("プロセスの起動に失敗しました", "Failed to start process")
+                .to_string(),
+            Self::TerminatedWithError => lang
+                .pick(
+                    "起動後にエラーで終了しました",
+                    "Host terminated with an error after starting",
+                )
+                .to_string(),
+            Self::Unknown(code) => match lang {
+                Lang::Ja => format!("不明な戻り値 {code}"),
+                Lang::En => format!("Unknown result {code}"),
+            },
+        }
+    }
+}
+
+/// RPC サーバーを利用できない（`HRESULT_FROM_WIN32(RPC_S_SERVER_UNAVAILABLE)`）
+const RPC_S_SERVER_UNAVAILABLE: HRESULT = HRESULT(0x800706BA_u32 as i32);
+/// リモートプロシージャコールに失敗した（`HRESULT_FROM_WIN32(RPC_S_CALL_FAILED)`）
+const RPC_S_CALL_FAILED: HRESULT = HRESULT(0x800706BE_u32 as i32);
+
+/// COM を呼び出さずに `HRESULT` で失敗したことを表すエラー（`fault::FaultyBackend` が使う）
+///
+/// `windows::core::Error` は作るときにエラー情報を取得するため、Windows 以外ではリンクできない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct HResultError(pub(crate) HRESULT);
+
+impl fmt::Display for HResultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HRESULT 0x{:08X}", self.0 .0 as u32)
+    }
+}
+
+impl StdError for HResultError {}
+
+/// `HRESULT` からエラーの種類を判定する
+pub(crate) fn kind_of_hresult(hresult: HRESULT) -> ErrorKind {
+    let is = |codes: &[HRESULT]| codes.contains(&hresult);
+    if is(&[CO_E_NOTINITIALIZED, RPC_E_CHANGED_MODE]) {
+        ErrorKind::ComInit
+    } else if is(&[
+        REGDB_E_CLASSNOTREG,
+        CO_E_CLASSSTRING,
+        CO_E_SERVER_EXEC_FAILURE,
+    ]) {
+        ErrorKind::ObjectCreation
+    } else if is(&[
+        RPC_E_DISCONNECTED,
+        RPC_S_SERVER_UNAVAILABLE,
+        RPC_S_CALL_FAILED,
+    ]) {
+        ErrorKind::HostNotRunning
+    } else if is(&[DISP_E_TYPEMISMATCH, DISP_E_OVERFLOW]) {
+        ErrorKind::Conversion
+    } else if is(&[ERROR_TIMEOUT.to_hresult()]) {
+        ErrorKind::Timeout
+    } else {
+        ErrorKind::Com
+    }
+}
+
+impl From<Report> for CeVIOError {
+    fn from(error: Report) -> Self {
+        let kind = error
+            .chain()
+            .find_map(|cause| {
+                if let Some(e) = cause.downcast_ref::<windows::core::Error>() {
+                    return Some(kind_of_hresult(e.code()));
+                }
+                if let Some(e) = cause.downcast_ref::<HResultError>() {
+                    return Some(kind_of_hresult(e.0));
+                }
+                let e = cause.downcast_ref::<std::io::Error>()?;
+                Some(match e.kind() {
+                    std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+                    _ => ErrorKind::Io,
+                })
+            })
+            .unwrap_or(ErrorKind::Other);
+        Self::new(kind, error)
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl From<anyhow::Error> for CeVIOError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::from(Report::from_anyhow(error))
+    }
+}
+
+/// 原因の連なりを持つエラーです。`CeVIOError` の各バリアントが持ちます。
+///
+/// `{}` で最後に付けた説明だけを、`{:#}` で原因までを `: ` でつないで表示します。
+///
+/// ```
+/// use cevio::error::{Context as _, Report};
+///
+/// let error: Report = "abc"
+///     .parse::<i32>()
+///     .context("Invalid volume")
+///     .unwrap_err();
+/// assert_eq!(error.to_string(), "Invalid volume");
+/// assert_eq!(format!("{error:#}"), "Invalid volume: invalid digit found in string");
+/// assert!(error.downcast_ref::<std::num::ParseIntError>().is_some());
+/// ```
+pub struct Report(Box<dyn StdError + Send + Sync + 'static>);
+
+impl Report {
+    /// エラーから作成します。
+    pub fn new(error: impl StdError + Send + Sync + 'static) -> Self {
+        Self(Box::new(error))
+    }
+
+    /// メッセージだけのエラーを作成します。
+    pub fn msg(message: impl fmt::Display) -> Self {
+        Self(Box::new(Message(message.to_string())))
+    }
+
+    /// `anyhow::Error` から変換します。原因の連なりはそのまま残ります。
+    #[cfg(feature = "anyhow")]
+    pub fn from_anyhow(error: anyhow::Error) -> Self {
+        Self(error.into())
+    }
+
+    /// 説明を付けます。
+    pub fn context(self, message: impl fmt::Display) -> Self {
+        Self(Box::new(WithContext {
+            message: message.to_string(),
+            source: self.0,
+        }))
+    }
+
+    /// このエラーから順に、原因をたどります。
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
+        let first: &(dyn StdError + 'static) = &*self.0;
+        std::iter::successors(Some(first), |&error| error.source())
+    }
+
+    /// 最も根本の原因を取得します。
+    pub fn root_cause(&self) -> &(dyn StdError + 'static) {
+        self.chain().last().unwrap_or(&*self.0)
+    }
+
+    /// 原因の連なりから `E` のエラーを探します。
+    pub fn downcast_ref<E: StdError + 'static>(&self) -> Option<&E> {
+        self.chain().find_map(|error| error.downcast_ref::<E>())
+    }
+}
+
+impl<E: StdError + Send + Sync + 'static> From<E> for Report {
+    fn from(error: E) -> Self {
+        Self::new(error)
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl From<Report> for anyhow::Error {
+    fn from(report: Report) -> Self {
+        anyhow::Error::from_boxed(report.0)
+    }
+}
+
+impl From<Report> for Box<dyn StdError + Send + Sync + 'static> {
+    fn from(report: Report) -> Self {
+        report.0
+    }
+}
+
+impl Deref for Report {
+    type Target = dyn StdError + Send + Sync + 'static;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        if f.alternate() {
+            for cause in self.chain().skip(1) {
+                write!(f, ": {cause}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:#}")
+    }
+}
+
+/// `Report::msg` のエラー
+#[derive(Debug)]
+struct Message(String);
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for Message {}
+
+/// `Report::context` のエラー
+#[derive(Debug)]
+struct WithContext {
+    message: String,
+    source: Box<dyn StdError + Send + Sync + 'static>,
+}
+
+impl fmt::Display for WithContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl StdError for WithContext {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// `Result` と `Option` に説明を付けて `Report` に変換します。
+pub trait Context<T> {
+    /// 失敗した場合に説明を付けます。
+    fn context(self, message: impl fmt::Display) -> std::result::Result<T, Report>;
+
+    /// 失敗した場合だけ説明を作って付けます。
+    fn with_context<M: fmt::Display>(self, f: impl FnOnce() -> M)
+        -> std::result::Result<T, Report>;
+}
+
+impl<T, E: StdError + Send + Sync + 'static> Context<T> for std::result::Result<T, E> {
+    fn context(self, message: impl fmt::Display) -> std::result::Result<T, Report> {
+        self.map_err(|e| Report::new(e).context(message))
+    }
+
+    fn with_context<M: fmt::Display>(
+        self,
+        f: impl FnOnce() -> M,
+    ) -> std::result::Result<T, Report> {
+        self.map_err(|e| Report::new(e).context(f()))
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, Report> {
+    fn context(self, message: impl fmt::Display) -> std::result::Result<T, Report> {
+        self.map_err(|e| e.context(message))
+    }
+
+    fn with_context<M: fmt::Display>(
+        self,
+        f: impl FnOnce() -> M,
+    ) -> std::result::Result<T, Report> {
+        self.map_err(|e| e.context(f()))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, message: impl fmt::Display) -> std::result::Result<T, Report> {
+        self.ok_or_else(|| Report::msg(message))
+    }
+
+    fn with_context<M: fmt::Display>(
+        self,
+        f: impl FnOnce() -> M,
+    ) -> std::result::Result<T, Report> {
+        self.ok_or_else(|| Report::msg(f()))
+    }
+}
+
+/// 書式を指定して `Report` を作る
+macro_rules! report {
+    ($($arg:tt)*) => {
+        $crate::error::Report::msg(format!($($arg)*))
+    };
+}
+pub(crate) use report;
@@ -0,0 +1,336 @@
+//! 失敗を注入するテスト用のバックエンド（`fault` フィーチャー）
+//!
+//! `FaultyBackend` で `TalkerBackend` を包むと、設定した呼び出しを COM の呼び出しと同じエラーで失敗させます。
+//! 再試行や再接続の処理が実際に動くかどうかの確認に使います。
+//!
+//! ```no_run
+//! use cevio::{
+//!     backend::{MockBackend, TalkerBackend},
+//!     fault::{Fault, FaultyBackend},
+//! };
+//!
+//! let backend = FaultyBackend::new(MockBackend::new())
+//!     .fail_nth(1, Fault::Busy)
+//!     .kill_host_after(3);
+//!
+//! assert!(matches!(backend.speak("1"), Err(e) if e.is_busy()));
+//! backend.speak("2").unwrap();
+//! backend.speak("3").unwrap();
+//! assert!(matches!(backend.speak("4"), Err(e) if e.is_host_not_running()));
+//!
+//! backend.revive();
+//! backend.speak("5").unwrap();
+//! ```
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use windows::{
+    core::HRESULT,
+    Win32::Foundation::{ERROR_TIMEOUT, RPC_E_CALL_REJECTED, RPC_E_DISCONNECTED},
+};
+
+use crate::{
+    backend::{Playback, TalkerBackend},
+    error::{self, report, ErrorKind},
+    overwrite::OverwritePolicy,
+    processor::OutputProcessor,
+    Params, PhonemeData,
+};
+
+/// 注入する失敗です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// CeVIO が他の処理中で呼び出しを受け付けない（`RPC_E_CALL_REJECTED`）。`is_busy` が `true` になります
+    Busy,
+    /// 時間内に終わらない（`ERROR_TIMEOUT`）。`is_timeout` が `true` になります
+    Timeout,
+    /// CeVIO との接続が切れた（`RPC_E_DISCONNECTED`）。`is_host_not_running` が `true` になります
+    Disconnected,
+    /// 指定した `HRESULT` で失敗する
+    HResult(HRESULT),
+    /// 指定した種類のエラーで失敗する
+    Error(ErrorKind),
+}
+
+impl Fault {
+    fn to_error(self, method: &str) -> error::CeVIOError {
+        let hresult = match self {
+            Self::Busy => RPC_E_CALL_REJECTED,
+            Self::Timeout => ERROR_TIMEOUT.to_hresult(),
+            Self::Disconnected => RPC_E_DISCONNECTED,
+            Self::HResult(hresult) => hresult,
+            Self::Error(kind) => {
+                return error::CeVIOError::new(kind, report!("Injected failure in `{method}`"))
+            }
+        };
+        error::CeVIOError::from(
+            error::Report::new(error::HResultError(hresult))
+                .context(format!("Injected failure in `{method}`")),
+        )
+    }
+}
+
+/// いつ失敗させるかの条件
+enum Rule {
+    /// 全体で `n` 回目の呼び出し
+    Nth(u64, Fault),
+    /// 全体で `n` 回ごとの呼び出し
+    Every(u64, Fault),
+    /// 指定したメソッドの呼び出しを残り `remaining` 回
+    Method {
+        method: &'static str,
+        fault: Fault,
+        remaining: u64,
+    },
+}
+
+struct State {
+    rules: Vec<Rule>,
+    /// 呼び出しの回数
+    calls: u64,
+    /// この回数の呼び出しの後に CeVIO が終了したことにする
+    kill_after: Option<u64>,
+    dead: bool,
+}
+
+/// 設定した呼び出しを失敗させるバックエンドです。
+///
+/// 条件に当てはまる呼び出しは元のバックエンドを呼ばずに失敗します。呼び出しの回数は 1 から数えます。
+pub struct FaultyBackend<B> {
+    inner: B,
+    state: Mutex<State>,
+}
+
+impl<B: TalkerBackend> FaultyBackend<B> {
+    /// `inner` を包みます。条件を設定するまでは失敗しません。
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(State {
+                rules: Vec::new(),
+                calls: 0,
+                kill_after: None,
+                dead: false,
+            }),
+        }
+    }
+
+    /// `n` 回目の呼び出しを失敗させます。
+    pub fn fail_nth(self, n: u64, fault: Fault) -> Self {
+        self.lock().rules.push(Rule::Nth(n, fault));
+        self
+    }
+
+    /// `n` 回ごとに呼び出しを失敗させます。
+    pub fn fail_every(self, n: u64, fault: Fault) -> Self {
+        self.lock().rules.push(Rule::Every(n.max(1), fault));
+        self
+    }
+
+    /// `method`（`speak` などの `TalkerBackend` のメソッド名）の呼び出しを `times` 回失敗させます。
+    pub fn fail_method(self, method: &'static str, fault: Fault, times: u64) -> Self {
+        self.lock().rules.push(Rule::Method {
+            method,
+            fault,
+            remaining: times,
+        });
+        self
+    }
+
+    /// `n` 回の呼び出しの後に CeVIO が終了したことにします。
+    ///
+    /// 以降の呼び出しは `revive` を呼ぶまで `Fault::Disconnected` で失敗します。連続した処理の途中で CeVIO が落ちた場合の確認に使います。
+    pub fn kill_host_after(self, n: u64) -> Self {
+        self.lock().kill_after = Some(n);
+        self
+    }
+
+    /// すぐに CeVIO が終了したことにします。
+    pub fn kill_host(&self) {
+        self.lock().dead = true;
+    }
+
+    /// CeVIO が再び起動したことにします。
+    pub fn revive(&self) {
+        let mut state = self.lock();
+        state.dead = false;
+        state.kill_after = None;
+    }
+
+    /// これまでの呼び出しの回数を取得します。失敗させた呼び出しも含みます。
+    pub fn calls(&self) -> u64 {
+        self.lock().calls
+    }
+
+    /// 元のバックエンドを取得します。
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 呼び出しを数え、条件に当てはまれば失敗する
+    fn check(&self, method: &'static str) -> error::Result<()> {
+        let mut state = self.lock();
+        state.calls += 1;
+        let calls = state.calls;
+        if state.kill_after.is_some_and(|n| calls > n) {
+            state.dead = true;
+        }
+        if state.dead {
+            return Err(Fault::Disconnected.to_error(method));
+        }
+        let fault = state.rules.iter_mut().find_map(|rule| match rule {
+            Rule::Nth(n, fault) => (*n == calls).then_some(*fault),
+            Rule::Every(n, fault) => calls.is_multiple_of(*n).then_some(*fault),
+            Rule::Method {
+                method: m,
+                fault,
+                remaining,
+            } => (*m == method && *remaining > 0).then(|| {
+                *remaining -= 1;
+                *fault
+            }),
+        });
+        match fault {
+            Some(fault) => Err(fault.to_error(method)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<B: TalkerBackend> TalkerBackend for FaultyBackend<B> {
+    fn get_cast(&self) -> error::Result<String> {
+        self.check("get_cast")?;
+        self.inner.get_cast()
+    }
+
+    fn set_cast(&self, cast: &str) -> error::Result<()> {
+        self.check("set_cast")?;
+        self.inner.set_cast(cast)
+    }
+
+    fn get_available_casts(&self) -> error::Result<Vec<String>> {
+        self.check("get_available_casts")?;
+        self.inner.get_available_casts()
+    }
+
+    fn current_params(&self) -> error::Result<Params> {
+        self.check("current_params")?;
+        self.inner.current_params()
+    }
+
+    fn apply_params(&self, params: &Params) -> error::Result<()> {
+        self.check("apply_params")?;
+        self.inner.apply_params(params)
+    }
+
+    fn speak(&self, text: &str) -> error::Result<Box<dyn Playback>> {
+        self.check("speak")?;
+        self.inner.speak(text)
+    }
+
+    fn stop(&self) -> error::Result<bool> {
+        self.check("stop")?;
+        self.inner.stop()
+    }
+
+    fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>> {
+        self.check("get_phonemes")?;
+        self.inner.get_phonemes(text)
+    }
+
+    fn output_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        self.check("output_wave_to_file")?;
+        self.inner.output_wave_to_file(text, path)
+    }
+
+    fn output_raw_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        self.check("output_raw_wave_to_file")?;
+        self.inner.output_raw_wave_to_file(text, path)
+    }
+
+    // 設定の取得は CeVIO を呼び出さないため、失敗させない
+
+    fn overwrite_policy(&self) -> OverwritePolicy {
+        self.inner.overwrite_policy()
+    }
+
+    fn output_processors(&self) -> Vec<Arc<dyn OutputProcessor>> {
+        self.inner.output_processors()
+    }
+
+    fn cast_profile(&self, cast: &str) -> Option<Params> {
+        self.inner.cast_profile(cast)
+    }
+
+    fn fallback_casts(&self) -> Vec<String> {
+        self.inner.fallback_casts()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+    use crate::{backend::MockBackend, project::temp_project, queue::SpeechQueue};
+
+    #[test]
+    fn project_render_restores_params_after_fault() {
+        let (_dir, project) = temp_project("[早口] こんにちは。\nさようなら。\n");
+        let backend = FaultyBackend::new(MockBackend::new()).fail_method(
+            "output_raw_wave_to_file",
+            Fault::Busy,
+            1,
+        );
+        let before = backend.inner().current_params().unwrap();
+
+        let err = project.render(&backend).unwrap_err();
+        assert!(err.is_busy());
+        assert_eq!(backend.inner().current_params().unwrap(), before);
+
+        // 失敗した行はキャッシュされず、次は合成し直す
+        assert_eq!(project.render(&backend).unwrap().len(), 2);
+        assert_eq!(backend.inner().current_params().unwrap(), before);
+    }
+
+    #[test]
+    fn project_render_fails_when_host_is_killed() {
+        let (_dir, project) = temp_project("こんにちは。\n");
+        let backend = FaultyBackend::new(MockBackend::new()).kill_host_after(1);
+
+        let err = project.render(&backend).unwrap_err();
+        assert!(err.is_host_not_running());
+    }
+
+    #[test]
+    fn speech_queue_continues_after_fault() {
+        let backend =
+            Arc::new(FaultyBackend::new(MockBackend::new()).fail_method("speak", Fault::Busy, 1));
+        let before = backend.inner().current_params().unwrap();
+        let queue = SpeechQueue::with_backend(backend.clone()).unwrap();
+        let fast = Params {
+            speed: Some(80),
+            ..Params::default()
+        };
+        queue.push("bot", "一", fast.clone());
+        queue.push("bot", "二", fast);
+
+        let status = || queue.client_status("bot").unwrap();
+        for _ in 0..500 {
+            if status().spoken == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(status().spoken, 2);
+        assert_eq!(backend.inner().spoken(), ["二"]);
+        assert_eq!(backend.inner().current_params().unwrap(), before);
+    }
+}
@@ -0,0 +1,595 @@
+//! ffmpeg で音声・字幕・画像（または動画）を動画にまとめます（`ffmpeg` フィーチャー）
+//!
+//! ffmpeg は同梱しません。`PATH` にあるか、`Mux::program` でパスを指定してください。
+//!
+//! 画像（`png`、`jpg`、`jpeg`、`bmp`、`webp`）の場合は音声の長さだけ表示し続け、動画の場合は音声の長さまで繰り返します。
+//! 字幕（`srt`、`ass` など）は既定では映像に焼き込み、`Mux::burn_in(false)` で字幕トラックとして埋め込みます。
+//!
+//! `encode_with_chapters` は行ごとの WAV を 1 つの MP3 か Opus にまとめ、各行の位置をチャプター
+//! （MP3 は ID3v2 の `CHAP`、Opus は `CHAPTERxx` タグ）として書き込みます。長いナレーションをポッドキャストのプレーヤーで移動できます。
+//! アップロードできる長さや大きさに上限がある場合は、`Encoder::max_duration`、`Encoder::max_size` と `Encoder::run_split` で行の区切りで複数のファイルに分けます。
+//!
+//! ```no_run
+//! use cevio::{ffmpeg, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! cevio.output_wave_to_file("こんにちは。", r"E:\voice.wav").unwrap();
+//! ffmpeg::mux_video(
+//!     r"E:\voice.wav",
+//!     Some(r"E:\voice.srt".as_ref()),
+//!     r"E:\background.png",
+//!     r"E:\out.mp4",
+//! )
+//! .unwrap();
+//!
+//! // 台本の各行をチャプターにする
+//! let project = cevio::Project::open(r"E:\narration").unwrap();
+//! let outputs = project.render(&cevio).unwrap();
+//! let lines = project
+//!     .script()
+//!     .iter()
+//!     .map(|line| line.text.clone())
+//!     .zip(outputs);
+//! ffmpeg::encode_with_chapters(lines.clone(), r"E:\narration.mp3").unwrap();
+//!
+//! // 10 分か 95 MB ごとに narration_001.mp3, narration_002.mp3, ... に分ける
+//! ffmpeg::Encoder::new()
+//!     .max_duration(std::time::Duration::from_secs(10 * 60))
+//!     .max_size(95 * 1000 * 1000)
+//!     .run_split(lines, r"E:\narration.mp3")
+//!     .unwrap();
+//! ```
+//!
+//! ```
+//! use cevio::ffmpeg::Mux;
+//!
+//! let args = Mux::new().args("voice.wav", "background.png", "out.mp4");
+//! assert!(args.iter().any(|arg| arg == "-loop"));
+//! assert_eq!(args.last().unwrap(), "out.mp4");
+//! ```
+
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    fmt::Write as _,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    fs_util,
+    overwrite::OverwritePolicy,
+    temp::TempWav,
+    wav::wav_duration,
+};
+
+/// 静止画として扱う拡張子
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "webp"];
+
+/// 静止画を動画にするときのフレームレート
+const IMAGE_FRAME_RATE: &str = "30";
+
+/// 音声・字幕・画像（または動画）から動画を作る ffmpeg の呼び出しです。
+#[derive(Debug, Clone)]
+pub struct Mux {
+    program: PathBuf,
+    subtitles: Option<PathBuf>,
+    burn_in: bool,
+    overwrite: OverwritePolicy,
+}
+
+impl Default for Mux {
+    fn default() -> Self {
+        Self {
+            program: PathBuf::from("ffmpeg"),
+            subtitles: None,
+            burn_in: true,
+            overwrite: OverwritePolicy::default(),
+        }
+    }
+}
+
+impl Mux {
+    /// `PATH` の ffmpeg を使い、字幕を付けない設定で作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ffmpeg のパスを指定します。
+    pub fn program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// 字幕ファイルを指定します。
+    pub fn subtitles(mut self, subtitles: impl Into<PathBuf>) -> Self {
+        self.subtitles = Some(subtitles.into());
+        self
+    }
+
+    /// 字幕を映像に焼き込むかどうかを指定します。省略時は焼き込みます。`false` の場合は字幕トラック（`mov_text`）として埋め込みます。
+    pub fn burn_in(mut self, burn_in: bool) -> Self {
+        self.burn_in = burn_in;
+        self
+    }
+
+    /// 出力先のファイルが既にある場合の動作を指定します。省略時は上書きします。
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// ffmpeg に渡す引数を作成します。
+    pub fn args(
+        &self,
+        audio: impl AsRef<Path>,
+        visual: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> Vec<OsString> {
+        let visual = visual.as_ref();
+        let is_image = visual
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        let mut args: Vec<OsString> =
+            vec!["-hide_banner".into(), "-loglevel".into(), "error".into()];
+        match is_image {
+            true => args.extend(["-loop", "1", "-framerate", IMAGE_FRAME_RATE].map(Into::into)),
+            false => args.extend(["-stream_loop", "-1"].map(Into::into)),
+        }
+        args.extend(["-i".into(), visual.into()]);
+        args.extend(["-i".into(), audio.as_ref().into()]);
+        args.extend(["-map", "0:v:0", "-map", "1:a:0"].map(Into::into));
+        match (&self.subtitles, self.burn_in) {
+            (Some(subtitles), true) => {
+                args.push("-vf".into());
+                args.push(format!("subtitles={}", escape_filter_path(subtitles)).into());
+            }
+            (Some(subtitles), false) => {
+                args.extend(["-i".into(), subtitles.into()]);
+                args.extend(["-map", "2:s:0", "-c:s", "mov_text"].map(Into::into));
+            }
+            (None, _) => {}
+        }
+        args.extend(["-c:v", "libx264"].map(Into::into));
+        if is_image {
+            args.extend(["-tune", "stillimage"].map(Into::into));
+        }
+        args.extend(["-pix_fmt", "yuv420p", "-c:a", "aac", "-b:a", "192k"].map(Into::into));
+        args.extend(["-shortest", "-y"].map(Into::into));
+        args.push(output.as_ref().into());
+        args
+    }
+
+    /// ffmpeg を実行し、終了を待ちます。
+    ///
+    /// 戻り値：
+    ///
+    /// 　出力したパス。ファイルが既にあり `OverwritePolicy::Skip` の場合は `None`。
+    ///
+    /// 　ffmpeg を起動できない場合は `CeVIOError::Io`、ffmpeg が失敗した場合は `CeVIOError::OperationFailed`（標準エラー出力を含みます）。
+    pub fn run(
+        &self,
+        audio: impl AsRef<Path>,
+        visual: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> error::Result<Option<PathBuf>> {
+        let Some(output) = self.overwrite.resolve(output.as_ref())? else {
+            return Ok(None);
+        };
+        let result = Command::new(&self.program)
+            .args(self.args(audio, visual, &output))
+            .output()
+            .with_context(|| format!("Failed to run `{}`", self.program.display()))
+            .map_err(error::CeVIOError::Io)?;
+        if !result.status.success() {
+            return Err(error::CeVIOError::OperationFailed(report!(
+                "ffmpeg failed ({}): {}",
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+        Ok(Some(output))
+    }
+}
+
+/// 音声・字幕・画像（または動画）から動画を作ります。
+///
+/// `Mux::new()` に字幕を指定して `run` するのと同じです。
+pub fn mux_video(
+    audio: impl AsRef<Path>,
+    subtitles: Option<&Path>,
+    visual: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+) -> error::Result<Option<PathBuf>> {
+    let mux = match subtitles {
+        Some(subtitles) => Mux::new().subtitles(subtitles),
+        None => Mux::new(),
+    };
+    mux.run(audio, visual, output)
+}
+
+/// `subtitles` フィルターに渡すパスをエスケープする
+///
+/// `\` は `/` にし、`:` をエスケープして `'` で囲む
+fn escape_filter_path(path: &Path) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace(':', "\\:")
+        .replace('\'', "'\\''");
+    format!("'{path}'")
+}
+
+/// 動画や音声のチャプターです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// タイトル
+    pub title: String,
+    /// 開始位置
+    pub start: Duration,
+    /// 終了位置
+    pub end: Duration,
+}
+
+/// 行ごとの WAV の長さから、続けて再生したときのチャプターを作ります。
+///
+/// `lines` は（タイトル, WAV ファイルのパス）です。
+pub fn chapters_from_wavs<P: AsRef<Path>>(
+    lines: impl IntoIterator<Item = (String, P)>,
+) -> error::Result<Vec<Chapter>> {
+    let mut start = Duration::ZERO;
+    lines
+        .into_iter()
+        .map(|(title, path)| {
+            let end = start + wav_duration(path.as_ref())?;
+            let chapter = Chapter { title, start, end };
+            start = end;
+            Ok(chapter)
+        })
+        .collect()
+}
+
+/// チャプターを ffmpeg のメタデータファイル（`FFMETADATA1`）の形式に変換します。
+///
+/// ```
+/// use std::time::Duration;
+/// use cevio::ffmpeg::{self, Chapter};
+///
+/// let chapters = [Chapter {
+///     title: "こんにちは。".to_string(),
+///     start: Duration::ZERO,
+///     end: Duration::from_millis(1500),
+/// }];
+/// assert_eq!(
+///     ffmpeg::ffmetadata(&chapters),
+///     ";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=1500\ntitle=こんにちは。\n",
+/// );
+/// ```
+pub fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut s = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        let _ = write!(
+            s,
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            chapter.start.as_millis(),
+            chapter.end.as_millis(),
+            escape_metadata(&chapter.title)
+        );
+    }
+    s
+}
+
+/// 行ごとの WAV を 1 つの MP3（`.mp3`）か Opus（`.opus`、`.ogg`）にまとめ、各行をチャプターとして書き込みます。
+///
+/// `lines` は（チャプターのタイトル, WAV ファイルのパス）で、`PATH` の ffmpeg を使います。
+/// それ以外は `Encoder::run` と同じです。
+pub fn encode_with_chapters<P: AsRef<Path>>(
+    lines: impl IntoIterator<Item = (String, P)>,
+    output: impl AsRef<Path>,
+) -> error::Result<Option<PathBuf>> {
+    Encoder::new().run(lines, output)
+}
+
+/// `max_size` から長さの上限を見積もるときの MP3 のビットレート。`-q:a 2` の平均より大きめにする
+const MP3_ESTIMATED_BITRATE: u64 = 256_000;
+/// `max_size` から長さの上限を見積もるときの Opus のビットレート。`-b:a 96k` にコンテナの分を足す
+const OPUS_ESTIMATED_BITRATE: u64 = 104_000;
+
+/// 行ごとの WAV をチャプター付きの MP3 か Opus にまとめる ffmpeg の呼び出しです。
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    program: PathBuf,
+    overwrite: OverwritePolicy,
+    max_duration: Option<Duration>,
+    max_size: Option<u64>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self {
+            program: PathBuf::from("ffmpeg"),
+            overwrite: OverwritePolicy::default(),
+            max_duration: None,
+            max_size: None,
+        }
+    }
+}
+
+impl Encoder {
+    /// `PATH` の ffmpeg を使う設定で作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ffmpeg のパスを指定します。
+    pub fn program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// 出力先のファイルが既にある場合の動作を指定します。省略時は上書きします。
+    pub fn overwrite(mut self, overwrite: OverwritePolicy) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// `run_split` で分けるファイルの長さの上限を指定します。
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// `run_split` で分けるファイルの大きさの上限を指定します。単位はバイト
+    ///
+    /// ビットレートから見積もった長さで分け、出力したファイルが上限を超えた場合は行の数を半分にして出力し直します。
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// ffmpeg に渡す引数を作成します。
+    ///
+    /// `list` は concat demuxer の入力ファイル、`metadata` は `ffmetadata` の内容を書いたファイルです。
+    /// 出力の拡張子が `mp3`、`opus`、`ogg` 以外の場合は `CeVIOError::InvalidInput` です。
+    pub fn args(
+        &self,
+        list: impl AsRef<Path>,
+        metadata: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+    ) -> error::Result<Vec<OsString>> {
+        let output = output.as_ref();
+        let extension = output
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        let codec: &[&str] = match extension.as_deref() {
+            // ID3v2.3 の方が対応しているプレーヤーが多い
+            Some("mp3") => &["-c:a", "libmp3lame", "-q:a", "2", "-id3v2_version", "3"],
+            Some("opus" | "ogg") => &["-c:a", "libopus", "-b:a", "96k"],
+            _ => {
+                return Err(error::CeVIOError::InvalidInput(report!(
+                    "Unsupported output `{}` (expected .mp3, .opus or .ogg)",
+                    output.display()
+                )))
+            }
+        };
+        let mut args: Vec<OsString> = ["-hide_banner", "-loglevel", "error"]
+            .map(Into::into)
+            .to_vec();
+        args.extend(["-f", "concat", "-safe", "0", "-i"].map(Into::into));
+        args.push(list.as_ref().into());
+        args.push("-i".into());
+        args.push(metadata.as_ref().into());
+        args.extend(["-map", "0:a", "-map_metadata", "1", "-map_chapters", "1"].map(Into::into));
+        args.extend(codec.iter().map(Into::into));
+        args.push("-y".into());
+        args.push(output.into());
+        Ok(args)
+    }
+
+    /// ffmpeg を実行し、終了を待ちます。
+    ///
+    /// `lines` は（チャプターのタイトル, WAV ファイルのパス）です。
+    ///
+    /// 戻り値：
+    ///
+    /// 　出力したパス。ファイルが既にあり `OverwritePolicy::Skip` の場合は `None`。
+    ///
+    /// 　WAV を読み込めない場合と ffmpeg を起動できない場合は `CeVIOError::Io`、ffmpeg が失敗した場合は `CeVIOError::OperationFailed`。
+    pub fn run<P: AsRef<Path>>(
+        &self,
+        lines: impl IntoIterator<Item = (String, P)>,
+        output: impl AsRef<Path>,
+    ) -> error::Result<Option<PathBuf>> {
+        let lines = absolute_lines(lines)?;
+        self.encode(&lines, output.as_ref())
+    }
+
+    /// `max_duration`、`max_size` を超えないように行の区切りで複数のファイルに分けて ffmpeg を実行し、出力したパスを返します。
+    ///
+    /// ファイル名は `output` の名前に `_001`、`_002`、... を付けたものです。分ける必要がない場合は `output` に出力します。
+    /// チャプターはファイルごとに先頭から数えます。1 行で上限を超える場合は、その行だけのファイルにします。
+    ///
+    /// ファイルが既にあり `OverwritePolicy::Skip` の場合は、既存のファイルのパスを返します。それ以外は `run` と同じです。
+    pub fn run_split<P: AsRef<Path>>(
+        &self,
+        lines: impl IntoIterator<Item = (String, P)>,
+        output: impl AsRef<Path>,
+    ) -> error::Result<Vec<PathBuf>> {
+        let output = output.as_ref();
+        let lines = absolute_lines(lines)?;
+        let durations = lines
+            .iter()
+            .map(|(_, path)| wav_duration(path))
+            .collect::<error::Result<Vec<_>>>()?;
+        let estimated = match self.max_size {
+            Some(size) => Some(size_to_duration(size, estimated_bitrate(output)?)),
+            None => None,
+        };
+        let limit = match (self.max_duration, estimated) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (limit, None) | (None, limit) => limit,
+        };
+        let mut ranges = match limit {
+            Some(limit) => VecDeque::from(split_at_lines(&durations, limit)),
+            None => std::iter::once(0..lines.len()).collect(),
+        };
+
+        let mut single = ranges.len() <= 1;
+        let mut outputs = Vec::with_capacity(ranges.len());
+        while let Some(range) = ranges.pop_front() {
+            let path = match single {
+                true => output.to_path_buf(),
+                false => numbered_path(output, outputs.len() + 1),
+            };
+            let Some(written) = self.encode(&lines[range.clone()], &path)? else {
+                outputs.push(path);
+                continue;
+            };
+            if range.len() > 1 && self.max_size.is_some_and(|max| file_size(&written) > max) {
+                // 見積もりより大きかったので、半分ずつ出力し直す
+                let _ = fs::remove_file(&written);
+                let middle = range.start + range.len() / 2;
+                ranges.push_front(middle..range.end);
+                ranges.push_front(range.start..middle);
+                single = false;
+                continue;
+            }
+            outputs.push(written);
+        }
+        Ok(outputs)
+    }
+
+    /// 行を 1 つのファイルにまとめる
+    fn encode(&self, lines: &[(String, PathBuf)], output: &Path) -> error::Result<Option<PathBuf>> {
+        let chapters = chapters_from_wavs(lines.iter().map(|(t, p)| (t.clone(), p)))?;
+        let Some(output) = self.overwrite.resolve(output)? else {
+            return Ok(None);
+        };
+        let list = lines
+            .iter()
+            .map(|(_, path)| format!("file {}\n", quote_concat_path(path)))
+            .collect::<String>();
+        let (list_path, metadata_path) = (
+            TempWav::with_extension("txt"),
+            TempWav::with_extension("txt"),
+        );
+        for (temp, content) in [(&list_path, list), (&metadata_path, ffmetadata(&chapters))] {
+            fs::write(temp, content)
+                .with_context(|| format!("Failed to write `{}`", temp.path().display()))
+                .map_err(error::CeVIOError::Io)?;
+        }
+        let result = Command::new(&self.program)
+            .args(self.args(&list_path, &metadata_path, &output)?)
+            .output()
+            .with_context(|| format!("Failed to run `{}`", self.program.display()))
+            .map_err(error::CeVIOError::Io)?;
+        if !result.status.success() {
+            return Err(error::CeVIOError::OperationFailed(report!(
+                "ffmpeg failed ({}): {}",
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim()
+            )));
+        }
+        Ok(Some(output))
+    }
+}
+
+/// 行の長さから、`max` を超えないように行の区切りで分けた範囲を返します。
+///
+/// 前から順に、次の行を足すと `max` を超える位置で区切ります。1 行で `max` を超える場合は、その行だけの範囲にします。
+///
+/// ```
+/// use std::time::Duration;
+/// use cevio::ffmpeg::split_at_lines;
+///
+/// let secs = |s| Duration::from_secs(s);
+/// let durations = [secs(4), secs(4), secs(4), secs(12), secs(1)];
+/// assert_eq!(split_at_lines(&durations, secs(10)), vec![0..2, 2..3, 3..4, 4..5]);
+/// ```
+pub fn split_at_lines(durations: &[Duration], max: Duration) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut total = Duration::ZERO;
+    for (i, &duration) in durations.iter().enumerate() {
+        if i > start && total + duration > max {
+            ranges.push(start..i);
+            start = i;
+            total = Duration::ZERO;
+        }
+        total += duration;
+    }
+    if start < durations.len() {
+        ranges.push(start..durations.len());
+    }
+    ranges
+}
+
+/// 行の WAV ファイルのパスを絶対パスにする
+fn absolute_lines<P: AsRef<Path>>(
+    lines: impl IntoIterator<Item = (String, P)>,
+) -> error::Result<Vec<(String, PathBuf)>> {
+    lines
+        .into_iter()
+        .map(|(title, path)| Ok((title, fs_util::absolute(path.as_ref())?)))
+        .collect()
+}
+
+/// 出力の拡張子から、大きさの見積もりに使うビットレートを返す
+fn estimated_bitrate(output: &Path) -> error::Result<u64> {
+    let extension = output
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("mp3") => Ok(MP3_ESTIMATED_BITRATE),
+        Some("opus" | "ogg") => Ok(OPUS_ESTIMATED_BITRATE),
+        _ => Err(error::CeVIOError::InvalidInput(report!(
+            "Unsupported output `{}` (expected .mp3, .opus or .ogg)",
+            output.display()
+        ))),
+    }
+}
+
+/// `size` バイトに収まる長さを `bitrate` から見積もる
+fn size_to_duration(size: u64, bitrate: u64) -> Duration {
+    Duration::from_secs_f64(size as f64 * 8.0 / bitrate as f64)
+}
+
+/// `output` の名前に `_001` のような番号を付けたパス
+fn numbered_path(output: &Path, number: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(extension) => format!("{stem}_{number:03}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{number:03}"),
+    };
+    output.with_file_name(name)
+}
+
+/// ファイルの大きさ。取得できない場合は 0
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+/// メタデータファイルの値の特殊文字（`=`、`;`、`#`、`\`、改行）をエスケープする
+fn escape_metadata(value: &str) -> String {
+    value.chars().fold(String::new(), |mut s, c| {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            s.push('\\');
+        }
+        s.push(c);
+        s
+    })
+}
+
+/// concat demuxer の `file` に渡すパスを `'` で囲む
+fn quote_concat_path(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
@@ -0,0 +1,542 @@
+//! 実際の呼び出し結果の記録と再生（`fixture` フィーチャー）
+//!
+//! `RecordingBackend` で実際の CeVIO に対する呼び出しの結果をメソッドと入力ごとに記録して JSON ファイルに保存し、
+//! `ReplayBackend` で読み込んで同じ結果を返します。CeVIO のない CI でも、記録した時と同じ結果で `TalkerBackend` を使う処理をテストできます。
+//!
+//! 同じメソッドと入力の呼び出しが複数回記録されている場合は、記録した順に返し、最後の結果を繰り返します。
+//!
+//! ```no_run
+//! use cevio::{
+//!     backend::TalkerBackend,
+//!     fixture::{RecordingBackend, ReplayBackend},
+//!     CeVIO,
+//! };
+//!
+//! // CeVIO のある環境で記録する
+//! let recorder = RecordingBackend::new(CeVIO::new().unwrap());
+//! recorder.set_cast("花隈千冬").unwrap();
+//! recorder.get_phonemes("こんにちは。").unwrap();
+//! recorder.save("tests/fixtures/greet.json").unwrap();
+//!
+//! // CI で再生する
+//! let replay = ReplayBackend::load("tests/fixtures/greet.json").unwrap();
+//! replay.set_cast("花隈千冬").unwrap();
+//! let phonemes = replay.get_phonemes("こんにちは。").unwrap();
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{Completed, Playback, TalkerBackend},
+    error::{self, report, Context as _, ErrorKind},
+    overwrite::OverwritePolicy,
+    processor::OutputProcessor,
+    Params, PhonemeData,
+};
+
+/// 1 回の呼び出しの記録です。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// メソッド名
+    pub method: String,
+    /// 入力。複数ある場合は改行で区切ります
+    pub input: String,
+    /// 結果
+    pub output: Output,
+}
+
+/// 呼び出しの結果です。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Output {
+    /// 値のない成功
+    Unit,
+    /// 真偽値
+    Bool(bool),
+    /// 文字列
+    String(String),
+    /// 文字列の一覧
+    Strings(Vec<String>),
+    /// 音素単位のデータ（音素, 開始時間, 終了時間）
+    Phonemes(Vec<(String, f64, f64)>),
+    /// WAV ファイルの内容
+    Wave(Vec<u8>),
+    /// 失敗
+    Error {
+        /// エラーの種類（`ErrorKind` のバリアント名）
+        kind: String,
+        /// エラーメッセージ
+        message: String,
+    },
+}
+
+/// 記録した呼び出しの一覧です。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+    /// 呼び出した順の記録
+    pub entries: Vec<Entry>,
+}
+
+impl Fixture {
+    /// JSON ファイルから読み込みます。
+    pub fn load(path: impl AsRef<Path>) -> error::Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))
+            .map_err(error::CeVIOError::from)?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse `{}`", path.display()))
+            .map_err(error::CeVIOError::InvalidInput)
+    }
+
+    /// JSON ファイルに保存します。親ディレクトリがなければ作成します。
+    pub fn save(&self, path: impl AsRef<Path>) -> error::Result<()> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)
+            .context("Failed to serialize fixture")
+            .map_err(error::CeVIOError::Other)?;
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create `{}`", parent.display()))
+                .map_err(error::CeVIOError::from)?;
+        }
+        fs::write(path, json)
+            .with_context(|| format!("Failed to write `{}`", path.display()))
+            .map_err(error::CeVIOError::from)
+    }
+}
+
+const ERROR_KINDS: [ErrorKind; 14] = [
+    ErrorKind::ComInit,
+    ErrorKind::ObjectCreation,
+    ErrorKind::HostNotRunning,
+    ErrorKind::HostStart,
+    ErrorKind::InvalidCast,
+    ErrorKind::Conversion,
+    ErrorKind::Timeout,
+    ErrorKind::Io,
+    ErrorKind::InvalidInput,
+    ErrorKind::OperationFailed,
+    ErrorKind::Com,
+    ErrorKind::Unauthorized,
+    ErrorKind::Forbidden,
+    ErrorKind::Other,
+];
+
+impl Output {
+    fn error(e: &error::CeVIOError) -> Self {
+        Self::Error {
+            kind: format!("{:?}", e.kind()),
+            message: format!("{e:#}"),
+        }
+    }
+
+    /// 失敗の記録ならエラーに戻す
+    fn into_result(self) -> error::Result<Self> {
+        match self {
+            Self::Error { kind, message } => {
+                let kind = ERROR_KINDS
+                    .into_iter()
+                    .find(|k| format!("{k:?}") == kind)
+                    .unwrap_or(ErrorKind::Other);
+                Err(error::CeVIOError::new(kind, error::Report::msg(message)))
+            }
+            output => Ok(output),
+        }
+    }
+}
+
+/// 実際のバックエンドへの呼び出しを記録するバックエンドです。
+pub struct RecordingBackend<B> {
+    inner: B,
+    fixture: Mutex<Fixture>,
+}
+
+impl<B: TalkerBackend> RecordingBackend<B> {
+    /// `inner` への呼び出しを記録します。
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            fixture: Mutex::new(Fixture::default()),
+        }
+    }
+
+    /// 記録を取得します。
+    pub fn fixture(&self) -> Fixture {
+        self.lock().clone()
+    }
+
+    /// 記録を JSON ファイルに保存します。
+    pub fn save(&self, path: impl AsRef<Path>) -> error::Result<()> {
+        self.lock().save(path)
+    }
+
+    /// 元のバックエンドを取得します。
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Fixture> {
+        self.fixture.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record<T>(
+        &self,
+        method: &str,
+        input: String,
+        result: error::Result<T>,
+        output: impl FnOnce(&T) -> Output,
+    ) -> error::Result<T> {
+        let output = match &result {
+            Ok(value) => output(value),
+            Err(e) => Output::error(e),
+        };
+        self.lock().entries.push(Entry {
+            method: method.to_string(),
+            input,
+            output,
+        });
+        result
+    }
+}
+
+impl<B: TalkerBackend> TalkerBackend for RecordingBackend<B> {
+    fn get_cast(&self) -> error::Result<String> {
+        let result = self.inner.get_cast();
+        self.record("get_cast", String::new(), result, |cast| {
+            Output::String(cast.clone())
+        })
+    }
+
+    fn set_cast(&self, cast: &str) -> error::Result<()> {
+        let result = self.inner.set_cast(cast);
+        self.record("set_cast", cast.to_string(), result, |()| Output::Unit)
+    }
+
+    fn get_available_casts(&self) -> error::Result<Vec<String>> {
+        let result = self.inner.get_available_casts();
+        self.record("get_available_casts", String::new(), result, |casts| {
+            Output::Strings(casts.clone())
+        })
+    }
+
+    fn current_params(&self) -> error::Result<Params> {
+        let result = self.inner.current_params();
+        self.record("current_params", String::new(), result, |params| {
+            Output::String(params.to_text())
+        })
+    }
+
+    fn apply_params(&self, params: &Params) -> error::Result<()> {
+        let result = self.inner.apply_params(params);
+        self.record("apply_params", params.to_text(), result, |()| Output::Unit)
+    }
+
+    fn speak(&self, text: &str) -> error::Result<Box<dyn Playback>> {
+        let result = self.inner.speak(text);
+        self.record("speak", text.to_string(), result, |_| Output::Unit)
+    }
+
+    fn stop(&self) -> error::Result<bool> {
+        let result = self.inner.stop();
+        self.record("stop", String::new(), result, |&stopped| {
+            Output::Bool(stopped)
+        })
+    }
+
+    fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>> {
+        let result = self.inner.get_phonemes(text);
+        self.record("get_phonemes", text.to_string(), result, |phonemes| {
+            Output::Phonemes(
+                phonemes
+                    .iter()
+                    .map(|p| (p.phoneme.clone(), p.start_time, p.end_time))
+                    .collect(),
+            )
+        })
+    }
+
+    fn output_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        let result = self.inner.output_wave_to_file(text, path).and_then(|()| {
+            fs::read(path)
+                .with_context(|| format!("Failed to read `{}`", path.display()))
+                .map_err(error::CeVIOError::from)
+        });
+        self.record("output_wave_to_file", text.to_string(), result, |wave| {
+            Output::Wave(wave.clone())
+        })
+        .map(|_| ())
+    }
+
+    fn output_raw_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        let result = self
+            .inner
+            .output_raw_wave_to_file(text, path)
+            .and_then(|()| {
+                fs::read(path)
+                    .with_context(|| format!("Failed to read `{}`", path.display()))
+                    .map_err(error::CeVIOError::from)
+            });
+        self.record(
+            "output_raw_wave_to_file",
+            text.to_string(),
+            result,
+            |wave| Output::Wave(wave.clone()),
+        )
+        .map(|_| ())
+    }
+
+    // 設定の取得は CeVIO を呼び出さないため、記録しない
+
+    fn overwrite_policy(&self) -> OverwritePolicy {
+        self.inner.overwrite_policy()
+    }
+
+    fn output_processors(&self) -> Vec<Arc<dyn OutputProcessor>> {
+        self.inner.output_processors()
+    }
+
+    fn cast_profile(&self, cast: &str) -> Option<Params> {
+        self.inner.cast_profile(cast)
+    }
+
+    fn fallback_casts(&self) -> Vec<String> {
+        self.inner.fallback_casts()
+    }
+}
+
+/// 記録した結果を返すバックエンドです。
+///
+/// 記録のない呼び出しは `ErrorKind::InvalidInput` で失敗します。
+/// 上書きの動作、後処理、キャストごとのパラメータ、代替キャストは `TalkerBackend` の既定の値です。
+pub struct ReplayBackend {
+    outputs: Mutex<HashMap<(String, String), VecDeque<Output>>>,
+}
+
+impl ReplayBackend {
+    /// 記録から作成します。
+    pub fn new(fixture: Fixture) -> Self {
+        let mut outputs: HashMap<_, VecDeque<_>> = HashMap::new();
+        for entry in fixture.entries {
+            outputs
+                .entry((entry.method, entry.input))
+                .or_default()
+                .push_back(entry.output);
+        }
+        Self {
+            outputs: Mutex::new(outputs),
+        }
+    }
+
+    /// JSON ファイルから記録を読み込んで作成します。
+    pub fn load(path: impl AsRef<Path>) -> error::Result<Self> {
+        Ok(Self::new(Fixture::load(path)?))
+    }
+
+    /// 記録した結果を取り出す。最後の 1 つは取り出さずに繰り返し返す
+    fn replay(&self, method: &str, input: &str) -> error::Result<Output> {
+        let mut outputs = self.outputs.lock().unwrap_or_else(|e| e.into_inner());
+        let queue = outputs
+            .get_mut(&(method.to_string(), input.to_string()))
+            .ok_or_else(|| {
+                error::CeVIOError::InvalidInput(report!(
+                    "No fixture for `{method}` with input {input:?}"
+                ))
+            })?;
+        let output = match queue.len() {
+            1 => queue[0].clone(),
+            _ => queue.pop_front().unwrap_or(Output::Unit),
+        };
+        output.into_result()
+    }
+}
+
+impl ReplayBackend {
+    /// 記録した WAV ファイルの内容を `path` に書き込む
+    fn replay_wave(&self, method: &str, text: &str, path: &Path) -> error::Result<()> {
+        match self.replay(method, text)? {
+            Output::Wave(wave) => fs::write(path, wave)
+                .with_context(|| format!("Failed to write `{}`", path.display()))
+                .map_err(error::CeVIOError::from),
+            output => Err(mismatch(method, &output)),
+        }
+    }
+}
+
+/// 記録と異なる種類の結果だった場合のエラー
+fn mismatch(method: &str, output: &Output) -> error::CeVIOError {
+    error::CeVIOError::InvalidInput(report!(
+        "Fixture for `{method}` has unexpected output {output:?}"
+    ))
+}
+
+impl TalkerBackend for ReplayBackend {
+    fn get_cast(&self) -> error::Result<String> {
+        match self.replay("get_cast", "")? {
+            Output::String(cast) => Ok(cast),
+            output => Err(mismatch("get_cast", &output)),
+        }
+    }
+
+    fn set_cast(&self, cast: &str) -> error::Result<()> {
+        self.replay("set_cast", cast).map(|_| ())
+    }
+
+    fn get_available_casts(&self) -> error::Result<Vec<String>> {
+        match self.replay("get_available_casts", "")? {
+            Output::Strings(casts) => Ok(casts),
+            output => Err(mismatch("get_available_casts", &output)),
+        }
+    }
+
+    fn current_params(&self) -> error::Result<Params> {
+        match self.replay("current_params", "")? {
+            Output::String(params) => Params::parse(&params),
+            output => Err(mismatch("current_params", &output)),
+        }
+    }
+
+    fn apply_params(&self, params: &Params) -> error::Result<()> {
+        self.replay("apply_params", &params.to_text()).map(|_| ())
+    }
+
+    fn speak(&self, text: &str) -> error::Result<Box<dyn Playback>> {
+        self.replay("speak", text)?;
+        Ok(Box::new(Completed))
+    }
+
+    fn stop(&self) -> error::Result<bool> {
+        match self.replay("stop", "")? {
+            Output::Bool(stopped) => Ok(stopped),
+            output => Err(mismatch("stop", &output)),
+        }
+    }
+
+    fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>> {
+        match self.replay("get_phonemes", text)? {
+            Output::Phonemes(phonemes) => Ok(phonemes
+                .into_iter()
+                .map(|(phoneme, start_time, end_time)| PhonemeData {
+                    phoneme,
+                    start_time,
+                    end_time,
+                })
+                .collect()),
+            output => Err(mismatch("get_phonemes", &output)),
+        }
+    }
+
+    fn output_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        self.replay_wave("output_wave_to_file", text, path)
+    }
+
+    fn output_raw_wave_to_file(&self, text: &str, path: &Path) -> error::Result<()> {
+        self.replay_wave("output_raw_wave_to_file", text, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::*;
+    use crate::{
+        backend::MockBackend,
+        project::{temp_project, Project},
+        queue::SpeechQueue,
+        temp::TempDir,
+    };
+
+    fn project() -> (TempDir, Project) {
+        temp_project("こんにちは。\n[早口] さようなら。\n")
+    }
+
+    #[test]
+    fn project_render_replays_recorded_calls() {
+        let wave = b"RIFF-recorded".to_vec();
+        let (_recorded_dir, recorded) = project();
+        let recorder = RecordingBackend::new(MockBackend::new().with_wave(wave.clone()));
+        recorded.render(&recorder).unwrap();
+        let json = serde_json::to_string(&recorder.fixture()).unwrap();
+        let fixture: Fixture = serde_json::from_str(&json).unwrap();
+
+        // 再生した呼び出しをもう一度記録し、同じ入力で同じ順に呼び出したことを確かめる
+        let (_replayed_dir, replayed) = project();
+        let replay = RecordingBackend::new(ReplayBackend::new(fixture.clone()));
+        let outputs = replayed.render(&replay).unwrap();
+        assert_eq!(replay.fixture(), fixture);
+        assert_eq!(outputs.len(), 2);
+        for output in &outputs {
+            assert!(output.starts_with(replayed.root()));
+            assert_eq!(fs::read(output).unwrap(), wave);
+        }
+
+        // 2 回目はキャッシュを使い、合成しない
+        let replay = RecordingBackend::new(ReplayBackend::new(fixture));
+        replayed.render(&replay).unwrap();
+        assert!(replay
+            .fixture()
+            .entries
+            .iter()
+            .all(|entry| entry.method != "output_raw_wave_to_file"));
+    }
+
+    #[test]
+    fn project_render_fails_without_fixture() {
+        let (_dir, project) = project();
+        let err = project
+            .render(&ReplayBackend::new(Fixture::default()))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn speech_queue_replays_recorded_calls() {
+        let entry = |input: &str, output: Output| Entry {
+            method: "speak".to_string(),
+            input: input.to_string(),
+            output,
+        };
+        let fixture = Fixture {
+            entries: vec![
+                entry("こんにちは。", Output::Unit),
+                entry(
+                    "さようなら。",
+                    Output::Error {
+                        kind: "HostNotRunning".to_string(),
+                        message: "host is not running".to_string(),
+                    },
+                ),
+            ],
+        };
+        let replay = Arc::new(RecordingBackend::new(ReplayBackend::new(fixture.clone())));
+        let queue = SpeechQueue::with_backend(replay.clone()).unwrap();
+        queue.push("a", "こんにちは。", Params::default());
+        queue.push("b", "さようなら。", Params::default());
+
+        let status = |client: &str| queue.client_status(client).unwrap();
+        for _ in 0..500 {
+            if status("a").spoken + status("b").spoken == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(status("a").spoken, 1);
+        assert_eq!(status("a").last_error, None);
+        assert_eq!(status("b").spoken, 1);
+        assert!(status("b")
+            .last_error
+            .is_some_and(|e| e.contains("host is not running")));
+        // クライアントの間の順番は問わない
+        let mut entries = replay.fixture().entries;
+        entries.sort_by(|a, b| a.input.cmp(&b.input));
+        assert_eq!(entries, fixture.entries);
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::error::{self, report, Context as _};
+
+pub(crate) fn read_to_string(path: &Path) -> error::Result<String> {
+    fs::read_to_string(path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))
+        .map_err(error::CeVIOError::from)
+}
+
+pub(crate) fn create_dir_all(path: &Path) -> error::Result<()> {
+    fs::create_dir_all(path)
+        .with_context(|| format!("Failed to create `{}`", path.display()))
+        .map_err(error::CeVIOError::from)
+}
+
+pub(crate) fn path_to_str(path: &Path) -> error::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| report!("Path `{}` is not valid UTF-8", path.display()))
+        .map_err(error::CeVIOError::InvalidInput)
+}
+
+pub(crate) fn absolute(path: &Path) -> error::Result<PathBuf> {
+    std::path::absolute(path)
+        .with_context(|| format!("Failed to resolve `{}`", path.display()))
+        .map_err(error::CeVIOError::from)
+}
+
+/// 出力先のパスを絶対パスにする
+///
+/// `base` がある場合、相対パスは `base` から解決し、`..` などで `base` の外を指す場合は失敗する
+pub(crate) fn resolve_in(base: Option<&Path>, path: &Path) -> error::Result<PathBuf> {
+    let Some(base) = base.filter(|_| path.is_relative()) else {
+        return absolute(path);
+    };
+    let outside = || {
+        error::CeVIOError::InvalidInput(report!(
+            "Path `{}` is outside of the base directory `{}`",
+            path.display(),
+            base.display()
+        ))
+    };
+    let mut resolved = base.to_path_buf();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if resolved != base => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            // `..` で `base` より上を指す場合と、`\foo` や `C:foo` のようにドライブやルートを含む場合
+            _ => return Err(outside()),
+        }
+    }
+    Ok(resolved)
+}
+
+/// `\\?\` を付けずに扱えるパスの長さ（`MAX_PATH`、終端の NUL を含む）
+const MAX_PATH: usize = 260;
+
+/// CeVIO に渡すパスを作る
+///
+/// CeVIO は相対パスをカレントディレクトリから解決しないため絶対パスにし、`MAX_PATH` を超える場合は `\\?\` を付ける
+pub(crate) fn host_path(path: &Path) -> error::Result<String> {
+    let path = absolute(path)?;
+    Ok(long_path(path_to_str(&path)?))
+}
+
+/// `MAX_PATH` を超えるパスに `\\?\`（UNC パスは `\\?\UNC\`）を付ける
+fn long_path(path: &str) -> String {
+    if path.encode_utf16().count() < MAX_PATH || path.starts_with(r"\\?\") {
+        return path.to_string();
+    }
+    match path.strip_prefix(r"\\") {
+        Some(unc) => format!(r"\\?\UNC\{unc}"),
+        None => format!(r"\\?\{path}"),
+    }
+}
+
+/// 一時ディレクトリ内の重複しない WAV ファイルのパスを作る
+pub(crate) fn temp_wav_path() -> PathBuf {
+    temp_path("wav")
+}
+
+/// 一時ディレクトリ内の重複しないパスを作る
+pub(crate) fn temp_path(extension: &str) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("cevio-rs-{}-{n}.{extension}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> PathBuf {
+        std::env::temp_dir().join("base")
+    }
+
+    fn resolve(path: &str) -> error::Result<PathBuf> {
+        resolve_in(Some(&base()), Path::new(path))
+    }
+
+    #[test]
+    fn resolve_in_joins_relative_paths_to_base() {
+        assert_eq!(resolve("a.wav").unwrap(), base().join("a.wav"));
+        assert_eq!(
+            resolve("out/a.wav").unwrap(),
+            base().join("out").join("a.wav")
+        );
+        assert_eq!(
+            resolve("./out/./a.wav").unwrap(),
+            base().join("out").join("a.wav")
+        );
+    }
+
+    #[test]
+    fn resolve_in_allows_parent_dir_inside_base() {
+        assert_eq!(resolve("out/../a.wav").unwrap(), base().join("a.wav"));
+        assert_eq!(
+            resolve("a/b/../../c/a.wav").unwrap(),
+            base().join("c").join("a.wav")
+        );
+    }
+
+    #[test]
+    fn resolve_in_rejects_parent_dir_escapes() {
+        for path in [
+            "../a.wav",
+            "out/../../a.wav",
+            "a/b/../../../a.wav",
+            "..",
+            "./../base/a.wav",
+        ] {
+            let e = resolve(path).unwrap_err();
+            assert!(matches!(e, error::CeVIOError::InvalidInput(_)), "{path}");
+            assert!(
+                format!("{e:#}").contains("is outside of the base directory"),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_in_keeps_absolute_paths() {
+        let absolute = std::env::temp_dir().join("other").join("a.wav");
+        assert_eq!(resolve_in(Some(&base()), &absolute).unwrap(), absolute);
+        assert_eq!(resolve_in(None, &absolute).unwrap(), absolute);
+    }
+
+    #[test]
+    fn resolve_in_without_base_uses_current_dir() {
+        let current = std::env::current_dir().unwrap();
+        assert_eq!(
+            resolve_in(None, Path::new("a.wav")).unwrap(),
+            current.join("a.wav")
+        );
+        // `base` がなければ `..` も制限しない
+        assert!(resolve_in(None, Path::new("../a.wav")).is_ok());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn resolve_in_rejects_root_and_drive_relative_paths() {
+        assert!(resolve(r"\a.wav").is_err());
+        assert!(resolve("C:a.wav").is_err());
+    }
+
+    #[test]
+    fn long_path_adds_prefix_only_beyond_max_path() {
+        let short = format!(r"C:\{}", "a".repeat(MAX_PATH - 4));
+        assert_eq!(long_path(&short), short);
+        let long = format!(r"C:\{}", "a".repeat(MAX_PATH - 3));
+        assert_eq!(long_path(&long), format!(r"\\?\{long}"));
+        assert_eq!(long_path(&format!(r"\\?\{long}")), format!(r"\\?\{long}"));
+        let unc = format!(r"\\server\share\{}", "a".repeat(MAX_PATH));
+        assert_eq!(long_path(&unc), format!(r"\\?\UNC\{}", &unc[2..]));
+    }
+}
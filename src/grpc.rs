@@ -0,0 +1,251 @@
+//! gRPC サーバー（`grpc` フィーチャー）
+//!
+//! サービス定義はクレートに同梱している `proto/cevio.proto` です。
+//! 他の言語のクライアントはこのファイルから生成してください。
+//!
+//! 失敗した呼び出しは、エラーの種類に応じたステータスコードで返します。
+//!
+//! | エラー | ステータスコード |
+//! | --- | --- |
+//! | `ErrorKind::InvalidInput` | `INVALID_ARGUMENT` |
+//! | `ErrorKind::InvalidCast` | `NOT_FOUND` |
+//! | `ErrorKind::HostNotRunning`、`CeVIOError::is_busy` | `UNAVAILABLE`（再試行できます） |
+//! | `ErrorKind::Timeout` | `DEADLINE_EXCEEDED` |
+//! | `ErrorKind::Unauthorized` | `UNAUTHENTICATED` |
+//! | `ErrorKind::Forbidden` | `PERMISSION_DENIED` |
+//! | その他 | `INTERNAL` |
+//!
+//! ```no_run
+//! # async fn run() {
+//! use cevio::{actor::Handle, grpc, HostKind};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! handle.call(|cevio| cevio.start_host(false)).unwrap();
+//! grpc::serve("127.0.0.1:50051".parse().unwrap(), handle).await.unwrap();
+//! # }
+//! ```
+
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt as _};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    actor::Handle,
+    error::{self, Context as _, ErrorKind},
+    CeVIO, Params,
+};
+
+/// `proto/cevio.proto` から生成したコードです。
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("cevio.v1");
+}
+
+use proto::{
+    cevio_service_server::{CevioService, CevioServiceServer},
+    event::Kind,
+    Event, ListCastsRequest, ListCastsResponse, SpeakRequest, SpeakResponse, StreamEventsRequest,
+    SynthesizeRequest, SynthesizeResponse,
+};
+
+/// `StreamEvents` の購読者ごとに保持するイベント数です。これを超えて遅れた購読者は古いイベントを読み飛ばします。
+const EVENT_CAPACITY: usize = 256;
+
+impl From<proto::Params> for Params {
+    fn from(params: proto::Params) -> Self {
+        Params {
+            cast: params.cast,
+            volume: params.volume,
+            speed: params.speed,
+            tone: params.tone,
+            tone_scale: params.tone_scale,
+            alpha: params.alpha,
+            components: params.components.into_iter().collect(),
+        }
+    }
+}
+
+/// エラーの種類に応じたステータスに変換する
+fn status(e: &error::CeVIOError) -> Status {
+    let message = format!("{e:#}");
+    match e.kind() {
+        ErrorKind::InvalidInput => Status::invalid_argument(message),
+        ErrorKind::InvalidCast => Status::not_found(message),
+        ErrorKind::HostNotRunning => Status::unavailable(message),
+        _ if e.is_busy() => Status::unavailable(message),
+        ErrorKind::Timeout => Status::deadline_exceeded(message),
+        ErrorKind::Unauthorized => Status::unauthenticated(message),
+        ErrorKind::Forbidden => Status::permission_denied(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// gRPC サービスの実装です。
+pub struct Service {
+    handle: Handle,
+    events: broadcast::Sender<Event>,
+    next_id: AtomicU64,
+}
+
+impl Service {
+    /// ハンドルからサービスを作成します。
+    pub fn new(handle: Handle) -> Self {
+        Self {
+            handle,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// tonic のサーバーに登録できる形に変換します。
+    pub fn into_server(self) -> CevioServiceServer<Self> {
+        CevioServiceServer::new(self)
+    }
+
+    fn emit(&self, id: u64, kind: Kind, text: &str, message: String) {
+        // 購読者がいない場合は失敗するが、イベントを捨てるだけでよい
+        let _ = self.events.send(Event {
+            id,
+            kind: kind.into(),
+            text: text.to_string(),
+            message,
+        });
+    }
+
+    /// 操作をブロッキング用のスレッドで実行し、前後にイベントを送る
+    async fn run<R, F>(&self, text: String, f: F) -> Result<R, Status>
+    where
+        R: Send + 'static,
+        F: FnOnce(&CeVIO, &str) -> error::Result<R> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.emit(id, Kind::Started, &text, String::new());
+        let handle = self.handle.clone();
+        let result = {
+            let text = text.clone();
+            tokio::task::spawn_blocking(move || handle.call(move |cevio| f(cevio, &text)))
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+        };
+        match result {
+            Ok(r) => {
+                self.emit(id, Kind::Finished, &text, String::new());
+                Ok(r)
+            }
+            Err(e) => {
+                self.emit(id, Kind::Failed, &text, format!("{e:#}"));
+                Err(status(&e))
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl CevioService for Service {
+    async fn speak(
+        &self,
+        request: Request<SpeakRequest>,
+    ) -> Result<Response<SpeakResponse>, Status> {
+        let request = request.into_inner();
+        let params = Params::from(request.params.unwrap_or_default());
+        self.run(request.text, move |cevio, text| {
+            cevio.say(text).params(&params).play()
+        })
+        .await?;
+        Ok(Response::new(SpeakResponse {}))
+    }
+
+    async fn synthesize(
+        &self,
+        request: Request<SynthesizeRequest>,
+    ) -> Result<Response<SynthesizeResponse>, Status> {
+        let request = request.into_inner();
+        let params = Params::from(request.params.unwrap_or_default());
+        let wav = self
+            .run(request.text, move |cevio, text| {
+                cevio.say(text).params(&params).to_vec()
+            })
+            .await?;
+        Ok(Response::new(SynthesizeResponse { wav }))
+    }
+
+    async fn list_casts(
+        &self,
+        _request: Request<ListCastsRequest>,
+    ) -> Result<Response<ListCastsResponse>, Status> {
+        let handle = self.handle.clone();
+        let casts =
+            tokio::task::spawn_blocking(move || handle.call(|cevio| cevio.get_available_casts()))
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+                .map_err(|e| status(&e))?;
+        Ok(Response::new(ListCastsResponse { casts }))
+    }
+
+    type StreamEventsStream = Pin<Box<dyn Stream<Item = Result<Event, Status>> + Send>>;
+
+    async fn stream_events(
+        &self,
+        _request: Request<StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        // 遅れて読み飛ばしたイベント（Lagged）は無視して続ける
+        let stream = BroadcastStream::new(self.events.subscribe())
+            .filter_map(|event| event.ok())
+            .map(Ok);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// 指定したアドレスで gRPC サーバーを起動します。
+pub async fn serve(addr: SocketAddr, handle: Handle) -> error::Result<()> {
+    tonic::transport::Server::builder()
+        .add_service(Service::new(handle).into_server())
+        .serve(addr)
+        .await
+        .context("Failed to serve")
+        .map_err(error::CeVIOError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+    use windows::Win32::Foundation::RPC_E_CALL_REJECTED;
+
+    use super::*;
+    use crate::error::report;
+
+    fn code(kind: ErrorKind) -> Code {
+        status(&error::CeVIOError::new(kind, report!("failed"))).code()
+    }
+
+    #[test]
+    fn error_kinds_map_to_status_codes() {
+        assert_eq!(code(ErrorKind::InvalidInput), Code::InvalidArgument);
+        assert_eq!(code(ErrorKind::InvalidCast), Code::NotFound);
+        assert_eq!(code(ErrorKind::HostNotRunning), Code::Unavailable);
+        assert_eq!(code(ErrorKind::Timeout), Code::DeadlineExceeded);
+        assert_eq!(code(ErrorKind::Unauthorized), Code::Unauthenticated);
+        assert_eq!(code(ErrorKind::Forbidden), Code::PermissionDenied);
+        assert_eq!(code(ErrorKind::OperationFailed), Code::Internal);
+        assert_eq!(code(ErrorKind::Other), Code::Internal);
+    }
+
+    #[test]
+    fn busy_is_unavailable() {
+        let e =
+            error::CeVIOError::from(error::Report::new(error::HResultError(RPC_E_CALL_REJECTED)));
+        assert!(e.is_busy());
+        assert_eq!(status(&e).code(), Code::Unavailable);
+    }
+
+    #[test]
+    fn status_message_includes_causes() {
+        let e = error::CeVIOError::InvalidInput(report!("inner").context("outer"));
+        assert_eq!(status(&e).message(), "outer: inner");
+    }
+}
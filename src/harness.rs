@@ -0,0 +1,128 @@
+//! 実際の CeVIO を使う結合テストの準備と後片付け（`integration-tests` フィーチャー）
+//!
+//! `Harness` は CeVIO を起動し、テストが終わる（`Harness` が破棄される）と次の後片付けをします。
+//!
+//! - テスト前のキャスト、パラメータ、感情パラメータに戻す
+//! - 一時ディレクトリ（`temp_path` で作ったファイル）を削除する
+//! - テストのために起動した場合は CeVIO を終了する
+//!
+//! テストが失敗（パニック）した場合も後片付けをします。同じプロセスの `Harness` は 1 つずつ順番に作られるため、
+//! テストを並列に実行してもキャストの切り替えなどが混ざりません。
+//!
+//! 操作する製品は環境変数 `CEVIO_TEST_HOST`（`ai` か `cs`）で指定できます。
+//!
+//! ```sh
+//! cargo test --features integration-tests --test smoke
+//! ```
+//!
+//! ```no_run
+//! use cevio::harness::Harness;
+//!
+//! let harness = Harness::from_env().unwrap();
+//! let cevio = harness.cevio();
+//! cevio.set_cast(harness.first_cast().unwrap()).unwrap();
+//! let path = harness.temp_path("hello.wav");
+//! cevio.output_wave_to_file("こんにちは。", path).unwrap();
+//! ```
+
+use std::{
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    fs_util, CeVIO, HostKind, Params,
+};
+
+/// 操作する製品を指定する環境変数
+pub const HOST_ENV: &str = "CEVIO_TEST_HOST";
+
+/// 同時に 1 つの `Harness` だけが CeVIO を操作するためのロック
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// 実際の CeVIO を使うテストの準備と後片付けをします。
+pub struct Harness {
+    cevio: CeVIO,
+    /// テスト前のキャスト、パラメータ、感情パラメータ
+    original: Params,
+    /// `start` で CeVIO を起動したかどうか
+    started: bool,
+    dir: PathBuf,
+    // 後片付けが終わってから次のテストが始まるよう最後に置く
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Harness {
+    /// 指定した製品を起動し、現在のキャストとパラメータを記録します。
+    ///
+    /// 他の `Harness` が破棄されるまで待ちます。
+    pub fn start(host: HostKind) -> error::Result<Self> {
+        let lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let cevio = CeVIO::with_host(host)?;
+        let started = !cevio.get_is_host_started()?;
+        let dir = std::env::temp_dir().join(format!("cevio-rs-test-{}", std::process::id()));
+        // 以降で失敗した場合も `Drop` で後片付けをする
+        let mut harness = Self {
+            cevio,
+            original: Params::default(),
+            started,
+            dir,
+            _lock: lock,
+        };
+        if started {
+            harness.cevio.start_host(false)?;
+        }
+        fs_util::create_dir_all(&harness.dir)?;
+        harness.original = harness.cevio.current_params()?;
+        Ok(harness)
+    }
+
+    /// 環境変数 `CEVIO_TEST_HOST`（`ai` か `cs`、省略時は `HostKind::default()`）で指定した製品を起動します。
+    pub fn from_env() -> error::Result<Self> {
+        let host = match std::env::var(HOST_ENV).as_deref() {
+            Err(_) | Ok("") => HostKind::default(),
+            #[cfg(feature = "cevio-ai")]
+            Ok("ai") => HostKind::Ai,
+            #[cfg(feature = "cevio-cs")]
+            Ok("cs") => HostKind::Cs,
+            Ok(other) => {
+                return Err(error::CeVIOError::InvalidInput(report!(
+                    "Unknown host `{other}` in `{HOST_ENV}`"
+                )))
+            }
+        };
+        Self::start(host)
+    }
+
+    /// 操作対象のインスタンスを取得します。
+    pub fn cevio(&self) -> &CeVIO {
+        &self.cevio
+    }
+
+    /// 後片付けで削除される一時ディレクトリ内のパスを取得します。
+    pub fn temp_path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// 利用可能なキャストのうち最初のものを取得します。キャストがない場合は失敗します。
+    pub fn first_cast(&self) -> error::Result<String> {
+        self.cevio
+            .get_available_casts()?
+            .into_iter()
+            .next()
+            .context("No cast is available")
+            .map_err(error::CeVIOError::InvalidCast)
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        // 後片付けの失敗でテストの結果を変えないよう、エラーは無視する
+        let _ = self.cevio.apply_params(&self.original);
+        let _ = std::fs::remove_dir_all(&self.dir);
+        if self.started {
+            let _ = self.cevio.close_host(0);
+        }
+    }
+}
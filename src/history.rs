@@ -0,0 +1,362 @@
+//! 合成の履歴（`history` フィーチャー）
+//!
+//! `CeVIO::set_history` を設定すると、`speak`、`output_wave_to_file` などのすべての合成を、1 行に 1 つの JSON で履歴ファイルに追記します。
+//! 長時間動かす読み上げボットが実際に何を話したかを後から確認するためのものです。
+//!
+//! ```text
+//! {"timestamp_ms":1700000000000,"kind":"speak","cast":"花隈千冬","params":{"speed":50,"components":{"嬉しい":30}},"text":"こんにちは。","output":null,"elapsed_ms":42,"audio_ms":null,"result":"ok"}
+//! ```
+//!
+//! - `timestamp_ms`：合成を始めた時刻（UNIX 時間、ミリ秒）
+//! - `kind`：`speak`（再生）か `wave`（WAV の出力）
+//! - `cast`、`params`：`set_cast`、`set_volume`、`say` などで指定したキャストとパラメータ。指定していない項目は省きます
+//!   （前回と同じ値で CeVIO を呼び出さなかった項目も含みます）
+//! - `output`：出力したファイルのパス（`speak` の場合は `null`）
+//! - `elapsed_ms`：合成（`speak` の場合は再生の開始）までにかかった時間
+//! - `audio_ms`：出力した WAV の長さ（`speak` と、失敗した場合は `null`）
+//! - `result`：成功した場合は `ok`、失敗した場合はエラーメッセージ
+//!
+//! ファイルが `max_bytes` を超えると `<名前>.1`、`<名前>.2`、... に名前を変え、`keep` 個より古いものは削除します。
+//!
+//! ```no_run
+//! use cevio::{history::History, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//!
+//! cevio.set_history(Some(History::new(r"E:\bot\history.jsonl").max_bytes(1024 * 1024).keep(3)));
+//! cevio.speak("こんにちは。").unwrap().wait().unwrap();
+//! ```
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{error, observer::SpeechKind, params::Params};
+
+/// 既定のファイルの大きさの上限（10 MB）
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+/// 既定の残す古いファイルの数
+const DEFAULT_KEEP: usize = 5;
+
+/// 履歴ファイルの設定です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl History {
+    /// 履歴ファイルのパスを指定して作成します。ディレクトリは既にある必要があります。
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: DEFAULT_MAX_BYTES,
+            keep: DEFAULT_KEEP,
+        }
+    }
+
+    /// ファイルの大きさの上限を指定します。単位はバイト。省略時は 10 MB です。
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// 名前を変えて残す古いファイルの数を指定します。0 の場合は上限を超えたファイルを削除します。省略時は 5 です。
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// 履歴ファイルのパスを取得します。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 1 行追記する。追記すると上限を超える場合は、先にファイルを回す
+    fn append(&self, line: &str) -> io::Result<()> {
+        let len = fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+        if len > 0 && len + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")
+    }
+
+    /// `<名前>.n` を `<名前>.(n+1)` に、現在のファイルを `<名前>.1` に変える
+    fn rotate(&self) -> io::Result<()> {
+        if self.keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+        let _ = fs::remove_file(self.rotated(self.keep));
+        for n in (1..self.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+}
+
+/// 指定したキャストとパラメータの記録
+///
+/// `Written` と違い、書き込みを省いた値や書き込みに失敗した値も残す。
+/// キャストを変えるとパラメータが初期化されるため、キャスト以外の値は忘れる
+#[derive(Debug, Default)]
+pub(crate) struct Requested(Params);
+
+impl Requested {
+    pub(crate) fn set_cast(&mut self, cast: &str) {
+        if self.0.cast.as_deref() != Some(cast) {
+            self.0 = Params {
+                cast: Some(cast.to_string()),
+                ..Params::default()
+            };
+        }
+    }
+
+    /// `prop` は COM のプロパティ名
+    pub(crate) fn set_param(&mut self, prop: &str, value: i32) {
+        let param = match prop {
+            "Volume" => &mut self.0.volume,
+            "Speed" => &mut self.0.speed,
+            "Tone" => &mut self.0.tone,
+            "ToneScale" => &mut self.0.tone_scale,
+            "Alpha" => &mut self.0.alpha,
+            _ => return,
+        };
+        *param = Some(value);
+    }
+
+    pub(crate) fn set_component(&mut self, name: &str, value: i32) {
+        match self.0.components.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.0.components.push((name.to_string(), value)),
+        }
+    }
+}
+
+/// 履歴の 1 行
+pub(crate) struct Entry<'a> {
+    pub(crate) started: SystemTime,
+    pub(crate) kind: SpeechKind,
+    pub(crate) text: &'a str,
+    pub(crate) output: Option<&'a Path>,
+    pub(crate) elapsed: Duration,
+    /// 出力した WAV の長さ
+    pub(crate) audio: Option<Duration>,
+    pub(crate) error: Option<&'a error::CeVIOError>,
+}
+
+impl Entry<'_> {
+    /// `requested` は指定したキャストとパラメータ
+    fn to_json(&self, requested: &Params) -> String {
+        let mut params = serde_json::Map::new();
+        for (key, value) in [
+            ("volume", requested.volume),
+            ("speed", requested.speed),
+            ("tone", requested.tone),
+            ("tone_scale", requested.tone_scale),
+            ("alpha", requested.alpha),
+        ] {
+            if let Some(value) = value {
+                params.insert(key.to_string(), value.into());
+            }
+        }
+        if !requested.components.is_empty() {
+            let mut components = requested.components.clone();
+            components.sort();
+            let components = components
+                .into_iter()
+                .map(|(name, value)| (name, value.into()))
+                .collect::<serde_json::Map<_, _>>();
+            params.insert("components".to_string(), components.into());
+        }
+        let timestamp_ms = self
+            .started
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |t| t.as_millis() as u64);
+        serde_json::json!({
+            "timestamp_ms": timestamp_ms,
+            "kind": match self.kind {
+                SpeechKind::Speak => "speak",
+                SpeechKind::Wave => "wave",
+            },
+            "cast": requested.cast,
+            "params": params,
+            "text": self.text,
+            "output": self.output.map(|path| path.to_string_lossy()),
+            "elapsed_ms": self.elapsed.as_millis() as u64,
+            "audio_ms": self.audio.map(|audio| audio.as_millis() as u64),
+            "result": match self.error {
+                None => "ok".to_string(),
+                Some(e) => format!("{e:#}"),
+            },
+        })
+        .to_string()
+    }
+}
+
+impl crate::CeVIO {
+    /// 合成の履歴を追記するファイルを設定します。`None` の場合は記録しません。（既定）
+    pub fn set_history(&self, history: Option<History>) {
+        *self.history.borrow_mut() = history;
+    }
+
+    /// 合成の履歴を追記するファイルの設定を取得します。
+    pub fn history(&self) -> Option<History> {
+        self.history.borrow().clone()
+    }
+
+    /// 履歴を設定している場合は 1 行追記する。失敗しても合成の結果には影響させない
+    pub(crate) fn record_history(&self, entry: Entry<'_>) {
+        let history = self.history.borrow();
+        let Some(history) = history.as_ref() else {
+            return;
+        };
+        let line = entry.to_json(&self.requested.borrow().0);
+        if let Err(_e) = history.append(&line) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(path = %history.path.display(), error = %_e, "Failed to append history");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::TempDir;
+
+    fn history(dir: &TempDir) -> History {
+        History::new(dir.path().join("history.jsonl"))
+    }
+
+    fn read(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn rotates_only_when_line_exceeds_max_bytes() {
+        // "aaaa\n" で 5 バイト
+        let dir = TempDir::new();
+        let history = history(&dir).max_bytes(10);
+        history.append("aaaa").unwrap();
+        // ちょうど上限なら回さない
+        history.append("bbbb").unwrap();
+        assert_eq!(read(history.path()).unwrap(), "aaaa\nbbbb\n");
+        assert_eq!(read(&history.rotated(1)), None);
+        // 1 バイトでも超えれば回す
+        history.append("c").unwrap();
+        assert_eq!(read(history.path()).unwrap(), "c\n");
+        assert_eq!(read(&history.rotated(1)).unwrap(), "aaaa\nbbbb\n");
+    }
+
+    #[test]
+    fn does_not_rotate_empty_file_for_long_line() {
+        let dir = TempDir::new();
+        let history = history(&dir).max_bytes(3);
+        history.append("longer than max").unwrap();
+        assert_eq!(read(history.path()).unwrap(), "longer than max\n");
+        assert_eq!(read(&history.rotated(1)), None);
+    }
+
+    #[test]
+    fn keeps_at_most_keep_rotated_files() {
+        let dir = TempDir::new();
+        let history = history(&dir).max_bytes(1).keep(2);
+        for line in ["1", "2", "3", "4"] {
+            history.append(line).unwrap();
+        }
+        assert_eq!(read(history.path()).unwrap(), "4\n");
+        assert_eq!(read(&history.rotated(1)).unwrap(), "3\n");
+        assert_eq!(read(&history.rotated(2)).unwrap(), "2\n");
+        assert_eq!(read(&history.rotated(3)), None);
+    }
+
+    #[test]
+    fn keep_zero_removes_full_file() {
+        let dir = TempDir::new();
+        let history = history(&dir).max_bytes(1).keep(0);
+        history.append("1").unwrap();
+        history.append("2").unwrap();
+        assert_eq!(read(history.path()).unwrap(), "2\n");
+        assert_eq!(read(&history.rotated(1)), None);
+    }
+
+    #[test]
+    fn requested_forgets_params_when_cast_changes() {
+        let mut requested = Requested::default();
+        requested.set_cast("花隈千冬");
+        requested.set_param("Speed", 60);
+        requested.set_param("ToneScale", 40);
+        requested.set_component("嬉しい", 10);
+        requested.set_component("嬉しい", 30);
+        // 同じキャストなら値を残す
+        requested.set_cast("花隈千冬");
+        assert_eq!(
+            requested.0,
+            Params {
+                cast: Some("花隈千冬".to_string()),
+                speed: Some(60),
+                tone_scale: Some(40),
+                components: vec![("嬉しい".to_string(), 30)],
+                ..Params::default()
+            }
+        );
+        requested.set_cast("さとうささら");
+        assert_eq!(
+            requested.0,
+            Params {
+                cast: Some("さとうささら".to_string()),
+                ..Params::default()
+            }
+        );
+    }
+
+    #[test]
+    fn entry_records_requested_params() {
+        let entry = Entry {
+            started: SystemTime::UNIX_EPOCH + Duration::from_millis(1500),
+            kind: SpeechKind::Speak,
+            text: "こんにちは。",
+            output: None,
+            elapsed: Duration::from_millis(42),
+            audio: None,
+            error: None,
+        };
+        let requested = Params {
+            cast: Some("花隈千冬".to_string()),
+            speed: Some(50),
+            components: vec![("嬉しい".to_string(), 30), ("怒り".to_string(), 0)],
+            ..Params::default()
+        };
+        let json: serde_json::Value = serde_json::from_str(&entry.to_json(&requested)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "timestamp_ms": 1500,
+                "kind": "speak",
+                "cast": "花隈千冬",
+                "params": {"speed": 50, "components": {"嬉しい": 30, "怒り": 0}},
+                "text": "こんにちは。",
+                "output": null,
+                "elapsed_ms": 42,
+                "audio_ms": null,
+                "result": "ok",
+            })
+        );
+    }
+}
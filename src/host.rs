@@ -0,0 +1,272 @@
+/// 操作対象の製品です。
+///
+/// `Cs` は `cevio-cs` フィーチャー、`Ai` は `cevio-ai` フィーチャーで有効になります（既定ではどちらも有効）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HostKind {
+    /// CeVIO Creative Studio（`CeVIO.Talk.RemoteService`）
+    #[cfg(feature = "cevio-cs")]
+    Cs,
+    /// CeVIO AI（`CeVIO.Talk.RemoteService2`）
+    #[cfg(feature = "cevio-ai")]
+    Ai,
+}
+
+impl Default for HostKind {
+    /// `cevio-ai` フィーチャーが有効な場合は CeVIO AI、それ以外は CeVIO Creative Studio
+    fn default() -> Self {
+        #[cfg(feature = "cevio-ai")]
+        return HostKind::Ai;
+        #[cfg(not(feature = "cevio-ai"))]
+        return HostKind::Cs;
+    }
+}
+
+impl HostKind {
+    /// 有効な製品すべて（CeVIO AI、CeVIO Creative Studio の順）
+    pub const ALL: &'static [HostKind] = &[
+        #[cfg(feature = "cevio-ai")]
+        HostKind::Ai,
+        #[cfg(feature = "cevio-cs")]
+        HostKind::Cs,
+    ];
+
+    /// 製品名
+    pub(crate) fn product_name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "cevio-cs")]
+            HostKind::Cs => "CeVIO Creative Studio",
+            #[cfg(feature = "cevio-ai")]
+            HostKind::Ai => "CeVIO AI",
+        }
+    }
+
+    pub(crate) fn talker_prog_id(self) -> &'static str {
+        match self {
+            #[cfg(feature = "cevio-cs")]
+            HostKind::Cs => "CeVIO.Talk.RemoteService.Talker",
+            #[cfg(feature = "cevio-ai")]
+            HostKind::Ai => "CeVIO.Talk.RemoteService2.Talker2",
+        }
+    }
+
+    pub(crate) fn service_control_prog_id(self) -> &'static str {
+        match self {
+            #[cfg(feature = "cevio-cs")]
+            HostKind::Cs => "CeVIO.Talk.RemoteService.ServiceControl",
+            #[cfg(feature = "cevio-ai")]
+            HostKind::Ai => "CeVIO.Talk.RemoteService2.ServiceControl2",
+        }
+    }
+}
+
+/// 起動している製品の情報です。`CeVIO::host_info` で取得します。
+///
+/// バージョンは `major.minor.patch` として比較でき、4 つ目の数字はビルドメタデータ（`9.1.10+0` の `0`）になります。
+///
+/// ```
+/// use cevio::{HostInfo, HostKind};
+///
+/// let info = HostInfo::parse(HostKind::default(), "9.1.10.0", "9.0.0").unwrap();
+/// assert_eq!(info.version.major, 9);
+/// assert!(info.at_least(9, 1, 0));
+/// assert!(!info.at_least(10, 0, 0));
+/// assert!(info.matches(">=9.1, <10"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInfo {
+    /// 製品
+    pub kind: HostKind,
+    /// 製品のバージョン
+    pub version: semver::Version,
+    /// COM コンポーネントのインターフェースのバージョン
+    pub interface_version: semver::Version,
+}
+
+impl HostInfo {
+    /// 製品と、`HostVersion`、`InterfaceVersion` の文字列から作成します。
+    pub fn parse(
+        kind: HostKind,
+        version: &str,
+        interface_version: &str,
+    ) -> crate::error::Result<Self> {
+        Ok(Self {
+            kind,
+            version: parse_version(version)?,
+            interface_version: parse_version(interface_version)?,
+        })
+    }
+
+    /// 製品のバージョンが `major.minor.patch` 以上かどうかを取得します。
+    pub fn at_least(&self, major: u64, minor: u64, patch: u64) -> bool {
+        self.version >= semver::Version::new(major, minor, patch)
+    }
+
+    /// 製品のバージョンが条件（`>=9.1, <10` など）を満たすかどうかを取得します。条件が不正な場合は `false` です。
+    pub fn matches(&self, requirement: &str) -> bool {
+        semver::VersionReq::parse(requirement).is_ok_and(|req| req.matches(&self.version))
+    }
+}
+
+/// 製品のバージョンによって使えるかどうかが変わる機能です。`CeVIO::capabilities` で取得します。
+///
+/// 使えない機能を呼んで失敗させる代わりに、あらかじめ分岐するために使います。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// 感情パラメータ（`Components`）
+    pub supports_components: bool,
+    /// 音素データ（`GetPhonemes`）
+    pub supports_phonemes: bool,
+    /// セリフの長さ（`GetTextDuration`）
+    pub supports_text_duration: bool,
+    /// 英語のキャスト（インストールされている場合に `true`）
+    pub supports_english_casts: bool,
+}
+
+/// 製品の応答の状態です。`CeVIO::ping`、`actor::Handle::ping` で取得します。
+///
+/// サーバーやウォッチドッグで、処理に時間がかかっているだけ（`Slow`）か、応答できない（`Dead`）かを区別するために使います。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostHealth {
+    /// 期限内に応答しました。
+    Healthy {
+        /// 応答までの時間
+        latency: std::time::Duration,
+    },
+    /// 期限内に応答しませんでした。（他の処理中、または固まっている）
+    Slow,
+    /// 応答に失敗しました。（製品が起動していない、専用スレッドが終了したなど）
+    Dead {
+        /// 理由
+        reason: String,
+    },
+}
+
+impl HostHealth {
+    /// 期限内に応答したかどうかを取得します。
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Self::Healthy { .. })
+    }
+
+    /// 名前（`healthy`、`slow`、`dead`）を取得します。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Healthy { .. } => "healthy",
+            Self::Slow => "slow",
+            Self::Dead { .. } => "dead",
+        }
+    }
+}
+
+/// `9.1.10.0` のような CeVIO のバージョンを変換する
+///
+/// 3 つに満たない場合は 0 で補い、4 つ目以降はビルドメタデータにする
+fn parse_version(s: &str) -> crate::error::Result<semver::Version> {
+    let parts = s
+        .trim()
+        .split('.')
+        .map(str::parse::<u64>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| crate::error::Report::new(e).context(format!("Invalid version `{s}`")))
+        .map_err(crate::error::CeVIOError::Conversion)?;
+    let part = |i: usize| parts.get(i).copied().unwrap_or(0);
+    let mut version = semver::Version::new(part(0), part(1), part(2));
+    if parts.len() > 3 {
+        let build = parts[3..]
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(".");
+        version.build = semver::BuildMetadata::new(&build)
+            .map_err(|e| crate::error::CeVIOError::Conversion(e.into()))?;
+    }
+    Ok(version)
+}
+
+/// CeVIO と CeVIO AI を同時に操作するためのものです。（`cevio-cs` と `cevio-ai` の両方のフィーチャーが必要です。）
+///
+/// それぞれ独立した Talker と ServiceControl を持つため、
+/// CeVIO のキャストと CeVIO AI のキャストが混在する台本を 1 回の実行で出力できます。
+///
+/// ```no_run
+/// use cevio::{HostKind, Hosts};
+/// let hosts = Hosts::new().unwrap();
+///
+/// hosts.cs.start_host(false).unwrap();
+/// hosts.ai.start_host(false).unwrap();
+/// hosts.get(HostKind::Cs).set_cast("さとうささら").unwrap();
+/// hosts.get(HostKind::Ai).set_cast("花隈千冬").unwrap();
+/// ```
+#[cfg(all(feature = "cevio-cs", feature = "cevio-ai"))]
+pub struct Hosts {
+    /// CeVIO 用インスタンス
+    pub cs: crate::CeVIO,
+    /// CeVIO AI 用インスタンス
+    pub ai: crate::CeVIO,
+}
+
+#[cfg(all(feature = "cevio-cs", feature = "cevio-ai"))]
+impl Hosts {
+    /// CeVIO と CeVIO AI の両方のインスタンスを作成します。
+    pub fn new() -> crate::error::Result<Self> {
+        Ok(Self {
+            cs: crate::CeVIO::with_host(HostKind::Cs)?,
+            ai: crate::CeVIO::with_host(HostKind::Ai)?,
+        })
+    }
+
+    /// 指定した製品のインスタンスを取得します。
+    pub fn get(&self, kind: HostKind) -> &crate::CeVIO {
+        match kind {
+            #[cfg(feature = "cevio-cs")]
+            HostKind::Cs => &self.cs,
+            #[cfg(feature = "cevio-ai")]
+            HostKind::Ai => &self.ai,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> semver::Version {
+        semver::Version::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_version_moves_fourth_part_to_build_metadata() {
+        assert_eq!(parse_version("9.1.10.0").unwrap(), version("9.1.10+0"));
+        assert_eq!(parse_version("7.0.23.1").unwrap(), version("7.0.23+1"));
+        assert_eq!(parse_version("9.1.10.0.5").unwrap(), version("9.1.10+0.5"));
+        assert_eq!(parse_version("9.1.10").unwrap(), version("9.1.10"));
+    }
+
+    #[test]
+    fn parse_version_pads_missing_parts() {
+        assert_eq!(parse_version("9").unwrap(), version("9.0.0"));
+        assert_eq!(parse_version(" 9.1\r\n").unwrap(), version("9.1.0"));
+    }
+
+    #[test]
+    fn parse_version_rejects_malformed_versions() {
+        for s in [
+            "",
+            " ",
+            "9..1",
+            "9.1.",
+            ".9",
+            "v9.1.10",
+            "9.1.10-beta",
+            "9.-1",
+            "9.1.a",
+            "99999999999999999999",
+        ] {
+            let e = parse_version(s).unwrap_err();
+            assert!(
+                matches!(e, crate::error::CeVIOError::Conversion(_)),
+                "{s:?}"
+            );
+            assert!(format!("{e:#}").contains("Invalid version"), "{s:?}");
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! グローバルホットキーで選択中のテキストやクリップボードを読み上げます（`hotkey` フィーチャー）
+//!
+//! - 読み上げのホットキーを押すと、Ctrl+C を送って選択中のテキストをコピーし、読み上げます。
+//!   何も選択されていない場合は、クリップボードのテキストを読み上げます。
+//! - 停止のホットキーを押すと、再生を停止します。
+//!
+//! ```no_run
+//! use std::sync::atomic::AtomicBool;
+//! use cevio::{hotkey, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let options = hotkey::HotkeyOptions {
+//!     speak: "Ctrl+Alt+R".parse().unwrap(),
+//!     ..Default::default()
+//! };
+//! let stop = AtomicBool::new(false);
+//! hotkey::run(&cevio, &options, &stop).unwrap();
+//! ```
+
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows::Win32::{
+    Foundation::HWND,
+    UI::{
+        Input::KeyboardAndMouse::{
+            RegisterHotKey, SendInput, UnregisterHotKey, HOT_KEY_MODIFIERS, INPUT, INPUT_0,
+            INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP, MOD_ALT, MOD_CONTROL,
+            MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, VIRTUAL_KEY, VK_C, VK_CONTROL, VK_F1, VK_LWIN,
+            VK_MENU, VK_PAUSE, VK_RWIN, VK_SHIFT, VK_SPACE,
+        },
+        WindowsAndMessaging::{PeekMessageW, MSG, PM_REMOVE, WM_HOTKEY},
+    },
+};
+
+use crate::{
+    clipboard,
+    error::{self, report},
+    CeVIO,
+};
+
+/// メッセージを確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Ctrl+C を送ってからコピーされるまで待つ最大の時間
+const COPY_TIMEOUT: Duration = Duration::from_millis(500);
+
+const SPEAK_ID: i32 = 1;
+const STOP_ID: i32 = 2;
+
+/// ホットキーです。
+///
+/// `"Ctrl+Alt+S"` のように、修飾キー（`Ctrl`・`Alt`・`Shift`・`Win`）とキーを `+` でつないだ文字列から作れます。
+/// キーは `A`～`Z`、`0`～`9`、`F1`～`F24`、`Space`、`Pause` です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    /// Ctrl キー
+    pub ctrl: bool,
+    /// Alt キー
+    pub alt: bool,
+    /// Shift キー
+    pub shift: bool,
+    /// Windows キー
+    pub win: bool,
+    /// 仮想キーコード
+    pub key: u16,
+}
+
+impl Hotkey {
+    fn modifiers(&self) -> HOT_KEY_MODIFIERS {
+        [
+            (self.ctrl, MOD_CONTROL),
+            (self.alt, MOD_ALT),
+            (self.shift, MOD_SHIFT),
+            (self.win, MOD_WIN),
+        ]
+        .into_iter()
+        .filter(|(on, _)| *on)
+        .fold(MOD_NOREPEAT, |acc, (_, m)| acc | m)
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = error::CeVIOError;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        let invalid = || error::CeVIOError::InvalidInput(report!("Invalid hotkey `{s}`"));
+        let mut hotkey = Hotkey {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            win: false,
+            key: 0,
+        };
+        let (modifiers, key) = s.rsplit_once('+').unwrap_or(("", s));
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier.trim().to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => hotkey.ctrl = true,
+                "alt" => hotkey.alt = true,
+                "shift" => hotkey.shift = true,
+                "win" => hotkey.win = true,
+                _ => return Err(invalid()),
+            }
+        }
+        let key = key.trim().to_ascii_uppercase();
+        hotkey.key = match key.as_str() {
+            "SPACE" => VK_SPACE.0,
+            "PAUSE" => VK_PAUSE.0,
+            k if k.len() == 1 && k.as_bytes()[0].is_ascii_alphanumeric() => k.as_bytes()[0] as u16,
+            k => match k.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+                Some(n @ 1..=24) => VK_F1.0 + n - 1,
+                _ => return Err(invalid()),
+            },
+        };
+        Ok(hotkey)
+    }
+}
+
+/// 読み上げるテキストの取得元です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Source {
+    /// 選択中のテキスト。何も選択されていない場合はクリップボード
+    #[default]
+    Selection,
+    /// クリップボード
+    Clipboard,
+}
+
+/// ホットキーの設定です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotkeyOptions {
+    /// 読み上げのホットキー
+    pub speak: Hotkey,
+    /// 停止のホットキー
+    pub stop: Hotkey,
+    /// 読み上げるテキストの取得元
+    pub source: Source,
+    /// 読み上げる最大文字数。超えた部分は読み上げません。`None` は無制限
+    pub max_chars: Option<usize>,
+}
+
+impl Default for HotkeyOptions {
+    fn default() -> Self {
+        Self {
+            speak: "Ctrl+Alt+S".parse().unwrap(),
+            stop: "Ctrl+Alt+X".parse().unwrap(),
+            source: Source::default(),
+            max_chars: Some(200),
+        }
+    }
+}
+
+/// 登録したホットキーを破棄時に解除する
+struct Registration(i32);
+
+impl Registration {
+    fn new(id: i32, hotkey: &Hotkey) -> error::Result<Self> {
+        if !unsafe { RegisterHotKey(HWND(0), id, hotkey.modifiers(), hotkey.key as u32) }.as_bool()
+        {
+            return Err(error::CeVIOError::Other(report!(
+                "Failed to register hotkey (already in use?): {:?}",
+                windows::core::Error::from_win32()
+            )));
+        }
+        Ok(Self(id))
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        unsafe { UnregisterHotKey(HWND(0), self.0) };
+    }
+}
+
+fn key_input(key: VIRTUAL_KEY, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: key,
+                dwFlags: flags,
+                ..Default::default()
+            },
+        },
+    }
+}
+
+/// 押されているホットキーの修飾キーを離してから Ctrl+C を送る
+fn send_copy(hotkey: &Hotkey) {
+    let mut inputs = Vec::new();
+    for (on, key) in [
+        (hotkey.alt, VK_MENU),
+        (hotkey.shift, VK_SHIFT),
+        (hotkey.win, VK_LWIN),
+        (hotkey.win, VK_RWIN),
+    ] {
+        if on {
+            inputs.push(key_input(key, KEYEVENTF_KEYUP));
+        }
+    }
+    inputs.extend([
+        key_input(VK_CONTROL, KEYBD_EVENT_FLAGS(0)),
+        key_input(VK_C, KEYBD_EVENT_FLAGS(0)),
+        key_input(VK_C, KEYEVENTF_KEYUP),
+        key_input(VK_CONTROL, KEYEVENTF_KEYUP),
+    ]);
+    unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+}
+
+/// 選択中のテキストかクリップボードのテキストを取得する
+fn grab_text(options: &HotkeyOptions) -> Option<String> {
+    if options.source == Source::Selection {
+        let before = clipboard::sequence_number();
+        send_copy(&options.speak);
+        let start = Instant::now();
+        while clipboard::sequence_number() == before && start.elapsed() < COPY_TIMEOUT {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    let start = Instant::now();
+    loop {
+        match clipboard::read_text() {
+            Ok(text) => return text,
+            // コピーしたアプリがまだクリップボードを開いている
+            Err(clipboard::Busy) if start.elapsed() < COPY_TIMEOUT => thread::sleep(POLL_INTERVAL),
+            Err(clipboard::Busy) => return None,
+        }
+    }
+}
+
+/// ホットキーを登録し、押されるたびに読み上げ・停止します。
+///
+/// `stop` が `true` になるまで戻りません。ホットキーは呼び出したスレッドに登録され、戻るときに解除します。
+/// キャストなどのパラメータは `cevio` に設定されているものを使います。
+pub fn run(cevio: &CeVIO, options: &HotkeyOptions, stop: &AtomicBool) -> error::Result<()> {
+    let _speak = Registration::new(SPEAK_ID, &options.speak)?;
+    let _stop = Registration::new(STOP_ID, &options.stop)?;
+
+    let mut msg = MSG::default();
+    while !stop.load(Ordering::SeqCst) {
+        if !unsafe { PeekMessageW(&mut msg, HWND(0), WM_HOTKEY, WM_HOTKEY, PM_REMOVE) }.as_bool() {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        match msg.wParam.0 as i32 {
+            SPEAK_ID => {
+                let text = grab_text(options).unwrap_or_default();
+                let text = clipboard::normalize(&text, options.max_chars);
+                if !text.is_empty() {
+                    cevio.stop()?;
+                    cevio.speak(&text)?;
+                }
+            }
+            STOP_ID => {
+                cevio.stop()?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! メッセージの言語
+//!
+//! ユーザー向けのメッセージ（`CeVIOError::localized`、`diagnose::Report` の表示、CLI の出力）の言語を選びます。
+//! 既定は日本語で、環境変数 `CEVIO_LANG`（`ja` か `en`）か `set_lang` で変更できます。
+//!
+//! エラーの `Display`（`{e:#}`）はログの検索や照合に使えるよう、言語に関わらず英語のままです。
+//!
+//! ```
+//! use cevio::i18n::{self, Lang};
+//!
+//! i18n::set_lang(Lang::En);
+//! assert_eq!(Lang::current(), Lang::En);
+//! assert_eq!(Lang::current().pick("エラー", "Error"), "Error");
+//! ```
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 言語を指定する環境変数
+pub const LANG_ENV: &str = "CEVIO_LANG";
+
+/// メッセージの言語です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Lang {
+    /// 日本語
+    #[default]
+    Ja,
+    /// 英語
+    En,
+}
+
+/// `set_lang` で設定した言語（0 は未設定）
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+impl Lang {
+    /// 現在の言語を取得します。
+    ///
+    /// `set_lang` で設定していない場合は環境変数 `CEVIO_LANG`、それもない場合は日本語です。
+    pub fn current() -> Self {
+        match LANG.load(Ordering::Relaxed) {
+            1 => Self::Ja,
+            2 => Self::En,
+            _ => std::env::var(LANG_ENV)
+                .ok()
+                .and_then(|lang| Self::parse(&lang))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// `ja`、`en`、`ja_JP.UTF-8`、`en-US` などの言語タグを変換します。
+    pub fn parse(s: &str) -> Option<Self> {
+        let lang = s.split(['_', '-', '.']).next()?;
+        match lang.to_ascii_lowercase().as_str() {
+            "ja" => Some(Self::Ja),
+            "en" => Some(Self::En),
+            _ => None,
+        }
+    }
+
+    /// 言語に合わせて `ja` か `en` を返します。
+    pub fn pick<'a>(self, ja: &'a str, en: &'a str) -> &'a str {
+        match self {
+            Self::Ja => ja,
+            Self::En => en,
+        }
+    }
+}
+
+/// プロセス全体の言語を設定します。
+pub fn set_lang(lang: Lang) {
+    let value = match lang {
+        Lang::Ja => 1,
+        Lang::En => 2,
+    };
+    LANG.store(value, Ordering::Relaxed);
+}
+
+/// 現在の言語で書式を選んで `String` を作る
+///
+/// `cevio-cli` などのバイナリからも使うため公開しているが、ライブラリの API ではない
+#[doc(hidden)]
+#[macro_export]
+macro_rules! tr {
+    ($ja:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        match $crate::i18n::Lang::current() {
+            $crate::i18n::Lang::Ja => format!($ja $(, $arg)*),
+            $crate::i18n::Lang::En => format!($en $(, $arg)*),
+        }
+    };
+}
+pub(crate) use crate::tr;
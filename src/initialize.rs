@@ -1,7 +1,7 @@
 pub struct Initialize {}
 
 impl Initialize {
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new() -> Result<Self, crate::error::Report> {
         use windows::Win32::System::Com::{
             CoInitializeEx, COINIT_APARTMENTTHREADED, COINIT_DISABLE_OLE1DDE,
         };
@@ -0,0 +1,239 @@
+//! JSON Lines で読み上げを依頼するためのプロトコル（`jsonl` フィーチャー）
+//!
+//! 1 行に 1 つ JSON のリクエストを読み込み、1 行に 1 つ JSON のイベント・レスポンスを書き込みます。
+//! 標準入出力で使うと、Electron/Node.js/Python などのアプリから COM や HTTP を使わずに子プロセスとして組み込めます。
+//!
+//! リクエストの `op` で操作を指定します。`id` は任意の値で、そのリクエストに対するイベント・レスポンスにそのまま付けて返します。
+//! `op` と `id` 以外は HTTP サーバーと同じです。
+//!
+//! | `op`         | 内容                                    | レスポンス                                                       |
+//! | ------------ | --------------------------------------- | ---------------------------------------------------------------- |
+//! | `speak`      | セリフを再生し、再生終了後に応答します  | `{"ok":true}`（再生開始時に `{"event":"started"}`）              |
+//! | `synthesize` | セリフを WAV に変換します               | `path` 指定時は `{"ok":true,"path":...}`、それ以外は `{"ok":true,"wav":<Base64>}` |
+//! | `casts`      | 利用可能なキャスト名を取得します        | `{"ok":true,"casts":[...]}`                                      |
+//! | `phonemes`   | セリフの音素データを取得します          | `{"ok":true,"phonemes":[{"phoneme":...,"start":...,"end":...}]}` |
+//! | `components` | キャストの感情パラメータを取得します    | `{"ok":true,"components":[{"name":...,"value":...}]}`            |
+//!
+//! 失敗した場合は `{"ok":false,"error":<内容>,"code":...,"kind":...,"retryable":...}` を返します。
+//! `code`、`kind`、`retryable` は名前付きパイプサーバーと同じく `error::ErrorBody` の値です。
+//! リクエストのキャストとパラメータはそのリクエストの間だけ変更し、応答の前に元の値に戻します。
+//! `synthesize` の出力先が既にある場合は `CeVIO::set_overwrite_policy` の設定に従い、スキップした場合は `"skipped":true` を付けます。
+//!
+//! ```text
+//! → {"id":1,"op":"speak","text":"こんにちは。","cast":"花隈千冬"}
+//! ← {"id":1,"event":"started"}
+//! ← {"id":1,"ok":true}
+//! ```
+//!
+//! ```no_run
+//! use cevio::{jsonl, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//!
+//! jsonl::serve(&cevio, std::io::stdin().lock(), std::io::stdout().lock()).unwrap();
+//! ```
+
+use std::io::{BufRead, Write};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    config::ServerConfig,
+    error::{self, Context as _},
+    request::TextBody,
+    CeVIO,
+};
+
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    request: Request,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Speak(TextBody),
+    Synthesize {
+        path: Option<String>,
+        #[serde(flatten)]
+        body: TextBody,
+    },
+    Casts,
+    Phonemes(TextBody),
+    Components,
+}
+
+/// `id` を付けて 1 行書き込む
+fn write_line(writer: &mut impl Write, id: &Value, mut value: Value) -> error::Result<()> {
+    if let Value::Object(map) = &mut value {
+        map.insert("id".to_string(), id.clone());
+    }
+    writeln!(writer, "{value}")
+        .and_then(|()| writer.flush())
+        .context("Failed to write response")
+        .map_err(error::CeVIOError::from)
+}
+
+fn handle_request(
+    cevio: &CeVIO,
+    config: &ServerConfig,
+    request: Request,
+    mut started: impl FnMut() -> error::Result<()>,
+) -> error::Result<Value> {
+    match request {
+        Request::Speak(body) => {
+            let (text, params) = body.resolve(config)?;
+            // 再生中にパラメータを戻さないよう、終わるまで待つ
+            cevio.with_params(&params, |cevio| {
+                let state = cevio.speak(&text)?;
+                started()?;
+                state.wait()
+            })?;
+            Ok(json!({ "ok": true }))
+        }
+        Request::Synthesize { path, body } => {
+            let (text, params) = body.resolve(config)?;
+            cevio.with_params(&params, |cevio| match path {
+                Some(path) => {
+                    let path = cevio.resolve_output_path(&path)?;
+                    match cevio.output_wave(&text, &path)? {
+                        Some(path) => Ok(json!({ "ok": true, "path": path })),
+                        None => Ok(json!({ "ok": true, "path": path, "skipped": true })),
+                    }
+                }
+                None => {
+                    let wav = cevio.output_wave_to_vec(&text)?;
+                    Ok(json!({ "ok": true, "wav": base64(&wav) }))
+                }
+            })
+        }
+        Request::Casts => Ok(json!({ "ok": true, "casts": cevio.get_available_casts()? })),
+        Request::Phonemes(body) => {
+            let (text, params) = body.resolve(config)?;
+            let phonemes = cevio
+                .with_params(&params, |cevio| cevio.get_phonemes(&text))?
+                .into_iter()
+                .map(|p| json!({ "phoneme": p.phoneme, "start": p.start_time, "end": p.end_time }))
+                .collect::<Vec<_>>();
+            Ok(json!({ "ok": true, "phonemes": phonemes }))
+        }
+        Request::Components => {
+            let components = cevio
+                .get_components()?
+                .into_iter()
+                .map(|c| json!({ "id": c.id, "name": c.name, "value": c.value }))
+                .collect::<Vec<_>>();
+            Ok(json!({ "ok": true, "components": components }))
+        }
+    }
+}
+
+/// `reader` からリクエストを読み込み、`writer` にレスポンスを書き込みます。
+///
+/// `reader` が終わるまで戻りません。リクエストは 1 つずつ順番に処理します。
+pub fn serve(cevio: &CeVIO, reader: impl BufRead, writer: impl Write) -> error::Result<()> {
+    serve_with_config(cevio, &ServerConfig::default(), reader, writer)
+}
+
+/// 設定を適用して `serve` と同じ処理をします。
+pub fn serve_with_config(
+    cevio: &CeVIO,
+    config: &ServerConfig,
+    reader: impl BufRead,
+    mut writer: impl Write,
+) -> error::Result<()> {
+    for line in reader.lines() {
+        let line = line
+            .context("Failed to read request")
+            .map_err(error::CeVIOError::from)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let envelope = match serde_json::from_str::<Envelope>(&line) {
+            Ok(envelope) => envelope,
+            Err(e) => {
+                // 壊れた行でも他のリクエストは処理を続ける
+                let e = error::CeVIOError::InvalidInput(
+                    error::Report::new(e).context("Invalid request"),
+                );
+                write_line(&mut writer, &Value::Null, error_response(&e))?;
+                continue;
+            }
+        };
+        let id = envelope.id;
+        let result = handle_request(cevio, config, envelope.request, || {
+            write_line(&mut writer, &id, json!({ "event": "started" }))
+        });
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => error_response(&e),
+        };
+        write_line(&mut writer, &id, response)?;
+    }
+    Ok(())
+}
+
+/// 失敗した場合のレスポンス
+fn error_response(e: &error::CeVIOError) -> Value {
+    let body = e.to_body();
+    json!({
+        "ok": false,
+        "error": body.message,
+        "code": body.code,
+        "kind": body.kind,
+        "retryable": body.retryable,
+    })
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc4648_vectors() {
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(bytes.as_bytes()), encoded, "{bytes:?}");
+        }
+    }
+
+    #[test]
+    fn base64_pads_high_and_zero_bytes() {
+        assert_eq!(base64(&[0]), "AA==");
+        assert_eq!(base64(&[0, 0]), "AAA=");
+        assert_eq!(base64(&[0, 0, 0]), "AAAA");
+        assert_eq!(base64(&[0xff]), "/w==");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+    }
+}
@@ -0,0 +1,356 @@
+//! 読み替え辞書と、辞書ファイルの読み込み・書き出し
+//!
+//! `Lexicon` はセリフの単語を読みに置き換える辞書です。サーバーの設定（`config::ServerConfig::lexicon`）でも使います。
+//!
+//! 辞書ファイルはこのクレート独自の形式で、1 語を 1 行の CSV（`表記,読み[,その他の列…]`、`"` の囲みは RFC 4180 と同じ）で書きます。
+//! CeVIO の画面で読み込み・書き出しできる形式と同じであることは確かめていません。
+//! 3 列目以降は読み込んだまま書き出すため、ほかのツールで付けた列を失いません。
+//! 読み込みは UTF-8（BOM の有無を問わない）、UTF-16（BOM 付き）、Shift_JIS を判別し、書き出しは BOM 付き UTF-8 です。
+//!
+//! ```no_run
+//! use cevio::lexicon::Lexicon;
+//!
+//! let mut lexicon = Lexicon::load_user_dictionary(r"E:\user.csv").unwrap();
+//! lexicon.insert("CeVIO", "チェビオ");
+//! lexicon.save_user_dictionary(r"E:\user.csv").unwrap();
+//! ```
+//!
+//! ```
+//! use cevio::lexicon::Lexicon;
+//!
+//! let lexicon = Lexicon::parse_user_dictionary("CeVIO,チェビオ\nAI,エーアイ,0\n").unwrap();
+//! assert_eq!(lexicon.get("CeVIO"), Some("チェビオ"));
+//! assert_eq!(lexicon.apply("CeVIO AI"), "チェビオ エーアイ");
+//! assert_eq!(lexicon.to_user_dictionary(), "CeVIO,チェビオ\r\nAI,エーアイ,0\r\n");
+//! ```
+
+use std::path::Path;
+
+use crate::{
+    error::{self, report, Context as _},
+    text,
+};
+
+/// 読み替え辞書の 1 語です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// 表記
+    pub word: String,
+    /// 読み
+    pub reading: String,
+    /// 辞書ファイルの 3 列目以降
+    pub extra: Vec<String>,
+}
+
+/// 読み替え辞書です。
+///
+/// 同じ表記は 1 つだけ登録できます。置き換えはセリフの先頭から、その位置で一致する最も長い表記で行います。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lexicon {
+    entries: Vec<Entry>,
+}
+
+impl Lexicon {
+    /// 空の辞書を作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 単語を登録します。同じ表記が既にある場合は読みを置き換えます。（3 列目以降はそのままです。）
+    pub fn insert(&mut self, word: impl Into<String>, reading: impl Into<String>) {
+        let (word, reading) = (word.into(), reading.into());
+        match self.entries.iter_mut().find(|e| e.word == word) {
+            Some(entry) => entry.reading = reading,
+            None => self.entries.push(Entry {
+                word,
+                reading,
+                extra: Vec::new(),
+            }),
+        }
+    }
+
+    /// 単語の登録を解除し、登録されていた読みを返します。
+    pub fn remove(&mut self, word: &str) -> Option<String> {
+        let i = self.entries.iter().position(|e| e.word == word)?;
+        Some(self.entries.remove(i).reading)
+    }
+
+    /// 単語の読みを取得します。
+    pub fn get(&self, word: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.word == word)
+            .map(|e| e.reading.as_str())
+    }
+
+    /// 登録した単語を、登録した順に取得します。
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /// 登録した単語の数を取得します。
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 単語が登録されていないかどうかを取得します。
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// `other` の単語をすべて登録します。同じ表記は `other` の内容で置き換えます。
+    pub fn merge(&mut self, other: &Lexicon) {
+        for entry in &other.entries {
+            match self.entries.iter_mut().find(|e| e.word == entry.word) {
+                Some(e) => *e = entry.clone(),
+                None => self.entries.push(entry.clone()),
+            }
+        }
+    }
+
+    /// セリフの単語を読みに置き換えます。
+    ///
+    /// セリフを先頭から 1 度だけ調べ、各位置で一致する最も長い表記を読みに置き換えます。
+    /// 置き換えた読みは調べ直さないため、読みに別の表記が含まれていても置き換えません。
+    pub fn apply(&self, text: &str) -> String {
+        let mut applied = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let longest = self
+                .entries
+                .iter()
+                .filter(|e| !e.word.is_empty() && rest.starts_with(e.word.as_str()))
+                .max_by_key(|e| e.word.len());
+            match longest {
+                Some(e) => {
+                    applied.push_str(&e.reading);
+                    rest = &rest[e.word.len()..];
+                }
+                None => {
+                    applied.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        applied
+    }
+
+    /// 辞書ファイルの内容を読み込みます。
+    ///
+    /// 空行は無視します。`"` で囲んだ列は改行を含められます（改行は `\n` になります）。
+    /// 表記か読みがない行は `CeVIOError::InvalidInput` です。
+    pub fn parse_user_dictionary(s: &str) -> error::Result<Self> {
+        let mut lexicon = Self::new();
+        for (i, record) in split_records(s) {
+            if record.trim().is_empty() {
+                continue;
+            }
+            let mut fields = split_fields(&record)
+                .map_err(|e| e.context(format!("Invalid line {}", i + 1)))
+                .map_err(error::CeVIOError::InvalidInput)?
+                .into_iter();
+            let (word, reading) = match (fields.next(), fields.next()) {
+                (Some(word), Some(reading)) if !word.is_empty() && !reading.is_empty() => {
+                    (word, reading)
+                }
+                _ => {
+                    return Err(error::CeVIOError::InvalidInput(report!(
+                        "Missing word or reading at line {}",
+                        i + 1
+                    )))
+                }
+            };
+            let entry = Entry {
+                word,
+                reading,
+                extra: fields.collect(),
+            };
+            lexicon.merge(&Self {
+                entries: vec![entry],
+            });
+        }
+        Ok(lexicon)
+    }
+
+    /// 辞書ファイルの形式（改行は CRLF）に変換します。
+    pub fn to_user_dictionary(&self) -> String {
+        self.entries
+            .iter()
+            .map(|e| {
+                let fields = [&e.word, &e.reading].into_iter().chain(&e.extra);
+                let line = fields.map(|f| quote(f)).collect::<Vec<_>>().join(",");
+                line + "\r\n"
+            })
+            .collect()
+    }
+
+    /// 辞書ファイルを読み込みます。
+    pub fn load_user_dictionary(path: impl AsRef<Path>) -> error::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))
+            .map_err(error::CeVIOError::from)?;
+        let s = text::decode(&bytes)
+            .with_context(|| format!("Failed to decode `{}`", path.display()))
+            .map_err(error::CeVIOError::Conversion)?;
+        Self::parse_user_dictionary(&s)
+    }
+
+    /// 辞書ファイルを BOM 付き UTF-8 で書き出します。
+    pub fn save_user_dictionary(&self, path: impl AsRef<Path>) -> error::Result<()> {
+        let path = path.as_ref();
+        let content = format!("\u{FEFF}{}", self.to_user_dictionary());
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write `{}`", path.display()))
+            .map_err(error::CeVIOError::from)
+    }
+}
+
+impl<W: Into<String>, R: Into<String>> FromIterator<(W, R)> for Lexicon {
+    fn from_iter<I: IntoIterator<Item = (W, R)>>(iter: I) -> Self {
+        let mut lexicon = Self::new();
+        for (word, reading) in iter {
+            lexicon.insert(word, reading);
+        }
+        lexicon
+    }
+}
+
+/// 行に分け、（始まりの行番号（0 から）, 行）を返す。`"` で囲んだ列の中の改行では分けない
+fn split_records(s: &str) -> Vec<(usize, String)> {
+    let mut records = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+    for (i, line) in s.lines().enumerate() {
+        let (start, record) = match pending.take() {
+            Some((start, record)) => (start, record + "\n" + line),
+            None => (i, line.to_string()),
+        };
+        // `"` の数が奇数なら囲みが閉じていない
+        match record.matches('"').count() % 2 {
+            0 => records.push((start, record)),
+            _ => pending = Some((start, record)),
+        }
+    }
+    // 閉じていない囲みは `split_fields` でエラーにする
+    records.extend(pending);
+    records
+}
+
+/// CSV の 1 行を列に分ける（`"` で囲んだ列の `,` と `""` に対応する）
+fn split_fields(line: &str) -> std::result::Result<Vec<String>, error::Report> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (false, ',') => fields.push(std::mem::take(&mut field).trim().to_string()),
+            (_, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(report!("Unterminated quote"));
+    }
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
+
+/// 必要な場合は `"` で囲む
+fn quote(field: &str) -> String {
+    match field.contains([',', '"', '\r', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(line: &str) -> Vec<String> {
+        split_fields(line).unwrap()
+    }
+
+    #[test]
+    fn apply_does_not_replace_inside_readings() {
+        let lexicon = Lexicon::from_iter([("A", "エービー"), ("ビー", "B")]);
+        assert_eq!(lexicon.apply("Aとビー"), "エービーとB");
+    }
+
+    #[test]
+    fn apply_prefers_longest_match_at_each_position() {
+        let lexicon = Lexicon::from_iter([("CeVIO", "チェビオ"), ("CeVIO AI", "チェビオエーアイ")]);
+        assert_eq!(
+            lexicon.apply("CeVIO AIとCeVIO"),
+            "チェビオエーアイとチェビオ"
+        );
+        // 先に始まる表記を優先する
+        let lexicon = Lexicon::from_iter([("ab", "1"), ("bcd", "2")]);
+        assert_eq!(lexicon.apply("abcd"), "1cd");
+    }
+
+    #[test]
+    fn apply_ignores_empty_words() {
+        let mut lexicon = Lexicon::new();
+        lexicon.insert("", "カラ");
+        assert_eq!(lexicon.apply("あい"), "あい");
+    }
+
+    #[test]
+    fn split_fields_handles_quotes_and_commas() {
+        assert_eq!(fields("a,b,c"), ["a", "b", "c"]);
+        assert_eq!(fields(" a , b "), ["a", "b"]);
+        assert_eq!(fields(r#""a,b",c"#), ["a,b", "c"]);
+        assert_eq!(fields(r#""say ""hi""",x"#), [r#"say "hi""#, "x"]);
+        assert_eq!(fields(r#""",x"#), ["", "x"]);
+        assert_eq!(fields("a,"), ["a", ""]);
+        // 囲んでいない列の途中の `"` は文字として扱う
+        assert_eq!(fields(r#"a"b,c"#), [r#"a"b"#, "c"]);
+    }
+
+    #[test]
+    fn split_fields_rejects_unterminated_quote() {
+        assert!(split_fields(r#""a,b"#).is_err());
+        assert!(split_fields(r#"a,"b"#).is_err());
+    }
+
+    #[test]
+    fn quote_only_when_needed() {
+        assert_eq!(quote("abc"), "abc");
+        assert_eq!(quote("a,b"), r#""a,b""#);
+        assert_eq!(quote(r#"a"b"#), r#""a""b""#);
+        assert_eq!(quote("a\nb"), "\"a\nb\"");
+        assert_eq!(quote("a\r\nb"), "\"a\r\nb\"");
+    }
+
+    #[test]
+    fn quoted_fields_round_trip() {
+        for field in ["a,b", r#"say "hi""#, "1行目\n2行目", "\"\"", "plain"] {
+            assert_eq!(fields(&quote(field)), [field]);
+        }
+    }
+
+    #[test]
+    fn embedded_newline_round_trips_through_dictionary() {
+        let mut lexicon = Lexicon::new();
+        lexicon.insert("複数\n行", "フクスウギョウ");
+        lexicon.insert("a,b", r#""エービー""#);
+        let parsed = Lexicon::parse_user_dictionary(&lexicon.to_user_dictionary()).unwrap();
+        assert_eq!(parsed, lexicon);
+    }
+
+    #[test]
+    fn parse_reports_line_of_unterminated_quote() {
+        let e = Lexicon::parse_user_dictionary("a,ア\n\"b,ビ\nc,シ\n").unwrap_err();
+        assert!(format!("{e:#}").contains("Invalid line 2"), "{e:#}");
+    }
+}
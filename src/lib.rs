@@ -17,76 +17,394 @@
 //! ).unwrap();
 //! ```
 //!
+//! 起動、キャストの確認、パラメータの設定をまとめて行う場合は [`CeVIO::builder`](./struct.CeVIO.html#method.builder) を使用できます。
+//!
+//! よく使う型は `use cevio::prelude::*;` でまとめて読み込めます。
+//!
 //! 詳しくはこちら: [struct CeVIO](./struct.CeVIO.html)
 
-use anyhow::Context as _;
+#[cfg(not(any(feature = "cevio-ai", feature = "cevio-cs")))]
+compile_error!("`cevio-ai` と `cevio-cs` のどちらかのフィーチャーを有効にしてください");
+
+use std::path::Path;
+
 use windows::Win32::System::Com::VARIANT;
 
+pub mod actor;
+pub mod analysis;
+pub mod audition;
+#[cfg(feature = "server")]
+pub mod auth;
+pub mod backend;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+pub mod builder;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cast;
+mod cast_cache;
+pub mod casts;
+#[cfg(feature = "chat")]
+pub mod chat;
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
 mod com;
+mod component;
+pub mod config;
+pub mod diagnose;
+#[cfg(feature = "dsp")]
+pub mod dsp;
 pub mod error;
+#[cfg(any(test, feature = "fault"))]
+pub mod fault;
+#[cfg(feature = "ffmpeg")]
+pub mod ffmpeg;
+#[cfg(feature = "fixture")]
+pub mod fixture;
+mod fs_util;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "integration-tests")]
+pub mod harness;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod host;
+#[cfg(feature = "hotkey")]
+pub mod hotkey;
+pub mod i18n;
 mod initialize;
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+pub mod lexicon;
+pub mod metrics;
+pub mod observer;
+#[cfg(feature = "server")]
+pub mod openapi;
+pub mod overwrite;
+pub mod params;
+#[cfg(feature = "pipe")]
+pub mod pipe;
+pub mod playlist;
+pub mod prelude;
+pub mod process;
+pub mod processor;
+pub mod project;
+pub mod queue;
+pub mod reading;
+#[cfg(any(feature = "server", feature = "pipe", feature = "jsonl"))]
+mod request;
+mod say;
+#[cfg(feature = "server")]
+pub mod seika;
+#[cfg(feature = "sentiment")]
+pub mod sentiment;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "service")]
+pub mod service;
+pub mod settings;
+pub mod sound;
+mod speaking;
+pub mod stems;
+pub mod subtitle;
+pub mod tail;
+pub mod talkers;
+pub mod temp;
+pub mod text;
 mod variant_ext;
+#[cfg(feature = "server")]
+pub mod voicevox;
+mod wav;
+mod written;
 
+pub use builder::CeVIOBuilder;
+pub use cast::{CastInfo, CastIter, Casts, Language};
 use com::ComObject;
+pub use component::Component;
+#[cfg(all(feature = "cevio-cs", feature = "cevio-ai"))]
+pub use host::Hosts;
+pub use host::{Capabilities, HostHealth, HostInfo, HostKind};
 use initialize::Initialize;
+pub use params::Params;
+pub use process::{HostProcess, WindowState};
+pub use project::Project;
+pub use say::Say;
+pub use semver;
+pub use sound::Sound;
+pub use speaking::{PhonemeData, SpeakingState};
 use variant_ext::VariantExt;
 
+/// `warm_up` で出力するセリフ
+const WARM_UP_TEXT: &str = "あ。";
+
 pub struct CeVIO {
-    _init: Initialize,
+    host: HostKind,
     talker: ComObject,
     controller: ComObject,
-}
-
-fn make_error_message(method_name: &str, fn_name: &str) -> String {
-    format!("Failed to call `{method_name}` in fn `{fn_name}`")
+    latencies: std::sync::Arc<metrics::Latencies>,
+    observers: std::rc::Rc<observer::Observers>,
+    /// 最後に `speak` した再生状態
+    last_speech: std::cell::RefCell<Option<ComObject>>,
+    strict: std::cell::Cell<bool>,
+    /// `start_host` で一度起動に成功したか。起動し直した回数の記録に使う
+    host_started: std::cell::Cell<bool>,
+    overwrite: std::cell::Cell<overwrite::OverwritePolicy>,
+    /// キャストごとの既定のパラメータ
+    cast_profiles: std::cell::RefCell<std::collections::HashMap<String, Params>>,
+    /// キャストがインストールされていない場合に選ぶキャスト（優先順）
+    fallback_casts: std::cell::RefCell<Vec<String>>,
+    /// 相対パスを解決するディレクトリ
+    base_dir: std::cell::RefCell<Option<std::path::PathBuf>>,
+    /// 取得したキャストの一覧と感情パラメータの名前
+    cast_cache: std::cell::RefCell<cast_cache::CastCache>,
+    /// 書き込んだ値。同じ値の書き込みを省く
+    written: std::cell::RefCell<written::Written>,
+    /// 合成した量
+    usage: std::cell::RefCell<metrics::UsageStats>,
+    /// 出力した WAV に順に適用する後処理
+    output_processors: std::cell::RefCell<Vec<std::sync::Arc<dyn processor::OutputProcessor>>>,
+    /// 合成の履歴を追記するファイル
+    #[cfg(feature = "history")]
+    history: std::cell::RefCell<Option<history::History>>,
+    /// 指定したキャストとパラメータ。履歴に記録する
+    #[cfg(feature = "history")]
+    requested: std::cell::RefCell<history::Requested>,
+    // COM オブジェクトを解放してから CoUninitialize するため最後に置く
+    _init: Initialize,
 }
 
 impl CeVIO {
     /// CeVIO AI 用インスタンスを作成します。（`CeVIO::new_cevio_ai` と同じです。）
     ///
     /// CeVIO を使用する場合は `CeVIO::new_cevio()` を使用してください。
+    #[cfg(feature = "cevio-ai")]
     pub fn new() -> error::Result<Self> {
-        Ok(Self {
-            _init: Initialize::new().map_err(error::CeVIOError)?,
-            talker: ComObject::new("CeVIO.Talk.RemoteService2.Talker2")
-                .map_err(|e| e.into())
-                .map_err(error::CeVIOError)?,
-            controller: ComObject::new("CeVIO.Talk.RemoteService2.ServiceControl2")
-                .map_err(|e| e.into())
-                .map_err(error::CeVIOError)?,
-        })
+        Self::with_host(HostKind::Ai)
     }
 
     /// CeVIO 用インスタンスを作成します。
     ///
     /// CeVIO AI を使用する場合は `CeVIO::new_cevio_ai()` を使用してください。
+    #[cfg(feature = "cevio-cs")]
     pub fn new_cevio() -> error::Result<Self> {
-        Ok(Self {
-            _init: Initialize::new().map_err(error::CeVIOError)?,
-            talker: ComObject::new("CeVIO.Talk.RemoteService.Talker")
-                .map_err(|e| e.into())
-                .map_err(error::CeVIOError)?,
-            controller: ComObject::new("CeVIO.Talk.RemoteService.ServiceControl")
-                .map_err(|e| e.into())
-                .map_err(error::CeVIOError)?,
-        })
+        Self::with_host(HostKind::Cs)
     }
 
     /// CeVIO AI 用インスタンスを作成します。
     ///
     /// CeVIO を使用する場合は `CeVIO::new_cevio()` を使用してください。
+    #[cfg(feature = "cevio-ai")]
     pub fn new_cevio_ai() -> error::Result<Self> {
+        Self::with_host(HostKind::Ai)
+    }
+
+    /// 指定した製品用のインスタンスを作成します。
+    ///
+    /// 同じプロセスで CeVIO 用と CeVIO AI 用のインスタンスを同時に持つことができます。
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(?host), err)
+    )]
+    pub fn with_host(host: HostKind) -> error::Result<Self> {
+        Self::with_host_and_locale(host, com::LOCALE_USER_DEFAULT)
+    }
+
+    /// 指定した製品用のインスタンスを、COM の呼び出しに `locale` を使って作成する
+    pub(crate) fn with_host_and_locale(host: HostKind, locale: u32) -> error::Result<Self> {
+        let init = Initialize::new().map_err(error::CeVIOError::ComInit)?;
+        let latencies = std::sync::Arc::new(metrics::Latencies::default());
+        let controller = ComObject::new(host.service_control_prog_id())
+            .map_err(|e| error::CeVIOError::ObjectCreation(e.into()))?
+            .with_latencies(latencies.clone())
+            .with_locale(locale);
+        let context = std::rc::Rc::new(com::HostContext::new(host, &controller));
         Ok(Self {
-            _init: Initialize::new().map_err(error::CeVIOError)?,
-            talker: ComObject::new("CeVIO.Talk.RemoteService2.Talker2")
-                .map_err(|e| e.into())
-                .map_err(error::CeVIOError)?,
-            controller: ComObject::new("CeVIO.Talk.RemoteService2.ServiceControl2")
-                .map_err(|e| e.into())
-                .map_err(error::CeVIOError)?,
+            host,
+            talker: ComObject::new(host.talker_prog_id())
+                .map_err(|e| error::CeVIOError::ObjectCreation(e.into()))?
+                .with_latencies(latencies.clone())
+                .with_locale(locale)
+                .with_host(context.clone()),
+            controller: controller.with_host(context),
+            latencies,
+            observers: Default::default(),
+            last_speech: Default::default(),
+            strict: std::cell::Cell::new(true),
+            host_started: Default::default(),
+            overwrite: Default::default(),
+            cast_profiles: Default::default(),
+            cast_cache: Default::default(),
+            fallback_casts: Default::default(),
+            base_dir: Default::default(),
+            written: Default::default(),
+            usage: Default::default(),
+            output_processors: Default::default(),
+            #[cfg(feature = "history")]
+            history: Default::default(),
+            #[cfg(feature = "history")]
+            requested: Default::default(),
+            _init: init,
         })
     }
 
+    /// 同じ製品の Talker を新しく作成し、それを操作するインスタンスを返します。
+    ///
+    /// キャラクターごとにインスタンスを作成すると、キャラクターが交互に話す場合でも、
+    /// セリフごとにキャストやパラメータを設定し直す必要がありません。
+    ///
+    /// 所要時間の記録（`metrics`）とオブザーバーは元のインスタンスと共有します。
+    /// 厳格モード、上書きの動作、キャストごとのパラメータ、代わりのキャスト、相対パスを解決するディレクトリ、後処理、履歴の設定は、
+    /// 作成した時点の値をコピーします。キャストとパラメータは Talker の既定の値です。
+    ///
+    /// ```no_run
+    /// use cevio::CeVIO;
+    /// let cevio = CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    ///
+    /// let chifuyu = cevio.new_talker().unwrap();
+    /// chifuyu.set_cast("花隈千冬").unwrap();
+    /// let sato = cevio.new_talker().unwrap();
+    /// sato.set_cast("さとうささら").unwrap();
+    ///
+    /// chifuyu.speak("こんにちは。").unwrap().wait().unwrap();
+    /// sato.speak("こんにちは。").unwrap().wait().unwrap();
+    /// ```
+    pub fn new_talker(&self) -> error::Result<Self> {
+        let init = Initialize::new().map_err(error::CeVIOError::ComInit)?;
+        Ok(Self {
+            host: self.host,
+            talker: self
+                .talker
+                .new_sibling(self.host.talker_prog_id())
+                .map_err(|e| error::CeVIOError::ObjectCreation(e.into()))?,
+            controller: self.controller.clone(),
+            latencies: self.latencies.clone(),
+            observers: self.observers.clone(),
+            last_speech: Default::default(),
+            strict: self.strict.clone(),
+            host_started: self.host_started.clone(),
+            overwrite: self.overwrite.clone(),
+            cast_profiles: self.cast_profiles.clone(),
+            cast_cache: self.cast_cache.clone(),
+            fallback_casts: self.fallback_casts.clone(),
+            base_dir: self.base_dir.clone(),
+            written: Default::default(),
+            usage: Default::default(),
+            output_processors: self.output_processors.clone(),
+            #[cfg(feature = "history")]
+            history: self.history.clone(),
+            #[cfg(feature = "history")]
+            requested: Default::default(),
+            _init: init,
+        })
+    }
+
+    /// 操作対象の製品を取得します。
+    pub fn host(&self) -> HostKind {
+        self.host
+    }
+
+    /// 厳格モードを設定します。既定では有効です。
+    ///
+    /// 有効な場合、CeVIO が失敗を `false` で返したとき（`OutputWaveToFile` や再生の失敗）に `CeVIOError::OperationFailed` を返します。
+    /// 無効な場合は `false` を無視し、成功として扱います。
+    pub fn set_strict(&self, strict: bool) {
+        self.strict.set(strict);
+    }
+
+    /// 厳格モードが有効かどうかを取得します。
+    pub fn is_strict(&self) -> bool {
+        self.strict.get()
+    }
+
+    /// 出力先のファイルが既にある場合の動作を設定します。既定では上書きします。
+    ///
+    /// `output_wave_to_file` と、それを使うすべての API に適用されます。
+    pub fn set_overwrite_policy(&self, policy: overwrite::OverwritePolicy) {
+        self.overwrite.set(policy);
+    }
+
+    /// 出力先のファイルが既にある場合の動作を取得します。
+    pub fn overwrite_policy(&self) -> overwrite::OverwritePolicy {
+        self.overwrite.get()
+    }
+
+    /// 出力先の相対パスを解決するディレクトリを設定します。`None` の場合はカレントディレクトリから解決します。（既定）
+    ///
+    /// 設定すると、`output_wave_to_file` などに渡した相対パスはこのディレクトリから解決し、
+    /// `..` などでこのディレクトリの外を指す場合は `CeVIOError::InvalidInput` を返します。
+    /// 台本や設定ファイルに、環境によらない相対パスを書けるようにするためのものです。
+    ///
+    /// `dir` が相対パスの場合は、カレントディレクトリから解決した絶対パスを設定します。
+    pub fn set_base_dir(&self, dir: Option<&Path>) -> error::Result<()> {
+        let dir = dir.map(fs_util::absolute).transpose()?;
+        *self.base_dir.borrow_mut() = dir;
+        Ok(())
+    }
+
+    /// 出力先の相対パスを解決するディレクトリを取得します。
+    pub fn base_dir(&self) -> Option<std::path::PathBuf> {
+        self.base_dir.borrow().clone()
+    }
+
+    /// 出力先のパスを、`set_base_dir` の設定に従って絶対パスにします。
+    pub fn resolve_output_path(&self, path: impl AsRef<Path>) -> error::Result<std::path::PathBuf> {
+        fs_util::resolve_in(self.base_dir.borrow().as_deref(), path.as_ref())
+    }
+
+    /// 操作ごとの所要時間のヒストグラムを取得します。
+    ///
+    /// キーは COM のメソッド名（`Speak`、`OutputWaveToFile` など）と、プロパティの取得・設定（`get_Cast`、`put_Volume` など）です。
+    ///
+    /// 備考：
+    ///
+    /// 　`Speak` は再生の開始までの時間です。再生の終了までの時間は含みません。
+    pub fn metrics(&self) -> std::collections::BTreeMap<String, metrics::Histogram> {
+        self.latencies.snapshot()
+    }
+
+    /// `metrics` で取得する所要時間の記録を消去します。
+    pub fn reset_metrics(&self) {
+        self.latencies.reset()
+    }
+
+    /// 書き込んだキャスト・パラメータ・感情パラメータの記録を消去します。
+    ///
+    /// 備考：
+    ///
+    /// 　setter（`set_volume` など）は、前回書き込んだ値と同じ値の場合は CeVIO を呼び出しません。
+    /// 　CeVIO の画面や他のアプリでパラメータを変更した場合は、このメソッドを呼んでから設定してください。
+    /// 　`start_host`、`close_host` を呼ぶと自動で消去されます。
+    pub fn forget_written_values(&self) {
+        self.written.borrow_mut().clear();
+    }
+
+    /// 整数のプロパティを読み込み、値を記録する
+    fn get_param(&self, prop: &'static str) -> error::Result<i32> {
+        let value = self.talker.property(prop)?;
+        self.written.borrow_mut().set_param(prop, value);
+        Ok(value)
+    }
+
+    /// 整数のプロパティを書き込む。記録した値と同じなら何もしない
+    fn set_param(&self, prop: &'static str, value: i32) -> error::Result<()> {
+        #[cfg(feature = "history")]
+        self.requested.borrow_mut().set_param(prop, value);
+        if self.written.borrow().param(prop) == Some(value) {
+            return Ok(());
+        }
+        let result = self
+            .talker
+            .set_property(prop, None, VARIANT::from_i32(value));
+        let mut written = self.written.borrow_mut();
+        match result {
+            Ok(()) => written.set_param(prop, value),
+            // 書き込めたかどうか分からないため忘れる
+            Err(_) => written.forget_param(prop),
+        }
+        result
+    }
+
     /// 【CeVIO Creative Studio】を起動します。起動済みなら何もしません。
     ///
     /// 引数：
@@ -97,23 +415,32 @@ impl CeVIO {
     ///
     /// 戻り値：
     ///
-    /// 　 0：成功。起動済みの場合も含みます。
-    ///
-    /// 　-1：インストール状態が不明。
-    ///
-    /// 　-2：実行ファイルが見つからない。
-    ///
-    /// 　-3：プロセスの起動に失敗。
-    ///
-    /// 　-4：アプリケーション起動後、エラーにより終了。
-    pub fn start_host(&self, no_wait: bool) -> error::Result<i32> {
-        self.controller
-            .invoke_method("StartHost", vec![VARIANT::from_bool(no_wait)])
-            .with_context(|| make_error_message("invoke_method", "start_host"))
-            .map_err(error::CeVIOError)?
-            .to_i32()
-            .with_context(|| make_error_message("to_i32", "start_host"))
-            .map_err(error::CeVIOError)
+    /// 　成功した場合（起動済みの場合も含みます）は `Ok(())`。
+    ///
+    /// 　起動に失敗した場合は `CeVIOError::HostStart` で、`host_start_error` で理由（`error::HostStartError`）を取得できます。
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(host = ?self.host, no_wait), err)
+    )]
+    pub fn start_host(&self, no_wait: bool) -> error::Result<()> {
+        self.forget_written_values();
+        // 一度起動した後に終了していた場合は、起動し直したとして記録する
+        let restarting = self.host_started.get() && !self.get_is_host_started().unwrap_or(false);
+        let code = self
+            .controller
+            .call("StartHost", [VARIANT::from_bool(no_wait)])?;
+        match error::HostStartError::from_code(code) {
+            None => {
+                if restarting {
+                    metrics::global().record_host_restart();
+                }
+                self.host_started.set(true);
+                Ok(())
+            }
+            Some(e) => Err(error::CeVIOError::HostStart(
+                error::Report::new(e).context("Failed to start host"),
+            )),
+        }
     }
 
     /// 【CeVIO Creative Studio】に終了を要求します。
@@ -123,159 +450,206 @@ impl CeVIO {
     /// 　mode - 処理モード。
     ///
     /// 　 0：【CeVIO AI】が編集中の場合、保存や終了キャンセルが可能。
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(host = ?self.host, mode), err)
+    )]
     pub fn close_host(&self, mode: i32) -> error::Result<()> {
+        self.forget_written_values();
         self.controller
-            .invoke_method("CloseHost", vec![VARIANT::from_i32(mode)])
-            .with_context(|| make_error_message("invoke_method", "close_host"))
-            .map_err(error::CeVIOError)?;
+            .invoke_method("CloseHost", [VARIANT::from_i32(mode)])?;
         Ok(())
     }
 
+    /// 製品の種類とバージョンを取得します。
+    ///
+    /// バージョンを比較して、製品のバージョンによって使える機能を切り替えられます。
+    ///
+    /// ```no_run
+    /// let cevio = cevio::CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    /// let info = cevio.host_info().unwrap();
+    /// if info.at_least(8, 0, 0) {
+    ///     println!("{}", info.version);
+    /// }
+    /// ```
+    pub fn host_info(&self) -> error::Result<HostInfo> {
+        HostInfo::parse(
+            self.host,
+            &self.get_host_version()?,
+            &self.get_interface_version()?,
+        )
+    }
+
+    /// 使える機能を、製品の COM コンポーネントに調べて取得します。
+    ///
+    /// 機能ごとのメンバー（`Components` など）が Talker にあるかどうかを `GetIDsOfNames` で調べます。
+    /// 英語のキャストは、利用可能なキャストに英語のキャスト（`cast::Language::guess`）があるかどうかで調べます。
+    ///
+    /// ```no_run
+    /// let cevio = cevio::CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    /// if cevio.capabilities().unwrap().supports_components {
+    ///     println!("{:?}", cevio.get_components().unwrap());
+    /// }
+    /// ```
+    pub fn capabilities(&self) -> error::Result<Capabilities> {
+        let has = |name: &str| {
+            self.talker.has_member(name).map_err(|e| {
+                error::CeVIOError::from(
+                    error::Report::new(e).context(format!("Failed to look up `{name}`")),
+                )
+            })
+        };
+        Ok(Capabilities {
+            supports_components: has("Components")?,
+            supports_phonemes: has("GetPhonemes")?,
+            supports_text_duration: has("GetTextDuration")?,
+            supports_english_casts: self
+                .get_available_casts()?
+                .iter()
+                .any(|cast| cast::Language::guess(cast) == cast::Language::English),
+        })
+    }
+
     /// 【CeVIO Creative Studio】のバージョンを取得します。
+    ///
+    /// バージョンを比較する場合は `host_info` を使用してください。
     pub fn get_host_version(&self) -> error::Result<String> {
-        self.controller
-            .get_property("HostVersion", None)
-            .with_context(|| make_error_message("get_property", "get_host_version"))
-            .map_err(error::CeVIOError)?
-            .to_string()
-            .with_context(|| make_error_message("to_string", "get_host_version"))
-            .map_err(error::CeVIOError)
+        self.controller.property("HostVersion")
+    }
+
+    /// `HostVersion` を読み込んで、製品が応答するかどうかを確認します。
+    ///
+    /// 応答までに `timeout` 以上かかった場合は `HostHealth::Slow` です。
+    ///
+    /// 備考：
+    ///
+    /// 　同じスレッドでは COM の呼び出しを途中で打ち切れないため、応答するまで戻りません。
+    /// 　期限で打ち切る場合は `actor::Handle::ping` を使ってください。
+    pub fn ping(&self, timeout: std::time::Duration) -> HostHealth {
+        let start = std::time::Instant::now();
+        let result = self.get_host_version();
+        let latency = start.elapsed();
+        match result {
+            Ok(version) if version.is_empty() => HostHealth::Dead {
+                reason: "Host is not started".to_string(),
+            },
+            Ok(_) if latency >= timeout => HostHealth::Slow,
+            Ok(_) => HostHealth::Healthy { latency },
+            Err(e) => HostHealth::Dead {
+                reason: e.to_string(),
+            },
+        }
     }
 
     /// このライブラリのバージョンを取得します。
     pub fn get_interface_version(&self) -> error::Result<String> {
-        self.controller
-            .get_property("InterfaceVersion", None)
-            .with_context(|| make_error_message("get_property", "get_interface_version"))
-            .map_err(error::CeVIOError)?
-            .to_string()
-            .with_context(|| make_error_message("to_string", "get_interface_version"))
-            .map_err(error::CeVIOError)
+        self.controller.property("InterfaceVersion")
     }
 
     /// 【CeVIO Creative Studio】にアクセス可能かどうか取得します。
     pub fn get_is_host_started(&self) -> error::Result<bool> {
-        self.controller
-            .get_property("InterfaceVersion", None)
-            .with_context(|| make_error_message("get_property", "get_is_host_started"))
-            .map_err(error::CeVIOError)?
-            .to_bool()
-            .with_context(|| make_error_message("to_bool", "get_is_host_started"))
-            .map_err(error::CeVIOError)
+        self.controller.property("IsHostStarted")
     }
 
     /// 音の大きさ（0～100）を取得します。
     pub fn get_volume(&self) -> error::Result<i32> {
-        self.talker
-            .get_property("Volume", None)
-            .with_context(|| make_error_message("get_property", "get_volume"))
-            .map_err(error::CeVIOError)?
-            .to_i32()
-            .with_context(|| make_error_message("to_i32", "get_volume"))
-            .map_err(error::CeVIOError)
+        self.get_param("Volume")
     }
 
     /// 音の大きさ（0～100）を設定します。
     pub fn set_volume(&self, volume: i32) -> error::Result<()> {
-        self.talker
-            .set_property("Volume", None, VARIANT::from_i32(volume))
-            .with_context(|| make_error_message("set_property", "set_volume"))
-            .map_err(error::CeVIOError)
+        self.set_param("Volume", volume)
     }
 
     /// 話す速さ（0～100）を取得します。
     pub fn get_speed(&self) -> error::Result<i32> {
-        self.talker
-            .get_property("Speed", None)
-            .with_context(|| make_error_message("get_property", "get_speed"))
-            .map_err(error::CeVIOError)?
-            .to_i32()
-            .with_context(|| make_error_message("to_i32", "get_speed"))
-            .map_err(error::CeVIOError)
+        self.get_param("Speed")
     }
 
     /// 話す速さ（0～100）を設定します。
     pub fn set_speed(&self, speed: i32) -> error::Result<()> {
-        self.talker
-            .set_property("Speed", None, VARIANT::from_i32(speed))
-            .with_context(|| make_error_message("set_property", "set_speed"))
-            .map_err(error::CeVIOError)
+        self.set_param("Speed", speed)
     }
 
     /// 音の高さ（0～100）を取得します。
     pub fn get_tone(&self) -> error::Result<i32> {
-        self.talker
-            .get_property("Tone", None)
-            .with_context(|| make_error_message("get_property", "get_tone"))
-            .map_err(error::CeVIOError)?
-            .to_i32()
-            .with_context(|| make_error_message("to_i32", "get_tone"))
-            .map_err(error::CeVIOError)
+        self.get_param("Tone")
     }
 
     /// 音の高さ（0～100）を設定します。
     pub fn set_tone(&self, tone: i32) -> error::Result<()> {
-        self.talker
-            .set_property("Tone", None, VARIANT::from_i32(tone))
-            .with_context(|| make_error_message("set_property", "set_tone"))
-            .map_err(error::CeVIOError)
+        self.set_param("Tone", tone)
     }
 
     /// 抑揚（0～100）を取得します。
     pub fn get_tone_scale(&self) -> error::Result<i32> {
-        self.talker
-            .get_property("ToneScale", None)
-            .with_context(|| make_error_message("get_property", "get_tone_scale"))
-            .map_err(error::CeVIOError)?
-            .to_i32()
-            .with_context(|| make_error_message("to_i32", "get_tone_scale"))
-            .map_err(error::CeVIOError)
+        self.get_param("ToneScale")
     }
 
     /// 抑揚（0～100）を設定します。
     pub fn set_tone_scale(&self, tone_scale: i32) -> error::Result<()> {
-        self.talker
-            .set_property("ToneScale", None, VARIANT::from_i32(tone_scale))
-            .with_context(|| make_error_message("set_property", "set_tone_scale"))
-            .map_err(error::CeVIOError)
+        self.set_param("ToneScale", tone_scale)
     }
 
     /// 声質（0～100）を取得します。
     pub fn get_alpha(&self) -> error::Result<i32> {
-        self.talker
-            .get_property("Alpha", None)
-            .with_context(|| make_error_message("get_property", "get_alpha"))
-            .map_err(error::CeVIOError)?
-            .to_i32()
-            .with_context(|| make_error_message("to_i32", "get_alpha"))
-            .map_err(error::CeVIOError)
+        self.get_param("Alpha")
     }
 
     /// 声質（0～100）を設定します。
     pub fn set_alpha(&self, alpha: i32) -> error::Result<()> {
-        self.talker
-            .set_property("Alpha", None, VARIANT::from_i32(alpha))
-            .with_context(|| make_error_message("set_property", "set_alpha"))
-            .map_err(error::CeVIOError)
+        self.set_param("Alpha", alpha)
     }
 
     /// キャストを取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　書き込んだキャストと異なる場合（CeVIO の画面で変更した場合など）は、書き込んだ値の記録を消去します。
     pub fn get_cast(&self) -> error::Result<String> {
-        self.talker
-            .get_property("Cast", None)
-            .with_context(|| make_error_message("get_property", "get_cast"))
-            .map_err(error::CeVIOError)?
-            .to_string()
-            .with_context(|| make_error_message("to_string", "get_cast"))
-            .map_err(error::CeVIOError)
+        let cast: String = self.talker.property("Cast")?;
+        self.written.borrow_mut().set_cast(&cast);
+        Ok(cast)
     }
 
     /// キャストを設定します。
-    pub fn set_cast(&self, cast: &str) -> error::Result<()> {
+    ///
+    /// キャスト名の代わりに `casts::Cast` も指定できます。
+    /// キャストを切り替えた場合は、`set_cast_profile` で登録したそのキャストの既定のパラメータを適用します。
+    ///
+    /// キャストがインストールされていない場合は、`set_fallback_casts` で指定した代替キャストを選びます。（`select_cast` を参照。）
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(cast), err)
+    )]
+    pub fn set_cast(&self, cast: impl AsRef<str>) -> error::Result<()> {
+        self.select_cast(cast.as_ref()).map(drop)
+    }
+
+    /// 代替キャストを使わずにキャストを設定する
+    fn set_cast_exact(&self, cast: &str) -> error::Result<()> {
+        #[cfg(feature = "history")]
+        self.requested.borrow_mut().set_cast(cast);
+        if self.written.borrow().is_cast(cast) {
+            return Ok(());
+        }
         self.talker
             .set_property("Cast", None, VARIANT::from_str(cast))
-            .with_context(|| make_error_message("set_property", "set_cast"))
-            .map_err(error::CeVIOError)
+            // 存在しないキャストを設定すると COM の呼び出しが失敗する
+            .map_err(|e| {
+                self.written.borrow_mut().clear();
+                match e {
+                    error::CeVIOError::Com(e) => error::CeVIOError::InvalidCast(e),
+                    e => e,
+                }
+            })?;
+        self.written.borrow_mut().set_cast(cast);
+        match self.cast_profile(cast) {
+            Some(profile) => self.apply_params(&profile),
+            None => Ok(()),
+        }
     }
 
     /// 利用可能なキャスト名を取得します。
@@ -284,19 +658,35 @@ impl CeVIO {
     ///
     /// 　キャストの取り揃えは、インストールされている音源によります。
     ///
-    /// 注意点：
-    ///
-    /// 　型は、Visual C++環境でスマートポインタを利用する場合、下記に置き換えられます。
-    ///
-    /// 　IStringArray2Ptr
-    pub fn get_available_casts(&self) -> error::Result<String> {
-        self.talker
-            .get_property("AvailableCasts", None)
-            .with_context(|| make_error_message("get_property", "get_available_casts"))
-            .map_err(error::CeVIOError)?
-            .to_string()
-            .with_context(|| make_error_message("to_string", "get_available_casts"))
-            .map_err(error::CeVIOError)
+    /// 　最初に取得した一覧を記録し、以降は CeVIO を呼び出しません。
+    /// 　音源を追加・削除した場合は `refresh_casts` を呼んでください。（空の一覧は記録しません。）
+    pub fn get_available_casts(&self) -> error::Result<Vec<String>> {
+        if let Some(casts) = self.cast_cache.borrow().casts() {
+            return Ok(casts.to_vec());
+        }
+        let casts: ComObject = self.talker.property("AvailableCasts")?;
+        let length = casts.property("Length")?;
+        let casts = (0..length)
+            .map(|i| casts.call("At", [VARIANT::from_i32(i)]))
+            .collect::<error::Result<Vec<String>>>()?;
+        if !casts.is_empty() {
+            self.cast_cache.borrow_mut().set_casts(casts.clone());
+        }
+        Ok(casts)
+    }
+
+    /// 記録したキャストの一覧と感情パラメータの名前を消去し、キャストの一覧を取得し直します。
+    ///
+    /// ```no_run
+    /// let cevio = cevio::CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    /// let casts = cevio.get_available_casts().unwrap(); // CeVIO に問い合わせる
+    /// let casts = cevio.get_available_casts().unwrap(); // 記録した一覧を返す
+    /// let casts = cevio.refresh_casts().unwrap(); // 音源を追加した後などに取得し直す
+    /// ```
+    pub fn refresh_casts(&self) -> error::Result<Vec<String>> {
+        self.cast_cache.borrow_mut().clear();
+        self.get_available_casts()
     }
 
     /// 指定したセリフの再生を開始します。
@@ -313,19 +703,54 @@ impl CeVIO {
     ///
     /// 　再生終了を待たずに処理が戻ります。
     ///
-    /// 　再生終了を待つには戻り値（ISpeakingState2）のWaitを呼び出します。
-    ///
-    /// 注意点：
+    /// 　再生終了を待つには戻り値（SpeakingState）のwaitを呼び出します。
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(text_len = text.chars().count()), err)
+    )]
+    pub fn speak(&self, text: &str) -> error::Result<SpeakingState> {
+        let event = observer::SpeechEvent {
+            kind: observer::SpeechKind::Speak,
+            text: text.to_string(),
+        };
+        self.observers.start(&event);
+        #[cfg(feature = "history")]
+        let started = (std::time::SystemTime::now(), std::time::Instant::now());
+        let result = self
+            .talker
+            .call::<ComObject, 1>("Speak", [VARIANT::from_str(text)]);
+        #[cfg(feature = "history")]
+        self.record_history(history::Entry {
+            started: started.0,
+            kind: event.kind,
+            text,
+            output: None,
+            elapsed: started.1.elapsed(),
+            audio: None,
+            error: result.as_ref().err(),
+        });
+        match result {
+            Ok(state) => {
+                // 呼び出しを増やさないように、音声の長さは数えない
+                self.record_usage(text, std::time::Duration::ZERO);
+                *self.last_speech.borrow_mut() = Some(state.clone());
+                Ok(SpeakingState::new(state, self.is_strict())
+                    .with_pending(observer::Pending::new(&self.observers, event)))
+            }
+            Err(e) => {
+                self.observers.error(&event, &e);
+                Err(e)
+            }
+        }
+    }
+
+    /// 再生を停止します。
     ///
-    /// 　型は、Visual C++環境でスマートポインタを利用する場合、下記に置き換えられます。
+    /// 戻り値：
     ///
-    /// 　ISpeakingState2Ptr
-    pub fn speak(&self, text: &str) -> error::Result<()> {
-        self.talker
-            .invoke_method("Speak", vec![VARIANT::from_str(text)])
-            .with_context(|| make_error_message("invoke_method", "speak"))
-            .map_err(error::CeVIOError)?;
-        Ok(())
+    /// 　成功した場合は true。それ以外の場合は false。
+    pub fn stop(&self) -> error::Result<bool> {
+        self.talker.call("Stop", [])
     }
 
     /// 指定したセリフの音素単位のデータを取得します。
@@ -341,18 +766,49 @@ impl CeVIO {
     /// 備考：
     ///
     /// 　リップシンク等に利用できます。
-    ///
-    /// 注意点：
-    ///
-    /// 　型は、Visual C++環境でスマートポインタを利用する場合、下記に置き換えられます。
-    ///
-    /// 　IPhonemeDataArray2Ptr
-    pub fn get_phonemes(&self, text: &str) -> error::Result<()> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(text_len = text.chars().count()), err)
+    )]
+    pub fn get_phonemes(&self, text: &str) -> error::Result<Vec<PhonemeData>> {
+        let phonemes: ComObject = self.talker.call("GetPhonemes", [VARIANT::from_str(text)])?;
+        let length = phonemes.property("Length")?;
+        (0..length)
+            .map(|i| {
+                let phoneme: ComObject = phonemes.call("At", [VARIANT::from_i32(i)])?;
+                Ok(PhonemeData {
+                    phoneme: phoneme.property("Phoneme")?,
+                    start_time: phoneme.property("StartTime")?,
+                    end_time: phoneme.property("EndTime")?,
+                })
+            })
+            .collect()
+    }
+
+    /// 指定したセリフの長さを取得します。単位は秒。
+    pub fn get_text_duration(&self, text: &str) -> error::Result<f64> {
         self.talker
-            .invoke_method("GetPhonemes", vec![VARIANT::from_str(text)])
-            .with_context(|| make_error_message("invoke_method", "get_phonemes"))
-            .map_err(error::CeVIOError)?;
-        Ok(())
+            .call("GetTextDuration", [VARIANT::from_str(text)])
+    }
+
+    /// 指定したセリフの長さを推定します。
+    ///
+    /// 音素データを取得できる場合は最後の音素の終了時間、それ以外は `get_text_duration` を使います。
+    /// どちらも取得できない場合は `None` です。
+    pub fn estimate_duration(&self, text: &str) -> Option<std::time::Duration> {
+        let seconds = match self.get_phonemes(text) {
+            Ok(phonemes) if !phonemes.is_empty() => phonemes.last().map(|p| p.end_time),
+            _ => self.get_text_duration(text).ok(),
+        }?;
+        std::time::Duration::try_from_secs_f64(seconds).ok()
+    }
+
+    /// 指定したセリフの長さを推定してから再生を開始します。
+    ///
+    /// 戻り値の `SpeakingState::progress` で再生の進み具合を取得できます。それ以外は `speak` と同じです。
+    pub fn speak_with_progress(&self, text: &str) -> error::Result<SpeakingState> {
+        let duration = self.estimate_duration(text);
+        Ok(self.speak(text)?.with_duration(duration))
     }
 
     /// 指定したセリフをWAVファイルとして出力します。
@@ -361,23 +817,166 @@ impl CeVIO {
     ///
     /// 　text - セリフ。
     ///
-    /// 　path - 出力先パス。
+    /// 　path - 出力先パス。相対パスはカレントディレクトリ（`set_base_dir` を設定した場合はそのディレクトリ）から解決します。
     ///
     /// 戻り値：
     ///
-    /// 　成功した場合はtrue。それ以外の場合はfalse。
+    /// 　成功した場合は `Ok(())`。
+    ///
+    /// 　CeVIO が失敗を返した場合は、厳格モード（`set_strict`）では `CeVIOError::OperationFailed`、それ以外では `Ok(())`。
     ///
     /// 備考：
     ///
     /// 　出力形式はサンプリングレート48kHz, ビットレート16bit, モノラルです。
-    pub fn output_wave_to_file(&self, text: &str, path: &str) -> error::Result<()> {
+    ///
+    /// 　CeVIO には絶対パスを渡し、260 文字を超える場合は `\\?\` を付けて渡します。
+    ///
+    /// 　ファイルが既にある場合は `set_overwrite_policy` の設定に従います。実際に出力したパスは `output_wave` で取得できます。
+    ///
+    /// 　`add_output_processor` で登録した後処理を、出力した後に適用します。
+    pub fn output_wave_to_file(&self, text: &str, path: impl AsRef<Path>) -> error::Result<()> {
+        self.output_wave(text, path).map(drop)
+    }
+
+    /// 指定したセリフを WAV ファイルとして出力し、実際に出力したパスを返します。
+    ///
+    /// ファイルが既にある場合は `set_overwrite_policy` の設定に従い、`Skip` で出力しなかった場合は `None` を返します。
+    /// それ以外は `output_wave_to_file` と同じです。
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(text_len = text.chars().count(), path), err)
+    )]
+    pub fn output_wave(
+        &self,
+        text: &str,
+        path: impl AsRef<Path>,
+    ) -> error::Result<Option<std::path::PathBuf>> {
+        self.output_wave_with(text, path.as_ref(), true)
+    }
+
+    /// 後処理を適用せずに WAV ファイルとして出力する。後処理は呼び出し側が別のスレッドで行う
+    pub(crate) fn output_raw_wave(
+        &self,
+        text: &str,
+        path: &Path,
+    ) -> error::Result<Option<std::path::PathBuf>> {
+        self.output_wave_with(text, path, false)
+    }
+
+    fn output_wave_with(
+        &self,
+        text: &str,
+        path: &Path,
+        process: bool,
+    ) -> error::Result<Option<std::path::PathBuf>> {
+        let path = self.resolve_output_path(path)?;
+        let Some(output) = self.overwrite_policy().resolve(&path)? else {
+            return Ok(None);
+        };
+        let path = fs_util::host_path(&output)?;
+        let event = observer::SpeechEvent {
+            kind: observer::SpeechKind::Wave,
+            text: text.to_string(),
+        };
+        self.observers.start(&event);
+        #[cfg(feature = "history")]
+        let started = (std::time::SystemTime::now(), std::time::Instant::now());
+        let result = self
+            .talker
+            .call(
+                "OutputWaveToFile",
+                [VARIANT::from_str(text), VARIANT::from_str(&path)],
+            )
+            .and_then(|succeeded| {
+                self.check_succeeded(succeeded, || {
+                    format!("CeVIO failed to output `{path}` in fn `output_wave_to_file`")
+                })
+            })
+            .and_then(|()| match process {
+                true => self.process_output(text, &output),
+                false => Ok(()),
+            });
+        self.observers.finish(&event, &result);
+        let audio = result
+            .as_ref()
+            .ok()
+            .and_then(|()| wav::wav_duration(&output).ok());
+        if result.is_ok() {
+            self.record_usage(text, audio.unwrap_or_default());
+        }
+        #[cfg(feature = "history")]
+        self.record_history(history::Entry {
+            started: started.0,
+            kind: event.kind,
+            text,
+            output: Some(&output),
+            elapsed: started.1.elapsed(),
+            audio,
+            error: result.as_ref().err(),
+        });
+        result.map(|()| Some(output))
+    }
+
+    /// 厳格モードで `succeeded` が `false` の場合は失敗する
+    fn check_succeeded(
+        &self,
+        succeeded: bool,
+        message: impl FnOnce() -> String,
+    ) -> error::Result<()> {
+        match succeeded || !self.is_strict() {
+            true => Ok(()),
+            false => Err(error::CeVIOError::OperationFailed(error::Report::msg(
+                message(),
+            ))),
+        }
+    }
+
+    /// 指定したセリフを WAV 形式のバイト列として取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　一時ディレクトリに出力したファイル（`temp::TempWav`）を読み込み、削除します。
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(text_len = text.chars().count()), err)
+    )]
+    pub fn output_wave_to_vec(&self, text: &str) -> error::Result<Vec<u8>> {
+        temp::TempWav::synthesize(self, text)?.into_bytes()
+    }
+
+    /// 現在のキャストで短いセリフを一時ファイルに出力して捨て、かかった時間を返します。
+    ///
+    /// キャストを選んだ後の最初の合成には数秒かかることがあるため、最初のセリフの前に呼んでおくと待ち時間を隠せます。
+    ///
+    /// 備考：
+    ///
+    /// 　オブザーバー（`on_speech_start` など）は呼び出さず、`set_overwrite_policy` と `set_base_dir` の設定も使いません。
+    ///
+    /// ```no_run
+    /// let cevio = cevio::CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    /// cevio.set_cast("花隈千冬").unwrap();
+    /// let elapsed = cevio.warm_up().unwrap();
+    /// println!("{elapsed:?}");
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn warm_up(&self) -> error::Result<std::time::Duration> {
+        let start = std::time::Instant::now();
+        let wav = temp::TempWav::new();
+        let path = fs_util::host_path(wav.path())?;
         self.talker
-            .invoke_method(
+            .call(
                 "OutputWaveToFile",
-                vec![VARIANT::from_str(text), VARIANT::from_str(path)],
+                [VARIANT::from_str(WARM_UP_TEXT), VARIANT::from_str(&path)],
             )
-            .with_context(|| make_error_message("invoke_method", "speak"))
-            .map_err(error::CeVIOError)?;
-        Ok(())
+            .and_then(|succeeded| {
+                self.check_succeeded(succeeded, || {
+                    format!("CeVIO failed to output `{path}` in fn `warm_up`")
+                })
+            })?;
+        Ok(start.elapsed())
     }
 }
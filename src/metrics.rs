@@ -0,0 +1,431 @@
+//! Prometheus 形式のメトリクス
+//!
+//! プロセス全体で 1 つの値を `global()` で共有します。
+//! `server` フィーチャーの HTTP サーバーでは `GET /metrics` で取得できます。
+//!
+//! | 名前                                   | 種類      | 内容                                         |
+//! | -------------------------------------- | --------- | -------------------------------------------- |
+//! | `cevio_requests_total`                 | counter   | リクエスト数（`route`, `status` ごと）       |
+//! | `cevio_synthesis_duration_seconds`     | histogram | 合成・再生にかかった時間                     |
+//! | `cevio_host_restarts_total`            | counter   | CeVIO を起動し直した回数                     |
+//! | `cevio_cache_hits_total`               | counter   | キャッシュを再利用した回数                   |
+//! | `cevio_cache_misses_total`             | counter   | キャッシュがなく合成した回数                 |
+//! | `cevio_queue_depth`                    | gauge     | 再生待ちのセリフの数（HTTP サーバーのみ）    |
+//! | `cevio_syntheses_total`                | counter   | 合成した回数（`cast` ごと）                  |
+//! | `cevio_characters_total`               | counter   | 合成した文字数（`cast` ごと）                |
+//! | `cevio_audio_seconds_total`            | counter   | 出力した WAV の長さ（`cast` ごと）           |
+//!
+//! これとは別に、`CeVIO` のインスタンスごとに COM の呼び出しにかかった時間を記録し、`CeVIO::metrics` で取得できます。
+//! 合成した回数・文字数・音声の長さは、インスタンスごとの値も `CeVIO::stats` で取得できます。
+//!
+//! ```no_run
+//! use cevio::CeVIO;
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//! cevio.output_wave_to_file("こんにちは。", r"E:\file.wav").unwrap();
+//!
+//! for (operation, histogram) in cevio.metrics() {
+//!     println!("{operation}: {} 回, 平均 {:?}", histogram.count(), histogram.mean());
+//! }
+//!
+//! let stats = cevio.stats();
+//! println!("{} 文字, {:?}", stats.total.characters, stats.total.audio);
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// `cevio_synthesis_duration_seconds` のバケットの上限。単位は秒
+const BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// `Histogram` のバケットの上限。単位は秒
+const LATENCY_BUCKETS: [f64; 14] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// メトリクスの値です。
+pub struct Metrics {
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    synthesis_buckets: [AtomicU64; BUCKETS.len()],
+    synthesis_count: AtomicU64,
+    /// 合計時間。単位はマイクロ秒
+    synthesis_sum: AtomicU64,
+    host_restarts: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    usage: Mutex<UsageStats>,
+}
+
+static METRICS: Metrics = Metrics {
+    requests: Mutex::new(BTreeMap::new()),
+    synthesis_buckets: [const { AtomicU64::new(0) }; BUCKETS.len()],
+    synthesis_count: AtomicU64::new(0),
+    synthesis_sum: AtomicU64::new(0),
+    host_restarts: AtomicU64::new(0),
+    cache_hits: AtomicU64::new(0),
+    cache_misses: AtomicU64::new(0),
+    usage: Mutex::new(UsageStats {
+        total: Usage {
+            syntheses: 0,
+            characters: 0,
+            audio: Duration::ZERO,
+        },
+        casts: BTreeMap::new(),
+    }),
+};
+
+/// プロセス全体で共有するメトリクスを取得します。
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    /// リクエストを記録します。
+    pub fn record_request(&self, route: &str, status: u16) {
+        *self
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((route.to_string(), status))
+            .or_default() += 1;
+    }
+
+    /// 合成・再生にかかった時間を記録します。
+    pub fn observe_synthesis(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, le) in self.synthesis_buckets.iter().zip(BUCKETS) {
+            if seconds <= le {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.synthesis_count.fetch_add(1, Ordering::Relaxed);
+        self.synthesis_sum
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// CeVIO を起動し直したことを記録します。`CeVIO::start_host` が、一度起動した後に終了した CeVIO を起動した場合に呼びます。
+    pub fn record_host_restart(&self) {
+        self.host_restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// キャッシュを再利用したかどうかを記録します。
+    pub fn record_cache(&self, hit: bool) {
+        match hit {
+            true => self.cache_hits.fetch_add(1, Ordering::Relaxed),
+            false => self.cache_misses.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// 合成した量を記録します。
+    pub fn record_usage(&self, cast: &str, characters: u64, audio: Duration) {
+        self.usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(cast, characters, audio);
+    }
+
+    /// プロセス全体で合成した量を取得します。
+    pub fn usage(&self) -> UsageStats {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Prometheus のテキスト形式に変換します。
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+
+        s.push_str("# HELP cevio_requests_total Number of requests.\n");
+        s.push_str("# TYPE cevio_requests_total counter\n");
+        for ((route, status), count) in self
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            let _ = writeln!(
+                s,
+                "cevio_requests_total{{route=\"{}\",status=\"{status}\"}} {count}",
+                escape_label(route)
+            );
+        }
+
+        s.push_str(
+            "# HELP cevio_synthesis_duration_seconds Time spent on synthesis or playback.\n",
+        );
+        s.push_str("# TYPE cevio_synthesis_duration_seconds histogram\n");
+        for (bucket, le) in self.synthesis_buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(
+                s,
+                "cevio_synthesis_duration_seconds_bucket{{le=\"{le}\"}} {}",
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.synthesis_count.load(Ordering::Relaxed);
+        let _ = writeln!(
+            s,
+            "cevio_synthesis_duration_seconds_bucket{{le=\"+Inf\"}} {count}"
+        );
+        let _ = writeln!(
+            s,
+            "cevio_synthesis_duration_seconds_sum {}",
+            self.synthesis_sum.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(s, "cevio_synthesis_duration_seconds_count {count}");
+
+        for (name, help, value) in [
+            (
+                "cevio_host_restarts_total",
+                "Number of host restarts.",
+                &self.host_restarts,
+            ),
+            (
+                "cevio_cache_hits_total",
+                "Number of cache hits.",
+                &self.cache_hits,
+            ),
+            (
+                "cevio_cache_misses_total",
+                "Number of cache misses.",
+                &self.cache_misses,
+            ),
+        ] {
+            let _ = writeln!(s, "# HELP {name} {help}");
+            let _ = writeln!(s, "# TYPE {name} counter");
+            let _ = writeln!(s, "{name} {}", value.load(Ordering::Relaxed));
+        }
+
+        let usage = self.usage();
+        let per_cast = |value: fn(&Usage) -> String| {
+            usage
+                .casts
+                .iter()
+                .map(|(cast, usage)| (cast, value(usage)))
+                .collect::<Vec<_>>()
+        };
+        for (name, help, values) in [
+            (
+                "cevio_syntheses_total",
+                "Number of syntheses.",
+                per_cast(|u| u.syntheses.to_string()),
+            ),
+            (
+                "cevio_characters_total",
+                "Number of characters synthesized.",
+                per_cast(|u| u.characters.to_string()),
+            ),
+            (
+                "cevio_audio_seconds_total",
+                "Length of WAV output synthesized in seconds.",
+                per_cast(|u| u.audio.as_secs_f64().to_string()),
+            ),
+        ] {
+            let _ = writeln!(s, "# HELP {name} {help}");
+            let _ = writeln!(s, "# TYPE {name} counter");
+            for (cast, value) in values {
+                let _ = writeln!(s, "{name}{{cast=\"{}\"}} {value}", escape_label(cast));
+            }
+        }
+        s
+    }
+}
+
+/// 合成した量です。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// 合成した回数（`speak` は再生を始めた回数）
+    pub syntheses: u64,
+    /// 合成したセリフの文字数
+    pub characters: u64,
+    /// 合成した音声の長さ（WAV の出力のみ）
+    pub audio: Duration,
+}
+
+/// 合計とキャストごとの合成した量です。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageStats {
+    /// 合計
+    pub total: Usage,
+    /// キャストごとの値
+    pub casts: BTreeMap<String, Usage>,
+}
+
+impl UsageStats {
+    /// 合成した量を記録します。
+    pub fn record(&mut self, cast: &str, characters: u64, audio: Duration) {
+        let cast = match self.casts.get_mut(cast) {
+            Some(usage) => usage,
+            None => self.casts.entry(cast.to_string()).or_default(),
+        };
+        for usage in [&mut self.total, cast] {
+            usage.syntheses += 1;
+            usage.characters += characters;
+            usage.audio += audio;
+        }
+    }
+}
+
+/// 所要時間のヒストグラムです。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    /// 所要時間を記録します。
+    pub fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, le) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= le {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// 記録した回数を取得します。
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// 所要時間の合計を取得します。
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// 所要時間の最大値を取得します。
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// 所要時間の平均を取得します。記録がない場合は `None` です。
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).ok().filter(|&c| c > 0)?;
+        Some(self.sum / count)
+    }
+
+    /// 累積のバケットを `(上限の秒数, 上限以下だった回数)` の組で取得します。
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        LATENCY_BUCKETS
+            .into_iter()
+            .zip(self.buckets.iter().copied())
+    }
+
+    /// 割合 `q`（0.0〜1.0）の分位数を、バケットの上限で近似して取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　どのバケットにも収まらない場合は最大値を返します。記録がない場合は `None` です。
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        Some(
+            self.buckets()
+                .find(|&(_, count)| count >= rank)
+                .map_or(self.max, |(le, _)| {
+                    Duration::from_secs_f64(le).min(self.max)
+                }),
+        )
+    }
+}
+
+/// 操作ごとの所要時間です。
+///
+/// キーは COM のメソッド名（`Speak`、`OutputWaveToFile` など）と、プロパティの取得・設定（`get_Cast`、`put_Volume` など）です。
+#[derive(Debug, Default)]
+pub struct Latencies {
+    operations: Mutex<Operations>,
+}
+
+#[derive(Debug, Default)]
+struct Operations {
+    histograms: BTreeMap<String, Histogram>,
+    /// キーを組み立てるバッファ。記録済みの操作では確保し直さない
+    key: String,
+}
+
+impl Latencies {
+    /// `prefix` と `name` をつなげた操作の所要時間を記録します。
+    pub(crate) fn record(&self, prefix: &str, name: &str, duration: Duration) {
+        let mut operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+        let Operations { histograms, key } = &mut *operations;
+        key.clear();
+        key.push_str(prefix);
+        key.push_str(name);
+        match histograms.get_mut(key.as_str()) {
+            Some(histogram) => histogram.observe(duration),
+            None => histograms.entry(key.clone()).or_default().observe(duration),
+        }
+    }
+
+    /// 操作ごとのヒストグラムを取得します。
+    pub fn snapshot(&self) -> BTreeMap<String, Histogram> {
+        self.operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .histograms
+            .clone()
+    }
+
+    /// 記録を消去します。
+    pub fn reset(&self) {
+        self.operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .histograms
+            .clear();
+    }
+}
+
+impl crate::CeVIO {
+    /// このインスタンスで合成した回数・文字数・音声の長さを、合計とキャストごとに取得します。
+    ///
+    /// 音声の長さは WAV の出力で書き込んだファイルの長さです。`speak` は COM の呼び出しを増やさないように、回数と文字数だけを数えます。
+    /// プロセス全体の値は `metrics::global().usage()` で取得できます。
+    pub fn stats(&self) -> UsageStats {
+        self.usage.borrow().clone()
+    }
+
+    /// `stats` で取得する記録を消去します。プロセス全体の値は消去しません。
+    pub fn reset_stats(&self) {
+        *self.usage.borrow_mut() = UsageStats::default();
+    }
+
+    /// 合成した量を、このインスタンスとプロセス全体に記録する
+    pub(crate) fn record_usage(&self, text: &str, audio: Duration) {
+        let cast = match self.written.borrow().cast() {
+            Some(cast) => cast.to_string(),
+            None => self.get_cast().unwrap_or_default(),
+        };
+        let characters = text.chars().count() as u64;
+        self.usage.borrow_mut().record(&cast, characters, audio);
+        global().record_usage(&cast, characters, audio);
+    }
+}
+
+/// `f` にかかった時間を合成・再生の時間として記録する
+pub(crate) fn time_synthesis<R>(f: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = f();
+    global().observe_synthesis(start.elapsed());
+    result
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
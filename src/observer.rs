@@ -0,0 +1,198 @@
+//! 音声の合成を監視するオブザーバー
+//!
+//! `CeVIO::on_speech_start`、`CeVIO::on_speech_end`、`CeVIO::on_error` で登録した関数は、
+//! `speak`、`output_wave_to_file`、`output_wave_to_vec` のほか、それらを使う `Say`、`speak_lines`、HTTP サーバーなど、
+//! どの API から合成した場合も呼び出されます。ログ、字幕、アバターのアニメーションなどに使います。
+//!
+//! `actor::Handle` を使う場合は `Handle::call` の中で登録します。関数は専用スレッドで呼び出されます。
+//!
+//! `CeVIO::on_cast_fallback` で登録した関数は、指定したキャストの代わりに代替キャスト（`CeVIO::set_fallback_casts`）を選んだときに呼び出されます。
+//!
+//! `speak` の場合、再生の終了は `SpeakingState::wait`、`wait_timeout`、`is_completed` で完了を確認したときに通知します。
+//!
+//! ```no_run
+//! use cevio::CeVIO;
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let id = cevio.on_speech_start(|event| println!("開始: {}", event.text));
+//! cevio.on_speech_end(|event| println!("終了: {}", event.text));
+//! cevio.on_error(|event, e| eprintln!("失敗: {}: {e}", event.text));
+//!
+//! cevio.speak("こんにちは。").unwrap().wait().unwrap();
+//! cevio.remove_observer(id);
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use crate::{error, CeVIO};
+
+/// 合成の方法です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpeechKind {
+    /// 再生（`speak`）
+    Speak,
+    /// WAV の出力（`output_wave_to_file`、`output_wave_to_vec`）
+    Wave,
+}
+
+/// オブザーバーに渡す合成の情報です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpeechEvent {
+    /// 合成の方法
+    pub kind: SpeechKind,
+    /// セリフ
+    pub text: String,
+}
+
+/// 指定したキャストの代わりに選んだ代替キャストの情報です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CastFallback {
+    /// 指定したキャスト
+    pub requested: String,
+    /// 代わりに選んだキャスト
+    pub used: String,
+}
+
+/// 登録したオブザーバーの ID です。`CeVIO::remove_observer` で登録を解除できます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+type EventHook = Rc<dyn Fn(&SpeechEvent)>;
+type ErrorHook = Rc<dyn Fn(&SpeechEvent, &error::CeVIOError)>;
+type FallbackHook = Rc<dyn Fn(&CastFallback)>;
+
+/// 登録したオブザーバー
+#[derive(Default)]
+pub(crate) struct Observers {
+    next_id: Cell<u64>,
+    start: RefCell<Vec<(ObserverId, EventHook)>>,
+    end: RefCell<Vec<(ObserverId, EventHook)>>,
+    error: RefCell<Vec<(ObserverId, ErrorHook)>>,
+    fallback: RefCell<Vec<(ObserverId, FallbackHook)>>,
+}
+
+impl Observers {
+    fn next_id(&self) -> ObserverId {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        ObserverId(id)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.start.borrow().is_empty()
+            && self.end.borrow().is_empty()
+            && self.error.borrow().is_empty()
+    }
+
+    pub(crate) fn start(&self, event: &SpeechEvent) {
+        // オブザーバーの中で登録・解除できるよう、複製してから呼び出す
+        let hooks = self.start.borrow().clone();
+        hooks.iter().for_each(|(_, hook)| hook(event));
+    }
+
+    pub(crate) fn end(&self, event: &SpeechEvent) {
+        let hooks = self.end.borrow().clone();
+        hooks.iter().for_each(|(_, hook)| hook(event));
+    }
+
+    pub(crate) fn error(&self, event: &SpeechEvent, e: &error::CeVIOError) {
+        let hooks = self.error.borrow().clone();
+        hooks.iter().for_each(|(_, hook)| hook(event, e));
+    }
+
+    pub(crate) fn cast_fallback(&self, fallback: &CastFallback) {
+        let hooks = self.fallback.borrow().clone();
+        hooks.iter().for_each(|(_, hook)| hook(fallback));
+    }
+
+    /// 結果に応じて終了かエラーを通知する
+    pub(crate) fn finish<T>(&self, event: &SpeechEvent, result: &error::Result<T>) {
+        match result {
+            Ok(_) => self.end(event),
+            Err(e) => self.error(event, e),
+        }
+    }
+}
+
+/// `SpeakingState` が再生の終了を通知するためのもの
+pub(crate) struct Pending {
+    observers: Rc<Observers>,
+    event: SpeechEvent,
+    notified: Cell<bool>,
+}
+
+impl Pending {
+    /// オブザーバーが登録されている場合だけ作成する
+    pub(crate) fn new(observers: &Rc<Observers>, event: SpeechEvent) -> Option<Self> {
+        match observers.is_empty() {
+            true => None,
+            false => Some(Self {
+                observers: observers.clone(),
+                event,
+                notified: Cell::new(false),
+            }),
+        }
+    }
+
+    /// 1 回だけ終了かエラーを通知する
+    pub(crate) fn finish<T>(&self, result: &error::Result<T>) {
+        if !self.notified.replace(true) {
+            self.observers.finish(&self.event, result);
+        }
+    }
+}
+
+impl CeVIO {
+    /// 合成の開始時に呼び出す関数を登録します。
+    pub fn on_speech_start(&self, f: impl Fn(&SpeechEvent) + 'static) -> ObserverId {
+        let id = self.observers.next_id();
+        self.observers.start.borrow_mut().push((id, Rc::new(f)));
+        id
+    }
+
+    /// 合成の終了時（`speak` の場合は再生の終了を確認したとき）に呼び出す関数を登録します。
+    pub fn on_speech_end(&self, f: impl Fn(&SpeechEvent) + 'static) -> ObserverId {
+        let id = self.observers.next_id();
+        self.observers.end.borrow_mut().push((id, Rc::new(f)));
+        id
+    }
+
+    /// 合成に失敗したときに呼び出す関数を登録します。
+    pub fn on_error(&self, f: impl Fn(&SpeechEvent, &error::CeVIOError) + 'static) -> ObserverId {
+        let id = self.observers.next_id();
+        self.observers.error.borrow_mut().push((id, Rc::new(f)));
+        id
+    }
+
+    /// 指定したキャストの代わりに代替キャストを選んだときに呼び出す関数を登録します。
+    pub fn on_cast_fallback(&self, f: impl Fn(&CastFallback) + 'static) -> ObserverId {
+        let id = self.observers.next_id();
+        self.observers.fallback.borrow_mut().push((id, Rc::new(f)));
+        id
+    }
+
+    /// オブザーバーの登録を解除します。
+    ///
+    /// 戻り値：
+    ///
+    /// 　登録されていた場合は true。それ以外の場合は false。
+    pub fn remove_observer(&self, id: ObserverId) -> bool {
+        let observers = &self.observers;
+        let removed = retain(&observers.start, id) | retain(&observers.end, id);
+        let removed = removed | retain(&observers.error, id);
+        removed | retain(&observers.fallback, id)
+    }
+}
+
+/// `id` のオブザーバーを取り除き、取り除いたかどうかを返す
+fn retain<T>(hooks: &RefCell<Vec<(ObserverId, T)>>, id: ObserverId) -> bool {
+    let mut hooks = hooks.borrow_mut();
+    let len = hooks.len();
+    hooks.retain(|(hook_id, _)| *hook_id != id);
+    hooks.len() != len
+}
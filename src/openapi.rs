@@ -0,0 +1,301 @@
+//! HTTP サーバーの OpenAPI ドキュメント（`server` フィーチャー）
+//!
+//! HTTP サーバーでは `GET /openapi.json` で取得できます。クライアントの SDK の生成などに使えます。
+
+use serde_json::{json, Value};
+
+/// エラー時のレスポンス（本文は `error::ErrorBody`）
+fn error_response(description: &str) -> Value {
+    json_response(description, schema_ref("ErrorBody"))
+}
+
+fn client_parameter() -> Value {
+    json!({
+        "name": "client",
+        "in": "path",
+        "required": true,
+        "description": "クライアント名",
+        "schema": { "type": "string" }
+    })
+}
+
+fn json_body(schema: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{schema}") } } }
+    })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } }
+    })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+/// OpenAPI 3.0 のドキュメントを作成します。
+// パスとメソッドは `server` のルートと一致することを `server` のテストで確かめる
+pub fn spec() -> Value {
+    let param = |description: &str| json!({ "type": "integer", "format": "int32", "minimum": 0, "maximum": 100, "description": description });
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "cevio",
+            "description": "CeVIO/CeVIO AI の読み上げサーバー",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "security": [{}, { "bearer": [] }],
+        "paths": {
+            "/speak": {
+                "post": {
+                    "summary": "セリフを再生し、再生終了後に応答します",
+                    "operationId": "speak",
+                    "requestBody": json_body("TextBody"),
+                    "responses": {
+                        "204": { "description": "再生が終了しました" },
+                        "400": error_response("NG ワードを含むか、プリセットが存在しません"),
+                        "500": error_response("CeVIO の操作に失敗しました"),
+                        "503": error_response("CeVIO が起動していません")
+                    }
+                }
+            },
+            "/synthesize": {
+                "post": {
+                    "summary": "セリフを WAV に変換します",
+                    "operationId": "synthesize",
+                    "requestBody": json_body("TextBody"),
+                    "responses": {
+                        "200": {
+                            "description": "WAV データ",
+                            "content": { "audio/wav": { "schema": { "type": "string", "format": "binary" } } }
+                        },
+                        "400": error_response("NG ワードを含むか、プリセットが存在しません"),
+                        "500": error_response("CeVIO の操作に失敗しました"),
+                        "503": error_response("CeVIO が起動していません")
+                    }
+                }
+            },
+            "/casts": {
+                "get": {
+                    "summary": "利用可能なキャスト名を取得します",
+                    "operationId": "listCasts",
+                    "responses": {
+                        "200": json_response("キャスト名", json!({ "type": "array", "items": { "type": "string" } })),
+                        "500": error_response("CeVIO の操作に失敗しました"),
+                        "503": error_response("CeVIO が起動していません")
+                    }
+                }
+            },
+            "/ws": {
+                "get": {
+                    "summary": "WebSocket で合成の状態と音声を順次返します",
+                    "description": "テキストメッセージで `TextBody` かセリフを送ると、`started`、WAV のバイナリメッセージ、`finished` の順に返します。",
+                    "operationId": "websocket",
+                    "responses": { "101": { "description": "WebSocket に切り替えました" } }
+                }
+            },
+            "/queue": {
+                "get": {
+                    "summary": "キュー全体の状態を取得します",
+                    "operationId": "queueStatus",
+                    "responses": { "200": json_response("キューの状態", schema_ref("QueueStatus")) }
+                }
+            },
+            "/queue/{client}": {
+                "parameters": [client_parameter()],
+                "get": {
+                    "summary": "クライアントの状態を取得します",
+                    "operationId": "clientStatus",
+                    "responses": {
+                        "200": json_response("クライアントの状態", schema_ref("ClientStatus")),
+                        "404": error_response("クライアントが存在しません")
+                    }
+                },
+                "post": {
+                    "summary": "セリフをキューに追加します",
+                    "operationId": "enqueue",
+                    "requestBody": json_body("TextBody"),
+                    "responses": {
+                        "202": json_response("追加しました", json!({
+                            "type": "object",
+                            "required": ["id"],
+                            "properties": { "id": { "type": "integer", "format": "int64" } }
+                        })),
+                        "400": error_response("NG ワードを含むか、プリセットが存在しません")
+                    }
+                }
+            },
+            "/queue/{client}/priority": {
+                "parameters": [client_parameter()],
+                "put": {
+                    "summary": "クライアントの優先度を設定します",
+                    "operationId": "setPriority",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "type": "object",
+                            "required": ["priority"],
+                            "properties": { "priority": schema_ref("Priority") }
+                        } } }
+                    },
+                    "responses": { "204": { "description": "設定しました" } }
+                }
+            },
+            "/queue/{client}/flush": {
+                "parameters": [client_parameter()],
+                "post": {
+                    "summary": "クライアントのセリフを削除し、再生中なら停止します",
+                    "operationId": "flush",
+                    "responses": {
+                        "200": json_response("削除しました", json!({
+                            "type": "object",
+                            "required": ["removed"],
+                            "properties": { "removed": { "type": "integer" } }
+                        }))
+                    }
+                }
+            },
+            "/config/reload": {
+                "post": {
+                    "summary": "設定ファイルを読み込み直します",
+                    "operationId": "reloadConfig",
+                    "responses": {
+                        "204": { "description": "読み込み直しました" },
+                        "500": error_response("読み込みに失敗しました（今までの設定のままです）")
+                    }
+                }
+            },
+            "/health": {
+                "get": {
+                    "summary": "製品の応答の状態を取得します",
+                    "operationId": "health",
+                    "responses": {
+                        "200": json_response(
+                            "期限内に応答しました",
+                            schema_ref("Health")
+                        ),
+                        "503": json_response(
+                            "期限内に応答しなかったか（slow）、応答に失敗しました（dead）",
+                            schema_ref("Health")
+                        )
+                    }
+                }
+            },
+            "/metrics": {
+                "get": {
+                    "summary": "Prometheus 形式のメトリクスを取得します",
+                    "operationId": "metrics",
+                    "responses": {
+                        "200": {
+                            "description": "メトリクス",
+                            "content": { "text/plain": { "schema": { "type": "string" } } }
+                        }
+                    }
+                }
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "この OpenAPI ドキュメントを取得します",
+                    "operationId": "openapi",
+                    "responses": { "200": json_response("OpenAPI ドキュメント", json!({ "type": "object" })) }
+                }
+            }
+        },
+        "components": {
+            "securitySchemes": {
+                "bearer": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "`auth::Auth` でトークンを設定した場合に必要です。`?token=` クエリでも指定できます。"
+                }
+            },
+            "schemas": {
+                "Health": {
+                    "type": "object",
+                    "required": ["status"],
+                    "properties": {
+                        "status": { "type": "string", "enum": ["healthy", "slow", "dead"] },
+                        "latency_ms": { "type": "integer", "description": "応答までの時間（healthy の場合）" },
+                        "reason": { "type": "string", "description": "理由（dead の場合）" }
+                    }
+                },
+                "TextBody": {
+                    "type": "object",
+                    "required": ["text"],
+                    "properties": {
+                        "text": { "type": "string", "description": "セリフ" },
+                        "preset": { "type": "string", "description": "設定ファイルのプリセット名" },
+                        "cast": { "type": "string", "description": "キャスト" },
+                        "volume": param("音の大きさ"),
+                        "speed": param("話す速さ"),
+                        "tone": param("音の高さ"),
+                        "tone_scale": param("抑揚"),
+                        "alpha": param("声質"),
+                        "components": {
+                            "type": "object",
+                            "description": "感情パラメータ（名前: 0～100）",
+                            "additionalProperties": { "type": "integer", "format": "int32" }
+                        }
+                    }
+                },
+                "ErrorKind": {
+                    "type": "string",
+                    "enum": [
+                        "com_init", "object_creation", "host_not_running", "host_start", "invalid_cast",
+                        "conversion", "timeout", "io", "invalid_input", "operation_failed", "com",
+                        "unauthorized", "forbidden", "other"
+                    ]
+                },
+                "ErrorBody": {
+                    "type": "object",
+                    "required": ["code", "kind", "message", "retryable"],
+                    "properties": {
+                        "code": { "type": "string", "description": "`kind` と同じ値か、CeVIO が他の処理中の場合は `busy`" },
+                        "kind": schema_ref("ErrorKind"),
+                        "message": { "type": "string", "description": "エラーの内容" },
+                        "retryable": { "type": "boolean", "description": "再試行すると成功する可能性があるかどうか" }
+                    }
+                },
+                "Priority": {
+                    "type": "string",
+                    "enum": ["low", "normal", "high", "alert"]
+                },
+                "QueueItem": {
+                    "type": "object",
+                    "required": ["id", "client", "text"],
+                    "properties": {
+                        "id": { "type": "integer", "format": "int64" },
+                        "client": { "type": "string" },
+                        "text": { "type": "string" }
+                    }
+                },
+                "ClientStatus": {
+                    "type": "object",
+                    "required": ["client", "priority", "queued", "spoken"],
+                    "properties": {
+                        "client": { "type": "string" },
+                        "priority": schema_ref("Priority"),
+                        "queued": { "type": "array", "items": schema_ref("QueueItem") },
+                        "spoken": { "type": "integer", "format": "int64" },
+                        "last_error": { "type": "string", "nullable": true }
+                    }
+                },
+                "QueueStatus": {
+                    "type": "object",
+                    "required": ["clients"],
+                    "properties": {
+                        "speaking": {
+                            "allOf": [schema_ref("QueueItem")],
+                            "nullable": true
+                        },
+                        "clients": { "type": "array", "items": schema_ref("ClientStatus") }
+                    }
+                }
+            }
+        }
+    })
+}
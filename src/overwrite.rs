@@ -0,0 +1,121 @@
+//! 出力先のファイルが既にある場合の動作
+//!
+//! `CeVIO::set_overwrite_policy`（または `CeVIOBuilder::overwrite`）で指定すると、
+//! `output_wave_to_file` と、それを使う `Say::to_file`、`audition`、`Project::render`、CLI の `save`、`batch` などのすべてに適用されます。
+//! 手で調整したテイクを上書きしないよう、`Skip`、`Error`、`RenameWithSuffix` を指定できます。
+//!
+//! ```no_run
+//! use cevio::{overwrite::OverwritePolicy, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! cevio.set_overwrite_policy(OverwritePolicy::RenameWithSuffix);
+//! // E:\take.wav が既にある場合は E:\take (2).wav に出力する
+//! let path = cevio.output_wave("こんにちは。", r"E:\take.wav").unwrap();
+//! ```
+//!
+//! ```
+//! use cevio::overwrite::OverwritePolicy;
+//!
+//! assert_eq!("skip".parse::<OverwritePolicy>().unwrap(), OverwritePolicy::Skip);
+//! ```
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::error::{self, report, Report};
+
+/// 出力先のファイルが既にある場合の動作です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OverwritePolicy {
+    /// 上書きします。（既定）
+    #[default]
+    Overwrite,
+    /// 出力せず、既存のファイルをそのまま残します。
+    Skip,
+    /// 出力せず、`CeVIOError::Io` を返します。
+    Error,
+    /// `<名前> (2).wav` のように、重複しない名前に変えて出力します。
+    RenameWithSuffix,
+}
+
+impl OverwritePolicy {
+    /// すべての動作
+    pub const ALL: [Self; 4] = [
+        Self::Overwrite,
+        Self::Skip,
+        Self::Error,
+        Self::RenameWithSuffix,
+    ];
+
+    /// 名前（`overwrite`、`skip`、`error`、`rename`）を取得します。
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Overwrite => "overwrite",
+            Self::Skip => "skip",
+            Self::Error => "error",
+            Self::RenameWithSuffix => "rename",
+        }
+    }
+
+    /// 実際に出力するパスを決めます。
+    ///
+    /// 戻り値：
+    ///
+    /// 　出力するパス。`Skip` でファイルが既にある場合は `None`。
+    pub fn resolve(self, path: &Path) -> error::Result<Option<PathBuf>> {
+        if !path.exists() {
+            return Ok(Some(path.to_path_buf()));
+        }
+        match self {
+            Self::Overwrite => Ok(Some(path.to_path_buf())),
+            Self::Skip => Ok(None),
+            Self::Error => Err(error::CeVIOError::Io(
+                Report::new(std::io::Error::from(std::io::ErrorKind::AlreadyExists))
+                    .context(format!("`{}` already exists", path.display())),
+            )),
+            Self::RenameWithSuffix => Ok(Some(with_suffix(path))),
+        }
+    }
+}
+
+/// `<名前> (n).<拡張子>` のうち、存在しない最初のパスを作る
+fn with_suffix(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| e.to_string_lossy());
+    (2..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{stem} ({n}).{extension}"),
+                None => format!("{stem} ({n})"),
+            };
+            path.with_file_name(name)
+        })
+        .find(|path| !path.exists())
+        .expect("unbounded range")
+}
+
+impl fmt::Display for OverwritePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OverwritePolicy {
+    type Err = error::CeVIOError;
+
+    fn from_str(s: &str) -> error::Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.as_str() == s)
+            .ok_or_else(|| {
+                error::CeVIOError::InvalidInput(report!(
+                    "Unknown overwrite policy `{s}` (expected one of overwrite, skip, error, rename)"
+                ))
+            })
+    }
+}
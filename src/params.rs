@@ -0,0 +1,425 @@
+use crate::{
+    error::{self, report, Context as _},
+    CeVIO,
+};
+
+/// キャストとパラメータの組です。
+///
+/// `None` の項目は変更しません。
+///
+/// テキスト形式では 1 行に 1 つ `キー = 値` を書きます。`#` から始まる行と空行は無視されます。
+///
+/// ```text
+/// cast = 花隈千冬
+/// volume = 100
+/// speed = 50
+/// component.嬉しい = 30
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    /// キャスト
+    pub cast: Option<String>,
+    /// 音の大きさ（0～100）
+    pub volume: Option<i32>,
+    /// 話す速さ（0～100）
+    pub speed: Option<i32>,
+    /// 音の高さ（0～100）
+    pub tone: Option<i32>,
+    /// 抑揚（0～100）
+    pub tone_scale: Option<i32>,
+    /// 声質（0～100）
+    pub alpha: Option<i32>,
+    /// 感情パラメータ（名前, 0～100）
+    pub components: Vec<(String, i32)>,
+}
+
+impl Params {
+    /// `other` で指定された項目を上書きしたパラメータを返します。
+    pub fn merge(&self, other: &Params) -> Params {
+        Params {
+            cast: other.cast.clone().or_else(|| self.cast.clone()),
+            volume: other.volume.or(self.volume),
+            speed: other.speed.or(self.speed),
+            tone: other.tone.or(self.tone),
+            tone_scale: other.tone_scale.or(self.tone_scale),
+            alpha: other.alpha.or(self.alpha),
+            components: {
+                let mut components = self.components.clone();
+                for (name, value) in &other.components {
+                    match components.iter_mut().find(|(n, _)| n == name) {
+                        Some((_, v)) => *v = *value,
+                        None => components.push((name.clone(), *value)),
+                    }
+                }
+                components
+            },
+        }
+    }
+
+    /// テキスト形式のパラメータを読み込みます。
+    pub fn parse(s: &str) -> error::Result<Params> {
+        let mut params = Params::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| report!("Missing `=` at line {}", i + 1))
+                .map_err(error::CeVIOError::InvalidInput)?;
+            let (key, value) = (key.trim(), value.trim());
+            let parse_i32 = || {
+                value
+                    .parse::<i32>()
+                    .with_context(|| format!("Invalid value `{value}` for `{key}`"))
+                    .map_err(error::CeVIOError::from)
+            };
+            match key {
+                "cast" => params.cast = Some(value.to_string()),
+                "volume" => params.volume = Some(parse_i32()?),
+                "speed" => params.speed = Some(parse_i32()?),
+                "tone" => params.tone = Some(parse_i32()?),
+                "tone_scale" => params.tone_scale = Some(parse_i32()?),
+                "alpha" => params.alpha = Some(parse_i32()?),
+                _ if key.starts_with("component.") => {
+                    let name = key["component.".len()..].trim().to_string();
+                    params.components.push((name, parse_i32()?));
+                }
+                _ => {
+                    return Err(error::CeVIOError::InvalidInput(report!(
+                        "Unknown key `{key}` at line {}",
+                        i + 1
+                    )))
+                }
+            }
+        }
+        Ok(params)
+    }
+
+    /// テキスト形式に変換します。
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        if let Some(cast) = &self.cast {
+            s.push_str(&format!("cast = {cast}\n"));
+        }
+        for (key, value) in [
+            ("volume", self.volume),
+            ("speed", self.speed),
+            ("tone", self.tone),
+            ("tone_scale", self.tone_scale),
+            ("alpha", self.alpha),
+        ] {
+            if let Some(value) = value {
+                s.push_str(&format!("{key} = {value}\n"));
+            }
+        }
+        for (name, value) in &self.components {
+            s.push_str(&format!("component.{name} = {value}\n"));
+        }
+        s
+    }
+
+    /// `changes` を設定した後に、この値（現在の値）に戻すためのパラメータ
+    ///
+    /// キャストを切り替えるとパラメータが初期化されるため、その場合はすべての値、それ以外は `changes` で変える項目だけを返す
+    pub(crate) fn restore_for(self, changes: &Params) -> Params {
+        if changes.cast.is_some() && changes.cast != self.cast {
+            return self;
+        }
+        let changed = |changed: Option<i32>, value: Option<i32>| changed.and(value);
+        Params {
+            cast: None,
+            volume: changed(changes.volume, self.volume),
+            speed: changed(changes.speed, self.speed),
+            tone: changed(changes.tone, self.tone),
+            tone_scale: changed(changes.tone_scale, self.tone_scale),
+            alpha: changed(changes.alpha, self.alpha),
+            components: self
+                .components
+                .into_iter()
+                .filter(|(name, _)| changes.components.iter().any(|(n, _)| n == name))
+                .collect(),
+        }
+    }
+
+    /// セリフとパラメータ（キャストを含む）から決まるハッシュ値を取得します。
+    ///
+    /// Rust のバージョンや実行環境によらず同じ値になります。感情パラメータは指定した順番も含めて比較します。
+    pub fn content_hash(&self, text: &str) -> u64 {
+        fnv1a(format!("{}\n{text}", self.to_text()).as_bytes())
+    }
+
+    /// `content_hash` に、合成の後に行う処理を表す `salt` を加えたハッシュ値。`salt` が空の場合は `content_hash` と同じ
+    pub(crate) fn salted_content_hash(&self, text: &str, salt: &str) -> u64 {
+        match salt.is_empty() {
+            true => self.content_hash(text),
+            false => fnv1a(format!("{}\n{text}\n{salt}", self.to_text()).as_bytes()),
+        }
+    }
+
+    /// セリフとパラメータから決まる出力ファイル名（`<ハッシュ値 16 桁>.wav`）を取得します。
+    ///
+    /// 同じセリフとパラメータからは常に同じ名前になるため、生成した音声をキャッシュしたり、
+    /// アセットのパイプラインで出力先を固定したりするのに使えます。
+    /// `Project::render` のキャッシュは、行に適用するすべてのパラメータから、後処理がない場合は同じ名前にします。
+    ///
+    /// ```
+    /// use cevio::Params;
+    ///
+    /// let params = Params { speed: Some(60), ..Params::from("花隈千冬") };
+    /// let name = params.content_file_name("こんにちは。");
+    /// assert_eq!(name, params.content_file_name("こんにちは。"));
+    /// assert_ne!(name, Params::from("花隈千冬").content_file_name("こんにちは。"));
+    /// assert_eq!(name.len(), "0123456789abcdef.wav".len());
+    /// ```
+    pub fn content_file_name(&self, text: &str) -> String {
+        format!("{:016x}.wav", self.content_hash(text))
+    }
+}
+
+/// 64 bit FNV-1a ハッシュ。Rust のバージョンに依存せずキャッシュのキーを安定させるために使う
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl From<&str> for Params {
+    /// キャストだけを指定したパラメータを作成します。
+    fn from(cast: &str) -> Self {
+        Params {
+            cast: Some(cast.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl CeVIO {
+    /// 現在のキャスト、パラメータ、感情パラメータを取得する
+    ///
+    /// キャストが設定されていない場合、`cast` は `None`、`components` は空です。
+    pub(crate) fn current_params(&self) -> error::Result<Params> {
+        let cast = Some(self.get_cast()?).filter(|cast| !cast.is_empty());
+        // 感情パラメータはキャストが設定されている場合だけ取得できる
+        let components = match cast {
+            Some(_) => self
+                .get_components()?
+                .into_iter()
+                .map(|component| (component.name, component.value))
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(Params {
+            cast,
+            volume: Some(self.get_volume()?),
+            speed: Some(self.get_speed()?),
+            tone: Some(self.get_tone()?),
+            tone_scale: Some(self.get_tone_scale()?),
+            alpha: Some(self.get_alpha()?),
+            components,
+        })
+    }
+
+    /// パラメータをまとめて設定します。
+    ///
+    /// キャストを変更するとパラメータが初期化されるため、キャストを最初に設定します。
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(cast = ?params.cast), err)
+    )]
+    pub fn apply_params(&self, params: &Params) -> error::Result<()> {
+        if let Some(cast) = &params.cast {
+            self.set_cast(cast)?;
+        }
+        if let Some(volume) = params.volume {
+            self.set_volume(volume)?;
+        }
+        if let Some(speed) = params.speed {
+            self.set_speed(speed)?;
+        }
+        if let Some(tone) = params.tone {
+            self.set_tone(tone)?;
+        }
+        if let Some(tone_scale) = params.tone_scale {
+            self.set_tone_scale(tone_scale)?;
+        }
+        if let Some(alpha) = params.alpha {
+            self.set_alpha(alpha)?;
+        }
+        for (name, value) in &params.components {
+            self.set_component(name, *value)?;
+        }
+        Ok(())
+    }
+    /// キャストごとの既定のパラメータを登録します。
+    ///
+    /// `set_cast`（`apply_params`、`Say::cast` などを含む）でそのキャストに切り替えたときに自動で適用します。
+    /// `params.cast` は無視します。同じキャストを再度登録すると置き換えます。
+    ///
+    /// ```no_run
+    /// use cevio::{CeVIO, Params};
+    /// let cevio = CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    ///
+    /// cevio.set_cast_profile(
+    ///     "小春六花",
+    ///     Params {
+    ///         speed: Some(55),
+    ///         components: vec![("元気".to_string(), 30)],
+    ///         ..Default::default()
+    ///     },
+    /// );
+    /// cevio.set_cast("小春六花").unwrap(); // 話す速さは 55 になる
+    /// ```
+    pub fn set_cast_profile(&self, cast: impl Into<String>, params: Params) {
+        self.cast_profiles.borrow_mut().insert(
+            cast.into(),
+            Params {
+                cast: None,
+                ..params
+            },
+        );
+    }
+
+    /// キャストごとの既定のパラメータをまとめて登録します。（`config::ServerConfig::cast_profiles` など）
+    pub fn set_cast_profiles(&self, profiles: impl IntoIterator<Item = (String, Params)>) {
+        for (cast, params) in profiles {
+            self.set_cast_profile(cast, params);
+        }
+    }
+
+    /// 登録したキャストの既定のパラメータを取得します。
+    pub fn cast_profile(&self, cast: &str) -> Option<Params> {
+        self.cast_profiles.borrow().get(cast).cloned()
+    }
+
+    /// キャストの既定のパラメータの登録を解除し、登録されていたパラメータを返します。
+    pub fn remove_cast_profile(&self, cast: &str) -> Option<Params> {
+        self.cast_profiles.borrow_mut().remove(cast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chifuyu() -> Params {
+        Params {
+            speed: Some(60),
+            components: vec![("嬉しい".to_string(), 30)],
+            ..Params::from("花隈千冬")
+        }
+    }
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let params = Params::parse("# 早口\n\n  \n  # 字下げしたコメント\nspeed = 80\n").unwrap();
+        assert_eq!(
+            params,
+            Params {
+                speed: Some(80),
+                ..Params::default()
+            }
+        );
+        assert_eq!(Params::parse("").unwrap(), Params::default());
+    }
+
+    #[test]
+    fn parse_trims_keys_and_values() {
+        let params = Params::parse("  cast=花隈千冬  \nvolume =100\ntone= 40").unwrap();
+        assert_eq!(params.cast.as_deref(), Some("花隈千冬"));
+        assert_eq!(params.volume, Some(100));
+        assert_eq!(params.tone, Some(40));
+    }
+
+    #[test]
+    fn parse_rejects_missing_equals() {
+        let e = Params::parse("speed = 50\n\nvolume 100").unwrap_err();
+        assert!(matches!(e, error::CeVIOError::InvalidInput(_)));
+        assert!(format!("{e:#}").contains("Missing `=` at line 3"), "{e:#}");
+    }
+
+    #[test]
+    fn parse_rejects_unknown_keys() {
+        let e = Params::parse("# コメント\nspeeed = 50").unwrap_err();
+        assert!(matches!(e, error::CeVIOError::InvalidInput(_)));
+        assert!(
+            format!("{e:#}").contains("Unknown key `speeed` at line 2"),
+            "{e:#}"
+        );
+        // `component` だけではキーにならない
+        assert!(Params::parse("component = 50").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_invalid_numbers() {
+        let e = Params::parse("speed = fast").unwrap_err();
+        assert!(
+            format!("{e:#}").contains("Invalid value `fast` for `speed`"),
+            "{e:#}"
+        );
+        assert!(Params::parse("component.嬉しい = 3.5").is_err());
+    }
+
+    #[test]
+    fn parse_component_keys() {
+        let params =
+            Params::parse("component.嬉しい = 30\ncomponent. 怒り = 10\ncomponent.嬉しい = 40")
+                .unwrap();
+        // 同じ名前も書いた順に残す
+        assert_eq!(
+            params.components,
+            [
+                ("嬉しい".to_string(), 30),
+                ("怒り".to_string(), 10),
+                ("嬉しい".to_string(), 40)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_to_text_round_trip() {
+        let params = Params {
+            volume: Some(100),
+            tone: Some(50),
+            tone_scale: Some(0),
+            alpha: Some(20),
+            components: vec![("嬉しい".to_string(), 30), ("哀しみ".to_string(), 0)],
+            ..chifuyu()
+        };
+        assert_eq!(Params::parse(&params.to_text()).unwrap(), params);
+        assert_eq!(
+            Params::parse(&Params::default().to_text()).unwrap(),
+            Params::default()
+        );
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn content_hash_is_stable() {
+        // この値が変わると、既に作ったキャッシュのファイル名が使えなくなる
+        assert_eq!(chifuyu().content_hash("こんにちは。"), 0xe2418749a9784666);
+        assert_eq!(
+            Params::default().content_hash("こんにちは。"),
+            0x6873583071f27a4c
+        );
+        assert_eq!(
+            chifuyu().content_file_name("こんにちは。"),
+            "e2418749a9784666.wav"
+        );
+        assert_eq!(
+            chifuyu().salted_content_hash("こんにちは。", ""),
+            chifuyu().content_hash("こんにちは。")
+        );
+        assert_ne!(
+            chifuyu().salted_content_hash("こんにちは。", "loudnorm"),
+            chifuyu().content_hash("こんにちは。")
+        );
+    }
+}
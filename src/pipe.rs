@@ -0,0 +1,323 @@
+//! 名前付きパイプサーバー（`pipe` フィーチャー）
+//!
+//! HTTP や COM を使わずに、同じ PC の他のプロセスから読み上げを依頼するためのものです。
+//!
+//! メッセージは「4 バイトのリトルエンディアンの長さ」＋「本文」で送受信します。
+//! リクエストの本文は JSON で、`op` で操作を指定します。`op` 以外は HTTP サーバーと同じです。
+//!
+//! | `op`         | 内容                                      | レスポンス                                      |
+//! | ------------ | ----------------------------------------- | ----------------------------------------------- |
+//! | `speak`      | セリフを再生し、再生終了後に応答します    | `{"ok":true}`                                   |
+//! | `synthesize` | セリフを WAV に変換します                 | `{"ok":true,"bytes":<長さ>}` の後に WAV の本文  |
+//! | `casts`      | 利用可能なキャスト名を取得します          | `{"ok":true,"casts":[...]}`                     |
+//! | `enqueue`    | `client` のセリフをキューに追加します     | `{"ok":true,"id":<番号>}`                       |
+//! | `priority`   | `client` の優先度を `priority` にします   | `{"ok":true}`                                   |
+//! | `flush`      | `client` のセリフを削除します             | `{"ok":true,"removed":<数>}`                    |
+//! | `status`     | キュー（`client` 指定時はその状態）を取得 | `{"ok":true,"status":{...}}`                    |
+//! | `reload`     | 設定ファイルを読み込み直します            | `{"ok":true}`                                   |
+//!
+//! `enqueue` 以降はすべてのクライアントで共有する優先度付きのキュー（`queue::SpeechQueue`）を操作します。
+//! 優先度は `low`、`normal`、`high`、`alert` です。
+//!
+//! `serve_with_config` で設定を渡した場合の動作は HTTP サーバーと同じです。
+//!
+//! 失敗した場合は `{"ok":false,"error":<内容>,"code":...,"kind":...,"retryable":...}` を返します。
+//! `code`、`kind`、`retryable` は `error::ErrorBody` と同じです。
+//!
+//! ```json
+//! { "op": "speak", "text": "こんにちは。", "cast": "花隈千冬" }
+//! { "op": "enqueue", "client": "chat", "text": "こんにちは。" }
+//! ```
+//!
+//! ```no_run
+//! use cevio::{actor::Handle, pipe, HostKind};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! handle.call(|cevio| cevio.start_host(false)).unwrap();
+//! pipe::serve(pipe::DEFAULT_PIPE_NAME, handle).unwrap();
+//! ```
+
+use std::{
+    io::{self, Read, Write},
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::json;
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{CloseHandle, GetLastError, ERROR_PIPE_CONNECTED, HANDLE},
+        Storage::FileSystem::{FlushFileBuffers, ReadFile, WriteFile, PIPE_ACCESS_DUPLEX},
+        System::Pipes::{
+            ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE,
+            PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        },
+    },
+};
+
+use crate::{
+    actor::Handle,
+    config::SharedConfig,
+    error::{self, Context as _},
+    metrics,
+    queue::{Priority, SpeechQueue},
+    request::TextBody,
+};
+
+/// 既定のパイプ名です。
+pub const DEFAULT_PIPE_NAME: &str = r"\\.\pipe\cevio-rs";
+
+/// 1 メッセージの最大バイト数です。
+pub const MAX_MESSAGE_SIZE: u32 = 64 * 1024 * 1024;
+
+const BUFFER_SIZE: u32 = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Speak(TextBody),
+    Synthesize(TextBody),
+    Casts,
+    Enqueue {
+        client: String,
+        #[serde(flatten)]
+        body: TextBody,
+    },
+    Priority {
+        client: String,
+        priority: Priority,
+    },
+    Flush {
+        client: String,
+    },
+    Status {
+        client: Option<String>,
+    },
+    Reload,
+}
+
+struct Pipe(HANDLE);
+
+impl Pipe {
+    fn create(name: &str) -> error::Result<Self> {
+        let handle = unsafe {
+            CreateNamedPipeW(
+                &HSTRING::from(name),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                None,
+            )
+        };
+        if handle.is_invalid() {
+            return Err(windows::core::Error::from_win32())
+                .with_context(|| format!("Failed to create pipe `{name}`"))
+                .map_err(error::CeVIOError::from);
+        }
+        Ok(Self(handle))
+    }
+
+    fn connect(&self) -> error::Result<()> {
+        // クライアントが先に接続していた場合は ERROR_PIPE_CONNECTED になるが、接続済みなので成功とする
+        if unsafe { ConnectNamedPipe(self.0, None) }.as_bool()
+            || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED
+        {
+            return Ok(());
+        }
+        Err(windows::core::Error::from_win32())
+            .context("Failed to connect pipe")
+            .map_err(error::CeVIOError::from)
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        let ok = unsafe {
+            ReadFile(
+                self.0,
+                Some(buf.as_mut_ptr().cast()),
+                buf.len() as u32,
+                Some(&mut read),
+                None,
+            )
+        };
+        if !ok.as_bool() {
+            return Err(io::Error::other(windows::core::Error::from_win32()));
+        }
+        Ok(read as usize)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0;
+        let ok = unsafe { WriteFile(self.0, Some(buf), Some(&mut written), None) };
+        if !ok.as_bool() {
+            return Err(io::Error::other(windows::core::Error::from_win32()));
+        }
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        unsafe { FlushFileBuffers(self.0) };
+        Ok(())
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        unsafe {
+            FlushFileBuffers(self.0);
+            DisconnectNamedPipe(self.0);
+            CloseHandle(self.0);
+        }
+    }
+}
+
+fn read_message(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len);
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Message too large: {len} bytes"),
+        ));
+    }
+    let mut body = vec![0; len as usize];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(writer: &mut impl Write, body: &[u8]) -> io::Result<()> {
+    writer.write_all(&(body.len() as u32).to_le_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+fn handle_request(
+    handle: &Handle,
+    queue: &SpeechQueue,
+    config: &SharedConfig,
+    body: &[u8],
+) -> error::Result<(serde_json::Value, Vec<u8>)> {
+    let request = serde_json::from_slice::<Request>(body)
+        .context("Invalid request")
+        .map_err(error::CeVIOError::from)?;
+    match request {
+        Request::Speak(body) => {
+            let (text, params) = body.resolve(&config.get())?;
+            metrics::time_synthesis(|| {
+                handle.call(move |cevio| cevio.say(text).params(&params).play())
+            })?;
+            Ok((json!({ "ok": true }), Vec::new()))
+        }
+        Request::Synthesize(body) => {
+            let (text, params) = body.resolve(&config.get())?;
+            let wav = metrics::time_synthesis(|| {
+                handle.call(move |cevio| cevio.say(text).params(&params).to_vec())
+            })?;
+            Ok((json!({ "ok": true, "bytes": wav.len() }), wav))
+        }
+        Request::Casts => {
+            let casts = handle.call(|cevio| cevio.get_available_casts())?;
+            Ok((json!({ "ok": true, "casts": casts }), Vec::new()))
+        }
+        Request::Enqueue { client, body } => {
+            let (text, params) = body.resolve(&config.get())?;
+            let id = queue.push(&client, text, params);
+            Ok((json!({ "ok": true, "id": id }), Vec::new()))
+        }
+        Request::Priority { client, priority } => {
+            queue.set_priority(&client, priority);
+            Ok((json!({ "ok": true }), Vec::new()))
+        }
+        Request::Flush { client } => {
+            let removed = queue.flush(&client);
+            Ok((json!({ "ok": true, "removed": removed }), Vec::new()))
+        }
+        Request::Status { client: None } => {
+            Ok((json!({ "ok": true, "status": queue.status() }), Vec::new()))
+        }
+        Request::Status {
+            client: Some(client),
+        } => {
+            let status = queue.client_status(&client);
+            Ok((json!({ "ok": true, "status": status }), Vec::new()))
+        }
+        Request::Reload => {
+            config.reload()?;
+            Ok((json!({ "ok": true }), Vec::new()))
+        }
+    }
+}
+
+fn session(
+    mut pipe: Pipe,
+    handle: Handle,
+    queue: SpeechQueue,
+    config: SharedConfig,
+) -> io::Result<()> {
+    while let Some(body) = read_message(&mut pipe)? {
+        match handle_request(&handle, &queue, &config, &body) {
+            Ok((response, payload)) => {
+                write_message(&mut pipe, response.to_string().as_bytes())?;
+                if !payload.is_empty() {
+                    write_message(&mut pipe, &payload)?;
+                }
+            }
+            Err(e) => {
+                let body = e.to_body();
+                let response = json!({
+                    "ok": false,
+                    "error": body.message,
+                    "code": body.code,
+                    "kind": body.kind,
+                    "retryable": body.retryable,
+                });
+                write_message(&mut pipe, response.to_string().as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 指定した名前で名前付きパイプサーバーを起動します。
+///
+/// クライアントごとにスレッドを起動します。操作は `handle` のスレッドで順番に実行されます。
+pub fn serve(name: &str, handle: Handle) -> error::Result<()> {
+    serve_with_config(name, handle, SharedConfig::default())
+}
+
+/// 指定した名前で、設定を適用する名前付きパイプサーバーを起動します。
+pub fn serve_with_config(name: &str, handle: Handle, config: SharedConfig) -> error::Result<()> {
+    let queue = SpeechQueue::new(handle.clone())?;
+    loop {
+        let pipe = Pipe::create(name)?;
+        // クライアントが接続してすぐに切断した場合（ERROR_NO_DATA）などは、そのインスタンスを閉じて次の接続を待つ
+        if let Err(_e) = pipe.connect() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(pipe = name, error = %_e, "Failed to accept pipe client");
+            continue;
+        }
+        let handle = handle.clone();
+        let queue = queue.clone();
+        let config = config.clone();
+        thread::Builder::new()
+            .name("cevio-pipe".to_string())
+            .spawn(move || {
+                // クライアントの切断などによるエラーはそのクライアントだけの問題なので無視する
+                let _ = session(pipe, handle, queue, config);
+            })
+            .context("Failed to spawn pipe thread")
+            .map_err(error::CeVIOError::from)?;
+    }
+}
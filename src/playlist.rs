@@ -0,0 +1,357 @@
+//! セリフを 1 行ずつ進めるプレイリスト
+//!
+//! プレゼンテーションのナレーションのように、台本を順番に再生し、途中で一時停止・スキップ・前に戻るなどの操作をするためのものです。
+//! 再生は別のスレッドで行い、再生位置の変化は `subscribe` で受け取れます。
+//!
+//! ```no_run
+//! use cevio::{actor::Handle, playlist::{Playlist, PlaylistEvent}, HostKind, Params};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! let playlist = Playlist::new(handle).unwrap();
+//! for line in ["はじめに。", "本日の議題です。", "まとめです。"] {
+//!     playlist.push(line, Params::from("花隈千冬"));
+//! }
+//!
+//! let events = playlist.subscribe();
+//! // 1 行ずつ再生し、行が終わるたびに一時停止する
+//! playlist.step();
+//! for event in events {
+//!     match event {
+//!         PlaylistEvent::Started { index } => println!("{} 行目", index + 1),
+//!         PlaylistEvent::Paused { .. } => {
+//!             // 発表者の操作を待ってから次の行へ
+//!             std::thread::sleep(std::time::Duration::from_secs(3));
+//!             playlist.step();
+//!         }
+//!         PlaylistEvent::Ended => break,
+//!         _ => {}
+//!     }
+//! }
+//! ```
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use crate::{
+    actor::Handle,
+    backend::TalkerBackend,
+    error::{self, Context as _},
+    metrics,
+    queue::speak_until_stopped,
+    Params,
+};
+
+/// プレイリストの再生状態です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PlaylistState {
+    /// 停止中。再生位置は先頭です
+    #[default]
+    Stopped,
+    /// 再生中
+    Playing,
+    /// 一時停止中。`play` で再生位置から再開します
+    Paused,
+}
+
+/// 再生位置や再生状態の変化です。`index` は 0 から始まる行の番号です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaylistEvent {
+    /// 行の再生を始めた
+    Started { index: usize },
+    /// 行を最後まで再生した
+    Finished { index: usize },
+    /// 行の再生に失敗した。次の行に進みます
+    Failed { index: usize, error: String },
+    /// `skip`、`previous` で再生位置が変わった
+    Moved { index: usize },
+    /// `pause_between`、`step` で、次の行の前で一時停止した
+    Paused { index: usize },
+    /// `stop` で停止した
+    Stopped,
+    /// 最後の行まで再生した
+    Ended,
+}
+
+struct Line {
+    text: String,
+    params: Params,
+}
+
+#[derive(Default)]
+struct State {
+    lines: Vec<Line>,
+    /// 次に再生する行（再生中はその行）
+    position: usize,
+    mode: PlaylistState,
+    /// 再生中の行が終わったら一時停止する
+    pause_pending: bool,
+    /// 再生中の行と、その停止フラグ
+    speaking: Option<(usize, Arc<AtomicBool>)>,
+    subscribers: Vec<mpsc::Sender<PlaylistEvent>>,
+    closed: bool,
+}
+
+impl State {
+    fn emit(&mut self, event: PlaylistEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// 再生中の行を止め、その行の番号を返す
+    fn interrupt(&mut self) -> Option<usize> {
+        let (index, stop) = self.speaking.as_ref()?;
+        stop.store(true, Ordering::SeqCst);
+        Some(*index)
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // 再生スレッドはロック中にパニックしないので、ポイズンされていても状態は壊れていない
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct Inner {
+    shared: Arc<Shared>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        state.interrupt();
+        self.shared.ready.notify_all();
+    }
+}
+
+/// セリフを 1 行ずつ再生するプレイリストです。
+///
+/// `Clone` でき、すべてのプレイリストが破棄されると再生スレッドも終了します。
+#[derive(Clone)]
+pub struct Playlist {
+    inner: Arc<Inner>,
+}
+
+impl Playlist {
+    /// 再生スレッドを起動し、`handle` で再生する空のプレイリストを作成します。
+    pub fn new(handle: Handle) -> error::Result<Self> {
+        Self::spawn(move |text, params, stop| {
+            let (text, params) = (text.to_string(), params.clone());
+            handle.call(move |cevio| speak_until_stopped(cevio, &text, &params, &stop))
+        })
+    }
+
+    /// 再生スレッドを起動し、`backend` で再生する空のプレイリストを作成します。
+    ///
+    /// `backend` は再生スレッドに移して使います。`backend::MockBackend` などを使うと、CeVIO のない環境でプレイリストを使う処理をテストできます。
+    pub fn with_backend<B: TalkerBackend + Send + 'static>(backend: B) -> error::Result<Self> {
+        Self::spawn(move |text, params, stop| speak_until_stopped(&backend, text, params, &stop))
+    }
+
+    /// `speak` で 1 行ずつ再生する再生スレッドを起動する
+    fn spawn(
+        speak: impl FnMut(&str, &Params, Arc<AtomicBool>) -> error::Result<()> + Send + 'static,
+    ) -> error::Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::default(),
+            ready: Condvar::new(),
+        });
+        let worker = shared.clone();
+        thread::Builder::new()
+            .name("cevio-playlist".to_string())
+            .spawn(move || run(worker, speak))
+            .context("Failed to spawn playlist thread")
+            .map_err(error::CeVIOError::from)?;
+        Ok(Self {
+            inner: Arc::new(Inner { shared }),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.shared.lock()
+    }
+
+    fn notify(&self) {
+        self.inner.shared.ready.notify_all();
+    }
+
+    /// 最後に行を追加し、その行の番号を返します。
+    ///
+    /// `params` はこの行の間だけ使い、再生が終わると元の値に戻します。
+    pub fn push(&self, text: impl Into<String>, params: Params) -> usize {
+        let mut state = self.lock();
+        state.lines.push(Line {
+            text: text.into(),
+            params,
+        });
+        self.notify();
+        state.lines.len() - 1
+    }
+
+    /// 再生位置の変化を受け取るレシーバーを作成します。
+    ///
+    /// レシーバーを破棄すると、次のイベントから送られなくなります。
+    pub fn subscribe(&self) -> mpsc::Receiver<PlaylistEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock().subscribers.push(sender);
+        receiver
+    }
+
+    /// 再生位置から再生します。再生中の場合は何もしません。
+    ///
+    /// `pause_between` の予約は取り消します。
+    pub fn play(&self) {
+        let mut state = self.lock();
+        state.pause_pending = false;
+        state.mode = PlaylistState::Playing;
+        self.notify();
+    }
+
+    /// 再生中の行が終わったら、次の行の前で一時停止します。行を再生していない場合はすぐに一時停止します。
+    ///
+    /// `Finished` イベントを受け取ってから呼ぶと、次の行の再生が既に始まっていて、その行の後で止まる場合があります。
+    /// 1 行ごとに止める場合は `step` を使ってください。
+    pub fn pause_between(&self) {
+        let mut state = self.lock();
+        if state.mode != PlaylistState::Playing {
+            return;
+        }
+        if state.speaking.is_some() {
+            state.pause_pending = true;
+        } else {
+            state.mode = PlaylistState::Paused;
+            let index = state.position;
+            state.emit(PlaylistEvent::Paused { index });
+        }
+    }
+
+    /// 再生位置の 1 行だけを再生し、次の行の前で一時停止します。再生中の場合は、再生中の行が終わったら一時停止します。
+    ///
+    /// 一時停止の予約は再生を始める前にするため、1 行だけ再生することが保証されます。最後の行の後で呼ぶと `Ended` になります。
+    pub fn step(&self) {
+        let mut state = self.lock();
+        state.pause_pending = true;
+        state.mode = PlaylistState::Playing;
+        self.notify();
+    }
+
+    /// 再生中の行を止め、次の行に進みます。再生中の場合は次の行を続けて再生します。
+    pub fn skip(&self) {
+        let mut state = self.lock();
+        let index = state.interrupt().unwrap_or(state.position);
+        let next = (index + 1).min(state.lines.len());
+        self.move_to(&mut state, next);
+    }
+
+    /// 再生中の行を止め、前の行に戻ります。先頭の行の場合は先頭に戻ります。再生中の場合は続けて再生します。
+    pub fn previous(&self) {
+        let mut state = self.lock();
+        let index = state.interrupt().unwrap_or(state.position);
+        self.move_to(&mut state, index.saturating_sub(1));
+    }
+
+    fn move_to(&self, state: &mut State, index: usize) {
+        state.position = index;
+        state.emit(PlaylistEvent::Moved { index });
+        self.notify();
+    }
+
+    /// 再生を止め、再生位置を先頭に戻します。
+    pub fn stop(&self) {
+        let mut state = self.lock();
+        state.interrupt();
+        state.mode = PlaylistState::Stopped;
+        state.pause_pending = false;
+        state.position = 0;
+        state.emit(PlaylistEvent::Stopped);
+    }
+
+    /// 再生状態を取得します。
+    pub fn state(&self) -> PlaylistState {
+        self.lock().mode
+    }
+
+    /// 再生位置（再生中はその行、それ以外は次に再生する行）を取得します。
+    pub fn position(&self) -> usize {
+        self.lock().position
+    }
+
+    /// 行の数を取得します。
+    pub fn len(&self) -> usize {
+        self.lock().lines.len()
+    }
+
+    /// 行がないかどうかを取得します。
+    pub fn is_empty(&self) -> bool {
+        self.lock().lines.is_empty()
+    }
+
+    /// 行のセリフを取得します。
+    pub fn text(&self, index: usize) -> Option<String> {
+        self.lock().lines.get(index).map(|line| line.text.clone())
+    }
+}
+
+fn run(
+    shared: Arc<Shared>,
+    mut speak: impl FnMut(&str, &Params, Arc<AtomicBool>) -> error::Result<()>,
+) {
+    loop {
+        let (index, text, params, stop) = {
+            let mut state = shared.lock();
+            loop {
+                if state.closed {
+                    return;
+                }
+                if state.mode == PlaylistState::Playing {
+                    if state.position < state.lines.len() {
+                        break;
+                    }
+                    state.mode = PlaylistState::Stopped;
+                    state.position = 0;
+                    state.pause_pending = false;
+                    state.emit(PlaylistEvent::Ended);
+                }
+                state = shared.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+            let index = state.position;
+            let line = &state.lines[index];
+            let (text, params) = (line.text.clone(), line.params.clone());
+            let stop = Arc::new(AtomicBool::new(false));
+            state.speaking = Some((index, stop.clone()));
+            state.emit(PlaylistEvent::Started { index });
+            (index, text, params, stop)
+        };
+
+        let result = metrics::time_synthesis(|| speak(&text, &params, stop.clone()));
+
+        let mut state = shared.lock();
+        state.speaking = None;
+        // 操作で止めた場合は、操作が再生位置を変えている
+        if stop.load(Ordering::SeqCst) {
+            continue;
+        }
+        match result {
+            Ok(()) => state.emit(PlaylistEvent::Finished { index }),
+            Err(e) => state.emit(PlaylistEvent::Failed {
+                index,
+                error: format!("{e:#}"),
+            }),
+        }
+        state.position = index + 1;
+        if std::mem::take(&mut state.pause_pending) {
+            state.mode = PlaylistState::Paused;
+            state.emit(PlaylistEvent::Paused { index: index + 1 });
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//! よく使う型をまとめて読み込むためのモジュール
+//!
+//! ```no_run
+//! use cevio::prelude::*;
+//!
+//! let cevio = CeVIO::builder().auto_start(true).cast("花隈千冬").build().unwrap();
+//! let phonemes: Vec<PhonemeData> = cevio.get_phonemes("こんにちは").unwrap();
+//! if let Err(e) = cevio.say("こんにちは").speed(60).play() {
+//!     assert_ne!(e.kind(), ErrorKind::InvalidCast);
+//! }
+//! ```
+//!
+//! 名前が衝突しないよう、`error::Result` は含めていません。
+
+pub use crate::{
+    actor::Handle,
+    error::{CeVIOError, ErrorKind},
+    CastInfo, CeVIO, CeVIOBuilder, Component, HostInfo, HostKind, Params, PhonemeData, Project,
+    Say, SpeakingState,
+};
@@ -0,0 +1,161 @@
+use windows::Win32::{
+    Foundation::{CloseHandle, BOOL, HWND, LPARAM},
+    System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    },
+    UI::WindowsAndMessaging::{
+        EnumWindows, GetWindow, GetWindowTextLengthW, GetWindowThreadProcessId, IsWindowVisible,
+        ShowWindow, GW_OWNER, SHOW_WINDOW_CMD, SW_HIDE, SW_SHOW, SW_SHOWMINNOACTIVE,
+    },
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    CeVIO, HostKind,
+};
+
+/// 起動中の【CeVIO Creative Studio】のプロセスです。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostProcess {
+    /// プロセス ID
+    pub pid: u32,
+    /// メインウィンドウのハンドル（`HWND`）。ウィンドウが作られていない場合は `None`
+    pub main_window: Option<isize>,
+}
+
+/// ウィンドウの表示状態です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowState {
+    /// 通常表示
+    Show,
+    /// 最小化（アクティブにしない）
+    Minimize,
+    /// 非表示
+    Hide,
+}
+
+impl HostKind {
+    fn exe_name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "cevio-cs")]
+            HostKind::Cs => "CeVIO Creative Studio.exe",
+            #[cfg(feature = "cevio-ai")]
+            HostKind::Ai => "CeVIO AI.exe",
+        }
+    }
+}
+
+impl CeVIO {
+    /// 起動中の【CeVIO Creative Studio】のプロセスを取得します。起動していない場合は `None` を返します。
+    pub fn get_host_process(&self) -> error::Result<Option<HostProcess>> {
+        let Some(pid) = find_process(self.host.exe_name())? else {
+            return Ok(None);
+        };
+        Ok(Some(HostProcess {
+            pid,
+            main_window: find_main_window(pid).map(|hwnd| hwnd.0),
+        }))
+    }
+
+    /// 【CeVIO Creative Studio】のウィンドウの表示状態を変更します。
+    ///
+    /// 備考：
+    ///
+    /// 　`start_host` 直後はウィンドウが作られていないことがあるため、`start_host(false)` の後に呼び出してください。
+    pub fn set_host_window_state(&self, state: WindowState) -> error::Result<()> {
+        let hwnd = self
+            .get_host_process()?
+            .and_then(|p| p.main_window)
+            .ok_or_else(|| report!("Host window is not found"))
+            .map_err(error::CeVIOError::HostNotRunning)?;
+        let cmd: SHOW_WINDOW_CMD = match state {
+            WindowState::Show => SW_SHOW,
+            WindowState::Minimize => SW_SHOWMINNOACTIVE,
+            WindowState::Hide => SW_HIDE,
+        };
+        // 戻り値は以前の表示状態を表すため、エラーとしては扱わない
+        unsafe { ShowWindow(HWND(hwnd), cmd) };
+        Ok(())
+    }
+
+    /// 【CeVIO Creative Studio】のウィンドウを最小化します。
+    pub fn minimize_host_window(&self) -> error::Result<()> {
+        self.set_host_window_state(WindowState::Minimize)
+    }
+
+    /// 【CeVIO Creative Studio】のウィンドウを非表示にします。
+    pub fn hide_host_window(&self) -> error::Result<()> {
+        self.set_host_window_state(WindowState::Hide)
+    }
+
+    /// 【CeVIO Creative Studio】のウィンドウを表示します。
+    pub fn show_host_window(&self) -> error::Result<()> {
+        self.set_host_window_state(WindowState::Show)
+    }
+}
+
+fn find_process(exe_name: &str) -> error::Result<Option<u32>> {
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)
+            .context("Failed to call `CreateToolhelp32Snapshot`")
+            .map_err(error::CeVIOError::from)?;
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut pid = None;
+        let mut found = Process32FirstW(snapshot, &mut entry);
+        while found.as_bool() {
+            let len = entry
+                .szExeFile
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(entry.szExeFile.len());
+            if String::from_utf16_lossy(&entry.szExeFile[..len]).eq_ignore_ascii_case(exe_name) {
+                pid = Some(entry.th32ProcessID);
+                break;
+            }
+            found = Process32NextW(snapshot, &mut entry);
+        }
+        CloseHandle(snapshot);
+        Ok(pid)
+    }
+}
+
+struct FindWindow {
+    pid: u32,
+    visible: Option<HWND>,
+    hidden: Option<HWND>,
+}
+
+fn find_main_window(pid: u32) -> Option<HWND> {
+    let mut find = FindWindow {
+        pid,
+        visible: None,
+        hidden: None,
+    };
+    unsafe {
+        EnumWindows(
+            Some(enum_windows_proc),
+            LPARAM(&mut find as *mut _ as isize),
+        )
+    };
+    find.visible.or(find.hidden)
+}
+
+unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let find = &mut *(lparam.0 as *mut FindWindow);
+    let mut pid = 0;
+    GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    // オーナーを持たず、タイトルがあるトップレベルウィンドウをメインウィンドウとみなす
+    if pid != find.pid || GetWindow(hwnd, GW_OWNER).0 != 0 || GetWindowTextLengthW(hwnd) == 0 {
+        return true.into();
+    }
+    if IsWindowVisible(hwnd).as_bool() {
+        find.visible = Some(hwnd);
+        return false.into();
+    }
+    find.hidden.get_or_insert(hwnd);
+    true.into()
+}
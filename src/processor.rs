@@ -0,0 +1,258 @@
+//! 出力した音声の後処理
+//!
+//! `CeVIO::add_output_processor` で登録した `OutputProcessor` を、登録した順に WAV の出力に適用します。
+//! `output_wave_to_file` を使うすべての API（`output_wave_to_vec`、`Say`、`Project::render` など）に適用されます。
+//!
+//! 備考：
+//!
+//! 　`speak` は CeVIO が直接再生するため適用されません。
+//! 　`Project::render` のキャッシュは後処理をした音声で、キャッシュのキーには後処理の `cache_key` を含めます。
+//!
+//! ```no_run
+//! use cevio::{processor::{AudioBuffer, Normalize, OutputMeta, OutputProcessor, TrimSilence}, error, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//!
+//! /// 音量を半分にする
+//! struct Half;
+//!
+//! impl OutputProcessor for Half {
+//!     fn process(&self, mut buffer: AudioBuffer, _meta: &OutputMeta) -> error::Result<AudioBuffer> {
+//!         buffer.samples.iter_mut().for_each(|s| *s *= 0.5);
+//!         Ok(buffer)
+//!     }
+//! }
+//!
+//! cevio.add_output_processor(Normalize::default());
+//! cevio.add_output_processor(TrimSilence::default());
+//! cevio.add_output_processor(Half);
+//! cevio.output_wave_to_file("こんにちは。", r"E:\file.wav").unwrap();
+//! ```
+//!
+//! ```
+//! use cevio::processor::{AudioBuffer, Normalize, OutputMeta, OutputProcessor, TrimSilence};
+//!
+//! let buffer = AudioBuffer {
+//!     sample_rate: 1000,
+//!     channels: 1,
+//!     samples: [vec![0.0; 500], vec![0.25, -0.5, 0.25], vec![0.0; 500]].concat(),
+//! };
+//! let meta = OutputMeta { text: "あ。", cast: None, path: None };
+//! let buffer = Normalize { peak: 1.0 }.process(buffer, &meta).unwrap();
+//! assert_eq!(buffer.samples.iter().fold(0.0f32, |m, s| m.max(s.abs())), 1.0);
+//! let buffer = TrimSilence::default().process(buffer, &meta).unwrap();
+//! assert!(buffer.samples.len() < 1003);
+//! ```
+
+use std::{fs, path::Path, sync::Arc, time::Duration};
+
+use crate::{
+    error::{self, Context as _},
+    wav,
+};
+
+/// 後処理に渡す音声です。
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBuffer {
+    /// サンプリング周波数
+    pub sample_rate: u32,
+    /// チャンネル数
+    pub channels: u16,
+    /// サンプル（-1.0～1.0）。複数のチャンネルの場合は交互に並べます
+    pub samples: Vec<f32>,
+}
+
+impl AudioBuffer {
+    /// フレーム（チャンネルごとのサンプルの組）の数を取得します。
+    pub fn frames(&self) -> usize {
+        self.samples.len() / usize::from(self.channels.max(1))
+    }
+
+    /// 長さを取得します。
+    pub fn duration(&self) -> Duration {
+        match self.sample_rate {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(self.frames() as f64 / f64::from(rate)),
+        }
+    }
+}
+
+/// 後処理に渡す、出力の情報です。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputMeta<'a> {
+    /// セリフ
+    pub text: &'a str,
+    /// キャスト。不明な場合は `None`
+    pub cast: Option<&'a str>,
+    /// 出力先のパス
+    pub path: Option<&'a Path>,
+}
+
+/// 出力した音声の後処理です。
+///
+/// `Project::render` は書き込みのスレッドで後処理を行うため、`Send + Sync` が必要です。
+pub trait OutputProcessor: Send + Sync {
+    /// 音声を加工して返します。エラーを返すと、出力も失敗します。
+    fn process(&self, buffer: AudioBuffer, meta: &OutputMeta) -> error::Result<AudioBuffer>;
+
+    /// 処理の内容を表す文字列を返します。`Project::render` のキャッシュのキーに含めます。
+    ///
+    /// 既定では型の名前です。設定によって結果が変わる場合は、設定を含む値を返してください。
+    /// クロージャの型の名前は区別できないことがあるため、クロージャを使う場合は後処理を変えたら `Project::clean` でキャッシュを消去してください。
+    fn cache_key(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+}
+
+impl<F> OutputProcessor for F
+where
+    F: Fn(AudioBuffer, &OutputMeta) -> error::Result<AudioBuffer> + Send + Sync,
+{
+    fn process(&self, buffer: AudioBuffer, meta: &OutputMeta) -> error::Result<AudioBuffer> {
+        self(buffer, meta)
+    }
+}
+
+/// 最大の振幅が `peak` になるように音量をそろえます。無音の場合は何もしません。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalize {
+    /// 最大の振幅（0.0～1.0）
+    pub peak: f32,
+}
+
+impl Default for Normalize {
+    /// 最大の振幅を -1 dBFS（約 0.89）にします。
+    fn default() -> Self {
+        Self { peak: 0.891 }
+    }
+}
+
+impl OutputProcessor for Normalize {
+    fn process(&self, mut buffer: AudioBuffer, _meta: &OutputMeta) -> error::Result<AudioBuffer> {
+        let max = buffer
+            .samples
+            .iter()
+            .fold(0.0f32, |max, s| max.max(s.abs()));
+        if max > 0.0 {
+            let gain = self.peak.clamp(0.0, 1.0) / max;
+            buffer.samples.iter_mut().for_each(|s| *s *= gain);
+        }
+        Ok(buffer)
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// 前後の無音を取り除きます。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrimSilence {
+    /// 無音とみなす振幅（0.0～1.0）
+    pub threshold: f32,
+    /// 取り除いた後に前後に残す無音の長さ
+    pub padding: Duration,
+}
+
+impl Default for TrimSilence {
+    /// 振幅 0.01（-40 dBFS）以下を無音とみなし、前後に 50 ミリ秒残します。
+    fn default() -> Self {
+        Self {
+            threshold: 0.01,
+            padding: Duration::from_millis(50),
+        }
+    }
+}
+
+impl OutputProcessor for TrimSilence {
+    fn process(&self, mut buffer: AudioBuffer, _meta: &OutputMeta) -> error::Result<AudioBuffer> {
+        let channels = usize::from(buffer.channels.max(1));
+        let is_loud = |frame: &[f32]| frame.iter().any(|s| s.abs() > self.threshold);
+        let frames = buffer.samples.chunks(channels);
+        let Some(first) = frames.clone().position(is_loud) else {
+            return Ok(buffer);
+        };
+        let last = buffer.frames() - 1 - frames.rev().position(is_loud).unwrap_or(0);
+        let padding = (self.padding.as_secs_f64() * f64::from(buffer.sample_rate)) as usize;
+        let start = first.saturating_sub(padding);
+        let end = (last + 1 + padding).min(buffer.frames());
+        buffer.samples.truncate(end * channels);
+        buffer.samples.drain(..start * channels);
+        Ok(buffer)
+    }
+
+    fn cache_key(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl crate::CeVIO {
+    /// 出力した WAV に適用する後処理を、最後に追加します。後処理は追加した順に適用します。
+    ///
+    /// 後処理をした WAV は 16bit の PCM で書き直します。サンプリング周波数とチャンネル数は後処理の結果に従います。
+    pub fn add_output_processor(&self, processor: impl OutputProcessor + 'static) {
+        self.output_processors
+            .borrow_mut()
+            .push(Arc::new(processor));
+    }
+
+    /// 後処理をすべて削除します。
+    pub fn clear_output_processors(&self) {
+        self.output_processors.borrow_mut().clear();
+    }
+
+    /// 後処理の数を取得します。
+    pub fn output_processors_len(&self) -> usize {
+        self.output_processors.borrow().len()
+    }
+
+    /// 登録している後処理の一覧を複製して取得する
+    pub(crate) fn output_processors(&self) -> Vec<Arc<dyn OutputProcessor>> {
+        self.output_processors.borrow().clone()
+    }
+
+    /// 後処理を登録している場合は、`path` の WAV に順に適用して書き直す
+    pub(crate) fn process_output(&self, text: &str, path: &Path) -> error::Result<()> {
+        // 後処理の中で後処理を追加しても借用が衝突しないように、一覧を複製してから適用する
+        let processors = self.output_processors();
+        let cast = self.written.borrow().cast().map(str::to_string);
+        let meta = OutputMeta {
+            text,
+            cast: cast.as_deref(),
+            path: Some(path),
+        };
+        apply(&processors, &meta, path)
+    }
+}
+
+/// 後処理の一覧を表す文字列。後処理がない場合は空
+pub(crate) fn chain_key(processors: &[Arc<dyn OutputProcessor>]) -> String {
+    processors
+        .iter()
+        .map(|processor| processor.cache_key())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `processors` を `path` の WAV に順に適用して書き直す。後処理がない場合は何もしない
+pub(crate) fn apply(
+    processors: &[Arc<dyn OutputProcessor>],
+    meta: &OutputMeta,
+    path: &Path,
+) -> error::Result<()> {
+    if processors.is_empty() {
+        return Ok(());
+    }
+    let bytes = fs::read(path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))
+        .map_err(error::CeVIOError::Io)?;
+    let mut buffer = wav::decode(&bytes)
+        .with_context(|| format!("Failed to decode `{}`", path.display()))
+        .map_err(error::CeVIOError::Io)?;
+    for processor in processors {
+        buffer = processor.process(buffer, meta)?;
+    }
+    fs::write(path, wav::encode(&buffer))
+        .with_context(|| format!("Failed to write `{}`", path.display()))
+        .map_err(error::CeVIOError::Io)
+}
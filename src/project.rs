@@ -0,0 +1,488 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+};
+
+use crate::{
+    backend::TalkerBackend,
+    error::{self, report, Context as _},
+    fs_util::{absolute, create_dir_all, read_to_string},
+    metrics,
+    overwrite::OverwritePolicy,
+    params::Params,
+    processor::{self, OutputMeta, OutputProcessor},
+    stems,
+};
+
+const MANIFEST_FILE: &str = "project.txt";
+const SCRIPT_FILE: &str = "script.txt";
+const PRESETS_DIR: &str = "presets";
+const CACHE_DIR: &str = "cache";
+const OUTPUTS_DIR: &str = "outputs";
+const STEMS_DIR: &str = "stems";
+/// 後処理をする前の合成結果の拡張子
+const RAW_EXTENSION: &str = "raw.wav";
+/// 書き込みを待つ行の最大数。合成が書き込みより速い場合は合成を待たせる
+const PIPELINE_DEPTH: usize = 4;
+
+/// 台本・プリセット・キャッシュ・出力をまとめたプロジェクトディレクトリです。
+///
+/// ```text
+/// <root>/
+/// ├─ project.txt    マニフェスト（全行に適用するパラメータ、`Params` のテキスト形式）
+/// ├─ script.txt     台本（1 行 1 セリフ）
+/// ├─ presets/       プリセット（`<名前>.txt`、`Params` のテキスト形式）
+/// ├─ cache/         レンダリング結果のキャッシュ
+/// └─ outputs/       出力（`0001.wav`, `0002.wav`, ...、キャストごとのステムは `stems/`）
+/// ```
+///
+/// 台本では行頭に `[プリセット名]` を書くとその行にプリセットを適用します。
+/// `#` から始まる行と空行、`[プリセット名]` だけの行は無視されます。`]` で閉じていない `[` はセリフの一部です。
+///
+/// ```text
+/// # 冒頭
+/// 初めまして。
+/// [元気] よろしくお願いします！
+/// ```
+#[derive(Debug, Clone)]
+pub struct Project {
+    root: PathBuf,
+    manifest: Params,
+    presets: HashMap<String, Params>,
+    script: Vec<ScriptLine>,
+}
+
+/// 台本の 1 行です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptLine {
+    /// 適用するプリセット名
+    pub preset: Option<String>,
+    /// セリフ
+    pub text: String,
+}
+
+impl Project {
+    /// プロジェクトディレクトリを開きます。
+    ///
+    /// `project.txt` と `presets/` は省略できます。
+    pub fn open(root: impl AsRef<Path>) -> error::Result<Self> {
+        let root = absolute(root.as_ref())?;
+
+        let manifest_path = root.join(MANIFEST_FILE);
+        let manifest = if manifest_path.exists() {
+            Params::parse(&read_to_string(&manifest_path)?)?
+        } else {
+            Params::default()
+        };
+
+        let mut presets = HashMap::new();
+        let presets_dir = root.join(PRESETS_DIR);
+        if presets_dir.is_dir() {
+            for entry in fs::read_dir(&presets_dir)
+                .with_context(|| format!("Failed to read `{}`", presets_dir.display()))
+                .map_err(error::CeVIOError::from)?
+            {
+                let path = entry
+                    .with_context(|| format!("Failed to read `{}`", presets_dir.display()))
+                    .map_err(error::CeVIOError::from)?
+                    .path();
+                if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+                    continue;
+                }
+                let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                presets.insert(name.to_string(), Params::parse(&read_to_string(&path)?)?);
+            }
+        }
+
+        let script = parse_script(&read_to_string(&root.join(SCRIPT_FILE))?);
+        for line in &script {
+            if let Some(preset) = &line.preset {
+                if !presets.contains_key(preset) {
+                    return Err(error::CeVIOError::InvalidInput(report!(
+                        "Unknown preset `{preset}`"
+                    )));
+                }
+            }
+        }
+
+        Ok(Self {
+            root,
+            manifest,
+            presets,
+            script,
+        })
+    }
+
+    /// プロジェクトのルートディレクトリを取得します。
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// マニフェストのパラメータを取得します。
+    pub fn manifest(&self) -> &Params {
+        &self.manifest
+    }
+
+    /// プリセットを取得します。
+    pub fn preset(&self, name: &str) -> Option<&Params> {
+        self.presets.get(name)
+    }
+
+    /// 台本を取得します。
+    pub fn script(&self) -> &[ScriptLine] {
+        &self.script
+    }
+
+    /// 台本の各行に適用されるパラメータを取得します。
+    pub fn params_for(&self, line: &ScriptLine) -> Params {
+        match line.preset.as_ref().and_then(|p| self.presets.get(p)) {
+            Some(preset) => self.manifest.merge(preset),
+            None => self.manifest.clone(),
+        }
+    }
+
+    /// 台本の各行に適用されるパラメータを、プリセットのない行は推定した感情から決めて取得します。
+    ///
+    /// プリセットを書いた行は `params_for` と同じです。手で付けたプリセットを優先します。
+    #[cfg(feature = "sentiment")]
+    pub fn params_with_sentiment(
+        &self,
+        line: &ScriptLine,
+        classifier: &crate::sentiment::Classifier,
+        map: &crate::sentiment::EmotionMap,
+    ) -> Params {
+        if line.preset.is_some() {
+            return self.params_for(line);
+        }
+        self.manifest
+            .merge(&map.params_for(&classifier.classify(&line.text)))
+    }
+
+    /// 台本の各行を合成するときのキャストとパラメータを、省略した項目も含めてすべて求めます。
+    ///
+    /// 各行は、呼び出した時点のキャストとパラメータに `params_for` の値を重ねて合成します。前の行の設定は引き継ぎません。
+    /// キャストを切り替える行は、切り替えた後のキャストの既定のパラメータ（`CeVIO::set_cast_profile`）に重ねます。
+    /// インストールされていないキャストは、`CeVIO::set_fallback_casts` で指定した代替キャストに置き換えます。
+    pub fn resolve_params(&self, backend: &impl TalkerBackend) -> error::Result<Vec<Params>> {
+        let current = backend.current_params()?;
+        let fallbacks = backend.fallback_casts();
+        let available = match fallbacks.is_empty() {
+            true => Vec::new(),
+            false => backend.get_available_casts()?,
+        };
+        // `CeVIO::select_cast` と同じく、インストールされていない場合は最初に見つかった代替キャストを選ぶ
+        let select = |cast: String| match fallbacks.is_empty() || available.contains(&cast) {
+            true => cast,
+            false => fallbacks
+                .iter()
+                .find(|fallback| available.contains(fallback))
+                .cloned()
+                .unwrap_or(cast),
+        };
+        Ok(self
+            .script
+            .iter()
+            .map(|line| {
+                let params = self.params_for(line);
+                let cast = params.cast.clone().map(select);
+                let params = Params {
+                    cast: None,
+                    ..params
+                };
+                match cast.filter(|cast| current.cast.as_ref() != Some(cast)) {
+                    // キャストを切り替えるとパラメータが初期化され、キャストの既定のパラメータが適用される
+                    Some(cast) => Params::from(cast.as_str())
+                        .merge(&backend.cast_profile(&cast).unwrap_or_default())
+                        .merge(&params),
+                    None => current.merge(&params),
+                }
+            })
+            .collect())
+    }
+
+    /// 台本をすべて `outputs/` に出力し、出力したファイルのパスを返します。
+    ///
+    /// 各行は `resolve_params` で求めたキャストとパラメータで合成し、合成した後は呼び出す前の値に戻します。
+    ///
+    /// セリフ、`resolve_params` のパラメータ、後処理（`OutputProcessor::cache_key`）が同じ行は `cache/` から再利用します。
+    /// `outputs/` にファイルが既にある場合は `CeVIO::set_overwrite_policy` の設定に従います。
+    ///
+    /// 合成だけを `backend` のスレッドで行い、後処理（`CeVIO::add_output_processor`）と `cache/`、`outputs/` への書き込みは別のスレッドで行います。
+    /// ある行の後処理・書き込みと次の行の合成を同時に進めます。
+    ///
+    /// `backend` には `CeVIO` のほか、`backend::MockBackend` や `fixture::ReplayBackend` なども使えます。
+    pub fn render(&self, backend: &impl TalkerBackend) -> error::Result<Vec<PathBuf>> {
+        let resolved = self.resolve_params(backend)?;
+        self.render_resolved(backend, &resolved)
+    }
+
+    fn render_resolved(
+        &self,
+        backend: &impl TalkerBackend,
+        resolved: &[Params],
+    ) -> error::Result<Vec<PathBuf>> {
+        let cache_dir = self.root.join(CACHE_DIR);
+        let outputs_dir = self.root.join(OUTPUTS_DIR);
+        create_dir_all(&cache_dir)?;
+        create_dir_all(&outputs_dir)?;
+
+        let policy = backend.overwrite_policy();
+        let processors = backend.output_processors();
+        let chain = processor::chain_key(&processors);
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::sync_channel::<Job>(PIPELINE_DEPTH);
+            let writer = scope.spawn(move || {
+                let mut outputs = Vec::with_capacity(self.script.len());
+                for job in receiver {
+                    outputs.push(job.run(&processors, policy)?);
+                }
+                Ok::<_, error::CeVIOError>(outputs)
+            });
+
+            // `Err(None)` は書き込みのスレッドが先に終了したことを表す
+            let synthesized =
+                self.script
+                    .iter()
+                    .zip(resolved)
+                    .enumerate()
+                    .try_for_each(|(i, (line, params))| {
+                        let hash = params.salted_content_hash(&line.text, &chain);
+                        let cached = cache_dir.join(format!("{hash:016x}.wav"));
+                        let hit = cached.exists();
+                        metrics::global().record_cache(hit);
+                        let raw = match hit {
+                            true => None,
+                            false => {
+                                let raw = cached.with_extension(RAW_EXTENSION);
+                                // 前回中断した場合の残りは上書きする
+                                let _ = fs::remove_file(&raw);
+                                metrics::time_synthesis(|| {
+                                    backend.with_params(params, || {
+                                        backend.output_raw_wave_to_file(&line.text, &raw)
+                                    })
+                                })
+                                .map_err(Some)?;
+                                Some(raw)
+                            }
+                        };
+                        let job = Job {
+                            text: line.text.clone(),
+                            cast: params.cast.clone(),
+                            raw,
+                            cached,
+                            output: outputs_dir.join(format!("{:04}.wav", i + 1)),
+                        };
+                        // 書き込みが失敗して受信側が終了した場合は、合成をやめて書き込みのエラーを返す
+                        sender.send(job).map_err(|_| None)
+                    });
+            drop(sender);
+
+            let written = writer
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            match synthesized {
+                Err(Some(e)) => Err(e),
+                _ => written,
+            }
+        })
+    }
+
+    /// 台本をすべて出力し、キャストごとのステムを `outputs/stems/<キャスト名>.wav` に書き出して、（キャスト名, パス）を返します。
+    ///
+    /// 各ステムは台本全体と同じ長さで、ほかのキャストの行は無音です。（`stems::write_stems` を参照。）
+    /// 行のキャストは `resolve_params` のキャストです。キャストを指定していない行は、呼び出した時点のキャストの行として扱います。
+    pub fn render_stems(
+        &self,
+        backend: &impl TalkerBackend,
+    ) -> error::Result<Vec<(String, PathBuf)>> {
+        let resolved = self.resolve_params(backend)?;
+        let casts = resolved
+            .iter()
+            .map(|params| params.cast.clone().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let outputs = self.render_resolved(backend, &resolved)?;
+        stems::write_stems(
+            casts.into_iter().zip(outputs),
+            self.root.join(OUTPUTS_DIR).join(STEMS_DIR),
+            backend.overwrite_policy(),
+        )
+    }
+
+    /// `cache/` と `outputs/` を削除します。
+    pub fn clean(&self) -> error::Result<()> {
+        for dir in [CACHE_DIR, OUTPUTS_DIR] {
+            let dir = self.root.join(dir);
+            if dir.exists() {
+                fs::remove_dir_all(&dir)
+                    .with_context(|| format!("Failed to remove `{}`", dir.display()))
+                    .map_err(error::CeVIOError::from)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 書き込みのスレッドに渡す 1 行
+struct Job {
+    text: String,
+    cast: Option<String>,
+    /// 後処理をしていない合成結果。キャッシュを再利用する場合は `None`
+    raw: Option<PathBuf>,
+    cached: PathBuf,
+    output: PathBuf,
+}
+
+impl Job {
+    /// 合成結果に後処理をして `cache/` に移し、`outputs/` に書き込む
+    ///
+    /// 既存のファイルを残す場合は、そのパスを返す
+    fn run(
+        self,
+        processors: &[Arc<dyn OutputProcessor>],
+        policy: OverwritePolicy,
+    ) -> error::Result<PathBuf> {
+        if let Some(raw) = &self.raw {
+            let meta = OutputMeta {
+                text: &self.text,
+                cast: self.cast.as_deref(),
+                path: Some(&self.cached),
+            };
+            processor::apply(processors, &meta, raw)
+                .and_then(|()| {
+                    fs::rename(raw, &self.cached)
+                        .with_context(|| format!("Failed to move to `{}`", self.cached.display()))
+                        .map_err(error::CeVIOError::from)
+                })
+                .inspect_err(|_| {
+                    let _ = fs::remove_file(raw);
+                })?;
+        }
+        match policy.resolve(&self.output)? {
+            Some(output) => {
+                fs::copy(&self.cached, &output)
+                    .with_context(|| format!("Failed to copy to `{}`", output.display()))
+                    .map_err(error::CeVIOError::from)?;
+                Ok(output)
+            }
+            None => Ok(self.output),
+        }
+    }
+}
+
+fn parse_script(s: &str) -> Vec<ScriptLine> {
+    s.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if let Some((preset, text)) =
+                line.strip_prefix('[').and_then(|rest| rest.split_once(']'))
+            {
+                ScriptLine {
+                    preset: Some(preset.trim().to_string()),
+                    text: text.trim().to_string(),
+                }
+            } else {
+                ScriptLine {
+                    preset: None,
+                    text: line.to_string(),
+                }
+            }
+        })
+        // プリセット名だけの行は合成するセリフがない
+        .filter(|line| !line.text.is_empty())
+        .collect()
+}
+
+/// `早口` プリセット（`speed = 80`）と台本 `script` のあるプロジェクトを一時ディレクトリに作る（テスト用）
+#[cfg(test)]
+pub(crate) fn temp_project(script: &str) -> (crate::temp::TempDir, Project) {
+    let dir = crate::temp::TempDir::new();
+    fs::create_dir_all(dir.path().join("presets")).unwrap();
+    fs::write(dir.path().join("presets").join("早口.txt"), "speed = 80\n").unwrap();
+    fs::write(dir.path().join("script.txt"), script).unwrap();
+    let project = Project::open(dir.path()).unwrap();
+    (dir, project)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(preset: Option<&str>, text: &str) -> ScriptLine {
+        ScriptLine {
+            preset: preset.map(str::to_string),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_script_skips_comments_and_blank_lines() {
+        let script = parse_script(
+            "# 冒頭\n\n  \nこんにちは。\r\n  # 字下げしたコメント\n  さようなら。  \n",
+        );
+        assert_eq!(
+            script,
+            [line(None, "こんにちは。"), line(None, "さようなら。")]
+        );
+        assert!(parse_script("").is_empty());
+    }
+
+    #[test]
+    fn parse_script_reads_preset_tags() {
+        let script = parse_script("[早口] こんにちは。\n  [ 元気 ]よろしく！  \n[早口]a]b");
+        assert_eq!(
+            script,
+            [
+                line(Some("早口"), "こんにちは。"),
+                line(Some("元気"), "よろしく！"),
+                // 最初の `]` までがプリセット名
+                line(Some("早口"), "a]b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_script_keeps_unterminated_bracket_in_text() {
+        let script = parse_script("[早口 こんにちは。\n文中の [早口] はそのまま");
+        assert_eq!(
+            script,
+            [
+                line(None, "[早口 こんにちは。"),
+                line(None, "文中の [早口] はそのまま"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_script_skips_tag_only_lines() {
+        let script = parse_script("[早口]\n[早口]   \n[早口] こんにちは。");
+        assert_eq!(script, [line(Some("早口"), "こんにちは。")]);
+    }
+
+    #[test]
+    fn open_reads_script_and_presets() {
+        let (_dir, project) = temp_project("[早口] こんにちは。\nさようなら。\n");
+        assert_eq!(
+            project.script,
+            [
+                line(Some("早口"), "こんにちは。"),
+                line(None, "さようなら。")
+            ]
+        );
+        assert_eq!(project.presets["早口"].speed, Some(80));
+    }
+
+    #[test]
+    fn open_rejects_unknown_preset() {
+        let (dir, _) = temp_project("こんにちは。\n");
+        fs::write(dir.path().join(SCRIPT_FILE), "[早口] 一\n[遅口] 二\n").unwrap();
+        let e = Project::open(dir.path()).unwrap_err();
+        assert!(matches!(e, error::CeVIOError::InvalidInput(_)));
+        assert!(format!("{e:#}").contains("Unknown preset `遅口`"), "{e:#}");
+    }
+}
@@ -0,0 +1,491 @@
+//! 複数のクライアントからの読み上げを、優先度と公平性を考えて順番に再生するキュー
+//!
+//! - 優先度の高いクライアントのセリフから再生します。
+//! - 同じ優先度のクライアントが複数いる場合は、1 つずつ交互に再生します（ラウンドロビン）。
+//! - `Priority::Alert` のセリフが届くと、再生中の低い優先度のセリフを停止してすぐに再生します。
+//!
+//! ```no_run
+//! use cevio::{actor::Handle, queue::{Priority, SpeechQueue}, HostKind, Params};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! let queue = SpeechQueue::new(handle).unwrap();
+//!
+//! queue.set_priority("alert", Priority::Alert);
+//! queue.push("chat", "こんにちは。", Params::default());
+//! queue.push("alert", "地震です。", Params::from("花隈千冬")); // チャットの再生を止めて再生
+//! ```
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use crate::{
+    actor::Handle,
+    backend::TalkerBackend,
+    error::{self, Context as _},
+    metrics, Params,
+};
+
+/// 再生が終わったかを確認する間隔。単位は秒
+const POLL_INTERVAL: f64 = 0.05;
+
+/// クライアントの優先度です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    any(feature = "server", feature = "pipe"),
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Priority {
+    /// 低い
+    Low,
+    /// 通常
+    #[default]
+    Normal,
+    /// 高い
+    High,
+    /// 割り込み。再生中の低い優先度のセリフを停止します。
+    Alert,
+}
+
+/// キューに入っているセリフです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(any(feature = "server", feature = "pipe"), derive(serde::Serialize))]
+pub struct QueueItem {
+    /// 識別子
+    pub id: u64,
+    /// クライアント名
+    pub client: String,
+    /// セリフ
+    pub text: String,
+}
+
+/// クライアントごとの状態です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(any(feature = "server", feature = "pipe"), derive(serde::Serialize))]
+pub struct ClientStatus {
+    /// クライアント名
+    pub client: String,
+    /// 優先度
+    pub priority: Priority,
+    /// 再生待ちのセリフ
+    pub queued: Vec<QueueItem>,
+    /// 再生したセリフの数（停止・失敗を含む）
+    pub spoken: u64,
+    /// 最後に失敗したときのエラー
+    pub last_error: Option<String>,
+}
+
+/// キュー全体の状態です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(any(feature = "server", feature = "pipe"), derive(serde::Serialize))]
+pub struct QueueStatus {
+    /// 再生中のセリフ
+    pub speaking: Option<QueueItem>,
+    /// クライアントごとの状態
+    pub clients: Vec<ClientStatus>,
+}
+
+struct Item {
+    id: u64,
+    text: String,
+    params: Params,
+}
+
+#[derive(Default)]
+struct Client {
+    priority: Priority,
+    items: VecDeque<Item>,
+    /// 最後に再生した順番。同じ優先度ではこれが小さいクライアントから再生する
+    last_turn: u64,
+    spoken: u64,
+    last_error: Option<String>,
+}
+
+struct Speaking {
+    item: QueueItem,
+    priority: Priority,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct State {
+    clients: BTreeMap<String, Client>,
+    speaking: Option<Speaking>,
+    next_id: u64,
+    turn: u64,
+    closed: bool,
+}
+
+impl State {
+    fn client_status(&self, name: &str, client: &Client) -> ClientStatus {
+        ClientStatus {
+            client: name.to_string(),
+            priority: client.priority,
+            queued: client
+                .items
+                .iter()
+                .map(|item| QueueItem {
+                    id: item.id,
+                    client: name.to_string(),
+                    text: item.text.clone(),
+                })
+                .collect(),
+            spoken: client.spoken,
+            last_error: client.last_error.clone(),
+        }
+    }
+
+    /// セリフを追加し、識別子を返す。`Priority::Alert` の場合は再生中の低い優先度のセリフを停止する
+    fn push(&mut self, client: &str, text: String, params: Params) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        let entry = self.clients.entry(client.to_string()).or_default();
+        entry.items.push_back(Item { id, text, params });
+        let priority = entry.priority;
+        if priority == Priority::Alert {
+            if let Some(speaking) = &self.speaking {
+                if speaking.priority < priority {
+                    speaking.stop.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+        id
+    }
+
+    /// 次に再生するセリフを取り出す
+    fn pop(&mut self) -> Option<(String, Priority, Item)> {
+        let (name, client) = self
+            .clients
+            .iter_mut()
+            .filter(|(_, client)| !client.items.is_empty())
+            .max_by(|(_, a), (_, b)| {
+                a.priority
+                    .cmp(&b.priority)
+                    .then(b.last_turn.cmp(&a.last_turn))
+            })?;
+        self.turn += 1;
+        client.last_turn = self.turn;
+        let item = client.items.pop_front()?;
+        Some((name.clone(), client.priority, item))
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // 再生スレッドはロック中にパニックしないので、ポイズンされていても状態は壊れていない
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct Inner {
+    shared: Arc<Shared>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        if let Some(speaking) = &state.speaking {
+            speaking.stop.store(true, Ordering::SeqCst);
+        }
+        self.shared.ready.notify_all();
+    }
+}
+
+/// 複数のクライアントからの読み上げを優先度付きで順番に再生するキューです。
+///
+/// `Clone` でき、すべてのキューが破棄されると再生スレッドも終了します。
+#[derive(Clone)]
+pub struct SpeechQueue {
+    inner: Arc<Inner>,
+}
+
+impl SpeechQueue {
+    /// 再生スレッドを起動し、`handle` で再生するキューを作成します。
+    pub fn new(handle: Handle) -> error::Result<Self> {
+        Self::spawn(move |text, params, stop| {
+            let (text, params) = (text.to_string(), params.clone());
+            handle.call(move |cevio| speak_until_stopped(cevio, &text, &params, &stop))
+        })
+    }
+
+    /// 再生スレッドを起動し、`backend` で再生するキューを作成します。
+    ///
+    /// `backend` は再生スレッドに移して使います。`backend::MockBackend` などを使うと、CeVIO のない環境でキューを使う処理をテストできます。
+    pub fn with_backend<B: TalkerBackend + Send + 'static>(backend: B) -> error::Result<Self> {
+        Self::spawn(move |text, params, stop| speak_until_stopped(&backend, text, params, &stop))
+    }
+
+    /// `speak` で 1 つずつ再生する再生スレッドを起動する
+    fn spawn(
+        speak: impl FnMut(&str, &Params, Arc<AtomicBool>) -> error::Result<()> + Send + 'static,
+    ) -> error::Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::default(),
+            ready: Condvar::new(),
+        });
+        let worker = shared.clone();
+        thread::Builder::new()
+            .name("cevio-queue".to_string())
+            .spawn(move || run(worker, speak))
+            .context("Failed to spawn queue thread")
+            .map_err(error::CeVIOError::from)?;
+        Ok(Self {
+            inner: Arc::new(Inner { shared }),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.inner.shared.lock()
+    }
+
+    /// クライアントの優先度を設定します。
+    ///
+    /// 未登録のクライアントは `Priority::Normal` です。
+    pub fn set_priority(&self, client: &str, priority: Priority) {
+        self.lock()
+            .clients
+            .entry(client.to_string())
+            .or_default()
+            .priority = priority;
+    }
+
+    /// セリフをキューに追加し、識別子を返します。
+    ///
+    /// `params` はこのセリフの間だけ使い、再生が終わると元の値に戻します。
+    pub fn push(&self, client: &str, text: impl Into<String>, params: Params) -> u64 {
+        let id = self.lock().push(client, text.into(), params);
+        self.inner.shared.ready.notify_all();
+        id
+    }
+
+    /// クライアントの再生待ちのセリフを削除し、削除した数を返します。
+    ///
+    /// そのクライアントのセリフを再生中の場合は停止します。
+    pub fn flush(&self, client: &str) -> usize {
+        let mut state = self.lock();
+        if let Some(speaking) = &state.speaking {
+            if speaking.item.client == client {
+                speaking.stop.store(true, Ordering::SeqCst);
+            }
+        }
+        state
+            .clients
+            .get_mut(client)
+            .map(|client| client.items.drain(..).count())
+            .unwrap_or(0)
+    }
+
+    /// キュー全体の状態を取得します。
+    pub fn status(&self) -> QueueStatus {
+        let state = self.lock();
+        QueueStatus {
+            speaking: state.speaking.as_ref().map(|s| s.item.clone()),
+            clients: state
+                .clients
+                .iter()
+                .map(|(name, client)| state.client_status(name, client))
+                .collect(),
+        }
+    }
+
+    /// クライアントの状態を取得します。
+    ///
+    /// 一度もセリフの追加や優先度の設定をしていないクライアントは `None` です。
+    pub fn client_status(&self, client: &str) -> Option<ClientStatus> {
+        let state = self.lock();
+        state
+            .clients
+            .get(client)
+            .map(|c| state.client_status(client, c))
+    }
+}
+
+fn run(
+    shared: Arc<Shared>,
+    mut speak: impl FnMut(&str, &Params, Arc<AtomicBool>) -> error::Result<()>,
+) {
+    loop {
+        let (client, item, stop) = {
+            let mut state = shared.lock();
+            let (client, priority, item) = loop {
+                if state.closed {
+                    return;
+                }
+                if let Some(next) = state.pop() {
+                    break next;
+                }
+                state = shared.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+            };
+            let stop = Arc::new(AtomicBool::new(false));
+            state.speaking = Some(Speaking {
+                item: QueueItem {
+                    id: item.id,
+                    client: client.clone(),
+                    text: item.text.clone(),
+                },
+                priority,
+                stop: stop.clone(),
+            });
+            (client, item, stop)
+        };
+
+        let result = metrics::time_synthesis(|| speak(&item.text, &item.params, stop));
+
+        let mut state = shared.lock();
+        state.speaking = None;
+        if let Some(client) = state.clients.get_mut(&client) {
+            client.spoken += 1;
+            if let Err(e) = result {
+                client.last_error = Some(format!("{e:#}"));
+            }
+        }
+    }
+}
+
+/// `params` を設定してセリフを再生し、終わるか `stop` が `true` になるまで待つ
+///
+/// `params` はこのセリフの間だけ使い、終わると元の値に戻す
+pub(crate) fn speak_until_stopped(
+    backend: &impl TalkerBackend,
+    text: &str,
+    params: &Params,
+    stop: &AtomicBool,
+) -> error::Result<()> {
+    backend.with_params(params, || {
+        let state = backend.speak(text)?;
+        while !state.is_completed()? {
+            if stop.load(Ordering::SeqCst) {
+                backend.stop()?;
+                break;
+            }
+            state.wait_timeout(POLL_INTERVAL)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(priorities: &[(&str, Priority)]) -> State {
+        let mut state = State::default();
+        for (client, priority) in priorities {
+            state
+                .clients
+                .entry(client.to_string())
+                .or_default()
+                .priority = *priority;
+        }
+        state
+    }
+
+    fn push(state: &mut State, client: &str, text: &str) -> u64 {
+        state.push(client, text.to_string(), Params::default())
+    }
+
+    fn pop_text(state: &mut State) -> Option<String> {
+        state.pop().map(|(_, _, item)| item.text)
+    }
+
+    #[test]
+    fn higher_priority_wins() {
+        let mut state = state(&[("low", Priority::Low), ("high", Priority::High)]);
+        push(&mut state, "low", "low-1");
+        push(&mut state, "high", "high-1");
+        push(&mut state, "high", "high-2");
+        assert_eq!(pop_text(&mut state).as_deref(), Some("high-1"));
+        assert_eq!(pop_text(&mut state).as_deref(), Some("high-2"));
+        assert_eq!(pop_text(&mut state).as_deref(), Some("low-1"));
+        assert_eq!(pop_text(&mut state), None);
+    }
+
+    #[test]
+    fn equal_priority_clients_alternate() {
+        let mut state = State::default();
+        for i in 1..=3 {
+            push(&mut state, "a", &format!("a-{i}"));
+        }
+        for i in 1..=2 {
+            push(&mut state, "b", &format!("b-{i}"));
+        }
+        let order = std::iter::from_fn(|| pop_text(&mut state)).collect::<Vec<_>>();
+        // どちらが先でも、片方が空になるまで交互に再生する
+        let clients = order.iter().map(|text| &text[..1]).collect::<Vec<_>>();
+        assert!(clients[..4].windows(2).all(|w| w[0] != w[1]), "{order:?}");
+        assert_eq!(order.last().map(String::as_str), Some("a-3"));
+        // クライアントの中では追加した順
+        let of = |client: &str| {
+            order
+                .iter()
+                .filter(|text| text.starts_with(client))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(of("a"), ["a-1", "a-2", "a-3"]);
+        assert_eq!(of("b"), ["b-1", "b-2"]);
+    }
+
+    #[test]
+    fn pop_returns_client_and_priority() {
+        let mut state = state(&[("bot", Priority::High)]);
+        let id = push(&mut state, "bot", "hello");
+        let (client, priority, item) = state.pop().unwrap();
+        assert_eq!(
+            (client.as_str(), priority, item.id),
+            ("bot", Priority::High, id)
+        );
+    }
+
+    fn speaking(priority: Priority) -> (Speaking, Arc<AtomicBool>) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let speaking = Speaking {
+            item: QueueItem {
+                id: 0,
+                client: "other".to_string(),
+                text: String::new(),
+            },
+            priority,
+            stop: stop.clone(),
+        };
+        (speaking, stop)
+    }
+
+    #[test]
+    fn alert_stops_lower_priority_speech() {
+        let mut state = state(&[("alert", Priority::Alert)]);
+        let (speaking, stop) = speaking(Priority::High);
+        state.speaking = Some(speaking);
+        push(&mut state, "alert", "fire");
+        assert!(stop.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn alert_does_not_stop_alert_speech() {
+        let mut state = state(&[("alert", Priority::Alert)]);
+        let (speaking, stop) = speaking(Priority::Alert);
+        state.speaking = Some(speaking);
+        push(&mut state, "alert", "fire");
+        assert!(!stop.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn non_alert_push_does_not_stop_speech() {
+        let mut state = state(&[("high", Priority::High)]);
+        let (speaking, stop) = speaking(Priority::Low);
+        state.speaking = Some(speaking);
+        push(&mut state, "high", "hello");
+        assert!(!stop.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,258 @@
+//! 実際の読みの確認
+//!
+//! `CeVIO::check_reading` は音声を合成せずに音素データ（`GetPhonemes`）を取得し、カタカナの読みに変換します。
+//! 動画全体を出力する前に、人名や専門用語の読みをまとめて確認するために使います。
+//!
+//! 日本語を読めないアニメーターや音声の研究者向けに、音素をヘボン式ローマ字（`phonemes_to_romaji`）と IPA（`phonemes_to_ipa`）にも変換できます。
+//! タイミングのデータには、音素ごとに変換する `phoneme_to_ipa` を使います。
+//!
+//! ```no_run
+//! use cevio::CeVIO;
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let reading = cevio.check_reading("花隈千冬です。").unwrap();
+//! println!("{}", reading.kana);
+//! ```
+//!
+//! ```
+//! use cevio::reading;
+//!
+//! let phonemes = ["sil", "k", "o", "N", "n", "i", "ch", "i", "w", "a", "pau", "cl", "t", "e", "sil"];
+//! assert_eq!(reading::phonemes_to_kana(phonemes), "コンニチワ、ッテ");
+//! assert_eq!(reading::phonemes_to_romaji(phonemes), "konnichiwa tte");
+//! assert_eq!(reading::phonemes_to_romaji(["m", "a", "cl", "ch", "a"]), "matcha");
+//! assert_eq!(reading::phonemes_to_ipa(phonemes), "koɴnit͡ɕiɰa ʔte");
+//! assert_eq!(reading::phoneme_to_ipa("sh"), Some("ɕ"));
+//! ```
+
+use crate::{error, CeVIO, PhonemeData};
+
+/// セリフの実際の読みです。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    /// セリフ
+    pub text: String,
+    /// 音素データ
+    pub phonemes: Vec<PhonemeData>,
+    /// カタカナの読み。無音（`pau`）は `、` です
+    pub kana: String,
+}
+
+impl Reading {
+    /// ヘボン式ローマ字の読みを取得します。
+    pub fn romaji(&self) -> String {
+        phonemes_to_romaji(self.phonemes.iter().map(|p| p.phoneme.as_str()))
+    }
+
+    /// IPA の読みを取得します。
+    pub fn ipa(&self) -> String {
+        phonemes_to_ipa(self.phonemes.iter().map(|p| p.phoneme.as_str()))
+    }
+}
+
+impl CeVIO {
+    /// 音声を合成せずに、現在のキャストとパラメータでの実際の読みを取得します。
+    pub fn check_reading(&self, text: &str) -> error::Result<Reading> {
+        let phonemes = self.get_phonemes(text)?;
+        let kana = phonemes_to_kana(phonemes.iter().map(|p| p.phoneme.as_str()));
+        Ok(Reading {
+            text: text.to_string(),
+            phonemes,
+            kana,
+        })
+    }
+}
+
+/// 母音の順番（ア段、イ段、ウ段、エ段、オ段）
+const VOWELS: [&str; 5] = ["a", "i", "u", "e", "o"];
+
+/// 子音と、母音ごとのカナ（ない組み合わせは空文字列）
+const SYLLABLES: &[(&str, [&str; 5])] = &[
+    ("", ["ア", "イ", "ウ", "エ", "オ"]),
+    ("k", ["カ", "キ", "ク", "ケ", "コ"]),
+    ("ky", ["キャ", "", "キュ", "キェ", "キョ"]),
+    ("g", ["ガ", "ギ", "グ", "ゲ", "ゴ"]),
+    ("gy", ["ギャ", "", "ギュ", "ギェ", "ギョ"]),
+    ("s", ["サ", "スィ", "ス", "セ", "ソ"]),
+    ("sh", ["シャ", "シ", "シュ", "シェ", "ショ"]),
+    ("z", ["ザ", "ズィ", "ズ", "ゼ", "ゾ"]),
+    ("j", ["ジャ", "ジ", "ジュ", "ジェ", "ジョ"]),
+    ("t", ["タ", "ティ", "トゥ", "テ", "ト"]),
+    ("ty", ["テャ", "", "テュ", "", "テョ"]),
+    ("ch", ["チャ", "チ", "チュ", "チェ", "チョ"]),
+    ("ts", ["ツァ", "ツィ", "ツ", "ツェ", "ツォ"]),
+    ("d", ["ダ", "ディ", "ドゥ", "デ", "ド"]),
+    ("dy", ["デャ", "", "デュ", "", "デョ"]),
+    ("n", ["ナ", "ニ", "ヌ", "ネ", "ノ"]),
+    ("ny", ["ニャ", "", "ニュ", "ニェ", "ニョ"]),
+    ("h", ["ハ", "ヒ", "フ", "ヘ", "ホ"]),
+    ("hy", ["ヒャ", "", "ヒュ", "ヒェ", "ヒョ"]),
+    ("f", ["ファ", "フィ", "フ", "フェ", "フォ"]),
+    ("b", ["バ", "ビ", "ブ", "ベ", "ボ"]),
+    ("by", ["ビャ", "", "ビュ", "ビェ", "ビョ"]),
+    ("p", ["パ", "ピ", "プ", "ペ", "ポ"]),
+    ("py", ["ピャ", "", "ピュ", "ピェ", "ピョ"]),
+    ("m", ["マ", "ミ", "ム", "メ", "モ"]),
+    ("my", ["ミャ", "", "ミュ", "ミェ", "ミョ"]),
+    ("y", ["ヤ", "イ", "ユ", "イェ", "ヨ"]),
+    ("r", ["ラ", "リ", "ル", "レ", "ロ"]),
+    ("ry", ["リャ", "", "リュ", "リェ", "リョ"]),
+    ("w", ["ワ", "ウィ", "ウ", "ウェ", "ウォ"]),
+    ("v", ["ヴァ", "ヴィ", "ヴ", "ヴェ", "ヴォ"]),
+];
+
+/// CeVIO の音素の並びをカタカナに変換します。
+///
+/// 無声化した母音（大文字）は通常の母音として扱います。`N` は `ン`、`cl` は `ッ`、`pau` は `、` にし、`sil` は無視します。
+/// カナにできない音素は `[音素]` のように残します。
+pub fn phonemes_to_kana<'a>(phonemes: impl IntoIterator<Item = &'a str>) -> String {
+    let mut kana = String::new();
+    // 母音が続くのを待っている子音
+    let mut consonant: Option<&str> = None;
+    let flush = |kana: &mut String, consonant: Option<&str>| {
+        if let Some(consonant) = consonant {
+            kana.push_str(&format!("[{consonant}]"));
+        }
+    };
+    for phoneme in phonemes {
+        let vowel = phoneme.to_ascii_lowercase();
+        if let Some(i) = VOWELS.iter().position(|v| *v == vowel) {
+            match syllable(consonant.unwrap_or(""), i) {
+                Some(syllable) => kana.push_str(syllable),
+                None => kana.push_str(&format!("[{}{phoneme}]", consonant.unwrap_or(""))),
+            }
+            consonant = None;
+            continue;
+        }
+        flush(&mut kana, consonant.take());
+        match phoneme {
+            "sil" => {}
+            "pau" => kana.push('、'),
+            "N" => kana.push('ン'),
+            "cl" => kana.push('ッ'),
+            _ => consonant = Some(phoneme),
+        }
+    }
+    flush(&mut kana, consonant);
+    kana
+}
+
+fn syllable(consonant: &str, vowel: usize) -> Option<&'static str> {
+    SYLLABLES
+        .iter()
+        .find(|(c, _)| *c == consonant)
+        .map(|(_, row)| row[vowel])
+        .filter(|kana| !kana.is_empty())
+}
+
+/// 音素と IPA
+const IPA: &[(&str, &str)] = &[
+    ("a", "a"),
+    ("i", "i"),
+    ("u", "ɯ"),
+    ("e", "e"),
+    ("o", "o"),
+    ("A", "ḁ"),
+    ("I", "i̥"),
+    ("U", "ɯ̥"),
+    ("E", "e̥"),
+    ("O", "o̥"),
+    ("N", "ɴ"),
+    ("cl", "ʔ"),
+    ("k", "k"),
+    ("ky", "kʲ"),
+    ("g", "ɡ"),
+    ("gy", "ɡʲ"),
+    ("s", "s"),
+    ("sh", "ɕ"),
+    ("z", "z"),
+    ("j", "d͡ʑ"),
+    ("t", "t"),
+    ("ty", "tʲ"),
+    ("ch", "t͡ɕ"),
+    ("ts", "t͡s"),
+    ("d", "d"),
+    ("dy", "dʲ"),
+    ("n", "n"),
+    ("ny", "ɲ"),
+    ("h", "h"),
+    ("hy", "ç"),
+    ("f", "ɸ"),
+    ("b", "b"),
+    ("by", "bʲ"),
+    ("p", "p"),
+    ("py", "pʲ"),
+    ("m", "m"),
+    ("my", "mʲ"),
+    ("y", "j"),
+    ("r", "ɾ"),
+    ("ry", "ɾʲ"),
+    ("w", "ɰ"),
+    ("v", "v"),
+];
+
+/// 音素 1 つを IPA に変換します。`sil`、`pau` と、変換できない音素は `None` です。
+///
+/// 促音（`cl`）は `ʔ`、撥音（`N`）は `ɴ`、無声化した母音（大文字）は `̥` を付けた母音です。
+pub fn phoneme_to_ipa(phoneme: &str) -> Option<&'static str> {
+    IPA.iter().find(|(p, _)| *p == phoneme).map(|(_, ipa)| *ipa)
+}
+
+/// CeVIO の音素の並びを IPA に変換します。
+///
+/// `phoneme_to_ipa` と同じですが、`h` は `i` の前では `ç` にします。`pau` は空白にし、`sil` は無視します。
+/// 変換できない音素は `[音素]` のように残します。
+pub fn phonemes_to_ipa<'a>(phonemes: impl IntoIterator<Item = &'a str>) -> String {
+    let mut ipa = String::new();
+    let mut phonemes = phonemes.into_iter().peekable();
+    while let Some(phoneme) = phonemes.next() {
+        match phoneme {
+            "sil" => {}
+            "pau" => ipa.push(' '),
+            "h" if phonemes.peek().is_some_and(|p| p.eq_ignore_ascii_case("i")) => ipa.push('ç'),
+            _ => match phoneme_to_ipa(phoneme) {
+                Some(s) => ipa.push_str(s),
+                None => ipa.push_str(&format!("[{phoneme}]")),
+            },
+        }
+    }
+    ipa
+}
+
+/// CeVIO の音素の並びをヘボン式ローマ字に変換します。
+///
+/// 無声化した母音（大文字）は通常の母音にします。`N` は `n`（母音と `y` の前は `n'`）、
+/// `cl` は次の子音を重ね（`ch` の前は `t`）、`pau` は空白にし、`sil` は無視します。
+/// 長音は母音を重ねたままにします。
+pub fn phonemes_to_romaji<'a>(phonemes: impl IntoIterator<Item = &'a str>) -> String {
+    let mut romaji = String::new();
+    let mut phonemes = phonemes.into_iter().peekable();
+    while let Some(phoneme) = phonemes.next() {
+        let next = phonemes.peek().copied().unwrap_or("");
+        match phoneme {
+            "sil" => {}
+            "pau" => romaji.push(' '),
+            "N" => {
+                let vowel = VOWELS.iter().any(|v| v.eq_ignore_ascii_case(next));
+                romaji.push_str(if vowel || next.starts_with('y') {
+                    "n'"
+                } else {
+                    "n"
+                });
+            }
+            "cl" => match next {
+                n if n.starts_with("ch") => romaji.push('t'),
+                "sil" | "pau" => romaji.push('\''),
+                n => match n.chars().next().filter(|c| !"aiueoAIUEON".contains(*c)) {
+                    Some(c) => romaji.push(c),
+                    // 子音が続かない促音
+                    None => romaji.push('\''),
+                },
+            },
+            _ => romaji.push_str(&phoneme.to_ascii_lowercase()),
+        }
+    }
+    romaji
+}
@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::{config::ServerConfig, error, Params};
+
+/// サーバーが受け取るパラメータ部分
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ParamsBody {
+    cast: Option<String>,
+    volume: Option<i32>,
+    speed: Option<i32>,
+    tone: Option<i32>,
+    tone_scale: Option<i32>,
+    alpha: Option<i32>,
+    #[serde(default)]
+    components: BTreeMap<String, i32>,
+}
+
+impl From<ParamsBody> for Params {
+    fn from(body: ParamsBody) -> Self {
+        Params {
+            cast: body.cast,
+            volume: body.volume,
+            speed: body.speed,
+            tone: body.tone,
+            tone_scale: body.tone_scale,
+            alpha: body.alpha,
+            components: body.components.into_iter().collect(),
+        }
+    }
+}
+
+/// サーバーが受け取るセリフとパラメータ
+#[derive(Debug, Deserialize)]
+pub(crate) struct TextBody {
+    pub(crate) text: String,
+    pub(crate) preset: Option<String>,
+    #[serde(flatten)]
+    pub(crate) params: ParamsBody,
+}
+
+impl TextBody {
+    /// 設定を適用したセリフとパラメータを返す
+    pub(crate) fn resolve(self, config: &ServerConfig) -> error::Result<(String, Params)> {
+        let text = config.prepare_text(&self.text)?;
+        let params = config.params_for(self.preset.as_deref(), &Params::from(self.params))?;
+        Ok((text, params))
+    }
+}
@@ -0,0 +1,174 @@
+//! 一度だけパラメータを変えて合成する
+//!
+//! `CeVIO::say` でセリフを指定し、キャストやパラメータを続けて指定してから `play`、`to_file`、`to_vec` で合成します。
+//! 指定したキャストとパラメータは合成の間だけ使い、終わると元の値に戻します。
+//!
+//! ```no_run
+//! use cevio::CeVIO;
+//!
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! cevio
+//!     .say("こんにちは")
+//!     .cast("さとうささら")
+//!     .speed(60)
+//!     .emotion("哀しみ", 40)
+//!     .to_file(r"E:\hello.wav")
+//!     .unwrap();
+//! // キャストは花隈千冬のまま
+//! cevio.say("こんにちは").play().unwrap();
+//! ```
+
+use std::path::Path;
+
+use crate::{error, CeVIO, Params};
+
+/// 一度だけパラメータを変えて合成するためのビルダーです。
+///
+/// `CeVIO::say` で作成します。
+#[must_use = "`play`、`to_file`、`to_vec` を呼ぶまで合成されません"]
+pub struct Say<'a> {
+    cevio: &'a CeVIO,
+    text: String,
+    params: Params,
+}
+
+impl CeVIO {
+    /// セリフを指定し、一度だけパラメータを変えて合成するビルダーを取得します。
+    pub fn say(&self, text: impl Into<String>) -> Say<'_> {
+        Say {
+            cevio: self,
+            text: text.into(),
+            params: Params::default(),
+        }
+    }
+
+    /// `params` を設定して `f` を実行し、元の値に戻す
+    pub(crate) fn with_params<T>(
+        &self,
+        params: &Params,
+        f: impl FnOnce(&CeVIO) -> error::Result<T>,
+    ) -> error::Result<T> {
+        self.say(String::new())
+            .params(params)
+            .run(|cevio, _| f(cevio))
+    }
+}
+
+impl Say<'_> {
+    /// キャストを指定します。
+    pub fn cast(mut self, cast: impl Into<String>) -> Self {
+        self.params.cast = Some(cast.into());
+        self
+    }
+
+    /// 音の大きさ（0～100）を指定します。
+    pub fn volume(mut self, volume: i32) -> Self {
+        self.params.volume = Some(volume);
+        self
+    }
+
+    /// 話す速さ（0～100）を指定します。
+    pub fn speed(mut self, speed: i32) -> Self {
+        self.params.speed = Some(speed);
+        self
+    }
+
+    /// 音の高さ（0～100）を指定します。
+    pub fn tone(mut self, tone: i32) -> Self {
+        self.params.tone = Some(tone);
+        self
+    }
+
+    /// 抑揚（0～100）を指定します。
+    pub fn tone_scale(mut self, tone_scale: i32) -> Self {
+        self.params.tone_scale = Some(tone_scale);
+        self
+    }
+
+    /// 声質（0～100）を指定します。
+    pub fn alpha(mut self, alpha: i32) -> Self {
+        self.params.alpha = Some(alpha);
+        self
+    }
+
+    /// 感情パラメータ（0～100）を指定します。
+    pub fn emotion(mut self, name: impl Into<String>, value: i32) -> Self {
+        self.params.components.push((name.into(), value));
+        self
+    }
+
+    /// パラメータをまとめて指定します。個別に指定した項目は `params` で上書きされます。
+    pub fn params(mut self, params: &Params) -> Self {
+        self.params = self.params.merge(params);
+        self
+    }
+
+    /// 再生し、再生終了まで待ちます。
+    pub fn play(self) -> error::Result<()> {
+        // 再生中にパラメータを戻さないよう、終わるまで待つ
+        self.run(|cevio, text| cevio.speak(text)?.wait())
+    }
+
+    /// WAV ファイルに出力します。
+    pub fn to_file(self, path: impl AsRef<Path>) -> error::Result<()> {
+        self.run(|cevio, text| cevio.output_wave_to_file(text, path))
+    }
+
+    /// WAV 形式のバイト列に出力します。
+    pub fn to_vec(self) -> error::Result<Vec<u8>> {
+        self.run(|cevio, text| cevio.output_wave_to_vec(text))
+    }
+
+    /// パラメータを設定して `f` を実行し、元の値に戻す
+    fn run<T>(self, f: impl FnOnce(&CeVIO, &str) -> error::Result<T>) -> error::Result<T> {
+        let cevio = self.cevio;
+        if self.params == Params::default() {
+            return f(cevio, &self.text);
+        }
+        let original = self.current()?;
+        let result = cevio
+            .apply_params(&self.params)
+            .and_then(|()| f(cevio, &self.text));
+        // 合成に失敗した場合も戻し、合成のエラーを優先する
+        let restored = cevio.apply_params(&original);
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
+    /// 変更する項目の現在の値を取得する
+    fn current(&self) -> error::Result<Params> {
+        let cevio = self.cevio;
+        let cast = cevio.get_cast()?;
+        // キャストを変えるとパラメータが初期化されるため、すべての値を戻す
+        let all = self.params.cast.as_ref().is_some_and(|c| *c != cast);
+        let get = |changed: bool, get: fn(&CeVIO) -> error::Result<i32>| match all || changed {
+            true => get(cevio).map(Some),
+            false => Ok(None),
+        };
+        let changed = |name: &str| self.params.components.iter().any(|(n, _)| n == name);
+        // 感情パラメータはキャストが設定されている場合だけ取得できる
+        let components = match cast.is_empty() {
+            true => Vec::new(),
+            false => cevio
+                .get_components()?
+                .into_iter()
+                .filter(|component| all || changed(&component.name))
+                .map(|component| (component.name, component.value))
+                .collect(),
+        };
+        Ok(Params {
+            // キャストが設定されていなかった場合は戻せない
+            cast: Some(cast).filter(|cast| all && !cast.is_empty()),
+            volume: get(self.params.volume.is_some(), CeVIO::get_volume)?,
+            speed: get(self.params.speed.is_some(), CeVIO::get_speed)?,
+            tone: get(self.params.tone.is_some(), CeVIO::get_tone)?,
+            tone_scale: get(self.params.tone_scale.is_some(), CeVIO::get_tone_scale)?,
+            alpha: get(self.params.alpha.is_some(), CeVIO::get_alpha)?,
+            components,
+        })
+    }
+}
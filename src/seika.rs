@@ -0,0 +1,360 @@
+//! AssistantSeika 互換 API（`server` フィーチャー）
+//!
+//! AssistantSeika の HTTP 機能のうち、次のものを CeVIO のキャストとパラメータに対応させて提供します。
+//! SeikaSay2 やコメント読み上げツールなど、AssistantSeika に対応したツールから CeVIO のキャストを使うことができます。
+//!
+//! | メソッド | パス                | 内容                                                   |
+//! | -------- | ------------------- | ------------------------------------------------------ |
+//! | `GET`    | `/AVATOR2`          | キャスト一覧                                           |
+//! | `GET`    | `/AVATOR2/{cid}`    | キャストのパラメータ（`effects`）と感情（`emotions`）  |
+//! | `POST`   | `/PLAY2/{cid}`      | セリフを再生し、再生終了後に応答します                 |
+//! | `POST`   | `/PLAYASYNC2/{cid}` | セリフの再生を依頼し、すぐに応答します                 |
+//! | `POST`   | `/SAVE2/{cid}`      | セリフを WAV に変換して返します（`audio/wav`）         |
+//!
+//! CID は CeVIO AI のキャストが `90001` から、CeVIO のキャストが `60001` から、
+//! `get_available_casts` で得られる順番に割り当てます。
+//!
+//! `POST` の本文は次の JSON で、`effects` と `emotions` は省略できます。
+//! `effects` は `volume`・`speed`・`pitch`・`alpha`・`intonation` で、それぞれ
+//! Volume・Speed・Tone・Alpha・ToneScale（0～100）に変換します。`emotions` は感情パラメータです。
+//!
+//! ```json
+//! { "talktext": "こんにちは。", "effects": { "speed": 55 }, "emotions": { "嬉しい": 50 } }
+//! ```
+//!
+//! 備考：
+//!
+//! 　キャストとパラメータはリクエストの間だけ変更し、応答の前に元の値に戻します。
+//!
+//! 　AssistantSeika の Basic 認証には対応していません。アクセス制限には `auth::Auth` を使ってください。
+//!
+//! ```no_run
+//! # async fn run() {
+//! use cevio::{actor::Handle, seika, HostKind};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! handle.call(|cevio| cevio.start_host(false)).unwrap();
+//! seika::serve("127.0.0.1:7180", handle).await.unwrap();
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    actor::Handle,
+    error::{self, report},
+    server::{call, time_synthesis, ServerError},
+    CeVIO, HostKind, Params,
+};
+
+/// 先頭のキャストの CID
+fn cid_base(host: HostKind) -> u32 {
+    match host {
+        #[cfg(feature = "cevio-cs")]
+        HostKind::Cs => 60001,
+        #[cfg(feature = "cevio-ai")]
+        HostKind::Ai => 90001,
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Avator {
+    cid: u32,
+    name: String,
+    prod: String,
+    platform: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Range {
+    value: f64,
+    min: f64,
+    max: f64,
+    step: f64,
+}
+
+impl Range {
+    fn percent(value: i32) -> Self {
+        Range {
+            value: value as f64,
+            min: 0.0,
+            max: 100.0,
+            step: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TalkBody {
+    talktext: String,
+    #[serde(default)]
+    effects: BTreeMap<String, f64>,
+    #[serde(default)]
+    emotions: BTreeMap<String, f64>,
+}
+
+impl TalkBody {
+    fn to_params(&self, cast: String) -> error::Result<Params> {
+        let value = |v: f64| v.round().clamp(0.0, 100.0) as i32;
+        let mut params = Params::from(cast.as_str());
+        for (name, &v) in &self.effects {
+            let v = Some(value(v));
+            match name.as_str() {
+                "volume" => params.volume = v,
+                "speed" => params.speed = v,
+                "pitch" => params.tone = v,
+                "alpha" => params.alpha = v,
+                "intonation" => params.tone_scale = v,
+                _ => {
+                    return Err(error::CeVIOError::InvalidInput(report!(
+                        "Unknown effect `{name}`"
+                    )))
+                }
+            }
+        }
+        params.components = self
+            .emotions
+            .iter()
+            .map(|(name, &v)| (name.clone(), value(v)))
+            .collect();
+        Ok(params)
+    }
+}
+
+fn cast_by_cid(cevio: &CeVIO, cid: u32) -> error::Result<String> {
+    // ホストが起動していない場合などは、キャストの指定の誤りにしない
+    let casts = cevio.get_available_casts()?;
+    cast_at(casts, cevio.host(), cid)
+}
+
+/// `casts`（`get_available_casts` の順番）のうち `cid` に割り当てたキャスト
+fn cast_at(casts: Vec<String>, host: HostKind, cid: u32) -> error::Result<String> {
+    cid.checked_sub(cid_base(host))
+        .and_then(|i| casts.into_iter().nth(i as usize))
+        .ok_or_else(|| report!("Unknown cid `{cid}`"))
+        .map_err(error::CeVIOError::InvalidCast)
+}
+
+async fn avators(State(handle): State<Handle>) -> Result<Json<Vec<Avator>>, ServerError> {
+    let (host, casts) = call(&handle, |cevio| {
+        Ok((cevio.host(), cevio.get_available_casts()?))
+    })
+    .await?;
+    let prod = match host {
+        #[cfg(feature = "cevio-cs")]
+        HostKind::Cs => "CeVIO CS",
+        #[cfg(feature = "cevio-ai")]
+        HostKind::Ai => "CeVIO AI",
+    };
+    Ok(Json(
+        casts
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| Avator {
+                cid: cid_base(host) + i as u32,
+                name,
+                prod: prod.to_string(),
+                platform: "64".to_string(),
+            })
+            .collect(),
+    ))
+}
+
+async fn avator(
+    State(handle): State<Handle>,
+    Path(cid): Path<u32>,
+) -> Result<Json<serde_json::Value>, ServerError> {
+    let (effects, emotions) = call(&handle, move |cevio| {
+        // 読み込むだけなので、ほかのリクエストに影響しないように元のキャストに戻す
+        let cast = Params::from(cast_by_cid(cevio, cid)?.as_str());
+        cevio.with_params(&cast, |cevio| {
+            let effects = BTreeMap::from([
+                ("volume", Range::percent(cevio.get_volume()?)),
+                ("speed", Range::percent(cevio.get_speed()?)),
+                ("pitch", Range::percent(cevio.get_tone()?)),
+                ("alpha", Range::percent(cevio.get_alpha()?)),
+                ("intonation", Range::percent(cevio.get_tone_scale()?)),
+            ]);
+            let emotions = cevio
+                .get_components()?
+                .into_iter()
+                .map(|c| (c.name, Range::percent(c.value)))
+                .collect::<BTreeMap<_, _>>();
+            Ok((effects, emotions))
+        })
+    })
+    .await?;
+    Ok(Json(json!({ "effects": effects, "emotions": emotions })))
+}
+
+/// キャストとパラメータを求める
+async fn resolve(handle: &Handle, cid: u32, body: &TalkBody) -> Result<Params, ServerError> {
+    let cast = call(handle, move |cevio| cast_by_cid(cevio, cid)).await?;
+    body.to_params(cast).map_err(ServerError::bad_request)
+}
+
+async fn play(
+    State(handle): State<Handle>,
+    Path(cid): Path<u32>,
+    Json(body): Json<TalkBody>,
+) -> Result<Json<serde_json::Value>, ServerError> {
+    let params = resolve(&handle, cid, &body).await?;
+    let start = std::time::Instant::now();
+    time_synthesis(call(&handle, move |cevio| {
+        cevio.say(body.talktext).params(&params).play()
+    }))
+    .await?;
+    Ok(Json(json!({ "time": start.elapsed().as_millis() as u64 })))
+}
+
+async fn play_async(
+    State(handle): State<Handle>,
+    Path(cid): Path<u32>,
+    Json(body): Json<TalkBody>,
+) -> Result<Json<serde_json::Value>, ServerError> {
+    let params = resolve(&handle, cid, &body).await?;
+    // 再生の完了は待たないため、再生の失敗は応答できない
+    handle.send(move |cevio| {
+        let _ = cevio.say(body.talktext).params(&params).play();
+    })?;
+    Ok(Json(json!({ "time": 0 })))
+}
+
+async fn save(
+    State(handle): State<Handle>,
+    Path(cid): Path<u32>,
+    Json(body): Json<TalkBody>,
+) -> Result<Response, ServerError> {
+    let params = resolve(&handle, cid, &body).await?;
+    let wav = time_synthesis(call(&handle, move |cevio| {
+        cevio.say(body.talktext).params(&params).to_vec()
+    }))
+    .await?;
+    Ok(([(header::CONTENT_TYPE, "audio/wav")], wav).into_response())
+}
+
+/// AssistantSeika 互換のルーターを作成します。
+pub fn router(handle: Handle) -> Router {
+    Router::new()
+        .route("/AVATOR2", get(avators))
+        .route("/AVATOR2/:cid", get(avator))
+        .route("/PLAY2/:cid", post(play))
+        .route("/PLAYASYNC2/:cid", post(play_async))
+        .route("/SAVE2/:cid", post(save))
+        .with_state(handle)
+}
+
+/// 指定したアドレスで AssistantSeika 互換サーバーを起動します。
+///
+/// AssistantSeika の既定のポート番号は `7180` です。
+pub async fn serve(addr: impl tokio::net::ToSocketAddrs, handle: Handle) -> error::Result<()> {
+    crate::server::serve_router(addr, router(handle), std::future::pending()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(json: serde_json::Value) -> TalkBody {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn casts() -> Vec<String> {
+        vec!["さとうささら".to_string(), "すずきつづみ".to_string()]
+    }
+
+    #[test]
+    fn effects_map_to_params() {
+        let body = body(json!({
+            "talktext": "こんにちは。",
+            "effects": { "volume": 80, "speed": 55, "pitch": 40, "alpha": 30, "intonation": 20 },
+            "emotions": { "嬉しい": 50, "怒り": 0 }
+        }));
+        assert_eq!(
+            body.to_params("さとうささら".to_string()).unwrap(),
+            Params {
+                volume: Some(80),
+                speed: Some(55),
+                tone: Some(40),
+                alpha: Some(30),
+                tone_scale: Some(20),
+                // `BTreeMap` の順番
+                components: vec![("嬉しい".to_string(), 50), ("怒り".to_string(), 0)],
+                ..Params::from("さとうささら")
+            }
+        );
+    }
+
+    #[test]
+    fn omitted_effects_are_not_changed() {
+        let params = body(json!({ "talktext": "こんにちは。" }))
+            .to_params("さとうささら".to_string())
+            .unwrap();
+        assert_eq!(params, Params::from("さとうささら"));
+    }
+
+    #[test]
+    fn effect_values_are_rounded_and_clamped() {
+        let body = body(json!({
+            "talktext": "",
+            "effects": { "volume": 150.0, "speed": -3.0, "pitch": 49.5, "alpha": 49.4 },
+            "emotions": { "嬉しい": 100.6 }
+        }));
+        let params = body.to_params(String::new()).unwrap();
+        assert_eq!(params.volume, Some(100));
+        assert_eq!(params.speed, Some(0));
+        assert_eq!(params.tone, Some(50));
+        assert_eq!(params.alpha, Some(49));
+        assert_eq!(params.components, [("嬉しい".to_string(), 100)]);
+    }
+
+    #[test]
+    fn unknown_effect_is_rejected() {
+        let body = body(json!({ "talktext": "", "effects": { "speed": 50, "tone": 50 } }));
+        let e = body.to_params(String::new()).unwrap_err();
+        assert!(matches!(e, error::CeVIOError::InvalidInput(_)));
+        assert!(format!("{e:#}").contains("Unknown effect `tone`"), "{e:#}");
+    }
+
+    #[cfg(feature = "cevio-ai")]
+    #[test]
+    fn cid_counts_from_ai_base() {
+        assert_eq!(
+            cast_at(casts(), HostKind::Ai, 90001).unwrap(),
+            "さとうささら"
+        );
+        assert_eq!(
+            cast_at(casts(), HostKind::Ai, 90002).unwrap(),
+            "すずきつづみ"
+        );
+        for cid in [90003, 90000, 60001, 0] {
+            let e = cast_at(casts(), HostKind::Ai, cid).unwrap_err();
+            assert!(matches!(e, error::CeVIOError::InvalidCast(_)), "{cid}");
+        }
+    }
+
+    #[cfg(feature = "cevio-cs")]
+    #[test]
+    fn cid_counts_from_cs_base() {
+        assert_eq!(
+            cast_at(casts(), HostKind::Cs, 60001).unwrap(),
+            "さとうささら"
+        );
+        assert_eq!(
+            cast_at(casts(), HostKind::Cs, 60002).unwrap(),
+            "すずきつづみ"
+        );
+        for cid in [60003, 60000, 90001] {
+            assert!(cast_at(casts(), HostKind::Cs, cid).is_err(), "{cid}");
+        }
+    }
+}
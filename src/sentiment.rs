@@ -0,0 +1,325 @@
+//! セリフの感情の推定と、感情パラメータへの割り当て（`sentiment` フィーチャー）
+//!
+//! `Classifier` は語の一覧と記号（`！`、`…`）からセリフの感情を推定する、簡単な分類器です。
+//! `EmotionMap` は推定した感情ごとのパラメータ（感情パラメータのプリセット）の表です。
+//! 長い台本でも、すべての行に手でプリセットを書かずに感情の変化を付けられます。
+//!
+//! `EmotionMap` のテキスト形式は、感情ごとのセクションに `Params` のテキスト形式を書きます。
+//!
+//! ```text
+//! [joy]
+//! component.嬉しい = 80
+//!
+//! [anger]
+//! component.怒り = 70
+//!
+//! [sadness]
+//! component.哀しみ = 90
+//! speed = 45
+//! ```
+//!
+//! ```
+//! use cevio::sentiment::{Classifier, Emotion, EmotionMap};
+//!
+//! let classifier = Classifier::default();
+//! let classification = classifier.classify("合格したよ、本当に嬉しい！");
+//! assert_eq!(classification.emotion, Emotion::Joy);
+//! assert_eq!(classifier.classify("明日は月曜日です。").emotion, Emotion::Neutral);
+//!
+//! let map = EmotionMap::parse("[joy]\ncomponent.嬉しい = 80\n").unwrap();
+//! let params = map.params_for(&classification);
+//! assert!(params.components[0].1 <= 80);
+//! ```
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{
+    error::{self, report},
+    params::Params,
+};
+
+/// 感情と判定するのに必要なスコア
+const THRESHOLD: f32 = 1.0;
+/// 強さが 1 になるスコア
+const SATURATION: f32 = 2.0;
+/// `！` 1 つで、最もスコアの高い感情に加えるスコア
+const EXCLAMATION_WEIGHT: f32 = 0.5;
+/// `…` 1 つで、哀しみに加えるスコア
+const ELLIPSIS_WEIGHT: f32 = 0.5;
+
+/// 組み込みの語の一覧（語、感情、重み）
+const WORDS: &[(&str, Emotion, f32)] = &[
+    ("嬉しい", Emotion::Joy, 2.0),
+    ("うれしい", Emotion::Joy, 2.0),
+    ("楽しい", Emotion::Joy, 2.0),
+    ("たのしい", Emotion::Joy, 2.0),
+    ("やった", Emotion::Joy, 1.5),
+    ("最高", Emotion::Joy, 1.5),
+    ("ありがとう", Emotion::Joy, 1.0),
+    ("おめでとう", Emotion::Joy, 1.5),
+    ("大好き", Emotion::Joy, 1.5),
+    ("よかった", Emotion::Joy, 1.0),
+    ("面白い", Emotion::Joy, 1.0),
+    ("わーい", Emotion::Joy, 2.0),
+    ("怒", Emotion::Anger, 1.5),
+    ("許さない", Emotion::Anger, 2.0),
+    ("ふざけるな", Emotion::Anger, 2.0),
+    ("いい加減にして", Emotion::Anger, 2.0),
+    ("うるさい", Emotion::Anger, 1.5),
+    ("むかつく", Emotion::Anger, 2.0),
+    ("ムカつく", Emotion::Anger, 2.0),
+    ("最低", Emotion::Anger, 1.5),
+    ("なんで", Emotion::Anger, 0.5),
+    ("悲しい", Emotion::Sadness, 2.0),
+    ("哀しい", Emotion::Sadness, 2.0),
+    ("かなしい", Emotion::Sadness, 2.0),
+    ("寂しい", Emotion::Sadness, 2.0),
+    ("さみしい", Emotion::Sadness, 2.0),
+    ("残念", Emotion::Sadness, 1.5),
+    ("ごめん", Emotion::Sadness, 1.0),
+    ("つらい", Emotion::Sadness, 1.5),
+    ("辛い", Emotion::Sadness, 1.0),
+    ("泣", Emotion::Sadness, 1.5),
+    ("さようなら", Emotion::Sadness, 1.0),
+];
+
+/// 推定した感情です。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Emotion {
+    /// どの感情とも判定できなかった
+    #[default]
+    Neutral,
+    /// 喜び
+    Joy,
+    /// 怒り
+    Anger,
+    /// 哀しみ
+    Sadness,
+}
+
+impl Emotion {
+    /// すべての感情
+    pub const ALL: [Emotion; 4] = [
+        Emotion::Neutral,
+        Emotion::Joy,
+        Emotion::Anger,
+        Emotion::Sadness,
+    ];
+
+    /// テキスト形式の名前（`neutral`、`joy`、`anger`、`sadness`）を取得します。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Emotion::Neutral => "neutral",
+            Emotion::Joy => "joy",
+            Emotion::Anger => "anger",
+            Emotion::Sadness => "sadness",
+        }
+    }
+}
+
+impl fmt::Display for Emotion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Emotion {
+    type Err = error::CeVIOError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Emotion::ALL
+            .into_iter()
+            .find(|emotion| emotion.as_str() == s)
+            .ok_or_else(|| error::CeVIOError::InvalidInput(report!("Unknown emotion `{s}`")))
+    }
+}
+
+/// セリフの感情の推定結果です。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Classification {
+    /// 感情
+    pub emotion: Emotion,
+    /// 強さ（0～1）。`Emotion::Neutral` の場合は 0 です
+    pub intensity: f32,
+}
+
+/// 語の一覧からセリフの感情を推定する分類器です。
+///
+/// セリフに含まれる語の重みを感情ごとに合計し、最も大きい感情を選びます。
+/// `！` は最も大きい感情を強め、`…` は哀しみを強めます。合計が小さい場合は `Emotion::Neutral` です。
+#[derive(Debug, Clone)]
+pub struct Classifier {
+    words: Vec<(String, Emotion, f32)>,
+}
+
+impl Default for Classifier {
+    /// 組み込みの語の一覧を使う分類器を作成します。
+    fn default() -> Self {
+        Self {
+            words: WORDS
+                .iter()
+                .map(|(word, emotion, weight)| (word.to_string(), *emotion, *weight))
+                .collect(),
+        }
+    }
+}
+
+impl Classifier {
+    /// 語のない分類器を作成します。`add_word` で語を追加してください。
+    pub fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    /// 語を追加します。`weight` はセリフに 1 回含まれるごとに加えるスコアです。（組み込みの語は 0.5～2）
+    pub fn add_word(&mut self, word: impl Into<String>, emotion: Emotion, weight: f32) {
+        self.words.push((word.into(), emotion, weight));
+    }
+
+    /// セリフの感情を推定します。
+    pub fn classify(&self, text: &str) -> Classification {
+        let mut scores = HashMap::<Emotion, f32>::new();
+        for (word, emotion, weight) in &self.words {
+            let count = text.matches(word.as_str()).count();
+            if count > 0 {
+                *scores.entry(*emotion).or_default() += weight * count as f32;
+            }
+        }
+
+        let ellipses = text.matches('…').count() + text.matches("...").count();
+        if ellipses > 0 {
+            *scores.entry(Emotion::Sadness).or_default() += ELLIPSIS_WEIGHT * ellipses as f32;
+        }
+
+        // 同じスコアの場合も結果が変わらないように、`Emotion::ALL` の順で選ぶ
+        let top = Emotion::ALL
+            .into_iter()
+            .filter_map(|emotion| Some((emotion, *scores.get(&emotion)?)))
+            .fold(
+                None,
+                |top: Option<(Emotion, f32)>, (emotion, score)| match top {
+                    Some((_, top_score)) if top_score >= score => top,
+                    _ => Some((emotion, score)),
+                },
+            );
+        let Some((emotion, mut score)) = top else {
+            return Classification::default();
+        };
+
+        let exclamations = text.matches(['！', '!']).count();
+        score += EXCLAMATION_WEIGHT * exclamations as f32;
+
+        if score < THRESHOLD {
+            return Classification::default();
+        }
+        Classification {
+            emotion,
+            intensity: (score / SATURATION).min(1.0),
+        }
+    }
+}
+
+/// 感情ごとのパラメータの表です。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmotionMap {
+    map: HashMap<Emotion, Params>,
+}
+
+impl EmotionMap {
+    /// 空の表を作成します。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 感情のパラメータを設定します。
+    pub fn set(&mut self, emotion: Emotion, params: Params) {
+        self.map.insert(emotion, params);
+    }
+
+    /// 感情のパラメータを取得します。
+    pub fn get(&self, emotion: Emotion) -> Option<&Params> {
+        self.map.get(&emotion)
+    }
+
+    /// 感情のパラメータを削除します。
+    pub fn remove(&mut self, emotion: Emotion) -> Option<Params> {
+        self.map.remove(&emotion)
+    }
+
+    /// 推定結果に適用するパラメータを取得します。表にない感情の場合は空のパラメータです。
+    ///
+    /// 感情パラメータの値は推定した強さを掛けた値にします。（`Emotion::Neutral` は表の値のままです）
+    pub fn params_for(&self, classification: &Classification) -> Params {
+        let Some(params) = self.map.get(&classification.emotion) else {
+            return Params::default();
+        };
+        let mut params = params.clone();
+        if classification.emotion == Emotion::Neutral {
+            return params;
+        }
+        for (_, value) in &mut params.components {
+            *value = (*value as f32 * classification.intensity).round() as i32;
+        }
+        params
+    }
+
+    /// テキスト形式の表を読み込みます。
+    pub fn parse(s: &str) -> error::Result<Self> {
+        let mut map = Self::default();
+        let mut section: Option<Emotion> = None;
+        let mut text = String::new();
+
+        // セクションの行をまとめてから読み込む
+        let flush = |section: Option<Emotion>, text: &mut String, map: &mut EmotionMap| {
+            if let Some(emotion) = section {
+                let params = Params::parse(text).map_err(|e| {
+                    error::CeVIOError::InvalidInput(
+                        e.into_inner()
+                            .context(format!("Invalid section `[{emotion}]`")),
+                    )
+                })?;
+                map.set(emotion, params);
+            }
+            text.clear();
+            Ok::<_, error::CeVIOError>(())
+        };
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                flush(section, &mut text, &mut map)?;
+                section = Some(header.trim().parse().map_err(|_| {
+                    error::CeVIOError::InvalidInput(report!(
+                        "Unknown section `[{header}]` at line {}",
+                        i + 1
+                    ))
+                })?);
+                continue;
+            }
+            if section.is_none() {
+                return Err(error::CeVIOError::InvalidInput(report!(
+                    "Missing section header before line {}",
+                    i + 1
+                )));
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+        flush(section, &mut text, &mut map)?;
+        Ok(map)
+    }
+
+    /// テキスト形式に変換します。
+    pub fn to_text(&self) -> String {
+        Emotion::ALL
+            .into_iter()
+            .filter_map(|emotion| {
+                let params = self.map.get(&emotion)?;
+                Some(format!("[{emotion}]\n{}", params.to_text()))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,523 @@
+//! HTTP サーバー（`server` フィーチャー）
+//!
+//! | メソッド | パス            | 内容                                               |
+//! | -------- | --------------- | -------------------------------------------------- |
+//! | `POST`   | `/speak`        | セリフを再生し、再生終了後に `204` を返します      |
+//! | `POST`   | `/synthesize`   | セリフを WAV に変換して返します（`audio/wav`）     |
+//! | `GET`    | `/casts`        | 利用可能なキャスト名を JSON の配列で返します       |
+//! | `GET`    | `/ws`           | WebSocket で合成の状態と音声を順次返します         |
+//! | `GET`    | `/health`       | 製品の応答の状態を返します（`HostHealth`）         |
+//! | `GET`    | `/metrics`      | Prometheus 形式のメトリクスを返します（`metrics`） |
+//! | `GET`    | `/openapi.json` | OpenAPI ドキュメントを返します（`openapi`）        |
+//!
+//! 複数のクライアントから読み上げる場合は、優先度付きのキュー（`queue::SpeechQueue`）を使います。
+//!
+//! | メソッド | パス                        | 内容                                                                   |
+//! | -------- | --------------------------- | ---------------------------------------------------------------------- |
+//! | `POST`   | `/queue/{client}`           | セリフをキューに追加し、`202` と `{"id":<番号>}` を返します            |
+//! | `GET`    | `/queue`                    | キュー全体の状態を JSON で返します                                     |
+//! | `GET`    | `/queue/{client}`           | クライアントの状態を JSON で返します                                   |
+//! | `PUT`    | `/queue/{client}/priority`  | `{"priority":"alert"}` でクライアントの優先度を設定します              |
+//! | `POST`   | `/queue/{client}/flush`     | クライアントのセリフを削除し、`{"removed":<数>}` を返します            |
+//!
+//! 優先度は `low`、`normal`、`high`、`alert` です。
+//!
+//! `POST` の本文は JSON で、`text` 以外は省略できます。
+//!
+//! ```json
+//! { "text": "こんにちは。", "cast": "花隈千冬", "speed": 55, "components": { "嬉しい": 50 } }
+//! ```
+//!
+//! `router_with_config` で設定（`config::SharedConfig`）を渡すと、設定の既定のパラメータ、`preset` で指定したプリセット、
+//! 本文のパラメータの順に適用し、セリフに読み替え辞書を適用します。NG ワードを含むセリフは `400` を返します。
+//! `POST /config/reload` で設定ファイルを読み込み直します（処理中のリクエストは読み込み前の設定のまま処理します）。
+//!
+//! `/ws` ではテキストメッセージ（上と同じ JSON、または セリフそのもの）を受け取るたびに、次の順でメッセージを返します。
+//!
+//! 1. テキスト `{"event":"started","id":<番号>}`
+//! 2. バイナリ WAV データ（`WS_CHUNK_SIZE` バイトごとに分割）
+//! 3. テキスト `{"event":"finished","id":<番号>,"bytes":<合計バイト数>}`
+//!
+//! 失敗した場合は 2, 3 の代わりに `{"event":"error","id":<番号>,"code":...,"kind":...,"message":<内容>,"retryable":...}` を返します。
+//!
+//! HTTP のエラーレスポンスの本文は `error::ErrorBody` の JSON（`{"code":"busy","kind":"com","message":<内容>,"retryable":true}` など）です。
+//! `retryable` が `true` の場合は、少し待ってから同じリクエストを送り直すと成功することがあります。
+//!
+//! LAN などに公開する場合は `auth::Auth` でトークンと接続を許可するアドレスを設定してください。
+//!
+//! リクエストは 1 つのスレッドで順番に処理されるため、同時に届いた `/speak` は重ならずに順番に再生されます。
+//!
+//! ```no_run
+//! # async fn run() {
+//! use cevio::{actor::Handle, server, HostKind};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! handle.call(|cevio| cevio.start_host(false)).unwrap();
+//! server::serve("127.0.0.1:8080", handle).await.unwrap();
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        FromRef, MatchedPath, Path, Request, State,
+    },
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{on, MethodFilter},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    actor::Handle,
+    config::SharedConfig,
+    error::{self, Context as _},
+    metrics, openapi,
+    queue::{ClientStatus, Priority, QueueStatus, SpeechQueue},
+    request::{ParamsBody, TextBody},
+    CeVIO, HostHealth,
+};
+
+/// `/health` で応答を待つ最大時間
+pub const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+struct AppState {
+    handle: Handle,
+    queue: SpeechQueue,
+    config: SharedConfig,
+}
+
+impl FromRef<AppState> for SharedConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Handle {
+    fn from_ref(state: &AppState) -> Self {
+        state.handle.clone()
+    }
+}
+
+impl FromRef<AppState> for SpeechQueue {
+    fn from_ref(state: &AppState) -> Self {
+        state.queue.clone()
+    }
+}
+
+pub(crate) struct ServerError(pub(crate) StatusCode, pub(crate) error::ErrorBody);
+
+impl From<error::CeVIOError> for ServerError {
+    fn from(e: error::CeVIOError) -> Self {
+        let status = match e.kind() {
+            error::ErrorKind::InvalidCast | error::ErrorKind::InvalidInput => {
+                StatusCode::BAD_REQUEST
+            }
+            error::ErrorKind::HostNotRunning => StatusCode::SERVICE_UNAVAILABLE,
+            _ if e.is_busy() => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ServerError(status, e.to_body())
+    }
+}
+
+impl ServerError {
+    pub(crate) fn new(
+        status: StatusCode,
+        kind: error::ErrorKind,
+        message: impl Into<String>,
+    ) -> Self {
+        ServerError(status, error::ErrorBody::new(kind, message))
+    }
+
+    pub(crate) fn bad_request(e: error::CeVIOError) -> Self {
+        ServerError(StatusCode::BAD_REQUEST, e.to_body())
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        (self.0, Json(self.1)).into_response()
+    }
+}
+
+/// ハンドルの操作をブロッキング用のスレッドで待つ
+pub(crate) async fn call<R, F>(handle: &Handle, f: F) -> Result<R, ServerError>
+where
+    R: Send + 'static,
+    F: FnOnce(&CeVIO) -> error::Result<R> + Send + 'static,
+{
+    let handle = handle.clone();
+    tokio::task::spawn_blocking(move || handle.call(f))
+        .await
+        .map_err(|e| {
+            ServerError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error::ErrorKind::Other,
+                e.to_string(),
+            )
+        })?
+        .map_err(ServerError::from)
+}
+
+/// 合成・再生にかかった時間を記録する
+pub(crate) async fn time_synthesis<R>(f: impl std::future::Future<Output = R>) -> R {
+    let start = std::time::Instant::now();
+    let result = f.await;
+    metrics::global().observe_synthesis(start.elapsed());
+    result
+}
+
+async fn record_request(matched: Option<MatchedPath>, request: Request, next: Next) -> Response {
+    let route = matched.map(|path| path.as_str().to_string());
+    let response = next.run(request).await;
+    metrics::global().record_request(
+        route.as_deref().unwrap_or("unmatched"),
+        response.status().as_u16(),
+    );
+    response
+}
+
+async fn render_metrics(State(queue): State<SpeechQueue>) -> Response {
+    let depth: usize = queue
+        .status()
+        .clients
+        .iter()
+        .map(|client| client.queued.len())
+        .sum();
+    let body = format!(
+        "{}# HELP cevio_queue_depth Number of queued texts.\n# TYPE cevio_queue_depth gauge\ncevio_queue_depth {depth}\n",
+        metrics::global().render()
+    );
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response()
+}
+
+async fn health(State(handle): State<Handle>) -> (StatusCode, Json<serde_json::Value>) {
+    let health = tokio::task::spawn_blocking(move || handle.ping(HEALTH_TIMEOUT))
+        .await
+        .unwrap_or_else(|e| HostHealth::Dead {
+            reason: e.to_string(),
+        });
+    let body = match &health {
+        HostHealth::Healthy { latency } => {
+            json!({ "status": health.as_str(), "latency_ms": latency.as_millis() as u64 })
+        }
+        HostHealth::Slow => json!({ "status": health.as_str() }),
+        HostHealth::Dead { reason } => json!({ "status": health.as_str(), "reason": reason }),
+    };
+    match health.is_healthy() {
+        true => (StatusCode::OK, Json(body)),
+        false => (StatusCode::SERVICE_UNAVAILABLE, Json(body)),
+    }
+}
+
+async fn openapi_json() -> Json<serde_json::Value> {
+    Json(openapi::spec())
+}
+
+async fn speak(
+    State(handle): State<Handle>,
+    State(config): State<SharedConfig>,
+    Json(body): Json<TextBody>,
+) -> Result<StatusCode, ServerError> {
+    let (text, params) = body
+        .resolve(&config.get())
+        .map_err(ServerError::bad_request)?;
+    time_synthesis(call(&handle, move |cevio| {
+        cevio.say(text).params(&params).play()
+    }))
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn synthesize(
+    State(handle): State<Handle>,
+    State(config): State<SharedConfig>,
+    Json(body): Json<TextBody>,
+) -> Result<Response, ServerError> {
+    let (text, params) = body
+        .resolve(&config.get())
+        .map_err(ServerError::bad_request)?;
+    let wav = time_synthesis(call(&handle, move |cevio| {
+        cevio.say(text).params(&params).to_vec()
+    }))
+    .await?;
+    Ok(([(header::CONTENT_TYPE, "audio/wav")], wav).into_response())
+}
+
+async fn casts(State(handle): State<Handle>) -> Result<Json<Vec<String>>, ServerError> {
+    Ok(Json(
+        call(&handle, |cevio| cevio.get_available_casts()).await?,
+    ))
+}
+
+async fn enqueue(
+    State(queue): State<SpeechQueue>,
+    State(config): State<SharedConfig>,
+    Path(client): Path<String>,
+    Json(body): Json<TextBody>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ServerError> {
+    let (text, params) = body
+        .resolve(&config.get())
+        .map_err(ServerError::bad_request)?;
+    let id = queue.push(&client, text, params);
+    Ok((StatusCode::ACCEPTED, Json(json!({ "id": id }))))
+}
+
+async fn queue_status(State(queue): State<SpeechQueue>) -> Json<QueueStatus> {
+    Json(queue.status())
+}
+
+async fn client_status(
+    State(queue): State<SpeechQueue>,
+    Path(client): Path<String>,
+) -> Result<Json<ClientStatus>, ServerError> {
+    queue.client_status(&client).map(Json).ok_or_else(|| {
+        ServerError::new(
+            StatusCode::NOT_FOUND,
+            error::ErrorKind::InvalidInput,
+            format!("Unknown client `{client}`"),
+        )
+    })
+}
+
+#[derive(Deserialize)]
+struct PriorityBody {
+    priority: Priority,
+}
+
+async fn set_priority(
+    State(queue): State<SpeechQueue>,
+    Path(client): Path<String>,
+    Json(body): Json<PriorityBody>,
+) -> StatusCode {
+    queue.set_priority(&client, body.priority);
+    StatusCode::NO_CONTENT
+}
+
+async fn flush(
+    State(queue): State<SpeechQueue>,
+    Path(client): Path<String>,
+) -> Json<serde_json::Value> {
+    Json(json!({ "removed": queue.flush(&client) }))
+}
+
+/// `/ws` で音声を分割して送るときの 1 メッセージあたりのバイト数です。
+pub const WS_CHUNK_SIZE: usize = 32 * 1024;
+
+async fn reload_config(State(config): State<SharedConfig>) -> Result<StatusCode, ServerError> {
+    config.reload()?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn ws(
+    State(handle): State<Handle>,
+    State(config): State<SharedConfig>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| ws_session(handle, config, socket))
+}
+
+async fn ws_session(handle: Handle, config: SharedConfig, mut socket: WebSocket) {
+    let mut id = 0u64;
+    while let Some(Ok(message)) = socket.recv().await {
+        let Message::Text(text) = message else {
+            continue;
+        };
+        id += 1;
+        // JSON でなければセリフそのものとして扱う
+        let body = serde_json::from_str::<TextBody>(&text).unwrap_or(TextBody {
+            text,
+            preset: None,
+            params: ParamsBody::default(),
+        });
+        let started = json!({ "event": "started", "id": id });
+        if socket
+            .send(Message::Text(started.to_string()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let result = match body.resolve(&config.get()) {
+            Ok((text, params)) => {
+                time_synthesis(call(&handle, move |cevio| {
+                    cevio.say(text).params(&params).to_vec()
+                }))
+                .await
+            }
+            Err(e) => Err(ServerError::bad_request(e)),
+        };
+        let finished = match result {
+            Ok(wav) => {
+                for chunk in wav.chunks(WS_CHUNK_SIZE) {
+                    if socket.send(Message::Binary(chunk.to_vec())).await.is_err() {
+                        return;
+                    }
+                }
+                json!({ "event": "finished", "id": id, "bytes": wav.len() })
+            }
+            Err(ServerError(_, body)) => json!({
+                "event": "error",
+                "id": id,
+                "code": body.code,
+                "kind": body.kind,
+                "message": body.message,
+                "retryable": body.retryable,
+            }),
+        };
+        if socket
+            .send(Message::Text(finished.to_string()))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// ルーターを作成します。
+///
+/// 他のルーターと組み合わせる場合に使います。キューの再生スレッドを起動できない場合はエラーを返します。
+pub fn router(handle: Handle) -> error::Result<Router> {
+    router_with_config(handle, SharedConfig::default())
+}
+
+/// 設定を適用するルーターを作成します。
+pub fn router_with_config(handle: Handle, config: SharedConfig) -> error::Result<Router> {
+    let queue = SpeechQueue::new(handle.clone())?;
+    Ok(router_with_queue(handle, config, queue))
+}
+
+/// キューの読み上げに使うバックエンドを指定してルーターを作成します。
+///
+/// `/queue` 以下のエンドポイントは `queue` で読み上げます。`SpeechQueue::with_backend` で作成したキューを渡すと、
+/// `backend::MockBackend` や `fixture::ReplayBackend` で読み上げられます。その他のエンドポイントは `handle` を使います。
+pub fn router_with_queue(handle: Handle, config: SharedConfig, queue: SpeechQueue) -> Router {
+    routes()
+        .route_layer(middleware::from_fn(record_request))
+        .with_state(AppState {
+            queue,
+            handle,
+            config,
+        })
+}
+
+/// `ROUTES` とルートを同じ一覧から作る
+macro_rules! routes {
+    ($($method:ident $path:literal => $handler:expr,)*) => {
+        /// エンドポイントのメソッドとパス。`openapi::spec` と一致することをテストで確かめる
+        #[cfg(test)]
+        const ROUTES: &[(&str, &str)] = &[$((stringify!($method), $path)),*];
+
+        /// 同じパスのメソッドは 1 つのルートにまとめられる
+        fn routes() -> Router<AppState> {
+            Router::new()$(.route($path, on(MethodFilter::$method, $handler)))*
+        }
+    };
+}
+
+routes! {
+    POST "/speak" => speak,
+    POST "/synthesize" => synthesize,
+    GET "/casts" => casts,
+    GET "/ws" => ws,
+    GET "/queue" => queue_status,
+    GET "/queue/:client" => client_status,
+    POST "/queue/:client" => enqueue,
+    PUT "/queue/:client/priority" => set_priority,
+    POST "/queue/:client/flush" => flush,
+    POST "/config/reload" => reload_config,
+    GET "/health" => health,
+    GET "/metrics" => render_metrics,
+    GET "/openapi.json" => openapi_json,
+}
+
+/// 指定したアドレスで HTTP サーバーを起動します。
+pub async fn serve(addr: impl tokio::net::ToSocketAddrs, handle: Handle) -> error::Result<()> {
+    serve_with_shutdown(addr, handle, std::future::pending()).await
+}
+
+/// 指定したアドレスで HTTP サーバーを起動し、`signal` が完了したら処理中のリクエストを待って終了します。
+pub async fn serve_with_shutdown(
+    addr: impl tokio::net::ToSocketAddrs,
+    handle: Handle,
+    signal: impl std::future::Future<Output = ()> + Send + 'static,
+) -> error::Result<()> {
+    serve_router(addr, router(handle)?, signal).await
+}
+
+/// 指定したアドレスで `router` を使って HTTP サーバーを起動し、`signal` が完了したら処理中のリクエストを待って終了します。
+///
+/// 設定を適用する場合は `router_with_config` で作成したルーターを渡します。
+pub async fn serve_router(
+    addr: impl tokio::net::ToSocketAddrs,
+    router: Router,
+    signal: impl std::future::Future<Output = ()> + Send + 'static,
+) -> error::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .context("Failed to bind")
+        .map_err(error::CeVIOError::from)?;
+    // `auth::Auth` で接続元のアドレスを使うため、接続情報を渡す
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(signal)
+    .await
+    .context("Failed to serve")
+    .map_err(error::CeVIOError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    /// OpenAPI のパスアイテムでメソッドを表すキー
+    const HTTP_METHODS: &[&str] = &[
+        "get", "put", "post", "delete", "options", "head", "patch", "trace",
+    ];
+
+    #[test]
+    fn openapi_paths_match_routes() {
+        // axum の `:client` は OpenAPI では `{client}`
+        let routes = ROUTES
+            .iter()
+            .map(|(method, path)| {
+                let path = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(name) => format!("{{{name}}}"),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                (path, method.to_ascii_lowercase())
+            })
+            .collect::<BTreeSet<_>>();
+        let spec = openapi::spec();
+        let documented = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    // パスに共通の `parameters` などを除く
+                    .filter(|key| HTTP_METHODS.contains(&key.as_str()))
+                    .map(move |method| (path.clone(), method.clone()))
+            })
+            .collect::<BTreeSet<_>>();
+        assert_eq!(routes, documented);
+    }
+}
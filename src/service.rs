@@ -0,0 +1,381 @@
+//! Windows サービス（`service` フィーチャー）
+//!
+//! HTTP サーバー（`pipe` フィーチャーが有効な場合は名前付きパイプサーバーも）を Windows サービスとして動かします。
+//! PC の起動時に自動で開始し、開始時に CeVIO を起動するため、ログインしなくても読み上げを受け付けられます。
+//! CeVIO をすぐに起動できない場合は、HTTP サーバーを先に開始し、CeVIO の起動を再試行します。
+//!
+//! CeVIO は COM でサービスと同じユーザーのセッションに起動されるため、
+//! CeVIO をインストールしたユーザーのアカウントでサービスを実行してください。
+//!
+//! インストールとアンインストールは `cevio-service` コマンドでもできます。
+//!
+//! ```no_run
+//! use cevio::service::{self, ServiceOptions};
+//! // サービスコントロールマネージャーから起動されたプロセスで呼び出す
+//! service::run(service::DEFAULT_SERVICE_NAME, ServiceOptions::default()).unwrap();
+//! ```
+
+use std::{
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc, Arc, OnceLock,
+    },
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+use windows::{
+    core::{HSTRING, PCWSTR, PWSTR},
+    Win32::{
+        Security::SC_HANDLE,
+        System::Services::{
+            CloseServiceHandle, ControlService, CreateServiceW, DeleteService, OpenSCManagerW,
+            OpenServiceW, RegisterServiceCtrlHandlerExW, SetServiceStatus,
+            StartServiceCtrlDispatcherW, SC_MANAGER_CONNECT, SC_MANAGER_CREATE_SERVICE,
+            SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_ALL_ACCESS, SERVICE_AUTO_START,
+            SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP,
+            SERVICE_ERROR_NORMAL, SERVICE_RUNNING, SERVICE_START_PENDING, SERVICE_STATUS,
+            SERVICE_STATUS_CURRENT_STATE, SERVICE_STATUS_HANDLE, SERVICE_STOPPED,
+            SERVICE_STOP_PENDING, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+        },
+    },
+};
+
+use crate::{
+    actor::Handle,
+    auth::Auth,
+    config::SharedConfig,
+    error::{self, report, Context as _},
+    server, HostKind,
+};
+
+/// 既定のサービス名です。
+pub const DEFAULT_SERVICE_NAME: &str = "cevio-rs";
+
+const NO_ERROR: u32 = 0;
+const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+const ERROR_SERVICE_SPECIFIC_ERROR: u32 = 1066;
+
+/// 開始・停止の処理にかかる時間の目安。単位はミリ秒
+const WAIT_HINT: u32 = 60_000;
+/// CeVIO の起動に失敗した場合に、最初に再試行するまでの時間
+const START_RETRY_MIN: Duration = Duration::from_secs(1);
+/// CeVIO の起動を再試行する間隔の上限
+const START_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// 開始・停止の処理中に報告する進捗。報告するたびに増やす
+static CHECK_POINT: AtomicU32 = AtomicU32::new(0);
+
+/// サービスで動かすサーバーの設定です。
+#[derive(Debug, Clone)]
+pub struct ServiceOptions {
+    /// 操作対象の製品
+    pub host: HostKind,
+    /// HTTP サーバーのアドレス
+    pub addr: String,
+    /// 名前付きパイプサーバーのパイプ名。`None` の場合は起動しません
+    #[cfg(feature = "pipe")]
+    pub pipe_name: Option<String>,
+    /// 開始時に CeVIO を起動するかどうか
+    ///
+    /// 起動できない場合（PC の起動直後など）も HTTP サーバーは開始し、起動できるまで間隔を空けて再試行します。
+    /// 起動の状態は `/health` で確認できます。
+    pub start_host: bool,
+    /// 設定ファイル（`config::ServerConfig` のテキスト形式）。`None` の場合は設定を適用しません
+    pub config: Option<PathBuf>,
+    /// HTTP サーバーに必要なトークン。`None` の場合は不要です
+    pub token: Option<String>,
+    /// HTTP サーバーへの接続を許可するアドレス（`auth::Auth::allow` の形式）。空の場合はすべて許可します
+    pub allow: Vec<String>,
+}
+
+impl Default for ServiceOptions {
+    fn default() -> Self {
+        Self {
+            host: HostKind::default(),
+            addr: "127.0.0.1:8080".to_string(),
+            #[cfg(feature = "pipe")]
+            pipe_name: None,
+            start_host: true,
+            config: None,
+            token: None,
+            allow: Vec::new(),
+        }
+    }
+}
+
+struct Service {
+    name: String,
+    options: ServiceOptions,
+    stop: Arc<Notify>,
+    status: OnceLock<SERVICE_STATUS_HANDLE>,
+}
+
+static SERVICE: OnceLock<Service> = OnceLock::new();
+
+struct ScHandle(SC_HANDLE);
+
+impl Drop for ScHandle {
+    fn drop(&mut self) {
+        unsafe { CloseServiceHandle(self.0) };
+    }
+}
+
+/// サービスをインストールします。管理者権限が必要です。
+///
+/// 引数：
+///
+/// 　command_line - サービスとして起動するコマンドライン。そのプロセスで `run` を呼び出します。
+///
+/// 　account - 実行するアカウントとパスワード。`None` の場合は LocalSystem です。
+pub fn install(
+    name: &str,
+    display_name: &str,
+    command_line: &str,
+    account: Option<(&str, &str)>,
+) -> error::Result<()> {
+    let manager =
+        unsafe { OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CREATE_SERVICE) }
+            .map(ScHandle)
+            .context("Failed to open service control manager")
+            .map_err(error::CeVIOError::from)?;
+    let (user, password) = match account {
+        Some((user, password)) => (HSTRING::from(user), HSTRING::from(password)),
+        None => (HSTRING::new(), HSTRING::new()),
+    };
+    let pcwstr = |s: &HSTRING| match s.is_empty() {
+        true => PCWSTR::null(),
+        false => PCWSTR(s.as_ptr()),
+    };
+    unsafe {
+        CreateServiceW(
+            manager.0,
+            &HSTRING::from(name),
+            &HSTRING::from(display_name),
+            SERVICE_ALL_ACCESS,
+            SERVICE_WIN32_OWN_PROCESS,
+            SERVICE_AUTO_START,
+            SERVICE_ERROR_NORMAL,
+            &HSTRING::from(command_line),
+            PCWSTR::null(),
+            None,
+            PCWSTR::null(),
+            pcwstr(&user),
+            pcwstr(&password),
+        )
+    }
+    .map(ScHandle)
+    .with_context(|| format!("Failed to create service `{name}`"))
+    .map_err(error::CeVIOError::from)?;
+    Ok(())
+}
+
+/// サービスを停止してアンインストールします。管理者権限が必要です。
+pub fn uninstall(name: &str) -> error::Result<()> {
+    let manager = unsafe { OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CONNECT) }
+        .map(ScHandle)
+        .context("Failed to open service control manager")
+        .map_err(error::CeVIOError::from)?;
+    let service = unsafe { OpenServiceW(manager.0, &HSTRING::from(name), SERVICE_ALL_ACCESS) }
+        .map(ScHandle)
+        .with_context(|| format!("Failed to open service `{name}`"))
+        .map_err(error::CeVIOError::from)?;
+    // 停止済みの場合は失敗するが、削除には影響しないので無視する
+    let mut status = SERVICE_STATUS::default();
+    unsafe { ControlService(service.0, SERVICE_CONTROL_STOP, &mut status) };
+    if !unsafe { DeleteService(service.0) }.as_bool() {
+        return Err(windows::core::Error::from_win32())
+            .with_context(|| format!("Failed to delete service `{name}`"))
+            .map_err(error::CeVIOError::from);
+    }
+    Ok(())
+}
+
+/// サービスとして実行し、サービスが停止するまで待ちます。
+///
+/// サービスコントロールマネージャーから起動されたプロセスで、1 回だけ呼び出せます。
+pub fn run(name: &str, options: ServiceOptions) -> error::Result<()> {
+    SERVICE
+        .set(Service {
+            name: name.to_string(),
+            options,
+            stop: Arc::new(Notify::new()),
+            status: OnceLock::new(),
+        })
+        .map_err(|_| error::CeVIOError::Other(report!("Service is already running")))?;
+    let mut name = name.encode_utf16().chain([0]).collect::<Vec<_>>();
+    let table = [
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: PWSTR(name.as_mut_ptr()),
+            lpServiceProc: Some(service_main),
+        },
+        SERVICE_TABLE_ENTRYW::default(),
+    ];
+    if !unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) }.as_bool() {
+        return Err(windows::core::Error::from_win32())
+            .context("Failed to start service control dispatcher")
+            .map_err(error::CeVIOError::from);
+    }
+    Ok(())
+}
+
+/// サービスと同じサーバーをこのプロセスで実行します。
+///
+/// 動作を確認するためのもので、エラーが起きるまで戻りません。
+pub fn run_foreground(options: &ServiceOptions) -> error::Result<()> {
+    serve(options, std::future::pending(), || {})
+}
+
+fn serve(
+    options: &ServiceOptions,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    on_ready: impl FnOnce(),
+) -> error::Result<()> {
+    let config = match &options.config {
+        Some(path) => SharedConfig::load(path)?,
+        None => SharedConfig::default(),
+    };
+    let mut auth = Auth::new();
+    if let Some(token) = &options.token {
+        auth = auth.token(token.as_str());
+    }
+    for addr in &options.allow {
+        auth = auth.allow(addr)?;
+    }
+    let handle = Handle::spawn(options.host)?;
+    set_status(SERVICE_START_PENDING, 0);
+    // `_stop_retry` を破棄すると再試行をやめる
+    let (_stop_retry, stopped) = mpsc::channel::<()>();
+    if options.start_host {
+        let handle = handle.clone();
+        std::thread::Builder::new()
+            .name("cevio-start-host".to_string())
+            .spawn(move || start_host_with_retry(&handle, &stopped))
+            .context("Failed to spawn host start thread")
+            .map_err(error::CeVIOError::from)?;
+    }
+    #[cfg(feature = "pipe")]
+    if let Some(name) = options.pipe_name.clone() {
+        let handle = handle.clone();
+        let config = config.clone();
+        std::thread::Builder::new()
+            .name("cevio-pipe-server".to_string())
+            .spawn(move || {
+                // パイプが使えなくても HTTP サーバーは動かし続ける
+                if let Err(_e) = crate::pipe::serve_with_config(&name, handle, config) {
+                    #[cfg(feature = "tracing")]
+                    tracing::error!(pipe = %name, error = %_e, "Pipe server stopped");
+                }
+            })
+            .context("Failed to spawn pipe server thread")
+            .map_err(error::CeVIOError::from)?;
+    }
+    set_status(SERVICE_START_PENDING, 0);
+    let runtime = tokio::runtime::Runtime::new()
+        .context("Failed to create runtime")
+        .map_err(error::CeVIOError::from)?;
+    let router = server::router_with_config(handle, config)?;
+    on_ready();
+    runtime.block_on(server::serve_router(
+        options.addr.clone(),
+        auth.apply(router),
+        shutdown,
+    ))
+}
+
+/// CeVIO を起動できるまで、間隔を倍にしながら再試行する。`stopped` の送信側が破棄されたらやめる
+fn start_host_with_retry(handle: &Handle, stopped: &mpsc::Receiver<()>) {
+    let mut wait = START_RETRY_MIN;
+    loop {
+        match handle.call(|cevio| cevio.start_host(false)) {
+            Ok(()) => return,
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_e, retry_in = ?wait, "Failed to start host");
+            }
+        }
+        if let Err(mpsc::RecvTimeoutError::Disconnected) = stopped.recv_timeout(wait) {
+            return;
+        }
+        wait = (wait * 2).min(START_RETRY_MAX);
+    }
+}
+
+fn set_status(state: SERVICE_STATUS_CURRENT_STATE, exit_code: u32) {
+    let Some(status) = SERVICE.get().and_then(|s| s.status.get()) else {
+        return;
+    };
+    let controls = match state {
+        SERVICE_RUNNING => SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN,
+        _ => 0,
+    };
+    let service_status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        dwControlsAccepted: controls,
+        dwWin32ExitCode: match exit_code {
+            0 => NO_ERROR,
+            _ => ERROR_SERVICE_SPECIFIC_ERROR,
+        },
+        dwServiceSpecificExitCode: exit_code,
+        dwCheckPoint: match state {
+            SERVICE_START_PENDING | SERVICE_STOP_PENDING => {
+                CHECK_POINT.fetch_add(1, Ordering::Relaxed) + 1
+            }
+            _ => {
+                CHECK_POINT.store(0, Ordering::Relaxed);
+                0
+            }
+        },
+        dwWaitHint: match state {
+            SERVICE_START_PENDING | SERVICE_STOP_PENDING => WAIT_HINT,
+            _ => 0,
+        },
+    };
+    unsafe { SetServiceStatus(*status, &service_status) };
+}
+
+unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+    let Some(service) = SERVICE.get() else {
+        return;
+    };
+    let Ok(status) = RegisterServiceCtrlHandlerExW(
+        &HSTRING::from(service.name.as_str()),
+        Some(control_handler),
+        None,
+    ) else {
+        return;
+    };
+    let _ = service.status.set(status);
+    set_status(SERVICE_START_PENDING, 0);
+
+    let stop = service.stop.clone();
+    let result = serve(
+        &service.options,
+        async move { stop.notified().await },
+        || set_status(SERVICE_RUNNING, 0),
+    );
+    set_status(SERVICE_STOPPED, if result.is_ok() { 0 } else { 1 });
+}
+
+unsafe extern "system" fn control_handler(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut std::ffi::c_void,
+    _context: *mut std::ffi::c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            set_status(SERVICE_STOP_PENDING, 0);
+            if let Some(service) = SERVICE.get() {
+                // 待っている側がまだいなくても通知が残る
+                service.stop.notify_one();
+            }
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
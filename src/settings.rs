@@ -0,0 +1,222 @@
+//! `%APPDATA%\cevio-rs` に保存するユーザーの設定
+//!
+//! CLI や小さなツールで、最後に使ったキャストとパラメータ、プリセット、キャッシュの場所を次の実行でも使うために保存します。
+//! `%APPDATA%\cevio-rs\settings.txt` に、サーバーの設定ファイル（`config`）と同じ形式で書きます。
+//!
+//! ```text
+//! # 最初のセクションより前は最後に使ったパラメータ（`Params` のテキスト形式）
+//! cast = 花隈千冬
+//! speed = 50
+//!
+//! # プリセット（`Params` のテキスト形式）
+//! [preset.元気]
+//! component.嬉しい = 80
+//!
+//! # キャッシュの場所
+//! [paths]
+//! cache = D:\cevio-cache
+//! ```
+//!
+//! ```no_run
+//! use cevio::{settings::Settings, CeVIO, Params};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//!
+//! let mut settings = Settings::load().unwrap();
+//! cevio.apply_params(&settings.params).unwrap();
+//! settings.remember(&Params {
+//!     speed: Some(60),
+//!     ..Default::default()
+//! });
+//! settings.save().unwrap();
+//! ```
+//!
+//! ```
+//! use cevio::settings::Settings;
+//!
+//! let settings = Settings::parse("cast = 花隈千冬\n[paths]\ncache = D:\\cache\n").unwrap();
+//! assert_eq!(settings.params.cast.as_deref(), Some("花隈千冬"));
+//! assert_eq!(Settings::parse(&settings.to_text()).unwrap(), settings);
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    fs_util::{create_dir_all, read_to_string},
+    params::Params,
+};
+
+/// `%APPDATA%` の下のディレクトリ名
+const DIR_NAME: &str = "cevio-rs";
+/// 設定ファイル名
+const FILE_NAME: &str = "settings.txt";
+/// キャッシュの既定のディレクトリ名
+const CACHE_DIR: &str = "cache";
+
+/// ユーザーの設定です。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// 最後に使ったキャストとパラメータ
+    pub params: Params,
+    /// 名前を指定して使うパラメータ
+    pub presets: HashMap<String, Params>,
+    /// キャッシュの場所。`None` の場合は `%APPDATA%\cevio-rs\cache`
+    pub cache_dir: Option<PathBuf>,
+}
+
+enum Section {
+    Params,
+    Preset(String),
+    Paths,
+}
+
+impl Settings {
+    /// 設定を保存するディレクトリ（`%APPDATA%\cevio-rs`）を取得します。
+    ///
+    /// 環境変数 `APPDATA` がない場合は `CeVIOError::InvalidInput` です。
+    pub fn dir() -> error::Result<PathBuf> {
+        std::env::var_os("APPDATA")
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| PathBuf::from(dir).join(DIR_NAME))
+            .ok_or_else(|| error::CeVIOError::InvalidInput(report!("`APPDATA` is not set")))
+    }
+
+    /// 設定ファイル（`%APPDATA%\cevio-rs\settings.txt`）のパスを取得します。
+    pub fn path() -> error::Result<PathBuf> {
+        Ok(Self::dir()?.join(FILE_NAME))
+    }
+
+    /// 設定ファイルを読み込みます。ファイルがない場合は既定の設定です。
+    pub fn load() -> error::Result<Self> {
+        Self::load_from(Self::path()?)
+    }
+
+    /// 指定した設定ファイルを読み込みます。ファイルがない場合は既定の設定です。
+    pub fn load_from(path: impl AsRef<Path>) -> error::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::parse(&read_to_string(path)?)
+    }
+
+    /// 設定ファイル（`%APPDATA%\cevio-rs\settings.txt`）に保存します。ディレクトリがない場合は作成します。
+    pub fn save(&self) -> error::Result<()> {
+        self.save_to(Self::path()?)
+    }
+
+    /// 指定した設定ファイルに保存します。ディレクトリがない場合は作成します。
+    pub fn save_to(&self, path: impl AsRef<Path>) -> error::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+            .with_context(|| format!("Failed to write `{}`", path.display()))
+            .map_err(error::CeVIOError::from)
+    }
+
+    /// テキスト形式の設定を読み込みます。
+    pub fn parse(s: &str) -> error::Result<Self> {
+        let mut settings = Self::default();
+        let mut section = Section::Params;
+        let mut params_text = String::new();
+
+        // `Params` のセクションは行をまとめてから読み込む
+        let flush = |section: &Section, text: &mut String, settings: &mut Settings| {
+            match section {
+                Section::Params => settings.params = Params::parse(text)?,
+                Section::Preset(name) => {
+                    let params = Params::parse(text).map_err(|e| {
+                        error::CeVIOError::InvalidInput(
+                            e.into_inner().context(format!("Invalid preset `{name}`")),
+                        )
+                    })?;
+                    settings.presets.insert(name.clone(), params);
+                }
+                Section::Paths => {}
+            }
+            text.clear();
+            Ok::<_, error::CeVIOError>(())
+        };
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                flush(&section, &mut params_text, &mut settings)?;
+                section = match header.trim() {
+                    "paths" => Section::Paths,
+                    header => match header.strip_prefix("preset.") {
+                        Some(name) => Section::Preset(name.trim().to_string()),
+                        None => {
+                            return Err(error::CeVIOError::InvalidInput(report!(
+                                "Unknown section `[{header}]` at line {}",
+                                i + 1
+                            )))
+                        }
+                    },
+                };
+                continue;
+            }
+            match &section {
+                Section::Params | Section::Preset(_) => {
+                    params_text.push_str(line);
+                    params_text.push('\n');
+                }
+                Section::Paths => {
+                    let (key, value) = line
+                        .split_once('=')
+                        .ok_or_else(|| report!("Missing `=` at line {}", i + 1))
+                        .map_err(error::CeVIOError::InvalidInput)?;
+                    match key.trim() {
+                        "cache" => settings.cache_dir = Some(PathBuf::from(value.trim())),
+                        key => {
+                            return Err(error::CeVIOError::InvalidInput(report!(
+                                "Unknown key `{key}` at line {}",
+                                i + 1
+                            )))
+                        }
+                    }
+                }
+            }
+        }
+        flush(&section, &mut params_text, &mut settings)?;
+        Ok(settings)
+    }
+
+    /// テキスト形式に変換します。プリセットは名前の順に並べます。
+    pub fn to_text(&self) -> String {
+        let mut s = self.params.to_text();
+        let mut presets = self.presets.iter().collect::<Vec<_>>();
+        presets.sort_by_key(|(name, _)| name.as_str());
+        for (name, params) in presets {
+            let _ = write!(s, "\n[preset.{name}]\n{}", params.to_text());
+        }
+        if let Some(cache_dir) = &self.cache_dir {
+            let _ = write!(s, "\n[paths]\ncache = {}\n", cache_dir.display());
+        }
+        s
+    }
+
+    /// 使ったパラメータを最後に使ったパラメータに上書きします。
+    pub fn remember(&mut self, params: &Params) {
+        self.params = self.params.merge(params);
+    }
+
+    /// キャッシュの場所を取得します。指定していない場合は `%APPDATA%\cevio-rs\cache` です。
+    pub fn cache_location(&self) -> error::Result<PathBuf> {
+        match &self.cache_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => Ok(Self::dir()?.join(CACHE_DIR)),
+        }
+    }
+}
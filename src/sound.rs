@@ -0,0 +1,121 @@
+//! 最初に読み込むときに合成する音声
+//!
+//! `Sound` はセリフとパラメータだけを持ち、`Read`、`Seek`、`AsRef<[u8]>` などで最初に読み込むときに WAV を合成して保持します。
+//! リーダーを受け取る音声ライブラリ（`rodio::Decoder` など）に、そのまま渡せます。
+//!
+//! ```no_run
+//! use std::io::Read;
+//! use cevio::{CeVIO, Params};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let mut sound = cevio.sound("こんにちは。", &Params::default());
+//! // ここで合成する
+//! let mut header = [0; 44];
+//! sound.read_exact(&mut header).unwrap();
+//! ```
+
+use std::{
+    cell::OnceCell,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use crate::{error, CeVIO, Params};
+
+/// 最初に読み込むときに合成する音声です。
+///
+/// `CeVIO::sound` で作成します。合成には `CeVIO::say` を使うため、パラメータは合成の間だけ使い、終わると元の値に戻します。
+pub struct Sound<'a> {
+    cevio: &'a CeVIO,
+    text: String,
+    params: Params,
+    /// 合成した WAV
+    data: OnceCell<Vec<u8>>,
+    /// `Read` で次に読み込む位置
+    position: u64,
+}
+
+impl CeVIO {
+    /// セリフとパラメータを指定し、最初に読み込むときに合成する音声を取得します。
+    pub fn sound(&self, text: impl Into<String>, params: &Params) -> Sound<'_> {
+        Sound {
+            cevio: self,
+            text: text.into(),
+            params: params.clone(),
+            data: OnceCell::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Sound<'_> {
+    /// セリフを取得します。
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// パラメータを取得します。
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// 合成済みかどうかを取得します。
+    pub fn is_synthesized(&self) -> bool {
+        self.data.get().is_some()
+    }
+
+    /// WAV 形式のバイト列を取得します。合成していない場合は合成します。
+    ///
+    /// 失敗した場合は保持せず、次に読み込むときに合成し直します。
+    pub fn wav(&self) -> error::Result<&[u8]> {
+        if let Some(data) = self.data.get() {
+            return Ok(data);
+        }
+        let data = self.cevio.say(&self.text).params(&self.params).to_vec()?;
+        Ok(self.data.get_or_init(|| data))
+    }
+
+    /// WAV 形式のバイト列を取り出します。合成していない場合は合成します。
+    pub fn into_bytes(self) -> error::Result<Vec<u8>> {
+        self.wav()?;
+        Ok(self.data.into_inner().unwrap_or_default())
+    }
+}
+
+/// 合成に失敗した場合は空のバイト列です。エラーが必要な場合は `wav` を使ってください。
+impl AsRef<[u8]> for Sound<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.wav().unwrap_or_default()
+    }
+}
+
+impl Read for Sound<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let position = self.position;
+        let data = self.wav().map_err(io::Error::other)?;
+        let start = usize::try_from(position).map_or(data.len(), |p| p.min(data.len()));
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for Sound<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.wav().map_err(io::Error::other)?.len() as u64;
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
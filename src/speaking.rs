@@ -0,0 +1,168 @@
+use std::time::{Duration, Instant};
+
+use windows::Win32::System::Com::VARIANT;
+
+use crate::{
+    error::{self, report},
+    observer::Pending,
+    variant_ext::VariantExt,
+    ComObject,
+};
+
+/// 完了するまでの進み具合の上限
+const MAX_PROGRESS: f32 = 0.99;
+
+/// 再生状態を表すオブジェクトです。
+pub struct SpeakingState {
+    state: ComObject,
+    /// `speak` を呼んだ時点の厳格モード
+    strict: bool,
+    /// 再生の終了を通知するオブザーバー
+    pending: Option<Pending>,
+    /// 再生を開始した時刻
+    started: Instant,
+    /// 推定したセリフの長さ
+    duration: Option<Duration>,
+}
+
+impl SpeakingState {
+    pub(crate) fn new(state: ComObject, strict: bool) -> Self {
+        Self {
+            state,
+            strict,
+            pending: None,
+            started: Instant::now(),
+            duration: None,
+        }
+    }
+
+    pub(crate) fn with_pending(mut self, pending: Option<Pending>) -> Self {
+        self.pending = pending;
+        self
+    }
+
+    pub(crate) fn with_duration(mut self, duration: Option<Duration>) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// 推定したセリフの長さを取得します。`CeVIO::speak_with_progress` で再生した場合だけ `Some` です。
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// 再生を開始してからの時間を取得します。
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// 再生の進み具合（0.0～1.0）を推定します。
+    ///
+    /// 再生を開始してからの時間と、推定したセリフの長さ（`duration`）から求めます。
+    /// 完了するまでは 1.0 にならず、完了すると 1.0 です。セリフの長さが分からない場合は完了するまで 0.0 です。
+    ///
+    /// ```no_run
+    /// use std::{thread, time::Duration};
+    /// let cevio = cevio::CeVIO::new().unwrap();
+    /// cevio.start_host(false).unwrap();
+    /// cevio.set_cast("花隈千冬").unwrap();
+    ///
+    /// let state = cevio.speak_with_progress("こんにちは。よろしくお願いします。").unwrap();
+    /// while !state.is_completed().unwrap() {
+    ///     println!("{:.0}%", state.progress() * 100.0);
+    ///     thread::sleep(Duration::from_millis(100));
+    /// }
+    /// ```
+    pub fn progress(&self) -> f32 {
+        if self.is_completed().unwrap_or(false) {
+            return 1.0;
+        }
+        match self.duration {
+            Some(duration) if !duration.is_zero() => {
+                let progress = self.elapsed().as_secs_f32() / duration.as_secs_f32();
+                progress.clamp(0.0, MAX_PROGRESS)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// 再生が完了したかどうかを取得します。
+    ///
+    /// 備考：
+    ///
+    /// 　完了した場合は true。（失敗した場合も true。）
+    pub fn is_completed(&self) -> error::Result<bool> {
+        let completed = self.state.property("IsCompleted")?;
+        if completed {
+            if let Some(pending) = &self.pending {
+                pending.finish(&self.check_succeeded("is_completed"));
+            }
+        }
+        Ok(completed)
+    }
+
+    /// 再生が成功したかどうかを取得します。
+    pub fn is_succeeded(&self) -> error::Result<bool> {
+        self.state.property("IsSucceeded")
+    }
+
+    /// 再生終了を待ちます。
+    ///
+    /// 備考：
+    ///
+    /// 　厳格モード（`CeVIO::set_strict`）では、再生に失敗した場合に `CeVIOError::OperationFailed` を返します。
+    pub fn wait(&self) -> error::Result<()> {
+        let result = self
+            .state
+            .invoke_method("Wait", [])
+            .and_then(|_| self.check_succeeded("wait"));
+        if let Some(pending) = &self.pending {
+            pending.finish(&result);
+        }
+        result
+    }
+
+    /// 再生終了を待ちます。
+    ///
+    /// 引数：
+    ///
+    /// 　timeout - 最大待機時間。単位は秒。（0未満は無制限。）
+    ///
+    /// 備考：
+    ///
+    /// 　厳格モード（`CeVIO::set_strict`）では、時間内に再生が終わって失敗していた場合に `CeVIOError::OperationFailed` を返します。
+    pub fn wait_timeout(&self, timeout: f64) -> error::Result<()> {
+        self.state
+            .invoke_method("Wait_2", [VARIANT::from_f64(timeout)])?;
+        // 成否も終了の通知も不要な場合は完了を確認しない
+        if !self.strict && self.pending.is_none() {
+            return Ok(());
+        }
+        // 時間内に終わらなかった場合は成否がまだ分からない
+        if !self.is_completed()? || !self.strict {
+            return Ok(());
+        }
+        self.check_succeeded("wait_timeout")
+    }
+
+    /// 厳格モードで再生に失敗していた場合は失敗する
+    fn check_succeeded(&self, fn_name: &str) -> error::Result<()> {
+        if !self.strict || self.is_succeeded()? {
+            return Ok(());
+        }
+        Err(error::CeVIOError::OperationFailed(report!(
+            "CeVIO failed to play in fn `{fn_name}`"
+        )))
+    }
+}
+
+/// 音素単位のデータです。
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonemeData {
+    /// 音素
+    pub phoneme: String,
+    /// 開始時間。単位は秒
+    pub start_time: f64,
+    /// 終了時間。単位は秒
+    pub end_time: f64,
+}
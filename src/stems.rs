@@ -0,0 +1,200 @@
+//! キャストごとのステム（マルチトラック）の書き出し
+//!
+//! 複数のキャストが話す台本から、キャストごとに 1 つの WAV を書き出します。
+//! 各ステムは台本全体と同じ長さで、ほかのキャストの行は同じ長さの無音です。
+//! すべてのステムの位置がそろっているため、DAW にそのまま読み込んでミックスできます。
+//!
+//! ```no_run
+//! use cevio::{CeVIO, Project};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//!
+//! let project = Project::open(r"E:\drama").unwrap();
+//! // E:\drama\outputs\stems\花隈千冬.wav など
+//! for (cast, path) in project.render_stems(&cevio).unwrap() {
+//!     println!("{cast}\t{}", path.display());
+//! }
+//! ```
+//!
+//! ```
+//! use cevio::{overwrite::OverwritePolicy, stems};
+//!
+//! // 48kHz, 16bit, モノラルで `samples` サンプルの WAV
+//! let wav = |samples: u32| {
+//!     let data = samples * 2;
+//!     let mut wav = b"RIFF".to_vec();
+//!     wav.extend((36 + data).to_le_bytes());
+//!     wav.extend(b"WAVEfmt ");
+//!     wav.extend([16, 0, 0, 0, 1, 0, 1, 0, 0x80, 0xBB, 0, 0, 0x00, 0x77, 0x01, 0, 2, 0, 16, 0]);
+//!     wav.extend(b"data");
+//!     wav.extend(data.to_le_bytes());
+//!     wav.extend(vec![1; data as usize]);
+//!     wav
+//! };
+//! // 同時に実行したテストと重ならないよう、プロセス ID を付ける
+//! let dir = std::env::temp_dir().join(format!("cevio-stems-doctest-{}", std::process::id()));
+//! std::fs::create_dir_all(&dir).unwrap();
+//! std::fs::write(dir.join("0001.wav"), wav(100)).unwrap();
+//! std::fs::write(dir.join("0002.wav"), wav(50)).unwrap();
+//!
+//! let lines = [("A".to_string(), dir.join("0001.wav")), ("B".to_string(), dir.join("0002.wav"))];
+//! let stems = stems::write_stems(lines, dir.join("stems"), OverwritePolicy::Overwrite).unwrap();
+//! assert_eq!(stems.len(), 2);
+//! let a = std::fs::read(&stems[0].1).unwrap();
+//! assert_eq!(a.len(), 44 + 300);
+//! assert_eq!(&a[44 + 200..], &[0; 100]); // B の行は無音
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    fs_util::create_dir_all,
+    overwrite::OverwritePolicy,
+    wav,
+};
+
+/// 無音を書き込むときのバッファの大きさ
+const SILENCE_CHUNK: usize = 64 * 1024;
+
+/// 行の WAV の `data` チャンクの位置
+struct Line {
+    cast: String,
+    path: PathBuf,
+    /// `data` チャンクの中身の開始位置
+    offset: u64,
+    /// `data` チャンクの中身の大きさ
+    len: u32,
+}
+
+/// 行ごとの WAV から、キャストごとのステムを `out_dir` に `<キャスト名>.wav` として書き出し、（キャスト名, パス）を返します。
+///
+/// `lines` は（キャスト名, WAV ファイルのパス）で、台本の順に並べます。ステムはキャストが最初に現れる順に返します。
+/// すべての WAV は同じ形式（サンプリング周波数、ビット数、チャンネル数）の PCM でなければなりません。
+///
+/// ファイルが既にある場合は `overwrite` に従います。スキップした場合は既存のファイルのパスを返します。
+///
+/// 戻り値：
+///
+/// 　WAV を読み込めない場合と書き込めない場合は `CeVIOError::Io`、WAV の形式がそろっていない場合は `CeVIOError::InvalidInput`。
+pub fn write_stems<P: AsRef<Path>>(
+    lines: impl IntoIterator<Item = (String, P)>,
+    out_dir: impl AsRef<Path>,
+    overwrite: OverwritePolicy,
+) -> error::Result<Vec<(String, PathBuf)>> {
+    let mut format: Option<Vec<u8>> = None;
+    let mut parsed = Vec::new();
+    for (cast, path) in lines {
+        let path = path.as_ref();
+        let (fmt, offset, len) = read_header(path)
+            .with_context(|| format!("Failed to read WAV header of `{}`", path.display()))
+            .map_err(error::CeVIOError::Io)?;
+        match &format {
+            Some(format) if *format != fmt => {
+                return Err(error::CeVIOError::InvalidInput(report!(
+                    "`{}` has a different format from the other lines",
+                    path.display()
+                )))
+            }
+            Some(_) => {}
+            None => format = Some(fmt),
+        }
+        parsed.push(Line {
+            cast,
+            path: path.to_path_buf(),
+            offset,
+            len,
+        });
+    }
+    let Some(format) = format else {
+        return Ok(Vec::new());
+    };
+
+    let mut casts = Vec::<&str>::new();
+    for line in &parsed {
+        if !casts.contains(&line.cast.as_str()) {
+            casts.push(&line.cast);
+        }
+    }
+
+    let out_dir = out_dir.as_ref();
+    create_dir_all(out_dir)?;
+    casts
+        .into_iter()
+        .map(|cast| {
+            let path = out_dir.join(format!("{cast}.wav"));
+            let Some(path) = overwrite.resolve(&path)? else {
+                return Ok((cast.to_string(), path));
+            };
+            write_stem(&path, cast, &format, &parsed)
+                .with_context(|| format!("Failed to write `{}`", path.display()))
+                .map_err(error::CeVIOError::Io)?;
+            Ok((cast.to_string(), path))
+        })
+        .collect()
+}
+
+/// `fmt ` チャンクの中身と、`data` チャンクの中身の位置と大きさを読み込む。PCM 以外は失敗する
+fn read_header(path: &Path) -> std::result::Result<(Vec<u8>, u64, u32), error::Report> {
+    let mut file = File::open(path).map_err(error::Report::new)?;
+    let header = wav::read_header(&mut file)?;
+    if u16::from_le_bytes([header.0[0], header.0[1]]) != 1 {
+        return Err(report!("Only PCM WAV is supported"));
+    }
+    Ok(header)
+}
+
+/// `cast` の行はそのまま、ほかの行は無音にしたステムを書き込む
+fn write_stem(path: &Path, cast: &str, fmt: &[u8], lines: &[Line]) -> io::Result<()> {
+    let data_len = lines.iter().map(|line| u64::from(line.len)).sum::<u64>();
+    let data_len = u32::try_from(data_len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Stem exceeds 4 GiB"))?;
+    // `fmt ` は奇数の大きさの場合にパディングが入る
+    let fmt_len = fmt.len() as u32 + (fmt.len() as u32 & 1);
+    let riff_len = 4 + (8 + fmt_len) + (8 + data_len + (data_len & 1));
+
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(b"RIFF")?;
+    out.write_all(&riff_len.to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&(fmt.len() as u32).to_le_bytes())?;
+    out.write_all(fmt)?;
+    if fmt.len() & 1 == 1 {
+        out.write_all(&[0])?;
+    }
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+
+    // 8bit の PCM は 128 が無音
+    let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
+    let silence = vec![if bits_per_sample == 8 { 0x80 } else { 0 }; SILENCE_CHUNK];
+    for line in lines {
+        if line.cast == cast {
+            let mut file = File::open(&line.path)?;
+            file.seek(SeekFrom::Start(line.offset))?;
+            let copied = io::copy(&mut file.take(u64::from(line.len)), &mut out)?;
+            if copied < u64::from(line.len) {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("`{}` is truncated", line.path.display()),
+                ));
+            }
+        } else {
+            let mut rest = line.len as usize;
+            while rest > 0 {
+                let n = rest.min(SILENCE_CHUNK);
+                out.write_all(&silence[..n])?;
+                rest -= n;
+            }
+        }
+    }
+    if data_len & 1 == 1 {
+        out.write_all(&[0])?;
+    }
+    out.flush()
+}
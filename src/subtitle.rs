@@ -0,0 +1,68 @@
+//! 再生中のセリフを書き込む字幕ファイル
+//!
+//! OBS の「テキスト」ソースで「ファイルから読み取り」を有効にしてこのファイルを指定すると、
+//! 再生に合わせて字幕を表示できます。
+//!
+//! ```no_run
+//! use cevio::{subtitle::Subtitle, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let subtitle = Subtitle::new(r"C:\obs\subtitle.txt");
+//! cevio.speak_with_subtitle("こんにちは。", &subtitle).unwrap();
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{self, Context as _},
+    CeVIO,
+};
+
+/// 字幕ファイルです。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subtitle {
+    path: PathBuf,
+}
+
+impl Subtitle {
+    /// `path` に書き込む字幕ファイルを作成します。ファイルは最初に書き込むときに作られます。
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 字幕ファイルのパスを取得します。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 字幕を `text` にします。
+    pub fn show(&self, text: &str) -> error::Result<()> {
+        fs::write(&self.path, text)
+            .with_context(|| format!("Failed to write `{}`", self.path.display()))
+            .map_err(error::CeVIOError::from)
+    }
+
+    /// 字幕を消します。
+    pub fn clear(&self) -> error::Result<()> {
+        self.show("")
+    }
+}
+
+impl CeVIO {
+    /// セリフを再生し、再生が終わるまで待ちます。再生中は `subtitle` にセリフを書き込みます。
+    ///
+    /// 備考：
+    ///
+    /// 　再生に失敗した場合も字幕を消します。
+    pub fn speak_with_subtitle(&self, text: &str, subtitle: &Subtitle) -> error::Result<()> {
+        subtitle.show(text)?;
+        let result = self.speak(text).and_then(|state| state.wait());
+        let cleared = subtitle.clear();
+        result.and(cleared)
+    }
+}
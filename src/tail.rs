@@ -0,0 +1,166 @@
+//! 追記されていくファイルを監視し、追加された行を読み上げます
+//!
+//! ログファイルやゲームのチャットログの監視に使います。
+//!
+//! - `filter` が `true` を返した行だけを読み上げます。
+//! - 同じ行は `dedup_window` の間は 1 回だけ読み上げます。
+//! - 読み上げの間隔を `min_interval` 以上空け、読み上げ待ちが `max_pending` を超えた場合は古い行から捨てます。
+//! - ファイルが切り詰められた（ローテーションされた）場合は先頭から読み直します。
+//!
+//! ```no_run
+//! use std::sync::atomic::AtomicBool;
+//! use cevio::{tail, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let stop = AtomicBool::new(false);
+//! tail::tail_and_speak(
+//!     &cevio,
+//!     r"C:\game\chat.log",
+//!     |line| line.contains("[Party]"),
+//!     &tail::TailOptions::default(),
+//!     &stop,
+//! )
+//! .unwrap();
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{self, Context as _},
+    CeVIO, SpeakingState,
+};
+
+/// ファイルの監視の設定です。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TailOptions {
+    /// ファイルを確認する間隔
+    pub interval: Duration,
+    /// 読み上げを始めてから次の読み上げを始めるまでの最短の間隔
+    pub min_interval: Duration,
+    /// 同じ行を読み上げない期間
+    pub dedup_window: Duration,
+    /// 読み上げ待ちの最大の行数。超えた場合は古い行から捨てます
+    pub max_pending: usize,
+    /// `true` の場合は既存の内容も先頭から読み上げます。`false` の場合は追記された行だけを読み上げます
+    pub from_start: bool,
+}
+
+impl Default for TailOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            min_interval: Duration::ZERO,
+            dedup_window: Duration::from_secs(10),
+            max_pending: 10,
+            from_start: false,
+        }
+    }
+}
+
+/// 追記された内容を行に分けて読み込む
+struct Follower {
+    position: u64,
+    /// 改行で終わっていない最後の行
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    fn read_lines(&mut self, path: &Path) -> error::Result<Vec<String>> {
+        let mut file = File::open(path)
+            .with_context(|| format!("Failed to open `{}`", path.display()))
+            .map_err(error::CeVIOError::from)?;
+        let len = file
+            .metadata()
+            .with_context(|| format!("Failed to read metadata of `{}`", path.display()))
+            .map_err(error::CeVIOError::from)?
+            .len();
+        if len < self.position {
+            self.position = 0;
+            self.partial.clear();
+        }
+        if len == self.position {
+            return Ok(Vec::new());
+        }
+        let mut buf = std::mem::take(&mut self.partial);
+        file.seek(SeekFrom::Start(self.position))
+            .and_then(|_| file.take(len - self.position).read_to_end(&mut buf))
+            .with_context(|| format!("Failed to read `{}`", path.display()))
+            .map_err(error::CeVIOError::from)?;
+        self.position = len;
+
+        let complete = buf.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        self.partial = buf.split_off(complete);
+        Ok(String::from_utf8_lossy(&buf)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// `path` に追記された行のうち `filter` が `true` を返した行を読み上げます。
+///
+/// `stop` が `true` になるまで戻りません。キャストなどのパラメータは `cevio` に設定されているものを使います。
+pub fn tail_and_speak(
+    cevio: &CeVIO,
+    path: impl AsRef<Path>,
+    mut filter: impl FnMut(&str) -> bool,
+    options: &TailOptions,
+    stop: &AtomicBool,
+) -> error::Result<()> {
+    let path = path.as_ref();
+    let position = match options.from_start {
+        true => 0,
+        false => path.metadata().map(|m| m.len()).unwrap_or(0),
+    };
+    let mut follower = Follower {
+        position,
+        partial: Vec::new(),
+    };
+    let mut pending = VecDeque::new();
+    let mut spoken_at: HashMap<String, Instant> = HashMap::new();
+    let mut speaking: Option<(SpeakingState, Instant)> = None;
+
+    while !stop.load(Ordering::SeqCst) {
+        // 監視を始めた後に作られるファイルもあるため、存在しない間は待つ
+        if path.exists() {
+            pending.extend(follower.read_lines(path)?.into_iter().filter(|l| filter(l)));
+        }
+        while pending.len() > options.max_pending {
+            pending.pop_front();
+        }
+
+        let ready = match &speaking {
+            Some((state, started)) => {
+                state.is_completed()? && started.elapsed() >= options.min_interval
+            }
+            None => true,
+        };
+        if ready {
+            spoken_at.retain(|_, at| at.elapsed() < options.dedup_window);
+            while let Some(line) = pending.pop_front() {
+                if spoken_at.contains_key(&line) {
+                    continue;
+                }
+                let now = Instant::now();
+                speaking = Some((cevio.speak(&line)?, now));
+                spoken_at.insert(line, now);
+                break;
+            }
+        }
+
+        thread::sleep(options.interval);
+    }
+    Ok(())
+}
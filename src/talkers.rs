@@ -0,0 +1,81 @@
+//! キャラクターごとの Talker
+//!
+//! `Talkers` はキャストごとに `CeVIO::new_talker` で作成したインスタンスを持ちます。
+//! キャラクターが交互に話す台本でも、キャストやパラメータはキャラクターごとに保たれるため、セリフごとに設定し直す必要がありません。
+//!
+//! ```no_run
+//! use cevio::{talkers::Talkers, CeVIO, Params};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//!
+//! let mut talkers = Talkers::new(&cevio);
+//! talkers
+//!     .get("花隈千冬")
+//!     .unwrap()
+//!     .apply_params(&Params {
+//!         speed: Some(60),
+//!         ..Default::default()
+//!     })
+//!     .unwrap();
+//!
+//! for (cast, line) in [("花隈千冬", "こんにちは。"), ("さとうささら", "こんにちは。")] {
+//!     talkers.get(cast).unwrap().speak(line).unwrap().wait().unwrap();
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{error, CeVIO};
+
+/// キャストごとのインスタンスです。
+pub struct Talkers<'a> {
+    cevio: &'a CeVIO,
+    talkers: HashMap<String, CeVIO>,
+}
+
+impl<'a> Talkers<'a> {
+    /// `cevio` と同じ製品の Talker を、キャストごとに作成するようにします。
+    pub fn new(cevio: &'a CeVIO) -> Self {
+        Self {
+            cevio,
+            talkers: HashMap::new(),
+        }
+    }
+
+    /// キャストのインスタンスを取得します。ない場合は作成し、キャストを設定します。
+    ///
+    /// 代わりのキャスト（`set_fallback_casts`）を使った場合も、`cast` に指定した名前で保持します。
+    pub fn get(&mut self, cast: &str) -> error::Result<&CeVIO> {
+        if !self.talkers.contains_key(cast) {
+            let talker = self.cevio.new_talker()?;
+            talker.set_cast(cast)?;
+            self.talkers.insert(cast.to_string(), talker);
+        }
+        Ok(&self.talkers[cast])
+    }
+
+    /// キャストのインスタンスを、作成済みの場合だけ取得します。
+    pub fn get_existing(&self, cast: &str) -> Option<&CeVIO> {
+        self.talkers.get(cast)
+    }
+
+    /// 作成済みのキャストの一覧を取得します。順番は不定です。
+    pub fn casts(&self) -> impl Iterator<Item = &str> {
+        self.talkers.keys().map(String::as_str)
+    }
+
+    /// キャストのインスタンスを破棄します。
+    pub fn remove(&mut self, cast: &str) -> Option<CeVIO> {
+        self.talkers.remove(cast)
+    }
+
+    /// 作成済みのインスタンスの数を取得します。
+    pub fn len(&self) -> usize {
+        self.talkers.len()
+    }
+
+    /// 作成済みのインスタンスがないかどうかを取得します。
+    pub fn is_empty(&self) -> bool {
+        self.talkers.is_empty()
+    }
+}
@@ -0,0 +1,137 @@
+//! 破棄時に削除される一時 WAV ファイル
+//!
+//! `TempWav` は一時ディレクトリ内に重複しないパスを確保し、破棄されるとファイルを削除します。
+//! `CeVIO::output_wave_to_vec` など、ファイルを経由してメモリに読み込む API はこれを使います。
+//!
+//! ```no_run
+//! use std::io::Read;
+//! use cevio::{temp::TempWav, CeVIO};
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! let wav = TempWav::synthesize(&cevio, "こんにちは。").unwrap();
+//! let mut header = [0; 44];
+//! wav.reader().unwrap().read_exact(&mut header).unwrap();
+//! let bytes = wav.into_bytes().unwrap(); // ここでファイルは削除される
+//! ```
+
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{self, Context as _},
+    fs_util, CeVIO,
+};
+
+/// 破棄時に削除される一時 WAV ファイルです。
+#[derive(Debug)]
+pub struct TempWav {
+    path: PathBuf,
+    /// `false` の場合は破棄時に削除しない
+    delete: bool,
+}
+
+impl TempWav {
+    /// 一時ディレクトリ内に重複しないパスを確保します。ファイルはまだ作成しません。
+    pub fn new() -> Self {
+        Self {
+            path: fs_util::temp_wav_path(),
+            delete: true,
+        }
+    }
+
+    /// WAV 以外の拡張子で一時ディレクトリ内に重複しないパスを確保する（ffmpeg に渡すリストなど）
+    #[cfg(feature = "ffmpeg")]
+    pub(crate) fn with_extension(extension: &str) -> Self {
+        Self {
+            path: fs_util::temp_path(extension),
+            delete: true,
+        }
+    }
+
+    /// 指定したセリフを一時ファイルに出力します。
+    pub fn synthesize(cevio: &CeVIO, text: &str) -> error::Result<Self> {
+        let wav = Self::new();
+        cevio.output_wave_to_file(text, &wav.path)?;
+        Ok(wav)
+    }
+
+    /// 一時ファイルのパスを取得します。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 内容を読み込みます。
+    pub fn read(&self) -> error::Result<Vec<u8>> {
+        fs::read(&self.path)
+            .with_context(|| format!("Failed to read `{}`", self.path.display()))
+            .map_err(error::CeVIOError::from)
+    }
+
+    /// 内容を読み込むためのリーダーを取得します。
+    pub fn reader(&self) -> error::Result<BufReader<File>> {
+        File::open(&self.path)
+            .map(BufReader::new)
+            .with_context(|| format!("Failed to open `{}`", self.path.display()))
+            .map_err(error::CeVIOError::from)
+    }
+
+    /// 内容を読み込み、一時ファイルを削除します。
+    pub fn into_bytes(self) -> error::Result<Vec<u8>> {
+        self.read()
+    }
+
+    /// 破棄時に削除しないようにし、パスを返します。
+    pub fn keep(mut self) -> PathBuf {
+        self.delete = false;
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Default for TempWav {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsRef<Path> for TempWav {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempWav {
+    fn drop(&mut self) {
+        if self.delete {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// 破棄時に中身ごと削除される一時ディレクトリ（テスト用）
+#[cfg(test)]
+pub(crate) struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let path = fs_util::temp_path("dir");
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,239 @@
+//! テキストファイルや複数行のテキストを読み上げます
+//!
+//! 長いテキストは文の区切り（`。`、`！`、`？` など）で `MAX_CHARS` 文字以下に分け、順に読み上げます。
+//! テキストファイルは UTF-8（BOM の有無を問わない）、UTF-16（BOM 付き）、Shift_JIS を判別して読み込みます。
+//!
+//! ```no_run
+//! use cevio::CeVIO;
+//! let cevio = CeVIO::new().unwrap();
+//! cevio.start_host(false).unwrap();
+//! cevio.set_cast("花隈千冬").unwrap();
+//!
+//! cevio.speak_file(r"E:\script.txt").unwrap();
+//! cevio.speak_lines(["こんにちは。", "よろしくお願いします。"]).unwrap();
+//!
+//! // 試聴ボタン用に先頭の 30 文字以内だけ再生する
+//! cevio.preview("長い段落……", 30).unwrap().wait().unwrap();
+//! ```
+//!
+//! ```
+//! use cevio::text;
+//!
+//! assert_eq!(
+//!     text::split("こんにちは。今日はいい天気ですね！", 12),
+//!     ["こんにちは。", "今日はいい天気ですね！"],
+//! );
+//! assert_eq!(
+//!     text::head("こんにちは。今日はいい天気ですね！", 8).as_deref(),
+//!     Some("こんにちは。"),
+//! );
+//! ```
+
+use std::path::Path;
+
+use windows::Win32::Globalization::{MultiByteToWideChar, MB_ERR_INVALID_CHARS};
+
+use crate::{
+    backend::TalkerBackend,
+    error::{self, report, Context as _},
+    CeVIO, SpeakingState,
+};
+
+/// 1 回に読み上げる最大の文字数
+pub const MAX_CHARS: usize = 150;
+
+/// Shift_JIS のコードページ
+const CP_SHIFT_JIS: u32 = 932;
+
+/// 文の終わりを表す文字
+const SENTENCE_ENDS: &[char] = &['。', '．', '！', '？', '!', '?', '…'];
+
+/// 文の終わりの直後に続けてよい文字（閉じ括弧など）
+const CLOSING: &[char] = &['」', '』', '）', ')', '】', '”', '’'];
+
+/// 長すぎる文を分ける位置にできる文字
+const CLAUSE_ENDS: &[char] = &['、', '，', ',', '　', ' '];
+
+impl CeVIO {
+    /// テキストファイルを読み込み、行ごとに読み上げます。再生終了まで待ちます。
+    ///
+    /// 文字コードは UTF-8、UTF-16（BOM 付き）、Shift_JIS を判別します。空行は読み上げません。
+    pub fn speak_file(&self, path: impl AsRef<Path>) -> error::Result<()> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))
+            .map_err(error::CeVIOError::from)?;
+        let text = decode(&bytes)
+            .with_context(|| format!("Failed to decode `{}`", path.display()))
+            .map_err(error::CeVIOError::Conversion)?;
+        self.speak_lines(text.lines())
+    }
+
+    /// 行ごとに読み上げます。再生終了まで待ちます。
+    ///
+    /// 長い行は `MAX_CHARS` 文字以下に分けて読み上げます。空行は読み上げません。
+    pub fn speak_lines<'a>(&self, lines: impl IntoIterator<Item = &'a str>) -> error::Result<()> {
+        speak_lines(self, lines)
+    }
+
+    /// 現在の設定で、セリフの先頭の `max_chars` 文字以内だけ再生を開始します。
+    ///
+    /// 画面の試聴ボタンなどで、段落全体を合成せずに声を確認するために使います。
+    /// 先頭は `head` と同じく文の区切りで切り出します。再生終了を待たずに戻ります。
+    ///
+    /// 戻り値：
+    ///
+    /// 　再生状態を表すオブジェクト。セリフが空の場合は `CeVIOError::InvalidInput`。
+    pub fn preview(&self, text: &str, max_chars: usize) -> error::Result<SpeakingState> {
+        let head = head(text, max_chars)
+            .ok_or_else(|| error::CeVIOError::InvalidInput(report!("Text is empty")))?;
+        self.speak(&head)
+    }
+}
+
+/// `backend` で行ごとに読み上げます。再生終了まで待ちます。
+///
+/// 長い行は `MAX_CHARS` 文字以下に分けて読み上げます。空行は読み上げません。（`CeVIO::speak_lines` と同じです。）
+pub fn speak_lines<'a>(
+    backend: &impl TalkerBackend,
+    lines: impl IntoIterator<Item = &'a str>,
+) -> error::Result<()> {
+    for line in lines {
+        for chunk in split(line, MAX_CHARS) {
+            backend.speak(&chunk)?.wait()?;
+        }
+    }
+    Ok(())
+}
+
+/// テキストの先頭の `max_chars` 文字以内を、文の区切りで切り出します。
+///
+/// `split` の最初の部分です。空の場合は `None` を返します。
+pub fn head(text: &str, max_chars: usize) -> Option<String> {
+    split(text, max_chars).into_iter().next()
+}
+
+/// テキストを文の区切りで `max_chars` 文字以下に分けます。
+///
+/// 続く文は `max_chars` 文字を超えない範囲でまとめます。
+/// 1 文が `max_chars` 文字を超える場合は読点などで分け、それもなければ `max_chars` 文字で分けます。
+/// 前後の空白は取り除き、空になった部分は含めません。
+pub fn split(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for sentence in sentences(text) {
+        for piece in split_long(sentence, max_chars) {
+            if !current.is_empty() && current.chars().count() + piece.chars().count() > max_chars {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push_str(piece);
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// 文の終わり（と続く閉じ括弧）の直後で分ける
+fn sentences(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || loop {
+        if rest.is_empty() {
+            return None;
+        }
+        let mut end = rest.len();
+        let mut chars = rest.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if SENTENCE_ENDS.contains(&c) {
+                end = i + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !SENTENCE_ENDS.contains(&c) && !CLOSING.contains(&c) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                break;
+            }
+        }
+        let (sentence, next) = rest.split_at(end);
+        rest = next;
+        let sentence = sentence.trim();
+        if !sentence.is_empty() {
+            return Some(sentence);
+        }
+    })
+}
+
+/// `max_chars` 文字を超える文を読点などで分ける
+fn split_long(sentence: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = sentence;
+    while rest.chars().count() > max_chars {
+        // `max_chars` 文字目までで最後の区切り、なければ `max_chars` 文字目で分ける
+        let limit = rest
+            .char_indices()
+            .nth(max_chars)
+            .map_or(rest.len(), |(i, _)| i);
+        let end = rest[..limit]
+            .char_indices()
+            .rfind(|(_, c)| CLAUSE_ENDS.contains(c))
+            .map_or(limit, |(i, c)| i + c.len_utf8());
+        let (piece, next) = rest.split_at(end);
+        if !piece.trim().is_empty() {
+            pieces.push(piece.trim());
+        }
+        rest = next;
+    }
+    if !rest.trim().is_empty() {
+        pieces.push(rest.trim());
+    }
+    pieces
+}
+
+/// テキストファイルの内容を文字列に変換します。
+///
+/// BOM があれば UTF-8 か UTF-16 とし、なければ UTF-8 として正しい場合は UTF-8、それ以外は Shift_JIS とします。
+pub fn decode(bytes: &[u8]) -> std::result::Result<String, error::Report> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return String::from_utf8(rest.to_vec()).context("Invalid UTF-8");
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        return decode_utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        return decode_utf16(rest, u16::from_be_bytes);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(text.to_string()),
+        Err(_) => decode_shift_jis(bytes),
+    }
+}
+
+fn decode_utf16(
+    bytes: &[u8],
+    from_bytes: fn([u8; 2]) -> u16,
+) -> std::result::Result<String, error::Report> {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect::<Vec<_>>();
+    String::from_utf16(&units).context("Invalid UTF-16")
+}
+
+fn decode_shift_jis(bytes: &[u8]) -> std::result::Result<String, error::Report> {
+    let len = unsafe { MultiByteToWideChar(CP_SHIFT_JIS, MB_ERR_INVALID_CHARS, bytes, None) };
+    if len <= 0 {
+        return Err(report!("Neither UTF-8 nor Shift_JIS"));
+    }
+    let mut wide = vec![0u16; len as usize];
+    let len =
+        unsafe { MultiByteToWideChar(CP_SHIFT_JIS, MB_ERR_INVALID_CHARS, bytes, Some(&mut wide)) };
+    if len <= 0 {
+        return Err(report!("Neither UTF-8 nor Shift_JIS"));
+    }
+    wide.truncate(len as usize);
+    String::from_utf16(&wide).context("Invalid UTF-16")
+}
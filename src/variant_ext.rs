@@ -1,19 +1,72 @@
 use windows::{
     core::{self, BSTR},
     Win32::{
-        Foundation::VARIANT_BOOL,
+        Foundation::{E_POINTER, VARIANT_BOOL},
         System::{
             Com::{
-                SAFEARRAY, VARENUM, VARIANT, VARIANT_0_0, VT_ARRAY, VT_BOOL, VT_BSTR, VT_BYREF,
-                VT_I4, VT_NULL, VT_VARIANT,
+                IDispatch, SAFEARRAY, VARENUM, VARIANT, VARIANT_0_0, VT_ARRAY, VT_BOOL, VT_BSTR,
+                VT_BYREF, VT_DISPATCH, VT_EMPTY, VT_I4, VT_NULL, VT_R8, VT_VARIANT,
             },
             Ole::{VariantChangeType, VariantClear},
         },
     },
 };
 
-use std::mem::ManuallyDrop;
+use std::{mem::ManuallyDrop, ops::Deref};
 
+/// VARIANTの型
+///
+/// 変換先と同じ型の場合は `VariantChangeType` を呼ばずに値を直接読む
+fn vt(variant: &VARIANT) -> VARENUM {
+    unsafe { variant.Anonymous.Anonymous.vt }
+}
+
+/// 破棄するときに `VariantClear` を呼ぶ VARIANT
+///
+/// windows 0.48 の VARIANT は `Drop` を実装しておらず、`Invoke` の戻り値の BSTR や IDispatch の参照が解放されないため、これで包む
+pub struct OwnedVariant(VARIANT);
+
+impl OwnedVariant {
+    /// `variant` の中身の所有権を受け取る
+    pub fn new(variant: VARIANT) -> Self {
+        Self(variant)
+    }
+
+    /// 出力引数として渡す。中身は空（VT_EMPTY）でなければならない
+    pub fn as_out(&mut self) -> &mut VARIANT {
+        &mut self.0
+    }
+
+    /// VT_DISPATCH の場合は参照を増やさずに IDispatch を取り出す。それ以外は `to_dispatch` で変換する
+    pub fn into_dispatch(mut self) -> core::Result<IDispatch> {
+        if vt(&self.0) != VT_DISPATCH {
+            return self.0.to_dispatch();
+        }
+        unsafe {
+            let v00 = &mut *self.0.Anonymous.Anonymous;
+            let disp = ManuallyDrop::take(&mut v00.Anonymous.pdispVal);
+            // 取り出した参照を `Drop` で解放しないように空にする
+            v00.vt = VT_EMPTY;
+            disp.ok_or_else(|| core::Error::from(E_POINTER))
+        }
+    }
+}
+
+impl Deref for OwnedVariant {
+    type Target = VARIANT;
+
+    fn deref(&self) -> &VARIANT {
+        &self.0
+    }
+}
+
+impl Drop for OwnedVariant {
+    fn drop(&mut self) {
+        let _ = unsafe { VariantClear(&mut self.0) };
+    }
+}
+
+#[allow(unused)]
 pub trait VariantExt {
     /// VT_NULLなVARIANTを作る
     fn null() -> VARIANT;
@@ -26,6 +79,8 @@ pub trait VariantExt {
     fn from_str(s: &str) -> VARIANT;
     /// VT_BOOLを作る
     fn from_bool(b: bool) -> VARIANT;
+    /// VT_R8を作る
+    fn from_f64(n: f64) -> VARIANT;
     /// VT_ARRAY|VT_VARIANTを作る
     fn from_safearray(psa: *mut SAFEARRAY) -> VARIANT;
     /// VARIANTをi32にする
@@ -34,6 +89,10 @@ pub trait VariantExt {
     fn to_string(&self) -> core::Result<String>;
     /// VARIANTをboolにする
     fn to_bool(&self) -> core::Result<bool>;
+    /// VARIANTをf64にする
+    fn to_f64(&self) -> core::Result<f64>;
+    /// VARIANTをIDispatchにする
+    fn to_dispatch(&self) -> core::Result<IDispatch>;
 }
 
 impl VariantExt for VARIANT {
@@ -87,6 +146,16 @@ impl VariantExt for VARIANT {
         variant.Anonymous.Anonymous = ManuallyDrop::new(v00);
         variant
     }
+    fn from_f64(n: f64) -> VARIANT {
+        let mut variant = VARIANT::default();
+        let mut v00 = VARIANT_0_0 {
+            vt: VT_R8,
+            ..Default::default()
+        };
+        v00.Anonymous.dblVal = n;
+        variant.Anonymous.Anonymous = ManuallyDrop::new(v00);
+        variant
+    }
     fn from_safearray(psa: *mut SAFEARRAY) -> VARIANT {
         let mut variant = VARIANT::default();
         let mut v00 = VARIANT_0_0 {
@@ -98,6 +167,9 @@ impl VariantExt for VARIANT {
         variant
     }
     fn to_i32(&self) -> core::Result<i32> {
+        if vt(self) == VT_I4 {
+            return Ok(unsafe { self.Anonymous.Anonymous.Anonymous.lVal });
+        }
         unsafe {
             let mut new = VARIANT::default();
             VariantChangeType(&mut new, self, 0, VT_I4)?;
@@ -108,6 +180,9 @@ impl VariantExt for VARIANT {
         }
     }
     fn to_string(&self) -> core::Result<String> {
+        if vt(self) == VT_BSTR {
+            return Ok(unsafe { self.Anonymous.Anonymous.Anonymous.bstrVal.to_string() });
+        }
         unsafe {
             let mut new = VARIANT::default();
             VariantChangeType(&mut new, self, 0, VT_BSTR)?;
@@ -118,6 +193,9 @@ impl VariantExt for VARIANT {
         }
     }
     fn to_bool(&self) -> core::Result<bool> {
+        if vt(self) == VT_BOOL {
+            return Ok(unsafe { self.Anonymous.Anonymous.Anonymous.boolVal.as_bool() });
+        }
         unsafe {
             let mut new = VARIANT::default();
             VariantChangeType(&mut new, self, 0, VT_BOOL)?;
@@ -127,4 +205,31 @@ impl VariantExt for VARIANT {
             Ok(b)
         }
     }
+    fn to_f64(&self) -> core::Result<f64> {
+        if vt(self) == VT_R8 {
+            return Ok(unsafe { self.Anonymous.Anonymous.Anonymous.dblVal });
+        }
+        unsafe {
+            let mut new = VARIANT::default();
+            VariantChangeType(&mut new, self, 0, VT_R8)?;
+            let v00 = &new.Anonymous.Anonymous;
+            let n = v00.Anonymous.dblVal;
+            VariantClear(&mut new)?;
+            Ok(n)
+        }
+    }
+    fn to_dispatch(&self) -> core::Result<IDispatch> {
+        if vt(self) == VT_DISPATCH {
+            return unsafe { (*self.Anonymous.Anonymous.Anonymous.pdispVal).clone() }
+                .ok_or_else(|| core::Error::from(E_POINTER));
+        }
+        unsafe {
+            let mut new = VARIANT::default();
+            VariantChangeType(&mut new, self, 0, VT_DISPATCH)?;
+            let v00 = &new.Anonymous.Anonymous;
+            let disp = (*v00.Anonymous.pdispVal).clone();
+            VariantClear(&mut new)?;
+            disp.ok_or_else(|| core::Error::from(E_POINTER))
+        }
+    }
 }
@@ -0,0 +1,320 @@
+//! VOICEVOX ENGINE 互換 API（`server` フィーチャー）
+//!
+//! VOICEVOX ENGINE の HTTP API のうち、次のものを CeVIO のキャストとパラメータに対応させて提供します。
+//! VOICEVOX ENGINE に対応したツールから、そのまま CeVIO のキャストを使うことができます。
+//!
+//! | メソッド | パス                               | 内容                                         |
+//! | -------- | ---------------------------------- | -------------------------------------------- |
+//! | `GET`    | `/version`                         | このクレートのバージョン                     |
+//! | `GET`    | `/speakers`                        | キャスト一覧（キャストごとにスタイルは 1 つ）|
+//! | `POST`   | `/audio_query?text=..&speaker=..`  | 音声合成用のクエリを作成します               |
+//! | `POST`   | `/synthesis?speaker=..`            | クエリから WAV を作成します                  |
+//!
+//! 話者 ID（スタイル ID）は `get_available_casts` で得られるキャストの順番です。
+//! `speaker_uuid` はキャスト名から作る UUID（バージョン 5）で、キャストの順番が変わっても同じ値です。
+//!
+//! 備考：
+//!
+//! 　CeVIO はアクセント句を扱えないため、`accent_phrases` は常に空です。
+//! 　セリフはサーバーに保持し、`kana`（VOICEVOX では読み取り専用）にはセリフを表すキーを入れて返します。
+//! 　`/synthesis` には `/audio_query` の結果をそのまま渡してください。保持するセリフは新しいものから 1024 個までです。
+//!
+//! 　`speedScale`・`pitchScale`・`intonationScale`・`volumeScale` はそれぞれ
+//! 　Speed・Tone・ToneScale・Volume に変換します。
+//!
+//! ```no_run
+//! # async fn run() {
+//! use cevio::{actor::Handle, voicevox, HostKind};
+//! let handle = Handle::spawn(HostKind::Ai).unwrap();
+//! handle.call(|cevio| cevio.start_host(false)).unwrap();
+//! voicevox::serve("127.0.0.1:50021", handle).await.unwrap();
+//! # }
+//! ```
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{FromRef, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    actor::Handle,
+    error::{self, report},
+    server::{call, time_synthesis, ServerError},
+    CeVIO, Params,
+};
+
+/// 保持するセリフの数
+const TEXT_CAPACITY: usize = 1024;
+/// `kana` に入れるキーの接頭辞
+const KEY_PREFIX: &str = "cevio:";
+/// `speaker_uuid` の名前空間を作る URL
+const UUID_NAMESPACE_URL: &str = "https://github.com/JichouP/cevio-rs/voicevox/speaker";
+
+#[derive(Clone)]
+struct AppState {
+    handle: Handle,
+    texts: Arc<Mutex<Texts>>,
+}
+
+impl FromRef<AppState> for Handle {
+    fn from_ref(state: &AppState) -> Self {
+        state.handle.clone()
+    }
+}
+
+/// `/audio_query` で受け取ったセリフ
+#[derive(Default)]
+struct Texts {
+    texts: HashMap<String, String>,
+    /// 古い順のキー
+    order: VecDeque<String>,
+}
+
+impl Texts {
+    /// セリフを保持し、キーを返す。同じセリフは同じキーになる
+    fn insert(&mut self, text: String) -> String {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let key = format!("{KEY_PREFIX}{:016x}", hasher.finish());
+        if !self.texts.contains_key(&key) {
+            self.texts.insert(key.clone(), text);
+            self.order.push_back(key.clone());
+            while self.order.len() > TEXT_CAPACITY {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.texts.remove(&oldest);
+                }
+            }
+        }
+        key
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.texts.get(key).map(String::as_str)
+    }
+}
+
+/// キャスト名から `speaker_uuid` を作る
+fn speaker_uuid(cast: &str) -> Uuid {
+    let namespace = Uuid::new_v5(&Uuid::NAMESPACE_URL, UUID_NAMESPACE_URL.as_bytes());
+    Uuid::new_v5(&namespace, cast.as_bytes())
+}
+
+#[derive(Debug, Serialize)]
+struct Speaker {
+    name: String,
+    speaker_uuid: String,
+    styles: Vec<SpeakerStyle>,
+    version: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SpeakerStyle {
+    name: String,
+    id: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AudioQuery {
+    /// CeVIO はアクセント句を扱えないため常に空
+    #[serde(skip_deserializing)]
+    accent_phrases: Vec<()>,
+    speed_scale: f64,
+    pitch_scale: f64,
+    intonation_scale: f64,
+    volume_scale: f64,
+    pre_phoneme_length: f64,
+    post_phoneme_length: f64,
+    output_sampling_rate: u32,
+    output_stereo: bool,
+    /// セリフのキー（`Texts::insert`）
+    #[serde(default)]
+    kana: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AudioQueryParams {
+    text: String,
+    speaker: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SynthesisParams {
+    speaker: u32,
+}
+
+impl AudioQuery {
+    /// VOICEVOX の倍率を CeVIO の 0～100 の値に変換する
+    fn to_params(&self, cast: String) -> Params {
+        let scale = |v: f64| (v * 50.0).round().clamp(0.0, 100.0) as i32;
+        Params {
+            cast: Some(cast),
+            // speedScale は 0.5～2.0 で 1.0 が標準
+            speed: Some(scale(self.speed_scale)),
+            // pitchScale は -0.15～0.15 で 0.0 が標準
+            tone: Some(scale(1.0 + self.pitch_scale / 0.15)),
+            // intonationScale・volumeScale は 0.0～2.0 で 1.0 が標準
+            tone_scale: Some(scale(self.intonation_scale)),
+            volume: Some(scale(self.volume_scale)),
+            ..Default::default()
+        }
+    }
+}
+
+fn cast_by_id(cevio: &CeVIO, id: u32) -> error::Result<String> {
+    cevio
+        .get_available_casts()?
+        .into_iter()
+        .nth(id as usize)
+        .ok_or_else(|| report!("Unknown speaker `{id}`"))
+        .map_err(error::CeVIOError::InvalidCast)
+}
+
+async fn version() -> Json<&'static str> {
+    Json(env!("CARGO_PKG_VERSION"))
+}
+
+async fn speakers(State(handle): State<Handle>) -> Result<Json<Vec<Speaker>>, ServerError> {
+    let (casts, version) = call(&handle, |cevio| {
+        Ok((cevio.get_available_casts()?, cevio.get_host_version()?))
+    })
+    .await?;
+    Ok(Json(
+        casts
+            .into_iter()
+            .enumerate()
+            .map(|(id, name)| Speaker {
+                speaker_uuid: speaker_uuid(&name).to_string(),
+                styles: vec![SpeakerStyle {
+                    name: "ノーマル".to_string(),
+                    id: id as u32,
+                }],
+                name,
+                version: version.clone(),
+            })
+            .collect(),
+    ))
+}
+
+async fn audio_query(
+    State(state): State<AppState>,
+    Query(query): Query<AudioQueryParams>,
+) -> Result<Json<AudioQuery>, ServerError> {
+    let speaker = query.speaker;
+    call(&state.handle, move |cevio| cast_by_id(cevio, speaker)).await?;
+    let key = state
+        .texts
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(query.text);
+    Ok(Json(AudioQuery {
+        accent_phrases: Vec::new(),
+        speed_scale: 1.0,
+        pitch_scale: 0.0,
+        intonation_scale: 1.0,
+        volume_scale: 1.0,
+        pre_phoneme_length: 0.1,
+        post_phoneme_length: 0.1,
+        // OutputWaveToFile の出力形式
+        output_sampling_rate: 48000,
+        output_stereo: false,
+        kana: key,
+    }))
+}
+
+async fn synthesis(
+    State(state): State<AppState>,
+    Query(query): Query<SynthesisParams>,
+    Json(body): Json<AudioQuery>,
+) -> Result<Response, ServerError> {
+    let text = state
+        .texts
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&body.kana)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            ServerError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                error::ErrorKind::InvalidInput,
+                "Unknown query; pass the query returned by `/audio_query` as is",
+            )
+        })?;
+    let wav = time_synthesis(call(&state.handle, move |cevio| {
+        let cast = cast_by_id(cevio, query.speaker)?;
+        cevio.say(text).params(&body.to_params(cast)).to_vec()
+    }))
+    .await?;
+    Ok(([(header::CONTENT_TYPE, "audio/wav")], wav).into_response())
+}
+
+/// VOICEVOX ENGINE 互換のルーターを作成します。
+pub fn router(handle: Handle) -> Router {
+    Router::new()
+        .route("/version", get(version))
+        .route("/speakers", get(speakers))
+        .route("/audio_query", post(audio_query))
+        .route("/synthesis", post(synthesis))
+        .with_state(AppState {
+            handle,
+            texts: Arc::default(),
+        })
+}
+
+/// 指定したアドレスで VOICEVOX ENGINE 互換サーバーを起動します。
+///
+/// VOICEVOX ENGINE の既定のアドレスは `127.0.0.1:50021` です。
+pub async fn serve(addr: impl tokio::net::ToSocketAddrs, handle: Handle) -> error::Result<()> {
+    crate::server::serve_router(addr, router(handle), std::future::pending()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_text_has_same_key() {
+        let mut texts = Texts::default();
+        let key = texts.insert("こんにちは".to_string());
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(texts.insert("こんにちは".to_string()), key);
+        assert_ne!(texts.insert("こんばんは".to_string()), key);
+        assert_eq!(texts.get(&key), Some("こんにちは"));
+        assert_eq!(texts.order.len(), 2);
+    }
+
+    #[test]
+    fn oldest_text_is_dropped_over_capacity() {
+        let mut texts = Texts::default();
+        let first = texts.insert("0".to_string());
+        let second = texts.insert("1".to_string());
+        for i in 2..=TEXT_CAPACITY {
+            texts.insert(i.to_string());
+        }
+        assert_eq!(texts.get(&first), None);
+        assert_eq!(texts.get(&second), Some("1"));
+        assert_eq!(texts.texts.len(), TEXT_CAPACITY);
+        assert_eq!(texts.get("こんにちは"), None);
+    }
+
+    #[test]
+    fn speaker_uuid_is_stable_version_5() {
+        let uuid = speaker_uuid("さとうささら");
+        assert_eq!(uuid.get_version_num(), 5);
+        // 値が変わるとクライアントに保存された話者が別人になる
+        assert_eq!(uuid.to_string(), "ab4f0a79-ea89-5da8-b9fe-52b897d42626");
+        assert_eq!(uuid, speaker_uuid("さとうささら"));
+        assert_ne!(uuid, speaker_uuid("すずきつづみ"));
+    }
+}
@@ -0,0 +1,301 @@
+//! WAV ファイルの読み書き
+
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{
+    error::{self, report, Context as _},
+    processor::AudioBuffer,
+};
+
+/// `fmt ` チャンクの大きさの上限。壊れたファイルで大きな領域を確保しないようにする
+const MAX_FMT_SIZE: u32 = 64 * 1024;
+
+/// WAV ファイルの長さを `fmt ` チャンクのバイトレートと `data` チャンクの大きさから求める
+pub(crate) fn wav_duration(path: &Path) -> error::Result<Duration> {
+    read_wav_duration(path)
+        .with_context(|| format!("Failed to read WAV header of `{}`", path.display()))
+        .map_err(error::CeVIOError::Io)
+}
+
+fn read_wav_duration(path: &Path) -> std::result::Result<Duration, error::Report> {
+    let mut file = File::open(path).map_err(error::Report::new)?;
+    let (fmt, _, len) = read_header(&mut file)?;
+    let byte_rate = u32::from_le_bytes([fmt[8], fmt[9], fmt[10], fmt[11]]);
+    if byte_rate == 0 {
+        return Err(report!("Invalid byte rate"));
+    }
+    Ok(Duration::from_secs_f64(
+        f64::from(len) / f64::from(byte_rate),
+    ))
+}
+
+/// `fmt ` チャンクの中身と、`data` チャンクの中身の位置と大きさを読み込む
+///
+/// 読み込んだ後の位置は `data` チャンクの中身の先頭
+pub(crate) fn read_header(
+    reader: &mut (impl Read + Seek),
+) -> std::result::Result<(Vec<u8>, u64, u32), error::Report> {
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header).map_err(error::Report::new)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(report!("Not a WAV file"));
+    }
+    let mut fmt = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        reader.read_exact(&mut chunk).map_err(error::Report::new)?;
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        match &chunk[0..4] {
+            b"fmt " => {
+                if size > MAX_FMT_SIZE {
+                    return Err(report!("Too large `fmt ` chunk ({size} bytes)"));
+                }
+                let mut body = vec![0u8; size as usize];
+                reader.read_exact(&mut body).map_err(error::Report::new)?;
+                if body.len() < 16 {
+                    return Err(report!("Invalid `fmt ` chunk"));
+                }
+                // 奇数の大きさのチャンクはパディングが入る
+                reader
+                    .seek(SeekFrom::Current(i64::from(size & 1)))
+                    .map_err(error::Report::new)?;
+                fmt = Some(body);
+            }
+            b"data" => {
+                let fmt = fmt.ok_or_else(|| report!("Missing `fmt ` chunk"))?;
+                let offset = reader.stream_position().map_err(error::Report::new)?;
+                return Ok((fmt, offset, size));
+            }
+            _ => {
+                let rest = i64::from(size) + i64::from(size & 1);
+                reader
+                    .seek(SeekFrom::Current(rest))
+                    .map_err(error::Report::new)?;
+            }
+        }
+    }
+}
+
+/// PCM の WAV を読み込む。8/16/24/32 bit の整数に対応する
+pub(crate) fn decode(bytes: &[u8]) -> std::result::Result<AudioBuffer, error::Report> {
+    let (fmt, offset, len) = read_header(&mut Cursor::new(bytes))?;
+    if u16::from_le_bytes([fmt[0], fmt[1]]) != 1 {
+        return Err(report!("Only PCM WAV is supported"));
+    }
+    let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+    let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+    let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
+    if !matches!(bits_per_sample, 8 | 16 | 24 | 32) {
+        return Err(report!("Unsupported bits per sample `{bits_per_sample}`"));
+    }
+    let body = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| bytes.get(offset..offset + len as usize))
+        .ok_or_else(|| report!("Truncated `data` chunk"))?;
+    let width = usize::from(bits_per_sample / 8);
+    let samples = body
+        .chunks_exact(width)
+        .map(|sample| match sample {
+            [s] => (f32::from(*s) - 128.0) / 128.0,
+            [a, b] => f32::from(i16::from_le_bytes([*a, *b])) / 32768.0,
+            [a, b, c] => (i32::from_le_bytes([0, *a, *b, *c]) >> 8) as f32 / 8388608.0,
+            [a, b, c, d] => i32::from_le_bytes([*a, *b, *c, *d]) as f32 / 2147483648.0,
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok(AudioBuffer {
+        sample_rate,
+        channels,
+        samples,
+    })
+}
+
+/// 16bit の PCM の WAV に変換する。範囲外のサンプルは切り詰める
+pub(crate) fn encode(buffer: &AudioBuffer) -> Vec<u8> {
+    let channels = buffer.channels.max(1);
+    let data_len = buffer.samples.len() as u32 * 2;
+    let block_align = channels * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend(b"RIFF");
+    wav.extend((36 + data_len).to_le_bytes());
+    wav.extend(b"WAVEfmt ");
+    wav.extend(16u32.to_le_bytes());
+    wav.extend(1u16.to_le_bytes());
+    wav.extend(channels.to_le_bytes());
+    wav.extend(buffer.sample_rate.to_le_bytes());
+    wav.extend((buffer.sample_rate * u32::from(block_align)).to_le_bytes());
+    wav.extend(block_align.to_le_bytes());
+    wav.extend(16u16.to_le_bytes());
+    wav.extend(b"data");
+    wav.extend(data_len.to_le_bytes());
+    for sample in &buffer.samples {
+        let sample = (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16;
+        wav.extend(sample.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `fmt ` チャンクの中身（PCM 以外の形式も作れる）
+    fn fmt(format: u16, channels: u16, sample_rate: u32, bits: u16) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let mut fmt = format.to_le_bytes().to_vec();
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(sample_rate.to_le_bytes());
+        fmt.extend((sample_rate * u32::from(block_align)).to_le_bytes());
+        fmt.extend(block_align.to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+        fmt
+    }
+
+    /// チャンクを並べた WAV。奇数の大きさのチャンクにはパディングを入れる
+    fn wav(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut body = b"WAVE".to_vec();
+        for (id, data) in chunks {
+            body.extend(*id);
+            body.extend((data.len() as u32).to_le_bytes());
+            body.extend(*data);
+            if data.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((body.len() as u32).to_le_bytes());
+        wav.extend(body);
+        wav
+    }
+
+    fn pcm(bits: u16, data: &[u8]) -> Vec<u8> {
+        wav(&[(b"fmt ", &fmt(1, 1, 48000, bits)), (b"data", data)])
+    }
+
+    #[test]
+    fn decode_8bit_unsigned() {
+        let buffer = decode(&pcm(8, &[0, 128, 255])).unwrap();
+        assert_eq!(buffer.samples, [-1.0, 0.0, 127.0 / 128.0]);
+    }
+
+    #[test]
+    fn decode_16bit() {
+        let data = [i16::MIN, 0, 16384, i16::MAX]
+            .into_iter()
+            .flat_map(i16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let buffer = decode(&pcm(16, &data)).unwrap();
+        assert_eq!(buffer.samples, [-1.0, 0.0, 0.5, 32767.0 / 32768.0]);
+    }
+
+    #[test]
+    fn decode_24bit_sign_extends() {
+        // -8388608、-1、4194304
+        let data = [0x00, 0x00, 0x80, 0xff, 0xff, 0xff, 0x00, 0x00, 0x40];
+        let buffer = decode(&pcm(24, &data)).unwrap();
+        assert_eq!(buffer.samples, [-1.0, -1.0 / 8388608.0, 0.5]);
+    }
+
+    #[test]
+    fn decode_32bit() {
+        let data = [i32::MIN, -1 << 30, 1 << 30]
+            .into_iter()
+            .flat_map(i32::to_le_bytes)
+            .collect::<Vec<_>>();
+        let buffer = decode(&pcm(32, &data)).unwrap();
+        assert_eq!(buffer.samples, [-1.0, -0.5, 0.5]);
+    }
+
+    #[test]
+    fn decode_reads_format() {
+        let bytes = wav(&[(b"fmt ", &fmt(1, 2, 44100, 16)), (b"data", &[0; 8])]);
+        let buffer = decode(&bytes).unwrap();
+        assert_eq!((buffer.sample_rate, buffer.channels), (44100, 2));
+        assert_eq!(buffer.samples.len(), 4);
+    }
+
+    #[test]
+    fn decode_skips_odd_sized_chunks_with_padding() {
+        // 奇数の大きさの `fmt ` と、その前後の不明なチャンク
+        let mut fmt = fmt(1, 1, 48000, 8);
+        fmt.extend([0, 0, 0]);
+        let bytes = wav(&[
+            (b"LIST", b"abc"),
+            (b"fmt ", &fmt),
+            (b"junk", b"x"),
+            (b"data", &[128, 255]),
+        ]);
+        let buffer = decode(&bytes).unwrap();
+        assert_eq!(buffer.samples, [0.0, 127.0 / 128.0]);
+    }
+
+    #[test]
+    fn decode_rejects_truncated_data() {
+        let mut bytes = pcm(16, &[0; 8]);
+        bytes.truncate(bytes.len() - 1);
+        let e = decode(&bytes).unwrap_err();
+        assert!(format!("{e:#}").contains("Truncated `data` chunk"), "{e:#}");
+    }
+
+    #[test]
+    fn decode_rejects_unsupported_formats() {
+        assert!(decode(b"RIFF\0\0\0\0WAVX").is_err());
+        // IEEE float
+        assert!(decode(&wav(&[
+            (b"fmt ", &fmt(3, 1, 48000, 32)),
+            (b"data", &[0; 4])
+        ]))
+        .is_err());
+        assert!(decode(&pcm(12, &[0; 4])).is_err());
+        // `fmt ` より前の `data`
+        assert!(decode(&wav(&[
+            (b"data", &[0; 2]),
+            (b"fmt ", &fmt(1, 1, 48000, 16))
+        ]))
+        .is_err());
+        // 16 バイトに満たない `fmt `
+        assert!(decode(&wav(&[(b"fmt ", &[1, 0]), (b"data", &[0; 2])])).is_err());
+    }
+
+    #[test]
+    fn read_header_rejects_huge_fmt_chunk_before_allocating() {
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        bytes.extend(u32::MAX.to_le_bytes());
+        let e = read_header(&mut Cursor::new(bytes)).unwrap_err();
+        assert!(format!("{e:#}").contains("Too large `fmt ` chunk"), "{e:#}");
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let buffer = AudioBuffer {
+            sample_rate: 48000,
+            channels: 2,
+            samples: vec![0.0, 0.5, -0.5, 1.0, -1.0, 0.25],
+        };
+        let bytes = encode(&buffer);
+        assert_eq!(bytes.len(), 44 + 12);
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!((decoded.sample_rate, decoded.channels), (48000, 2));
+        // 書き込みは 32767 倍、読み込みは 1/32768 倍のため、1 段階の差は許す
+        for (a, b) in decoded.samples.iter().zip(&buffer.samples) {
+            assert!((a - b).abs() < 2.0 / 32768.0, "{a} != {b}");
+        }
+        assert_eq!(decoded.samples.len(), buffer.samples.len());
+    }
+
+    #[test]
+    fn encode_clamps_out_of_range_samples() {
+        let buffer = AudioBuffer {
+            sample_rate: 8000,
+            channels: 1,
+            samples: vec![2.0, -2.0],
+        };
+        let bytes = encode(&buffer);
+        assert_eq!(&bytes[44..], [0xff, 0x7f, 0x01, 0x80]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+/// CeVIO に書き込んだ（または読み込んだ）値の記録
+///
+/// 同じ値の書き込みを省くために使う。キャストを変更するとパラメータが初期化されるため、
+/// キャスト以外の値はキャストごとに記録し直す
+#[derive(Debug, Default)]
+pub(crate) struct Written {
+    cast: Option<String>,
+    /// プロパティ名ごとの値
+    params: HashMap<&'static str, i32>,
+    /// 感情パラメータの名前ごとの値
+    components: HashMap<String, i32>,
+}
+
+impl Written {
+    pub(crate) fn param(&self, prop: &str) -> Option<i32> {
+        self.params.get(prop).copied()
+    }
+
+    pub(crate) fn set_param(&mut self, prop: &'static str, value: i32) {
+        self.params.insert(prop, value);
+    }
+
+    pub(crate) fn forget_param(&mut self, prop: &str) {
+        self.params.remove(prop);
+    }
+
+    pub(crate) fn cast(&self) -> Option<&str> {
+        self.cast.as_deref()
+    }
+
+    pub(crate) fn is_cast(&self, cast: &str) -> bool {
+        self.cast.as_deref() == Some(cast)
+    }
+
+    /// キャストが変わった場合は他の値を忘れる
+    pub(crate) fn set_cast(&mut self, cast: &str) {
+        if !self.is_cast(cast) {
+            *self = Self {
+                cast: Some(cast.to_string()),
+                ..Self::default()
+            };
+        }
+    }
+
+    pub(crate) fn component(&self, name: &str) -> Option<i32> {
+        self.components.get(name).copied()
+    }
+
+    pub(crate) fn set_component(&mut self, name: &str, value: i32) {
+        match self.components.get_mut(name) {
+            Some(v) => *v = value,
+            None => {
+                self.components.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    pub(crate) fn forget_component(&mut self, name: &str) {
+        self.components.remove(name);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
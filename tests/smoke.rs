@@ -0,0 +1,78 @@
+//! 実際の CeVIO を使う結合テスト（`integration-tests` フィーチャー）
+//!
+//! CeVIO がインストールされた環境で実行します。
+//!
+//! ```sh
+//! cargo test --features integration-tests --test smoke
+//! # CeVIO Creative Studio を使う場合
+//! CEVIO_TEST_HOST=cs cargo test --features integration-tests --test smoke
+//! ```
+
+use cevio::{harness::Harness, Params};
+
+#[test]
+fn host_is_started() {
+    let harness = Harness::from_env().unwrap();
+    let cevio = harness.cevio();
+    assert!(cevio.get_is_host_started().unwrap());
+    assert!(!cevio.get_host_version().unwrap().is_empty());
+}
+
+#[test]
+fn casts_are_available() {
+    let harness = Harness::from_env().unwrap();
+    let cevio = harness.cevio();
+    let casts = cevio.get_available_casts().unwrap();
+    assert!(!casts.is_empty());
+
+    cevio.set_cast(&casts[0]).unwrap();
+    assert_eq!(cevio.get_cast().unwrap(), casts[0]);
+    assert!(cevio
+        .set_cast("存在しないキャスト")
+        .unwrap_err()
+        .is_cast_invalid());
+}
+
+#[test]
+fn params_round_trip() {
+    let harness = Harness::from_env().unwrap();
+    let cevio = harness.cevio();
+    cevio
+        .apply_params(&Params {
+            cast: Some(harness.first_cast().unwrap()),
+            volume: Some(40),
+            speed: Some(60),
+            tone: Some(45),
+            tone_scale: Some(55),
+            alpha: Some(50),
+            components: Vec::new(),
+        })
+        .unwrap();
+    assert_eq!(cevio.get_volume().unwrap(), 40);
+    assert_eq!(cevio.get_speed().unwrap(), 60);
+    assert_eq!(cevio.get_tone().unwrap(), 45);
+    assert_eq!(cevio.get_tone_scale().unwrap(), 55);
+    assert_eq!(cevio.get_alpha().unwrap(), 50);
+    for component in cevio.get_components().unwrap() {
+        cevio.set_component(&component.name, 0).unwrap();
+    }
+}
+
+#[test]
+fn short_synthesis() {
+    let harness = Harness::from_env().unwrap();
+    let cevio = harness.cevio();
+    cevio.set_cast(harness.first_cast().unwrap()).unwrap();
+
+    let path = harness.temp_path("smoke.wav");
+    cevio.output_wave_to_file("テスト。", &path).unwrap();
+    let wav = std::fs::read(&path).unwrap();
+    assert_eq!(&wav[..4], b"RIFF");
+    assert!(wav.len() > 44);
+
+    let phonemes = cevio.get_phonemes("テスト。").unwrap();
+    assert!(!phonemes.is_empty());
+    assert!(phonemes
+        .windows(2)
+        .all(|p| p[0].end_time <= p[1].start_time + 1e-6));
+}